@@ -2,6 +2,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=proto/cluster.proto");
     println!("cargo:rerun-if-changed=proto/healthcheck.proto");
     println!("cargo:rerun-if-changed=proto/index.proto");
+    println!("cargo:rerun-if-changed=proto/raft.proto");
+    println!("cargo:rerun-if-changed=proto/shard.proto");
 
     let cfg = prost_build::Config::default();
     tonic_build::configure()
@@ -62,6 +64,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .field_attribute("index.Query.Kind.RANGE", "#[serde(rename = \"range\")]")
         .field_attribute("index.Query.Kind.REGEX", "#[serde(rename = \"regex\")]")
         .field_attribute("index.Query.Kind.TERM", "#[serde(rename = \"term\")]")
+        .field_attribute(
+            "index.Query.Kind.MORE_LIKE_THIS",
+            "#[serde(rename = \"more_like_this\")]",
+        )
+        .field_attribute(
+            "index.Query.Kind.FUNCTION_SCORE",
+            "#[serde(rename = \"function_score\")]",
+        )
+        .field_attribute(
+            "index.Query.Kind.GEO_DISTANCE",
+            "#[serde(rename = \"geo_distance\")]",
+        )
+        .field_attribute(
+            "index.Query.Kind.GEO_BOUNDING_BOX",
+            "#[serde(rename = \"geo_bounding_box\")]",
+        )
         .field_attribute("index.Sort.Order.UNKNOWN", "#[serde(rename = \"unknown\")]")
         .field_attribute("index.Sort.Order.ASC", "#[serde(rename = \"asc\")]")
         .field_attribute("index.Sort.Order.DESC", "#[serde(rename = \"desc\")]")
@@ -72,6 +90,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "./proto/cluster.proto",
                 "./proto/healthcheck.proto",
                 "./proto/index.proto",
+                "./proto/raft.proto",
+                "./proto/shard.proto",
             ],
             &["./proto"],
         )?;
@@ -0,0 +1,467 @@
+pub mod service;
+
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        oneshot,
+    },
+};
+use tonic::Status;
+use tracing::error;
+
+use crate::auth::Privilege;
+
+pub const AUDIT_DIR: &str = "audit";
+pub const AUDIT_LOG_FILE: &str = "audit.log";
+pub const DEFAULT_AUDIT_LOG_MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+pub const DEFAULT_AUDIT_LOG_MAX_FILES: usize = 10;
+
+// The number of the records that may wait for the writer.
+const AUDIT_LOG_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub enum AuditErrorKind {
+    InvalidLevel,
+    FileOpenFailure,
+}
+
+impl AuditErrorKind {
+    pub fn with_error<E>(self, source: E) -> AuditError
+    where
+        anyhow::Error: From<E>,
+    {
+        AuditError {
+            kind: self,
+            source: From::from(source),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("AuditError(kind={kind:?}, source={source})")]
+pub struct AuditError {
+    pub kind: AuditErrorKind,
+    #[source]
+    source: anyhow::Error,
+}
+
+impl AuditError {
+    pub fn add_context<C>(self, ctx: C) -> Self
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        AuditError {
+            kind: self.kind,
+            source: self.source.context(ctx),
+        }
+    }
+
+    pub fn kind(&self) -> AuditErrorKind {
+        self.kind
+    }
+}
+
+/// The operations that are recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    CreateIndex,
+    DeleteIndex,
+    GetIndex,
+    ModifyIndex,
+    PutDocuments,
+    DeleteDocuments,
+    Commit,
+    Rollback,
+    Search,
+    GetCacheStats,
+    Analyze,
+    ReloadAnalyzers,
+    PutUserDictionary,
+    DeleteUserDictionary,
+}
+
+impl Operation {
+    /// Returns the privilege that the operation requires.
+    pub fn privilege(&self) -> Privilege {
+        match self {
+            Operation::CreateIndex
+            | Operation::DeleteIndex
+            | Operation::ModifyIndex
+            | Operation::ReloadAnalyzers
+            | Operation::PutUserDictionary
+            | Operation::DeleteUserDictionary => Privilege::Manage,
+            Operation::PutDocuments
+            | Operation::DeleteDocuments
+            | Operation::Commit
+            | Operation::Rollback => Privilege::Write,
+            Operation::GetIndex
+            | Operation::Search
+            | Operation::GetCacheStats
+            | Operation::Analyze => Privilege::Read,
+        }
+    }
+}
+
+/// The operations that are recorded in the audit log.
+/// `admin` records the operations that require the `manage` privilege, `write` also records the
+/// operations that require the `write` privilege, and `all` records all operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditLevel {
+    Admin,
+    Write,
+    All,
+}
+
+impl AuditLevel {
+    pub fn includes(&self, operation: Operation) -> bool {
+        match (self, operation.privilege()) {
+            (AuditLevel::All, _) => true,
+            (AuditLevel::Write, privilege) => privilege != Privilege::Read,
+            (AuditLevel::Admin, privilege) => privilege == Privilege::Manage,
+        }
+    }
+}
+
+impl FromStr for AuditLevel {
+    type Err = AuditError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "admin" => Ok(AuditLevel::Admin),
+            "write" => Ok(AuditLevel::Write),
+            "all" => Ok(AuditLevel::All),
+            _ => Err(AuditErrorKind::InvalidLevel.with_error(anyhow::anyhow!(
+                "Audit level must be one of admin, write or all: {:?}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+/// A line of the audit log.
+#[derive(Debug, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub principal: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_address: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forwarded_for: Option<String>,
+    pub operation: Operation,
+    pub index: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub shard_id: String,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documents: Option<usize>,
+    pub elapsed_ms: f64,
+}
+
+impl AuditRecord {
+    /// Creates the record of the operation that starts now.
+    pub fn new(principal: &str, operation: Operation, index: &str, shard_id: &str) -> Self {
+        Self {
+            timestamp: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            principal: principal.to_string(),
+            remote_address: None,
+            forwarded_for: None,
+            operation,
+            index: index.to_string(),
+            shard_id: shard_id.to_string(),
+            outcome: Outcome::Success,
+            status_code: None,
+            message: None,
+            documents: None,
+            elapsed_ms: 0.0,
+        }
+    }
+
+    pub fn set_result<T>(&mut self, result: &Result<T, Status>) {
+        match result {
+            Ok(_) => {
+                self.outcome = Outcome::Success;
+                self.status_code = None;
+                self.message = None;
+            }
+            Err(status) => {
+                self.outcome = Outcome::Failure;
+                self.status_code = Some(format!("{:?}", status.code()));
+                self.message = Some(status.message().to_string());
+            }
+        }
+    }
+}
+
+enum AuditMessage {
+    Record(Box<AuditRecord>),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Writes the audit records to the JSON lines file in the audit directory.
+/// When the file exceeds the maximum size, it is renamed to `audit.log.1`, the older files are
+/// shifted, and the files beyond the maximum number are deleted.
+pub struct AuditLog {
+    level: AuditLevel,
+    sender: Sender<AuditMessage>,
+}
+
+impl AuditLog {
+    pub async fn new(
+        audit_dir: PathBuf,
+        level: AuditLevel,
+        max_file_size: u64,
+        max_files: usize,
+    ) -> Result<Self, AuditError> {
+        fs::create_dir_all(&audit_dir).await.map_err(|error| {
+            AuditErrorKind::FileOpenFailure
+                .with_error(error)
+                .add_context(format!("{:?}", audit_dir))
+        })?;
+
+        let writer = AuditWriter::open(audit_dir, max_file_size, max_files).await?;
+
+        // The records are written in the order they are sent, without blocking the requests
+        // unless the writer falls behind.
+        let (sender, receiver) = channel(AUDIT_LOG_CHANNEL_CAPACITY);
+        tokio::spawn(writer.run(receiver));
+
+        Ok(Self { level, sender })
+    }
+
+    pub fn level(&self) -> AuditLevel {
+        self.level
+    }
+
+    /// Sends the record to the writer. If the writer falls behind, e.g. while the disk is
+    /// stalled, the requests wait for it, so that the records are neither dropped nor piled up
+    /// in memory.
+    pub async fn write(&self, record: AuditRecord) {
+        if self
+            .sender
+            .send(AuditMessage::Record(Box::new(record)))
+            .await
+            .is_err()
+        {
+            error!("Failed to send audit record.");
+        }
+    }
+
+    /// Waits until the records that have been sent are written to the file.
+    pub async fn flush(&self) {
+        let (sender, receiver) = oneshot::channel();
+        if self.sender.send(AuditMessage::Flush(sender)).await.is_err() {
+            error!("Failed to flush audit log.");
+            return;
+        }
+        let _ = receiver.await;
+    }
+}
+
+struct AuditWriter {
+    audit_dir: PathBuf,
+    max_file_size: u64,
+    max_files: usize,
+    file: File,
+    file_size: u64,
+}
+
+impl AuditWriter {
+    async fn open(
+        audit_dir: PathBuf,
+        max_file_size: u64,
+        max_files: usize,
+    ) -> Result<Self, AuditError> {
+        let path = audit_dir.join(AUDIT_LOG_FILE);
+        let (file, file_size) = open_log_file(&path).await.map_err(|error| {
+            AuditErrorKind::FileOpenFailure
+                .with_error(error)
+                .add_context(format!("{:?}", path))
+        })?;
+
+        Ok(Self {
+            audit_dir,
+            max_file_size,
+            max_files,
+            file,
+            file_size,
+        })
+    }
+
+    async fn run(mut self, mut receiver: Receiver<AuditMessage>) {
+        while let Some(message) = receiver.recv().await {
+            let record = match message {
+                AuditMessage::Record(record) => record,
+                AuditMessage::Flush(sender) => {
+                    let _ = sender.send(());
+                    continue;
+                }
+            };
+            let mut line = match serde_json::to_vec(&record) {
+                Ok(line) => line,
+                Err(error) => {
+                    error!(?error, "Failed to serialize audit record.");
+                    continue;
+                }
+            };
+            line.push(b'\n');
+
+            if let Err(error) = self.write(&line).await {
+                error!(?error, "Failed to write audit record.");
+            }
+        }
+    }
+
+    async fn write(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.file_size > 0 && self.file_size + line.len() as u64 > self.max_file_size {
+            self.rotate().await?;
+        }
+
+        self.file.write_all(line).await?;
+        self.file.flush().await?;
+        self.file_size += line.len() as u64;
+
+        Ok(())
+    }
+
+    async fn rotate(&mut self) -> std::io::Result<()> {
+        let path = |n: usize| match n {
+            0 => self.audit_dir.join(AUDIT_LOG_FILE),
+            n => self.audit_dir.join(format!("{}.{}", AUDIT_LOG_FILE, n)),
+        };
+
+        // The current file is kept in addition to the rotated files.
+        let _ = fs::remove_file(path(self.max_files)).await;
+        for n in (0..self.max_files).rev() {
+            if fs::metadata(path(n)).await.is_ok() {
+                fs::rename(path(n), path(n + 1)).await?;
+            }
+        }
+
+        let (file, file_size) = open_log_file(&path(0)).await?;
+        self.file = file;
+        self.file_size = file_size;
+
+        Ok(())
+    }
+}
+
+async fn open_log_file(path: &Path) -> std::io::Result<(File, u64)> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    let file_size = file.metadata().await?.len();
+
+    Ok((file, file_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use tonic::Status;
+
+    use crate::audit::{AuditLevel, AuditLog, AuditRecord, Operation, AUDIT_LOG_FILE};
+
+    fn record(operation: Operation) -> AuditRecord {
+        let mut record = AuditRecord::new("alice", operation, "example", "");
+        record.remote_address = Some("127.0.0.1:5000".parse().unwrap());
+        record.documents = Some(10);
+        record.elapsed_ms = 1.5;
+        record
+    }
+
+    #[test]
+    fn test_audit_level() {
+        assert_eq!("write".parse::<AuditLevel>().unwrap(), AuditLevel::Write);
+        assert!("writes".parse::<AuditLevel>().is_err());
+
+        assert!(AuditLevel::Admin.includes(Operation::DeleteIndex));
+        assert!(!AuditLevel::Admin.includes(Operation::PutDocuments));
+        assert!(AuditLevel::Write.includes(Operation::PutDocuments));
+        assert!(!AuditLevel::Write.includes(Operation::Search));
+        assert!(AuditLevel::All.includes(Operation::Search));
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let tmp_dir = TempDir::new("test_audit_log").unwrap();
+        let audit_dir = tmp_dir.path().to_path_buf();
+
+        // Each record is about 200 bytes, so the file is rotated every 2 records.
+        let audit_log = AuditLog::new(audit_dir.clone(), AuditLevel::All, 500, 2)
+            .await
+            .unwrap();
+        for operation in [
+            Operation::CreateIndex,
+            Operation::PutDocuments,
+            Operation::Commit,
+            Operation::Search,
+            Operation::Search,
+            Operation::DeleteIndex,
+            Operation::Rollback,
+        ] {
+            audit_log.write(record(operation)).await;
+        }
+        let mut failure = record(Operation::Analyze);
+        failure.set_result::<()>(&Err(Status::permission_denied("denied")));
+        audit_log.write(failure).await;
+        audit_log.flush().await;
+
+        let read_lines = |file_name: String| {
+            std::fs::read_to_string(audit_dir.join(file_name))
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let lines = read_lines(AUDIT_LOG_FILE.to_string());
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["operation"], "rollback");
+        assert_eq!(lines[0]["principal"], "alice");
+        assert_eq!(lines[0]["remote_address"], "127.0.0.1:5000");
+        assert_eq!(lines[0]["outcome"], "success");
+        assert_eq!(lines[0]["documents"], 10);
+        assert!(lines[0].get("shard_id").is_none());
+        assert!(lines[0].get("status_code").is_none());
+        assert!(!lines[0]["timestamp"].as_str().unwrap().is_empty());
+        assert_eq!(lines[1]["operation"], "analyze");
+        assert_eq!(lines[1]["outcome"], "failure");
+        assert_eq!(lines[1]["status_code"], "PermissionDenied");
+        assert_eq!(lines[1]["message"], "denied");
+
+        let lines = read_lines(format!("{}.1", AUDIT_LOG_FILE));
+        assert_eq!(lines[0]["operation"], "search");
+        let lines = read_lines(format!("{}.2", AUDIT_LOG_FILE));
+        assert_eq!(lines[0]["operation"], "commit");
+
+        // The oldest file is deleted.
+        assert!(!audit_dir.join(format!("{}.3", AUDIT_LOG_FILE)).exists());
+    }
+}
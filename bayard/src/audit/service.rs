@@ -0,0 +1,258 @@
+use std::sync::Arc;
+
+use tokio::time::Instant;
+use tonic::{Request, Response, Status};
+
+use crate::{
+    audit::{AuditLog, AuditRecord, Operation},
+    auth::{Principal, X_FORWARDED_FOR},
+    proto::index::{
+        index_service_server::IndexService as ProtoIndexService, AnalyzeRequest, AnalyzeResponse,
+        CommitRequest, CommitResponse, CreateIndexRequest, CreateIndexResponse,
+        DeleteDocumentsRequest, DeleteDocumentsResponse, DeleteIndexRequest, DeleteIndexResponse,
+        DeleteUserDictionaryRequest, DeleteUserDictionaryResponse, GetCacheStatsRequest,
+        GetCacheStatsResponse, GetIndexRequest, GetIndexResponse, ModifyIndexRequest,
+        ModifyIndexResponse, PutDocumentsRequest, PutDocumentsResponse, PutUserDictionaryRequest,
+        PutUserDictionaryResponse, ReloadAnalyzersRequest, ReloadAnalyzersResponse,
+        RollbackRequest, RollbackResponse, SearchRequest, SearchResponse,
+    },
+    service::index::IndexService,
+};
+
+struct AuditEntry {
+    record: AuditRecord,
+    start: Instant,
+}
+
+/// The index service that records the calls to the wrapped service in the audit log.
+/// The calls made on behalf of another call, such as the shard requests of a search, are also
+/// recorded by the nodes that serve them.
+pub struct AuditedIndexService {
+    inner: IndexService,
+    audit_log: Option<Arc<AuditLog>>,
+}
+
+impl AuditedIndexService {
+    pub fn new(inner: IndexService, audit_log: Option<Arc<AuditLog>>) -> Self {
+        Self { inner, audit_log }
+    }
+
+    fn start<T>(
+        &self,
+        request: &Request<T>,
+        operation: Operation,
+        index: &str,
+        shard_id: &str,
+    ) -> Option<AuditEntry> {
+        let audit_log = self.audit_log.as_ref()?;
+        if !audit_log.level().includes(operation) {
+            return None;
+        }
+
+        let principal = request
+            .extensions()
+            .get::<Principal>()
+            .map(|principal| principal.name())
+            .unwrap_or_default();
+        let mut record = AuditRecord::new(principal, operation, index, shard_id);
+        record.remote_address = request.remote_addr();
+        record.forwarded_for = request
+            .metadata()
+            .get(X_FORWARDED_FOR)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+
+        Some(AuditEntry {
+            record,
+            start: Instant::now(),
+        })
+    }
+
+    async fn finish<T>(
+        &self,
+        entry: Option<AuditEntry>,
+        documents: Option<usize>,
+        result: &Result<T, Status>,
+    ) {
+        if let (Some(audit_log), Some(mut entry)) = (self.audit_log.as_ref(), entry) {
+            entry.record.documents = documents;
+            entry.record.set_result(result);
+            entry.record.elapsed_ms = entry.start.elapsed().as_secs_f64() * 1000.0;
+            audit_log.write(entry.record).await;
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl ProtoIndexService for AuditedIndexService {
+    async fn create_index(
+        &self,
+        request: Request<CreateIndexRequest>,
+    ) -> Result<Response<CreateIndexResponse>, Status> {
+        let name = request.get_ref().name.clone();
+        let entry = self.start(&request, Operation::CreateIndex, &name, "");
+        let resp = self.inner.create_index(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn delete_index(
+        &self,
+        request: Request<DeleteIndexRequest>,
+    ) -> Result<Response<DeleteIndexResponse>, Status> {
+        let name = request.get_ref().name.clone();
+        let entry = self.start(&request, Operation::DeleteIndex, &name, "");
+        let resp = self.inner.delete_index(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn get_index(
+        &self,
+        request: Request<GetIndexRequest>,
+    ) -> Result<Response<GetIndexResponse>, Status> {
+        let name = request.get_ref().name.clone();
+        let entry = self.start(&request, Operation::GetIndex, &name, "");
+        let resp = self.inner.get_index(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn modify_index(
+        &self,
+        request: Request<ModifyIndexRequest>,
+    ) -> Result<Response<ModifyIndexResponse>, Status> {
+        let name = request.get_ref().name.clone();
+        let entry = self.start(&request, Operation::ModifyIndex, &name, "");
+        let resp = self.inner.modify_index(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn put_documents(
+        &self,
+        request: Request<PutDocumentsRequest>,
+    ) -> Result<Response<PutDocumentsResponse>, Status> {
+        let req = request.get_ref();
+        let (name, shard_id, documents) = (req.name.clone(), req.shard_id.clone(), req.docs.len());
+        let entry = self.start(&request, Operation::PutDocuments, &name, &shard_id);
+        let resp = self.inner.put_documents(request).await;
+        self.finish(entry, Some(documents), &resp).await;
+        resp
+    }
+
+    async fn delete_documents(
+        &self,
+        request: Request<DeleteDocumentsRequest>,
+    ) -> Result<Response<DeleteDocumentsResponse>, Status> {
+        let req = request.get_ref();
+        let (name, shard_id, documents) =
+            (req.name.clone(), req.shard_id.clone(), req.doc_ids.len());
+        let entry = self.start(&request, Operation::DeleteDocuments, &name, &shard_id);
+        let resp = self.inner.delete_documents(request).await;
+        self.finish(entry, Some(documents), &resp).await;
+        resp
+    }
+
+    async fn commit(
+        &self,
+        request: Request<CommitRequest>,
+    ) -> Result<Response<CommitResponse>, Status> {
+        let (name, shard_id) = (
+            request.get_ref().name.clone(),
+            request.get_ref().shard_id.clone(),
+        );
+        let entry = self.start(&request, Operation::Commit, &name, &shard_id);
+        let resp = self.inner.commit(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn rollback(
+        &self,
+        request: Request<RollbackRequest>,
+    ) -> Result<Response<RollbackResponse>, Status> {
+        let (name, shard_id) = (
+            request.get_ref().name.clone(),
+            request.get_ref().shard_id.clone(),
+        );
+        let entry = self.start(&request, Operation::Rollback, &name, &shard_id);
+        let resp = self.inner.rollback(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn search(
+        &self,
+        request: Request<SearchRequest>,
+    ) -> Result<Response<SearchResponse>, Status> {
+        let (name, shard_id) = (
+            request.get_ref().name.clone(),
+            request.get_ref().shard_id.clone(),
+        );
+        let entry = self.start(&request, Operation::Search, &name, &shard_id);
+        let resp = self.inner.search(request).await;
+        // The number of the documents returned to the caller.
+        let documents = resp
+            .as_ref()
+            .ok()
+            .map(|resp| resp.get_ref().documents.len());
+        self.finish(entry, documents, &resp).await;
+        resp
+    }
+
+    async fn get_cache_stats(
+        &self,
+        request: Request<GetCacheStatsRequest>,
+    ) -> Result<Response<GetCacheStatsResponse>, Status> {
+        let name = request.get_ref().name.clone();
+        let entry = self.start(&request, Operation::GetCacheStats, &name, "");
+        let resp = self.inner.get_cache_stats(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn analyze(
+        &self,
+        request: Request<AnalyzeRequest>,
+    ) -> Result<Response<AnalyzeResponse>, Status> {
+        let name = request.get_ref().name.clone();
+        let entry = self.start(&request, Operation::Analyze, &name, "");
+        let resp = self.inner.analyze(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn reload_analyzers(
+        &self,
+        request: Request<ReloadAnalyzersRequest>,
+    ) -> Result<Response<ReloadAnalyzersResponse>, Status> {
+        let name = request.get_ref().name.clone();
+        let entry = self.start(&request, Operation::ReloadAnalyzers, &name, "");
+        let resp = self.inner.reload_analyzers(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn put_user_dictionary(
+        &self,
+        request: Request<PutUserDictionaryRequest>,
+    ) -> Result<Response<PutUserDictionaryResponse>, Status> {
+        let name = request.get_ref().name.clone();
+        let entry = self.start(&request, Operation::PutUserDictionary, &name, "");
+        let resp = self.inner.put_user_dictionary(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+
+    async fn delete_user_dictionary(
+        &self,
+        request: Request<DeleteUserDictionaryRequest>,
+    ) -> Result<Response<DeleteUserDictionaryResponse>, Status> {
+        let name = request.get_ref().name.clone();
+        let entry = self.start(&request, Operation::DeleteUserDictionary, &name, "");
+        let resp = self.inner.delete_user_dictionary(request).await;
+        self.finish(entry, None, &resp).await;
+        resp
+    }
+}
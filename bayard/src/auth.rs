@@ -0,0 +1,923 @@
+pub mod middleware;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, RwLock},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequest, RequestParts},
+};
+use http::{header::AUTHORIZATION, HeaderMap};
+use lru::LruCache;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use tonic::{
+    metadata::{AsciiMetadataValue, MetadataMap},
+    service::Interceptor,
+    Code, Request, Status,
+};
+
+use crate::{
+    common::read_file,
+    proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery},
+    search::query::boolean::{create_boolean_proto_query, Occurrence, SubQuery},
+};
+
+const BASIC_SCHEME: &str = "basic";
+const API_KEY_SCHEME: &str = "apikey";
+
+const VERIFIED_CACHE_CAPACITY: usize = 1024;
+const VERIFIED_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// The metadata key of the address of the original client.
+pub const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+#[derive(Debug, Clone, Copy)]
+pub enum AuthErrorKind {
+    FileReadFailure,
+    ParseFailure,
+    InvalidConfig,
+    Unauthenticated,
+    PermissionDenied,
+}
+
+impl AuthErrorKind {
+    pub fn with_error<E>(self, source: E) -> AuthError
+    where
+        anyhow::Error: From<E>,
+    {
+        AuthError {
+            kind: self,
+            source: From::from(source),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("AuthError(kind={kind:?}, source={source})")]
+pub struct AuthError {
+    pub kind: AuthErrorKind,
+    #[source]
+    source: anyhow::Error,
+}
+
+impl AuthError {
+    pub fn add_context<C>(self, ctx: C) -> Self
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        AuthError {
+            kind: self.kind,
+            source: self.source.context(ctx),
+        }
+    }
+
+    pub fn kind(&self) -> AuthErrorKind {
+        self.kind
+    }
+
+    pub fn to_status(&self) -> Status {
+        match self.kind {
+            AuthErrorKind::PermissionDenied => {
+                Status::new(Code::PermissionDenied, self.to_string())
+            }
+            AuthErrorKind::Unauthenticated => Status::new(Code::Unauthenticated, self.to_string()),
+            _ => Status::new(Code::Internal, self.to_string()),
+        }
+    }
+}
+
+/// The privileges that are granted on the indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Privilege {
+    /// Get the index metadata, search, analyze and get the cache statistics.
+    Read,
+    /// Put and delete documents, commit and rollback.
+    Write,
+    /// Create, modify and delete the index, reload the analyzers and manage the user dictionaries.
+    Manage,
+}
+
+impl fmt::Display for Privilege {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Privilege::Read => write!(f, "read"),
+            Privilege::Write => write!(f, "write"),
+            Privilege::Manage => write!(f, "manage"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct GrantConfig {
+    indices: Vec<String>,
+    privileges: Vec<Privilege>,
+    #[serde(default)]
+    filter: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct UserConfig {
+    password_hash: String,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ApiKeyConfig {
+    key: String,
+    #[serde(default)]
+    roles: Vec<String>,
+    #[serde(default)]
+    filter: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct AuthConfig {
+    #[serde(default)]
+    roles: HashMap<String, Vec<GrantConfig>>,
+    #[serde(default)]
+    users: HashMap<String, UserConfig>,
+    #[serde(default)]
+    api_keys: HashMap<String, ApiKeyConfig>,
+}
+
+/// Parses the filter query that is written as `{"kind": <KIND>, "options": <OPTIONS>}`.
+fn parse_filter(filter: &serde_json::Value) -> Result<ProtoQuery, AuthError> {
+    let kind = filter["kind"]
+        .as_str()
+        .and_then(ProtoQueryKind::from_str)
+        .ok_or_else(|| {
+            AuthErrorKind::InvalidConfig.with_error(anyhow::anyhow!(
+                "Invalid filter query kind: {}",
+                filter["kind"]
+            ))
+        })?;
+    let options = match filter["options"].as_object() {
+        Some(options) => serde_json::to_vec(options)
+            .map_err(|error| AuthErrorKind::InvalidConfig.with_error(error))?,
+        None => Vec::new(),
+    };
+
+    Ok(ProtoQuery {
+        kind: kind as i32,
+        options,
+    })
+}
+
+/// The privileges granted on the indices whose names match the patterns.
+/// The filter restricts the documents that can be read with the grant.
+#[derive(Clone)]
+struct Grant {
+    indices: Regex,
+    privileges: Vec<Privilege>,
+    filter: Option<ProtoQuery>,
+}
+
+impl Grant {
+    fn new(config: &GrantConfig) -> Result<Self, AuthError> {
+        // The patterns are the index names that may contain `*` wildcards.
+        let patterns = config
+            .indices
+            .iter()
+            .map(|pattern| {
+                pattern
+                    .split('*')
+                    .map(regex::escape)
+                    .collect::<Vec<_>>()
+                    .join(".*")
+            })
+            .collect::<Vec<_>>();
+        let indices = Regex::new(&format!("^(?:{})$", patterns.join("|")))
+            .map_err(|error| AuthErrorKind::InvalidConfig.with_error(error))?;
+
+        let filter = config.filter.as_ref().map(parse_filter).transpose()?;
+
+        Ok(Self {
+            indices,
+            privileges: config.privileges.clone(),
+            filter,
+        })
+    }
+
+    fn allows(&self, privilege: Privilege, index: &str) -> bool {
+        self.privileges.contains(&privilege) && self.indices.is_match(index)
+    }
+}
+
+/// The authenticated user or API key and the privileges granted by its roles.
+/// The filter of the API key restricts the documents that can be read on all indices.
+#[derive(Clone)]
+pub struct Principal {
+    name: String,
+    grants: Arc<Vec<Grant>>,
+    filter: Option<ProtoQuery>,
+}
+
+impl fmt::Debug for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Principal")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl Principal {
+    /// The principal that has all privileges on all indices, used when the authentication is disabled.
+    pub fn anonymous() -> Self {
+        let grant = Grant::new(&GrantConfig {
+            indices: vec!["*".to_string()],
+            privileges: vec![Privilege::Read, Privilege::Write, Privilege::Manage],
+            filter: None,
+        })
+        .unwrap();
+
+        Self {
+            name: "anonymous".to_string(),
+            grants: Arc::new(vec![grant]),
+            filter: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn authorize(&self, privilege: Privilege, index: &str) -> Result<(), AuthError> {
+        if self
+            .grants
+            .iter()
+            .any(|grant| grant.allows(privilege, index))
+        {
+            return Ok(());
+        }
+
+        Err(AuthErrorKind::PermissionDenied.with_error(anyhow::anyhow!(
+            "{:?} does not have the {} privilege on index {:?}.",
+            self.name,
+            privilege,
+            index
+        )))
+    }
+
+    /// Returns the filter query that every search on the index must be restricted with,
+    /// or `None` if the principal can read all documents of the index.
+    /// The documents readable by any of the read grants on the index are allowed, so a grant
+    /// without a filter lifts the restriction of the others. The filter of the API key is
+    /// always applied on top of them.
+    pub fn document_filter(&self, index: &str) -> Option<ProtoQuery> {
+        let grants = self
+            .grants
+            .iter()
+            .filter(|grant| grant.allows(Privilege::Read, index))
+            .collect::<Vec<_>>();
+
+        let mut filters = Vec::new();
+        if !grants.is_empty() && grants.iter().all(|grant| grant.filter.is_some()) {
+            let mut grant_filters = grants
+                .iter()
+                .filter_map(|grant| grant.filter.clone())
+                .collect::<Vec<_>>();
+            if grant_filters.len() == 1 {
+                filters.append(&mut grant_filters);
+            } else {
+                filters.push(create_boolean_proto_query(
+                    grant_filters
+                        .into_iter()
+                        .map(|filter| SubQuery::new(Occurrence::Should, filter))
+                        .collect(),
+                ));
+            }
+        }
+        if let Some(filter) = &self.filter {
+            filters.push(filter.clone());
+        }
+
+        match filters.len() {
+            0 => None,
+            1 => filters.pop(),
+            _ => Some(create_boolean_proto_query(
+                filters
+                    .into_iter()
+                    .map(|filter| SubQuery::new(Occurrence::Filter, filter))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// The users and the API keys in the auth file.
+struct Accounts {
+    users: HashMap<String, (String, Principal)>,
+    api_keys: HashMap<String, Principal>,
+}
+
+impl Accounts {
+    fn new(config_bytes: &[u8]) -> Result<Self, AuthError> {
+        let config = serde_json::from_slice::<AuthConfig>(config_bytes)
+            .map_err(|error| AuthErrorKind::ParseFailure.with_error(error))?;
+
+        let mut roles = HashMap::new();
+        for (role_name, grant_configs) in config.roles.iter() {
+            let mut grants = Vec::new();
+            for grant_config in grant_configs.iter() {
+                grants.push(
+                    Grant::new(grant_config)
+                        .map_err(|error| error.add_context(format!("role {:?}", role_name)))?,
+                );
+            }
+            roles.insert(role_name.as_str(), grants);
+        }
+
+        let principal = |name: &str,
+                         role_names: &[String],
+                         filter: Option<ProtoQuery>|
+         -> Result<Principal, AuthError> {
+            let mut grants = Vec::new();
+            for role_name in role_names.iter() {
+                let role = roles.get(role_name.as_str()).ok_or_else(|| {
+                    AuthErrorKind::InvalidConfig.with_error(anyhow::anyhow!(
+                        "Role {:?} of {:?} is not defined.",
+                        role_name,
+                        name
+                    ))
+                })?;
+                grants.extend(role.iter().cloned());
+            }
+
+            Ok(Principal {
+                name: name.to_string(),
+                grants: Arc::new(grants),
+                filter,
+            })
+        };
+
+        let mut users = HashMap::new();
+        for (name, user_config) in config.users.iter() {
+            user_config
+                .password_hash
+                .parse::<bcrypt::HashParts>()
+                .map_err(|error| {
+                    AuthErrorKind::InvalidConfig
+                        .with_error(error)
+                        .add_context(format!("password hash of user {:?}", name))
+                })?;
+            users.insert(
+                name.clone(),
+                (
+                    user_config.password_hash.clone(),
+                    principal(name, &user_config.roles, None)?,
+                ),
+            );
+        }
+
+        let mut api_keys = HashMap::new();
+        for (name, api_key_config) in config.api_keys.iter() {
+            if api_key_config.key.is_empty() {
+                return Err(AuthErrorKind::InvalidConfig
+                    .with_error(anyhow::anyhow!("API key {:?} is empty.", name)));
+            }
+            let filter = api_key_config
+                .filter
+                .as_ref()
+                .map(parse_filter)
+                .transpose()
+                .map_err(|error| error.add_context(format!("API key {:?}", name)))?;
+            if api_keys
+                .insert(
+                    api_key_config.key.clone(),
+                    principal(name, &api_key_config.roles, filter)?,
+                )
+                .is_some()
+            {
+                return Err(AuthErrorKind::InvalidConfig
+                    .with_error(anyhow::anyhow!("API key {:?} is duplicated.", name)));
+            }
+        }
+
+        Ok(Self { users, api_keys })
+    }
+
+    async fn verify_password(&self, credentials: &str) -> Result<Principal, AuthError> {
+        let invalid_credentials = || {
+            AuthErrorKind::Unauthenticated
+                .with_error(anyhow::anyhow!("Invalid user name or password."))
+        };
+
+        let decoded = base64::decode(credentials).map_err(|_| invalid_credentials())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid_credentials())?;
+        let (name, password) = decoded.split_once(':').ok_or_else(invalid_credentials)?;
+        let (password_hash, principal) = self.users.get(name).ok_or_else(invalid_credentials)?;
+
+        let password = password.to_string();
+        let password_hash = password_hash.clone();
+        let verified =
+            tokio::task::spawn_blocking(move || bcrypt::verify(password, &password_hash))
+                .await
+                .map_err(|error| AuthErrorKind::Unauthenticated.with_error(error))?
+                .map_err(|error| AuthErrorKind::Unauthenticated.with_error(error))?;
+        if !verified {
+            return Err(invalid_credentials());
+        }
+
+        Ok(principal.clone())
+    }
+}
+
+/// Authenticates the requests with the users and the API keys in the auth file.
+pub struct Authenticator {
+    accounts: RwLock<Arc<Accounts>>,
+    // The credentials that have already been verified, so that the password hashes that are
+    // deliberately slow to compute are not verified on every request. They are keyed by the
+    // digest of the credentials, so that the passwords are not kept in memory.
+    verified: Mutex<LruCache<[u8; 32], (Instant, Principal)>>,
+}
+
+impl Authenticator {
+    pub fn new(config_bytes: &[u8]) -> Result<Self, AuthError> {
+        Ok(Self {
+            accounts: RwLock::new(Arc::new(Accounts::new(config_bytes)?)),
+            verified: Mutex::new(LruCache::new(VERIFIED_CACHE_CAPACITY)),
+        })
+    }
+
+    pub async fn load(path: &Path) -> Result<Self, AuthError> {
+        let config_bytes = read_file(path)
+            .await
+            .map_err(|error| AuthErrorKind::FileReadFailure.with_error(error))?;
+
+        Self::new(&config_bytes)
+    }
+
+    /// Replaces the users and the API keys with the ones in the auth file.
+    /// The cached credentials are dropped, so that the changed passwords and roles take effect.
+    pub async fn reload(&self, path: &Path) -> Result<(), AuthError> {
+        let config_bytes = read_file(path)
+            .await
+            .map_err(|error| AuthErrorKind::FileReadFailure.with_error(error))?;
+        let accounts = Arc::new(Accounts::new(&config_bytes)?);
+
+        let mut verified = self.lock_verified();
+        *self
+            .accounts
+            .write()
+            .unwrap_or_else(|error| error.into_inner()) = accounts;
+        verified.clear();
+
+        Ok(())
+    }
+
+    fn accounts(&self) -> Arc<Accounts> {
+        Arc::clone(
+            &self
+                .accounts
+                .read()
+                .unwrap_or_else(|error| error.into_inner()),
+        )
+    }
+
+    fn lock_verified(&self) -> MutexGuard<'_, LruCache<[u8; 32], (Instant, Principal)>> {
+        self.verified.lock().unwrap_or_else(|error| {
+            let mut verified = error.into_inner();
+            verified.clear();
+            self.verified.clear_poison();
+            verified
+        })
+    }
+
+    /// Authenticates the credentials in the `Authorization` header.
+    /// The `Basic` scheme takes the user name and the password, and the `ApiKey` scheme takes the API key.
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, AuthError> {
+        let authorization = headers
+            .get(AUTHORIZATION)
+            .ok_or_else(|| {
+                AuthErrorKind::Unauthenticated
+                    .with_error(anyhow::anyhow!("Credentials are required."))
+            })?
+            .to_str()
+            .map_err(|error| AuthErrorKind::Unauthenticated.with_error(error))?;
+
+        let (scheme, credentials) = authorization.split_once(' ').ok_or_else(|| {
+            AuthErrorKind::Unauthenticated
+                .with_error(anyhow::anyhow!("Invalid authorization header."))
+        })?;
+        let credentials = credentials.trim();
+
+        let accounts = self.accounts();
+        match scheme.to_lowercase().as_str() {
+            API_KEY_SCHEME => accounts.api_keys.get(credentials).cloned().ok_or_else(|| {
+                AuthErrorKind::Unauthenticated.with_error(anyhow::anyhow!("Invalid API key."))
+            }),
+            BASIC_SCHEME => {
+                let key: [u8; 32] = Sha256::digest(credentials.as_bytes()).into();
+                if let Some((verified_at, principal)) = self.lock_verified().get(&key) {
+                    if verified_at.elapsed() < VERIFIED_CACHE_TTL {
+                        return Ok(principal.clone());
+                    }
+                }
+
+                let principal = accounts.verify_password(credentials).await?;
+
+                // The principal is not cached if the auth file has been reloaded meanwhile.
+                let mut verified = self.lock_verified();
+                if Arc::ptr_eq(&accounts, &self.accounts()) {
+                    verified.put(key, (Instant::now(), principal.clone()));
+                }
+
+                Ok(principal)
+            }
+            _ => Err(AuthErrorKind::Unauthenticated.with_error(anyhow::anyhow!(
+                "Unsupported authorization scheme: {:?}",
+                scheme
+            ))),
+        }
+    }
+}
+
+/// Checks that the principal of the gRPC request has the privilege on the index.
+pub fn authorize<T>(
+    request: &Request<T>,
+    privilege: Privilege,
+    index: &str,
+) -> Result<(), AuthError> {
+    request
+        .extensions()
+        .get::<Principal>()
+        .ok_or_else(|| {
+            AuthErrorKind::Unauthenticated.with_error(anyhow::anyhow!("Credentials are required."))
+        })?
+        .authorize(privilege, index)
+}
+
+/// Returns the filter query that restricts the documents the principal of the gRPC request can
+/// read in the index.
+pub fn document_filter<T>(request: &Request<T>, index: &str) -> Option<ProtoQuery> {
+    request
+        .extensions()
+        .get::<Principal>()
+        .and_then(|principal| principal.document_filter(index))
+}
+
+/// The credentials and the address of the original request that are forwarded to the gRPC calls
+/// made on behalf of it.
+#[derive(Clone, Default)]
+pub struct Credentials {
+    authorization: Option<AsciiMetadataValue>,
+    forwarded_for: Option<AsciiMetadataValue>,
+}
+
+impl Credentials {
+    pub fn from_metadata(metadata: &MetadataMap) -> Self {
+        Self {
+            authorization: metadata.get(AUTHORIZATION.as_str()).cloned(),
+            forwarded_for: metadata.get(X_FORWARDED_FOR).cloned(),
+        }
+    }
+
+    /// Takes the credentials from the gRPC request. If the request has not been forwarded, the
+    /// remote address of the request is forwarded as the original address.
+    pub fn from_request<T>(request: &Request<T>) -> Self {
+        let mut credentials = Self::from_metadata(request.metadata());
+        if credentials.forwarded_for.is_none() {
+            credentials.forwarded_for = request
+                .remote_addr()
+                .and_then(|remote_addr| AsciiMetadataValue::try_from(remote_addr.to_string()).ok());
+        }
+        credentials
+    }
+
+    /// Takes the credentials from the HTTP request. The remote address is appended to the
+    /// `X-Forwarded-For` header set by the proxies in front of the node.
+    pub fn from_headers(headers: &HeaderMap, remote_addr: Option<SocketAddr>) -> Self {
+        let forwarded_for = match (headers.get(X_FORWARDED_FOR), remote_addr) {
+            (Some(value), Some(remote_addr)) => value
+                .to_str()
+                .ok()
+                .map(|value| format!("{}, {}", value, remote_addr)),
+            (Some(value), None) => value.to_str().ok().map(|value| value.to_string()),
+            (None, Some(remote_addr)) => Some(remote_addr.to_string()),
+            (None, None) => None,
+        };
+
+        Self {
+            authorization: headers
+                .get(AUTHORIZATION)
+                .and_then(|value| AsciiMetadataValue::try_from(value.as_bytes()).ok()),
+            forwarded_for: forwarded_for
+                .and_then(|forwarded_for| AsciiMetadataValue::try_from(forwarded_for).ok()),
+        }
+    }
+
+    /// Creates the gRPC request that carries the credentials.
+    pub fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        self.insert(request.metadata_mut());
+        request
+    }
+
+    fn insert(&self, metadata: &mut MetadataMap) {
+        if let Some(value) = &self.authorization {
+            metadata.insert(AUTHORIZATION.as_str(), value.clone());
+        }
+        if let Some(value) = &self.forwarded_for {
+            metadata.insert(X_FORWARDED_FOR, value.clone());
+        }
+    }
+}
+
+impl Interceptor for Credentials {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        self.insert(request.metadata_mut());
+        Ok(request)
+    }
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for Credentials
+where
+    B: Send,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let remote_addr = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|connect_info| connect_info.0);
+        Ok(Credentials::from_headers(req.headers(), remote_addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use http::{header::AUTHORIZATION, HeaderMap, HeaderValue};
+    use serde_json::Value;
+    use tempdir::TempDir;
+
+    use crate::auth::{Authenticator, Principal, Privilege};
+
+    async fn api_key(authenticator: &Authenticator, key: &str) -> Principal {
+        authenticator
+            .authenticate(&headers(&format!("ApiKey {}", key)))
+            .await
+            .unwrap()
+    }
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn authenticator() -> Authenticator {
+        let config = serde_json::json!({
+            "roles": {
+                "admin": [
+                    {"indices": ["*"], "privileges": ["read", "write", "manage"]}
+                ],
+                "example_writer": [
+                    {"indices": ["example", "example_*"], "privileges": ["read", "write"]}
+                ]
+            },
+            "users": {
+                "alice": {
+                    "password_hash": bcrypt::hash("secret", 4).unwrap(),
+                    "roles": ["admin"]
+                },
+                "bob": {
+                    "password_hash": bcrypt::hash("password", 4).unwrap(),
+                    "roles": ["example_writer"]
+                }
+            },
+            "api_keys": {
+                "ingest": {"key": "abcdef", "roles": ["example_writer"]}
+            }
+        });
+
+        Authenticator::new(&serde_json::to_vec(&config).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let authenticator = authenticator();
+
+        let principal = authenticator
+            .authenticate(&headers(&format!(
+                "Basic {}",
+                base64::encode("alice:secret")
+            )))
+            .await
+            .unwrap();
+        assert_eq!(principal.name(), "alice");
+
+        // The verified credentials are cached.
+        let principal = authenticator
+            .authenticate(&headers(&format!(
+                "basic {}",
+                base64::encode("alice:secret")
+            )))
+            .await
+            .unwrap();
+        assert_eq!(principal.name(), "alice");
+
+        let principal = authenticator
+            .authenticate(&headers("ApiKey abcdef"))
+            .await
+            .unwrap();
+        assert_eq!(principal.name(), "ingest");
+
+        assert!(authenticator
+            .authenticate(&headers(&format!(
+                "Basic {}",
+                base64::encode("alice:password")
+            )))
+            .await
+            .is_err());
+        assert!(authenticator
+            .authenticate(&headers(&format!(
+                "Basic {}",
+                base64::encode("carol:secret")
+            )))
+            .await
+            .is_err());
+        assert!(authenticator
+            .authenticate(&headers("ApiKey abc"))
+            .await
+            .is_err());
+        assert!(authenticator
+            .authenticate(&headers("Bearer abcdef"))
+            .await
+            .is_err());
+        assert!(authenticator.authenticate(&HeaderMap::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let authenticator = authenticator();
+
+        let principal = authenticator
+            .authenticate(&headers(&format!(
+                "Basic {}",
+                base64::encode("bob:password")
+            )))
+            .await
+            .unwrap();
+        assert!(principal.authorize(Privilege::Read, "example").is_ok());
+        assert!(principal
+            .authorize(Privilege::Write, "example_2022")
+            .is_ok());
+        assert!(principal.authorize(Privilege::Manage, "example").is_err());
+        assert!(principal.authorize(Privilege::Read, "examples").is_err());
+        assert!(principal.authorize(Privilege::Read, "other").is_err());
+
+        let principal = authenticator
+            .authenticate(&headers(&format!(
+                "Basic {}",
+                base64::encode("alice:secret")
+            )))
+            .await
+            .unwrap();
+        assert!(principal.authorize(Privilege::Manage, "other").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_document_filter() {
+        let term_filter = |term: &str| {
+            serde_json::json!({
+                "kind": "term",
+                "options": {"term": term, "field": "tenant"}
+            })
+        };
+        let config = serde_json::json!({
+            "roles": {
+                "tenant_a": [
+                    {"indices": ["shared"], "privileges": ["read"], "filter": term_filter("a")}
+                ],
+                "tenant_b": [
+                    {"indices": ["shared"], "privileges": ["read"], "filter": term_filter("b")}
+                ],
+                "reader": [
+                    {"indices": ["*"], "privileges": ["read"]}
+                ]
+            },
+            "api_keys": {
+                "a": {"key": "a", "roles": ["tenant_a"]},
+                "ab": {"key": "ab", "roles": ["tenant_a", "tenant_b"]},
+                "all": {"key": "all", "roles": ["tenant_a", "reader"]},
+                "restricted": {"key": "restricted", "roles": ["reader"], "filter": term_filter("c")}
+            }
+        });
+        let authenticator = Authenticator::new(&serde_json::to_vec(&config).unwrap()).unwrap();
+        let filter = |principal: Principal, index: &str| {
+            principal
+                .document_filter(index)
+                .map(|filter| serde_json::from_slice::<Value>(&filter.options).unwrap())
+        };
+
+        assert_eq!(
+            filter(api_key(&authenticator, "a").await, "shared"),
+            Some(serde_json::json!({"term": "a", "field": "tenant"}))
+        );
+
+        // The filters of the roles are combined with OR.
+        let options = filter(api_key(&authenticator, "ab").await, "shared").unwrap();
+        assert_eq!(options["subqueries"][0]["occurrence"], "should");
+        assert_eq!(options["subqueries"][1]["occurrence"], "should");
+
+        // The role without a filter can read all documents.
+        assert_eq!(filter(api_key(&authenticator, "all").await, "shared"), None);
+
+        // The filter of the API key is applied on all indices.
+        assert_eq!(
+            filter(api_key(&authenticator, "restricted").await, "other"),
+            Some(serde_json::json!({"term": "c", "field": "tenant"}))
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        // Undefined role.
+        assert!(Authenticator::new(
+            br#"{"api_keys": {"ingest": {"key": "abcdef", "roles": ["writer"]}}}"#
+        )
+        .is_err());
+        // Plain text password.
+        assert!(Authenticator::new(
+            br#"{"users": {"alice": {"password_hash": "secret", "roles": []}}}"#
+        )
+        .is_err());
+        // Unknown filter query kind.
+        assert!(Authenticator::new(
+            br#"{"roles": {"reader": [{"indices": ["*"], "privileges": ["read"], "filter": {"kind": "unknown"}}]}}"#
+        )
+        .is_err());
+        // Unknown privilege.
+        assert!(Authenticator::new(
+            br#"{"roles": {"admin": [{"indices": ["*"], "privileges": ["all"]}]}}"#
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let config = |password: &str, privileges: &[&str]| {
+            serde_json::to_vec(&serde_json::json!({
+                "roles": {
+                    "example_user": [
+                        {"indices": ["example"], "privileges": privileges}
+                    ]
+                },
+                "users": {
+                    "alice": {
+                        "password_hash": bcrypt::hash(password, 4).unwrap(),
+                        "roles": ["example_user"]
+                    }
+                }
+            }))
+            .unwrap()
+        };
+        let basic = |credentials: &str| headers(&format!("Basic {}", base64::encode(credentials)));
+
+        let auth_dir = TempDir::new("auth").unwrap();
+        let auth_file = auth_dir.path().join("auth.json");
+        fs::write(&auth_file, config("secret", &["read"])).unwrap();
+        let authenticator = Authenticator::load(&auth_file).await.unwrap();
+
+        // The verified credentials are cached.
+        let principal = authenticator
+            .authenticate(&basic("alice:secret"))
+            .await
+            .unwrap();
+        assert!(principal.authorize(Privilege::Write, "example").is_err());
+
+        // The cached credentials are dropped on reload.
+        fs::write(&auth_file, config("changed", &["read", "write"])).unwrap();
+        authenticator.reload(&auth_file).await.unwrap();
+        assert!(authenticator
+            .authenticate(&basic("alice:secret"))
+            .await
+            .is_err());
+        let principal = authenticator
+            .authenticate(&basic("alice:changed"))
+            .await
+            .unwrap();
+        assert!(principal.authorize(Privilege::Write, "example").is_ok());
+
+        // An invalid auth file keeps the current users.
+        fs::write(&auth_file, b"{").unwrap();
+        assert!(authenticator.reload(&auth_file).await.is_err());
+        assert!(authenticator
+            .authenticate(&basic("alice:changed"))
+            .await
+            .is_ok());
+    }
+}
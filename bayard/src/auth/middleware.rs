@@ -0,0 +1,271 @@
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_util::future::BoxFuture;
+use http::{
+    header::WWW_AUTHENTICATE, Extensions, HeaderValue, Method, Request as HttpRequest,
+    Response as HttpResponse, StatusCode,
+};
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use tower::{Layer, Service};
+use tracing::warn;
+
+use crate::{
+    auth::{AuthError, AuthErrorKind, Authenticator, Principal, Privilege},
+    tls::TlsConfig,
+};
+
+const HTTP_HEALTHCHECK_PATH: &str = "/healthcheck/";
+const GRPC_HEALTHCHECK_PATH: &str = "/healthcheck.HealthCheckService/";
+// The Raft RPCs and the shard hand-offs are made between the nodes, which are identified by the
+// server name in their client certificates.
+const GRPC_INTERNAL_PATHS: [&str; 2] = ["/raft.RaftService/", "/shard.ShardService/"];
+// Decommissioning a node moves the shards of all indices.
+const HTTP_DECOMMISSION_PATH: &str = "/cluster/decommission";
+const ALL_INDICES: &str = "*";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Http,
+    Grpc,
+}
+
+/// The layer that authenticates the requests and puts the [`Principal`] into the request extensions.
+/// The HTTP requests are also authorized by the method and the path, while the gRPC requests are
+/// authorized by the services because the index name is in the message.
+/// If the authenticator is not given, the requests are served as the anonymous principal.
+/// If the TLS config that requires the client certificates is given, the internal gRPC services
+/// are served only to the nodes.
+#[derive(Clone)]
+pub struct AuthLayer {
+    authenticator: Option<Arc<Authenticator>>,
+    tls_config: Option<Arc<TlsConfig>>,
+    protocol: Protocol,
+}
+
+impl AuthLayer {
+    pub fn http(authenticator: Option<Arc<Authenticator>>) -> Self {
+        Self {
+            authenticator,
+            tls_config: None,
+            protocol: Protocol::Http,
+        }
+    }
+
+    pub fn grpc(authenticator: Option<Arc<Authenticator>>, tls_config: Option<TlsConfig>) -> Self {
+        Self {
+            authenticator,
+            tls_config: tls_config
+                .filter(|tls_config| tls_config.client_auth())
+                .map(Arc::new),
+            protocol: Protocol::Grpc,
+        }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            authenticator: self.authenticator.clone(),
+            tls_config: self.tls_config.clone(),
+            protocol: self.protocol,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    authenticator: Option<Arc<Authenticator>>,
+    tls_config: Option<Arc<TlsConfig>>,
+    protocol: Protocol,
+}
+
+impl<S, ReqBody, ResBody> Service<HttpRequest<ReqBody>> for AuthService<S>
+where
+    S: Service<HttpRequest<ReqBody>, Response = HttpResponse<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+    ResBody: Default,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: HttpRequest<ReqBody>) -> Self::Future {
+        // The inner service that has been driven to readiness is used for this request.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let authenticator = self.authenticator.clone();
+        let tls_config = self.tls_config.clone();
+        let protocol = self.protocol;
+
+        Box::pin(async move {
+            let path = request.uri().path().to_string();
+            let healthcheck_path = match protocol {
+                Protocol::Http => HTTP_HEALTHCHECK_PATH,
+                Protocol::Grpc => GRPC_HEALTHCHECK_PATH,
+            };
+            if path.starts_with(healthcheck_path) {
+                return inner.call(request).await;
+            }
+            if protocol == Protocol::Grpc
+                && GRPC_INTERNAL_PATHS
+                    .iter()
+                    .any(|internal_path| path.starts_with(internal_path))
+            {
+                if let Some(tls_config) = tls_config {
+                    if let Err(error) = verify_node(&tls_config, request.extensions()) {
+                        warn!(?path, ?error, "Failed to verify node.");
+                        return Ok(error_response(protocol, &error));
+                    }
+                }
+                return inner.call(request).await;
+            }
+
+            let principal = match authenticator {
+                Some(authenticator) => match authenticator.authenticate(request.headers()).await {
+                    Ok(principal) => principal,
+                    Err(error) => {
+                        warn!(?path, ?error, "Failed to authenticate.");
+                        return Ok(error_response(protocol, &error));
+                    }
+                },
+                None => Principal::anonymous(),
+            };
+
+            if protocol == Protocol::Http {
+                if let Some((privilege, index)) = http_privilege(request.method(), &path) {
+                    if let Err(error) = principal.authorize(privilege, index) {
+                        warn!(?path, ?error, "Failed to authorize.");
+                        return Ok(error_response(protocol, &error));
+                    }
+                }
+            }
+
+            request.extensions_mut().insert(principal);
+            inner.call(request).await
+        })
+    }
+}
+
+/// Verifies that the client is a node by the certificate that it has presented. The other gRPC
+/// clients may present the certificates signed by the same CA, but not for the server name.
+fn verify_node(tls_config: &TlsConfig, extensions: &Extensions) -> Result<(), AuthError> {
+    let certs = extensions
+        .get::<TlsConnectInfo<TcpConnectInfo>>()
+        .and_then(|connect_info| connect_info.peer_certs())
+        .ok_or_else(|| {
+            AuthErrorKind::Unauthenticated
+                .with_error(anyhow::anyhow!("No client certificate has been presented."))
+        })?;
+    let cert = certs.first().ok_or_else(|| {
+        AuthErrorKind::Unauthenticated
+            .with_error(anyhow::anyhow!("No client certificate has been presented."))
+    })?;
+    tls_config
+        .verify_node_certificate(cert.get_ref())
+        .map_err(|error| AuthErrorKind::PermissionDenied.with_error(error))
+}
+
+fn error_response<B: Default>(protocol: Protocol, error: &AuthError) -> HttpResponse<B> {
+    match protocol {
+        Protocol::Http => {
+            let mut response = HttpResponse::new(B::default());
+            match error.kind() {
+                AuthErrorKind::PermissionDenied => {
+                    *response.status_mut() = StatusCode::FORBIDDEN;
+                }
+                _ => {
+                    *response.status_mut() = StatusCode::UNAUTHORIZED;
+                    response.headers_mut().insert(
+                        WWW_AUTHENTICATE,
+                        HeaderValue::from_static("Basic realm=\"bayard\""),
+                    );
+                }
+            }
+            response
+        }
+        Protocol::Grpc => {
+            let (parts, _) = error.to_status().to_http().into_parts();
+            HttpResponse::from_parts(parts, B::default())
+        }
+    }
+}
+
+/// Returns the privilege and the index that the HTTP request requires.
+/// The requests that are not for an index, such as `/cluster/nodes`, only require the authentication,
+/// except for decommissioning a node, which requires the manage privilege on all indices.
+fn http_privilege<'a>(method: &Method, path: &'a str) -> Option<(Privilege, &'a str)> {
+    if path == HTTP_DECOMMISSION_PATH {
+        return Some((Privilege::Manage, ALL_INDICES));
+    }
+
+    let mut segments = path.trim_start_matches('/').split('/');
+    if segments.next() != Some("indices") {
+        return None;
+    }
+    let index = segments.next().filter(|index| !index.is_empty())?;
+
+    let privilege = match (method, segments.next()) {
+        (&Method::GET, None) => Privilege::Read,
+        (_, None) => Privilege::Manage,
+        (_, Some("documents" | "commit" | "rollback")) => Privilege::Write,
+        (_, Some("reload_analyzers" | "user_dictionaries")) => Privilege::Manage,
+        _ => Privilege::Read,
+    };
+
+    Some((privilege, index))
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use crate::auth::{middleware::http_privilege, Privilege};
+
+    #[test]
+    fn test_http_privilege() {
+        assert_eq!(
+            http_privilege(&Method::GET, "/indices/example"),
+            Some((Privilege::Read, "example"))
+        );
+        assert_eq!(
+            http_privilege(&Method::PUT, "/indices/example"),
+            Some((Privilege::Manage, "example"))
+        );
+        assert_eq!(
+            http_privilege(&Method::DELETE, "/indices/example/documents"),
+            Some((Privilege::Write, "example"))
+        );
+        assert_eq!(
+            http_privilege(&Method::GET, "/indices/example/commit"),
+            Some((Privilege::Write, "example"))
+        );
+        assert_eq!(
+            http_privilege(&Method::POST, "/indices/example/search"),
+            Some((Privilege::Read, "example"))
+        );
+        assert_eq!(
+            http_privilege(
+                &Method::PUT,
+                "/indices/example/user_dictionaries/userdic.csv"
+            ),
+            Some((Privilege::Manage, "example"))
+        );
+        assert_eq!(http_privilege(&Method::GET, "/cluster/nodes"), None);
+        assert_eq!(
+            http_privilege(&Method::POST, "/cluster/decommission"),
+            Some((Privilege::Manage, "*"))
+        );
+    }
+}
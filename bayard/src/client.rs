@@ -22,15 +22,19 @@ use tonic::{
 use tracing::{error, info, warn};
 
 use crate::{
+    auth::Credentials,
     cluster::{member::Member, members::Members, membership::Membership},
-    index::metastore::Metastore,
+    index::{metastore::Metastore, shards::Shards, DOC_ID_FIELD_NAME},
     proto::index::{
-        index_service_client::IndexServiceClient, sort::Order, CommitRequest, CommitResponse,
-        CreateIndexRequest, CreateIndexResponse, DeleteDocumentsRequest, DeleteDocumentsResponse,
-        DeleteIndexRequest, DeleteIndexResponse, GetIndexRequest, GetIndexResponse,
-        ModifyIndexRequest, ModifyIndexResponse, PutDocumentsRequest, PutDocumentsResponse,
-        RollbackRequest, RollbackResponse, SearchRequest, SearchResponse,
+        index_service_client::IndexServiceClient, query::Kind as QueryKind, sort::Order,
+        CollectionKind, CommitRequest, CommitResponse, CreateIndexRequest, CreateIndexResponse,
+        DeleteDocumentsRequest, DeleteDocumentsResponse, DeleteIndexRequest, DeleteIndexResponse,
+        GetIndexRequest, GetIndexResponse, ModifyIndexRequest, ModifyIndexResponse,
+        PutDocumentsRequest, PutDocumentsResponse, Query, RollbackRequest, RollbackResponse,
+        SearchRequest, SearchResponse,
     },
+    search::query::more_like_this::{resolve_more_like_this_docs, unresolved_more_like_this_docs},
+    tls::TlsConfig,
 };
 
 const EXPONENTIAL_BACKOFF_RETRIES: u32 = 5;
@@ -40,6 +44,7 @@ const EXPONENTIAL_BACKOFF_MAX_DURATION: Duration = Duration::from_millis(3000);
 #[derive(Debug, Clone, Copy)]
 pub enum ClientErrorKind {
     UriCreationFailure,
+    TlsConfigurationFailure,
     MetadataNotFound,
     GrpcAddressNotFound,
 }
@@ -80,17 +85,36 @@ impl ClientError {
     }
 }
 
-pub fn create_index_client(
+pub fn create_channel(
     grpc_address: &SocketAddr,
-) -> Result<IndexServiceClient<Channel>, ClientError> {
-    info!(?grpc_address, "Creating grpc client.");
+    tls_config: Option<&TlsConfig>,
+) -> Result<Channel, ClientError> {
     let uri = Uri::builder()
-        .scheme("http")
+        .scheme(if tls_config.is_some() {
+            "https"
+        } else {
+            "http"
+        })
         .authority(grpc_address.to_string())
         .path_and_query("/")
         .build()
         .map_err(|error| ClientErrorKind::UriCreationFailure.with_error(error))?;
-    let channel = Endpoint::from(uri).connect_lazy();
+    let endpoint = match tls_config {
+        Some(tls_config) => Endpoint::from(uri)
+            .tls_config(tls_config.client_tls_config())
+            .map_err(|error| ClientErrorKind::TlsConfigurationFailure.with_error(error))?,
+        None => Endpoint::from(uri),
+    };
+
+    Ok(endpoint.connect_lazy())
+}
+
+pub fn create_index_client(
+    grpc_address: &SocketAddr,
+    tls_config: Option<&TlsConfig>,
+) -> Result<IndexServiceClient<Channel>, ClientError> {
+    info!(?grpc_address, "Creating grpc client.");
+    let channel = create_channel(grpc_address, tls_config)?;
     let client = IndexServiceClient::new(channel);
 
     Ok(client)
@@ -102,11 +126,15 @@ pub struct ClientPool {
 }
 
 impl ClientPool {
-    pub fn new(members: Vec<Member>, mut members_stream: WatchStream<Members>) -> Self {
+    pub fn new(
+        members: Vec<Member>,
+        mut members_stream: WatchStream<Members>,
+        tls_config: Option<TlsConfig>,
+    ) -> Self {
         let mut inner = HashMap::new();
         for member in members.iter() {
             // Get metadata from member
-            let metadata = if let Some(metadata) = member.metadata {
+            let metadata = if let Some(metadata) = &member.metadata {
                 metadata
             } else {
                 error!(?member, "failed to get metadata");
@@ -122,7 +150,7 @@ impl ClientPool {
             };
 
             // Create gRPC client.
-            let grpc_client = create_index_client(&grpc_address).unwrap();
+            let grpc_client = create_index_client(&grpc_address, tls_config.as_ref()).unwrap();
             inner.insert(member.addr, grpc_client);
         }
         let members = Members::init(members);
@@ -142,7 +170,7 @@ impl ClientPool {
                 // Add new gRPC clients.
                 for member in received_members.iter() {
                     // Get metadata from member
-                    let metadata = if let Some(metadata) = member.metadata {
+                    let metadata = if let Some(metadata) = &member.metadata {
                         metadata
                     } else {
                         error!(?member, "failed to get metadata");
@@ -159,7 +187,7 @@ impl ClientPool {
                         };
 
                         // Create gRPC client.
-                        let client = match create_index_client(&grpc_address) {
+                        let client = match create_index_client(&grpc_address, tls_config.as_ref()) {
                             Ok(client) => client,
                             Err(err) => {
                                 error!(?err, "failed to create gRPC client");
@@ -226,20 +254,34 @@ pub struct Client {
     membership: Arc<Membership>,
     metastore: Arc<Metastore>,
     client_pool: Arc<ClientPool>,
+    tls_config: Option<TlsConfig>,
 }
 
 impl Client {
-    pub async fn new(membership: Arc<Membership>, metastore: Arc<Metastore>) -> Self {
+    pub async fn new(
+        membership: Arc<Membership>,
+        metastore: Arc<Metastore>,
+        tls_config: Option<TlsConfig>,
+    ) -> Self {
         let members = membership.members().await.iter().cloned().collect();
         let members_stream = membership.watch_members();
 
         Self {
             membership,
             metastore,
-            client_pool: Arc::new(ClientPool::new(members, members_stream)),
+            client_pool: Arc::new(ClientPool::new(members, members_stream, tls_config.clone())),
+            tls_config,
         }
     }
 
+    /// Create the client of the node that accepts the changes of the index metadata.
+    pub fn leader_client(&self, leader: &str) -> Result<IndexServiceClient<Channel>, ClientError> {
+        let grpc_address = leader
+            .parse::<SocketAddr>()
+            .map_err(|error| ClientErrorKind::GrpcAddressNotFound.with_error(error))?;
+        create_index_client(&grpc_address, self.tls_config.as_ref())
+    }
+
     pub async fn create_index(
         &self,
         request: Request<CreateIndexRequest>,
@@ -320,6 +362,7 @@ impl Client {
         &self,
         request: Request<PutDocumentsRequest>,
     ) -> Result<Response<PutDocumentsResponse>, Status> {
+        let credentials = Credentials::from_metadata(request.metadata());
         let req = request.into_inner();
 
         let metadatas = self.metastore.metadatas().await;
@@ -411,6 +454,8 @@ impl Client {
                     docs: shard_docs.clone(),
                 };
 
+                let credentials = credentials.clone();
+
                 let handle = tokio::spawn(async move {
                     let backoff = Backoff::new(
                         EXPONENTIAL_BACKOFF_RETRIES,
@@ -419,7 +464,7 @@ impl Client {
                     );
                     for duration in &backoff {
                         match client
-                            .put_documents(Request::new(distrib_req.clone()))
+                            .put_documents(credentials.request(distrib_req.clone()))
                             .await
                         {
                             Ok(response) => {
@@ -469,6 +514,7 @@ impl Client {
         &self,
         request: Request<DeleteDocumentsRequest>,
     ) -> Result<Response<DeleteDocumentsResponse>, Status> {
+        let credentials = Credentials::from_metadata(request.metadata());
         let req = request.into_inner();
 
         let metadatas = self.metastore.metadatas().await;
@@ -522,6 +568,8 @@ impl Client {
                     doc_ids: req.doc_ids.clone(),
                 };
 
+                let credentials = credentials.clone();
+
                 let handle = tokio::spawn(async move {
                     let backoff = Backoff::new(
                         EXPONENTIAL_BACKOFF_RETRIES,
@@ -530,7 +578,7 @@ impl Client {
                     );
                     for duration in &backoff {
                         match client
-                            .delete_documents(Request::new(distrib_req.clone()))
+                            .delete_documents(credentials.request(distrib_req.clone()))
                             .await
                         {
                             Ok(response) => {
@@ -580,6 +628,7 @@ impl Client {
         &self,
         request: Request<CommitRequest>,
     ) -> Result<Response<CommitResponse>, Status> {
+        let credentials = Credentials::from_metadata(request.metadata());
         let req = request.into_inner();
 
         let metadatas = self.metastore.metadatas().await;
@@ -632,6 +681,8 @@ impl Client {
                     shard_id: shard.id.clone(),
                 };
 
+                let credentials = credentials.clone();
+
                 let handle = tokio::spawn(async move {
                     let backoff = Backoff::new(
                         EXPONENTIAL_BACKOFF_RETRIES,
@@ -639,7 +690,10 @@ impl Client {
                         EXPONENTIAL_BACKOFF_MAX_DURATION,
                     );
                     for duration in &backoff {
-                        match client.commit(Request::new(distrib_req.clone())).await {
+                        match client
+                            .commit(credentials.request(distrib_req.clone()))
+                            .await
+                        {
                             Ok(response) => {
                                 return Ok(response);
                             }
@@ -684,6 +738,7 @@ impl Client {
         &self,
         request: Request<RollbackRequest>,
     ) -> Result<Response<RollbackResponse>, Status> {
+        let credentials = Credentials::from_metadata(request.metadata());
         let req = request.into_inner();
 
         let metadatas = self.metastore.metadatas().await;
@@ -736,6 +791,8 @@ impl Client {
                     shard_id: shard.id.clone(),
                 };
 
+                let credentials = credentials.clone();
+
                 let handle = tokio::spawn(async move {
                     let backoff = Backoff::new(
                         EXPONENTIAL_BACKOFF_RETRIES,
@@ -743,7 +800,10 @@ impl Client {
                         EXPONENTIAL_BACKOFF_MAX_DURATION,
                     );
                     for duration in &backoff {
-                        match client.rollback(Request::new(distrib_req.clone())).await {
+                        match client
+                            .rollback(credentials.request(distrib_req.clone()))
+                            .await
+                        {
                             Ok(response) => {
                                 return Ok(response);
                             }
@@ -791,6 +851,7 @@ impl Client {
         &self,
         request: Request<SearchRequest>,
     ) -> Result<Response<SearchResponse>, Status> {
+        let credentials = Credentials::from_metadata(request.metadata());
         let req = request.into_inner();
 
         let metadatas = self.metastore.metadatas().await;
@@ -830,22 +891,33 @@ impl Client {
             }
         };
 
+        // The source documents of the more like this queries may live on other shards,
+        // so fetch them before broadcasting the query.
+        let mut query = req.query.clone();
+        if let Some(query) = query.as_mut() {
+            self.resolve_more_like_this(&index_name, &shards, num_replicas, query, &credentials)
+                .await?;
+        }
+
         let mut handles: Vec<JoinHandle<Result<tonic::Response<SearchResponse>, Status>>> =
             Vec::new();
         for shard in shards.iter().cloned() {
             let distrib_req = SearchRequest {
                 name: index_name.clone(),
                 shard_id: shard.id.clone(),
-                query: req.query.clone(),
+                query: query.clone(),
                 collection_kind: req.collection_kind,
                 sort: req.sort.clone(),
                 fields: req.fields.clone(),
                 offset: 0,
                 hits: req.hits + req.offset,
+                post_filter: req.post_filter.clone(),
             };
 
             let client_pool = Arc::clone(&self.client_pool);
 
+            let credentials = credentials.clone();
+
             let handle = tokio::spawn(async move {
                 let backoff = Backoff::new(
                     EXPONENTIAL_BACKOFF_RETRIES,
@@ -854,7 +926,10 @@ impl Client {
                 );
                 for duration in &backoff {
                     match client_pool.rotate(&shard.id, num_replicas).await {
-                        Some(mut client) => match client.search(distrib_req.clone()).await {
+                        Some(mut client) => match client
+                            .search(credentials.request(distrib_req.clone()))
+                            .await
+                        {
                             Ok(response) => {
                                 return Ok(response);
                             }
@@ -958,4 +1033,80 @@ impl Client {
             documents: documents[req.offset as usize..end_offset as usize].to_vec(),
         }))
     }
+
+    async fn resolve_more_like_this(
+        &self,
+        index_name: &str,
+        shards: &Shards,
+        num_replicas: usize,
+        query: &mut Query,
+        credentials: &Credentials,
+    ) -> Result<(), Status> {
+        let docs = unresolved_more_like_this_docs(query);
+        if docs.is_empty() {
+            return Ok(());
+        }
+
+        let mut documents = serde_json::Map::new();
+        for (doc_id, fields) in docs {
+            let shard = shards.lookup_shard(&doc_id).ok_or_else(|| {
+                Status::new(
+                    Code::Internal,
+                    format!("Failed to lookup shard. doc_id: {}", doc_id),
+                )
+            })?;
+
+            let doc_id_query = serde_json::json!({
+                "term": doc_id,
+                "field": DOC_ID_FIELD_NAME,
+            });
+            let distrib_req = SearchRequest {
+                name: index_name.to_string(),
+                shard_id: shard.id.clone(),
+                query: Some(Query {
+                    kind: QueryKind::Term as i32,
+                    options: doc_id_query.to_string().as_bytes().to_vec(),
+                }),
+                collection_kind: CollectionKind::TopDocs as i32,
+                sort: None,
+                fields,
+                offset: 0,
+                hits: 1,
+                post_filter: None,
+            };
+
+            let mut client = self
+                .client_pool
+                .rotate(&shard.id, num_replicas)
+                .await
+                .ok_or_else(|| {
+                    Status::new(
+                        Code::Internal,
+                        format!("Failed to rotate client. shard_id: {}", shard.id),
+                    )
+                })?;
+            let response = client
+                .search(credentials.request(distrib_req))
+                .await?
+                .into_inner();
+            let doc = response.documents.first().ok_or_else(|| {
+                Status::new(
+                    Code::NotFound,
+                    format!("Document does not exist. doc_id: {}", doc_id),
+                )
+            })?;
+            let fields =
+                serde_json::from_slice::<serde_json::Value>(&doc.fields).map_err(|error| {
+                    Status::new(
+                        Code::Internal,
+                        format!("Failed to parse document fields: {}", error),
+                    )
+                })?;
+            documents.insert(doc_id, fields);
+        }
+
+        resolve_more_like_this_docs(query, &documents);
+
+        Ok(())
+    }
 }
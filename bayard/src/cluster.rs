@@ -1,4 +1,5 @@
 pub mod broadcast;
+pub mod envelope;
 pub mod member;
 pub mod members;
 pub mod membership;
@@ -0,0 +1,378 @@
+use std::{fmt, path::Path};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use rand::RngCore;
+
+use crate::common::read_file;
+
+// The header consists of the tag, the version, the flags and the checksum.
+const ENVELOPE_TAG: &[u8; 2] = b"BY";
+const ENVELOPE_VERSION: u8 = 1;
+const HEADER_LEN: usize = 8;
+const CHECKSUM_OFFSET: usize = 4;
+
+const FLAG_COMPRESSED: u8 = 0x01;
+const FLAG_ENCRYPTED: u8 = 0x02;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const AUTH_TAG_LEN: usize = 16;
+const COMPRESSED_SIZE_LEN: usize = 4;
+
+/// The maximum number of bytes the envelope adds to the data.
+pub const ENVELOPE_OVERHEAD: usize = HEADER_LEN + NONCE_LEN + AUTH_TAG_LEN;
+
+#[derive(Debug, Clone, Copy)]
+pub enum EnvelopeErrorKind {
+    KeyringLoadFailure,
+    InvalidKey,
+    InvalidHeader,
+    UnsupportedVersion,
+    ChecksumMismatch,
+    EncryptionFailure,
+    DecryptionFailure,
+    DecompressionFailure,
+}
+
+impl EnvelopeErrorKind {
+    pub fn with_error<E>(self, source: E) -> EnvelopeError
+    where
+        anyhow::Error: From<E>,
+    {
+        EnvelopeError {
+            kind: self,
+            source: From::from(source),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("EnvelopeError(kind={kind:?}, source={source})")]
+pub struct EnvelopeError {
+    pub kind: EnvelopeErrorKind,
+    #[source]
+    source: anyhow::Error,
+}
+
+impl EnvelopeError {
+    pub fn add_context<C>(self, ctx: C) -> Self
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        EnvelopeError {
+            kind: self.kind,
+            source: self.source.context(ctx),
+        }
+    }
+
+    pub fn kind(&self) -> EnvelopeErrorKind {
+        self.kind
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct KeyringFile {
+    keys: Vec<String>,
+}
+
+/// The shared keys used to encrypt the gossip datagrams.
+/// The first key encrypts the datagrams, and all the keys are tried to decrypt them,
+/// so that a new key can be rolled out to the nodes before it is used.
+#[derive(Clone)]
+pub struct Keyring {
+    ciphers: Vec<Aes256Gcm>,
+}
+
+impl Keyring {
+    pub fn new(keys: &[Vec<u8>]) -> Result<Self, EnvelopeError> {
+        if keys.is_empty() {
+            return Err(EnvelopeErrorKind::InvalidKey
+                .with_error(anyhow::anyhow!("Keyring must contain at least one key.")));
+        }
+
+        let mut ciphers = Vec::new();
+        for key in keys.iter() {
+            if key.len() != KEY_LEN {
+                return Err(EnvelopeErrorKind::InvalidKey.with_error(anyhow::anyhow!(
+                    "Key must be {} bytes, but {} bytes.",
+                    KEY_LEN,
+                    key.len()
+                )));
+            }
+            ciphers.push(
+                Aes256Gcm::new_from_slice(key)
+                    .map_err(|error| EnvelopeErrorKind::InvalidKey.with_error(error))?,
+            );
+        }
+
+        Ok(Keyring { ciphers })
+    }
+
+    /// Load the keyring from a JSON file that contains the base64 encoded keys.
+    pub async fn load(path: &Path) -> Result<Self, EnvelopeError> {
+        let keyring_bytes = read_file(path)
+            .await
+            .map_err(|error| EnvelopeErrorKind::KeyringLoadFailure.with_error(error))?;
+        let keyring_file = serde_json::from_slice::<KeyringFile>(&keyring_bytes)
+            .map_err(|error| EnvelopeErrorKind::KeyringLoadFailure.with_error(error))?;
+
+        let mut keys = Vec::new();
+        for key in keyring_file.keys.iter() {
+            keys.push(
+                base64::decode(key.trim())
+                    .map_err(|error| EnvelopeErrorKind::InvalidKey.with_error(error))?,
+            );
+        }
+
+        Keyring::new(&keys)
+    }
+
+    fn encrypt(&self, header: &[u8], data: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = self.ciphers[0]
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: data,
+                    aad: header,
+                },
+            )
+            .map_err(|error| {
+                EnvelopeErrorKind::EncryptionFailure.with_error(anyhow::anyhow!(error.to_string()))
+            })?;
+
+        let mut encrypted = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        encrypted.extend_from_slice(&nonce);
+        encrypted.extend_from_slice(&ciphertext);
+        Ok(encrypted)
+    }
+
+    fn decrypt(&self, header: &[u8], data: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+        if data.len() < NONCE_LEN {
+            return Err(EnvelopeErrorKind::DecryptionFailure
+                .with_error(anyhow::anyhow!("Encrypted payload is too short.")));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+
+        self.ciphers
+            .iter()
+            .find_map(|cipher| {
+                cipher
+                    .decrypt(
+                        Nonce::from_slice(nonce),
+                        Payload {
+                            msg: ciphertext,
+                            aad: header,
+                        },
+                    )
+                    .ok()
+            })
+            .ok_or_else(|| {
+                EnvelopeErrorKind::DecryptionFailure.with_error(anyhow::anyhow!(
+                    "No key in the keyring can decrypt the payload."
+                ))
+            })
+    }
+}
+
+/// Wraps the datagrams of the gossip protocol.
+///
+/// ```text
+/// +-----+---------+-------+----------+---------+
+/// | tag | version | flags | checksum | payload |
+/// +-----+---------+-------+----------+---------+
+///    2       1        1        4
+/// ```
+///
+/// The payload is optionally compressed with LZ4, and then optionally encrypted with
+/// AES-256-GCM, authenticating the header as well. The checksum is the CRC32 of the header
+/// without the checksum itself and the payload.
+#[derive(Clone)]
+pub struct Envelope {
+    keyring: Option<Keyring>,
+    compression: bool,
+    max_size: usize,
+}
+
+impl Envelope {
+    /// `max_size` limits the size of the decompressed payload.
+    pub fn new(keyring: Option<Keyring>, compression: bool, max_size: usize) -> Self {
+        Envelope {
+            keyring,
+            compression,
+            max_size,
+        }
+    }
+
+    pub fn seal(&self, data: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+        let mut flags = 0;
+
+        let mut payload = None;
+        if self.compression {
+            let compressed = lz4_flex::compress_prepend_size(data);
+            // Small datagrams do not get smaller.
+            if compressed.len() < data.len() {
+                flags |= FLAG_COMPRESSED;
+                payload = Some(compressed);
+            }
+        }
+        let mut payload = payload.unwrap_or_else(|| data.to_vec());
+
+        if self.keyring.is_some() {
+            flags |= FLAG_ENCRYPTED;
+        }
+        let header = [ENVELOPE_TAG[0], ENVELOPE_TAG[1], ENVELOPE_VERSION, flags];
+        if let Some(keyring) = &self.keyring {
+            payload = keyring.encrypt(&header, &payload)?;
+        }
+
+        let mut sealed = Vec::with_capacity(HEADER_LEN + payload.len());
+        sealed.extend_from_slice(&header);
+        sealed.extend_from_slice(&checksum(&header, &payload).to_be_bytes());
+        sealed.extend_from_slice(&payload);
+        Ok(sealed)
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+        if sealed.len() < HEADER_LEN || &sealed[..ENVELOPE_TAG.len()] != ENVELOPE_TAG {
+            return Err(EnvelopeErrorKind::InvalidHeader
+                .with_error(anyhow::anyhow!("Datagram is not enveloped.")));
+        }
+        let header = &sealed[..CHECKSUM_OFFSET];
+        let (version, flags) = (header[2], header[3]);
+        if version != ENVELOPE_VERSION {
+            return Err(EnvelopeErrorKind::UnsupportedVersion
+                .with_error(anyhow::anyhow!("Unsupported envelope version: {}", version)));
+        }
+
+        let mut checksum_bytes = [0u8; 4];
+        checksum_bytes.copy_from_slice(&sealed[CHECKSUM_OFFSET..HEADER_LEN]);
+        let payload = &sealed[HEADER_LEN..];
+        if u32::from_be_bytes(checksum_bytes) != checksum(header, payload) {
+            return Err(EnvelopeErrorKind::ChecksumMismatch
+                .with_error(anyhow::anyhow!("Checksum mismatch.")));
+        }
+
+        // Plain datagrams must not be accepted once the keyring is configured, or anyone could
+        // inject them, and vice versa.
+        let payload = match (&self.keyring, flags & FLAG_ENCRYPTED != 0) {
+            (Some(keyring), true) => keyring.decrypt(header, payload)?,
+            (None, false) => payload.to_vec(),
+            (Some(_), false) => {
+                return Err(EnvelopeErrorKind::DecryptionFailure
+                    .with_error(anyhow::anyhow!("Datagram is not encrypted.")))
+            }
+            (None, true) => {
+                return Err(EnvelopeErrorKind::DecryptionFailure
+                    .with_error(anyhow::anyhow!("Datagram is encrypted, but no keyring.")))
+            }
+        };
+
+        if flags & FLAG_COMPRESSED != 0 {
+            self.decompress(&payload)
+        } else {
+            Ok(payload)
+        }
+    }
+
+    fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>, EnvelopeError> {
+        if payload.len() < COMPRESSED_SIZE_LEN {
+            return Err(EnvelopeErrorKind::DecompressionFailure
+                .with_error(anyhow::anyhow!("Compressed payload is too short.")));
+        }
+        let mut size_bytes = [0u8; COMPRESSED_SIZE_LEN];
+        size_bytes.copy_from_slice(&payload[..COMPRESSED_SIZE_LEN]);
+        let size = u32::from_le_bytes(size_bytes) as usize;
+        if size > self.max_size {
+            return Err(
+                EnvelopeErrorKind::DecompressionFailure.with_error(anyhow::anyhow!(
+                    "Decompressed payload must not be larger than {} bytes.",
+                    self.max_size
+                )),
+            );
+        }
+
+        lz4_flex::decompress_size_prepended(payload)
+            .map_err(|error| EnvelopeErrorKind::DecompressionFailure.with_error(error))
+    }
+}
+
+fn checksum(header: &[u8], payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(header);
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cluster::envelope::{Envelope, Keyring};
+
+    const MAX_SIZE: usize = 1024 * 1024;
+
+    #[test]
+    fn test_envelope() {
+        let data = "gossip ".repeat(100).into_bytes();
+
+        let envelope = Envelope::new(None, false, MAX_SIZE);
+        let sealed = envelope.seal(&data).unwrap();
+        assert_eq!(envelope.open(&sealed).unwrap(), data);
+
+        // Corrupted datagrams are rejected.
+        let mut corrupted = sealed.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        assert!(envelope.open(&corrupted).is_err());
+        assert!(envelope.open(&data).is_err());
+
+        let compressed = Envelope::new(None, true, MAX_SIZE);
+        let sealed = compressed.seal(&data).unwrap();
+        assert!(sealed.len() < data.len());
+        assert_eq!(compressed.open(&sealed).unwrap(), data);
+        assert_eq!(envelope.open(&sealed).unwrap(), data);
+
+        let small = Envelope::new(None, true, 10);
+        assert!(small.open(&sealed).is_err());
+    }
+
+    #[test]
+    fn test_envelope_encryption() {
+        let data = b"create index".to_vec();
+        let old_key = vec![1u8; 32];
+        let new_key = vec![2u8; 32];
+
+        let old = Envelope::new(
+            Some(Keyring::new(std::slice::from_ref(&old_key)).unwrap()),
+            true,
+            MAX_SIZE,
+        );
+        let sealed = old.seal(&data).unwrap();
+        assert!(!sealed.windows(data.len()).any(|window| window == data));
+        assert_eq!(old.open(&sealed).unwrap(), data);
+
+        // The keys are rotated by adding the new key as a secondary key first.
+        let rotating = Envelope::new(
+            Some(Keyring::new(&[old_key, new_key.clone()]).unwrap()),
+            true,
+            MAX_SIZE,
+        );
+        assert_eq!(rotating.open(&sealed).unwrap(), data);
+
+        let new = Envelope::new(Some(Keyring::new(&[new_key]).unwrap()), true, MAX_SIZE);
+        assert!(new.open(&sealed).is_err());
+        assert_eq!(rotating.open(&new.seal(&data).unwrap()).unwrap(), data);
+
+        // Plain datagrams are rejected by the encrypted envelope.
+        let plain = Envelope::new(None, false, MAX_SIZE);
+        assert!(old.open(&plain.seal(&data).unwrap()).is_err());
+        assert!(plain.open(&sealed).is_err());
+
+        assert!(Keyring::new(&[vec![0u8; 16]]).is_err());
+        assert!(Keyring::new(&[]).is_err());
+    }
+}
@@ -51,7 +51,7 @@ impl Identity for Member {
     fn renew(&self) -> Option<Self> {
         let new_member = Self {
             addr: self.addr,
-            metadata: self.metadata,
+            metadata: self.metadata.clone(),
             version: OffsetDateTime::now_utc().unix_timestamp(),
         };
         info!(?new_member, "Renew.");
@@ -80,11 +80,11 @@ mod tests {
         let member = serde_json::from_slice::<Member>(member_json_bytes).unwrap();
         assert_eq!(member.addr, "0.0.0.0:9901".parse().unwrap());
         assert_eq!(
-            member.metadata.unwrap().grpc_address,
+            member.metadata.as_ref().unwrap().grpc_address,
             Some("0.0.0.0:9911".parse().unwrap())
         );
         assert_eq!(
-            member.metadata.unwrap().http_address,
+            member.metadata.as_ref().unwrap().http_address,
             Some("0.0.0.0:9921".parse().unwrap())
         );
         assert_eq!(member.version, 1);
@@ -1,10 +1,9 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{self, Debug, Formatter},
     net::SocketAddr,
 };
 
-use foca::Identity;
 use serde::{
     de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor},
     ser::{Serialize, SerializeStruct, Serializer},
@@ -12,10 +11,10 @@ use serde::{
 
 use crate::rendezvous::{
     hash::{DefaultNodeHasher, RendezvousNodes},
-    node::IdNode,
+    node::{Capacity, IdNode, Node, WeightedNode},
 };
 
-use super::member::Member;
+use super::{member::Member, metadata::DEFAULT_CAPACITY};
 
 // Our identity is a composite of a socket address and extra
 // stuff, but downstream consumers likely only care about
@@ -41,7 +40,27 @@ use super::member::Member;
 #[derive(Clone)]
 pub struct Members {
     inner: HashMap<SocketAddr, Member>,
-    hash: RendezvousNodes<IdNode<SocketAddr>, DefaultNodeHasher>,
+    hash: RendezvousNodes<WeightedNode<IdNode<SocketAddr>>, DefaultNodeHasher>,
+}
+
+/// Whether the member is being decommissioned, which excludes it from the shard placement.
+fn is_leaving(member: &Member) -> bool {
+    member
+        .metadata
+        .as_ref()
+        .is_some_and(|metadata| metadata.leaving)
+}
+
+/// The rendezvous node of a member, which is weighted by the capacity in the metadata.
+fn weighted_node(member: &Member) -> WeightedNode<IdNode<SocketAddr>> {
+    let capacity = member
+        .metadata
+        .as_ref()
+        .map_or(DEFAULT_CAPACITY, |metadata| metadata.capacity());
+    WeightedNode::new(
+        IdNode::new(member.addr),
+        Capacity::new(capacity.max(1) as f64).expect("capacity is positive"),
+    )
 }
 
 impl Members {
@@ -64,19 +83,26 @@ impl Members {
     pub fn push(&mut self, member: Member) -> Option<Member> {
         match self.inner.get_mut(&member.addr) {
             Some(mut_member) => {
-                if mut_member.metadata != member.metadata {
-                    mut_member.metadata = member.metadata;
-                    mut_member.renew();
-                    self.hash.insert(IdNode::new(mut_member.addr));
-                    Some(mut_member.clone())
+                // Keep the latest identity of the address, so that the down notification of the
+                // previous identity does not remove the member.
+                let metadata_has_changed = mut_member.metadata != member.metadata;
+                *mut_member = member.clone();
+                if metadata_has_changed {
+                    self.hash.remove(&member.addr);
+                    if !is_leaving(&member) {
+                        self.hash.insert(weighted_node(&member));
+                    }
+                    Some(member)
                 } else {
                     None
                 }
             }
             None => {
                 self.inner.insert(member.addr, member.clone());
-                self.hash.insert(IdNode::new(member.addr));
-                Some(member.clone())
+                if !is_leaving(&member) {
+                    self.hash.insert(weighted_node(&member));
+                }
+                Some(member)
             }
         }
     }
@@ -91,6 +117,16 @@ impl Members {
         }
     }
 
+    /// Removes the member only if it has the same identity, i.e. the address has not rejoined
+    /// with a new identity in the meantime.
+    pub fn remove_member(&mut self, member: &Member) -> Option<Member> {
+        if self.inner.get(&member.addr) == Some(member) {
+            self.remove(&member.addr)
+        } else {
+            None
+        }
+    }
+
     /// Returns whether or not the member at the specified address exists in the members.
     pub fn contains(&self, addr: &SocketAddr) -> bool {
         self.inner.contains_key(addr)
@@ -111,21 +147,47 @@ impl Members {
         self.lookup_members(key, 1).next()
     }
 
+    /// Returns the members responsible for the given key, which are spread across the zones.
     pub fn lookup_members<'a>(&'a self, key: &'a str, num: usize) -> impl Iterator<Item = &Member> {
         self.hash
-            .calc_top_n_candidates(&key, num)
-            .filter_map(|addr| self.get(&addr.clone().into_inner()))
+            .calc_top_n_candidates_by_group(&key, num, |node| self.zone(node.node_id()))
+            .into_iter()
+            .filter_map(|node| self.get(node.node_id()))
     }
 
     pub fn rotate_node(&self, key: &str, num: usize) -> Option<&Member> {
-        let addr = if let Some(node) = self.hash.rotate_candidate(&key, num) {
-            node.clone().into_inner()
+        let addr = if let Some(node) = self
+            .hash
+            .rotate_candidate_by_group(&key, num, |node| self.zone(node.node_id()))
+        {
+            *node.node_id()
         } else {
             return None;
         };
         self.get(&addr)
     }
 
+    fn zone(&self, addr: &SocketAddr) -> Option<&str> {
+        self.get(addr)
+            .and_then(|member| member.metadata.as_ref())
+            .and_then(|metadata| metadata.zone.as_deref())
+    }
+
+    /// Returns whether the member at the specified address is being decommissioned.
+    pub fn is_leaving(&self, addr: &SocketAddr) -> bool {
+        self.get(addr).is_some_and(is_leaving)
+    }
+
+    /// Returns the number of distinct zones of the members that take part in the shard placement.
+    pub fn num_zones(&self) -> usize {
+        self.inner
+            .values()
+            .filter(|member| !is_leaving(member))
+            .filter_map(|member| self.zone(&member.addr))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
     pub fn len(&self) -> usize {
         self.inner.len()
     }
@@ -247,7 +309,12 @@ impl Debug for Members {
 
 #[cfg(test)]
 mod tests {
-    use crate::cluster::members::Members;
+    use std::{
+        collections::{HashMap, HashSet},
+        net::SocketAddr,
+    };
+
+    use crate::cluster::{member::Member, members::Members, metadata::Metadata};
 
     #[test]
     fn test_members_from_slice() {
@@ -278,4 +345,102 @@ mod tests {
         let members = serde_json::from_slice::<Members>(members_json_bytes).unwrap();
         assert_eq!(members.len(), 2);
     }
+
+    #[test]
+    fn test_lookup_members_by_zone() {
+        let mut members = Members::new();
+        for (port, zone) in [(9901, "a"), (9902, "a"), (9903, "a"), (9904, "b")] {
+            let metadata = Metadata {
+                grpc_address: None,
+                http_address: None,
+                zone: Some(zone.to_string()),
+                capacity: None,
+                leaving: false,
+            };
+            members.push(Member::new_with_metadata(
+                SocketAddr::from(([0, 0, 0, 0], port)),
+                metadata,
+            ));
+        }
+        assert_eq!(members.num_zones(), 2);
+
+        // The replicas of every key are placed in both zones.
+        for key in ["foo", "bar", "baz", "qux"] {
+            let zones = members
+                .lookup_members(key, 2)
+                .filter_map(|member| member.metadata.as_ref())
+                .filter_map(|metadata| metadata.zone.clone())
+                .collect::<HashSet<_>>();
+            assert_eq!(zones.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_lookup_member_by_capacity() {
+        let mut members = Members::new();
+        for (port, capacity) in [(9901, 3), (9902, 1)] {
+            let metadata = Metadata {
+                grpc_address: None,
+                http_address: None,
+                zone: None,
+                capacity: Some(capacity),
+                leaving: false,
+            };
+            members.push(Member::new_with_metadata(
+                SocketAddr::from(([0, 0, 0, 0], port)),
+                metadata,
+            ));
+        }
+
+        // The keys are assigned in proportion to the capacities.
+        let mut counts = HashMap::new();
+        for key in 0..10000 {
+            let key = key.to_string();
+            let member = members.lookup_member(&key).unwrap();
+            *counts.entry(member.addr.port()).or_insert(0) += 1;
+        }
+        assert!((7300..7700).contains(&counts[&9901]));
+        assert!((2300..2700).contains(&counts[&9902]));
+    }
+
+    #[test]
+    fn test_lookup_members_without_leaving() {
+        let mut members = Members::new();
+        for port in [9901, 9902, 9903] {
+            let metadata = Metadata {
+                grpc_address: None,
+                http_address: None,
+                zone: None,
+                capacity: None,
+                leaving: false,
+            };
+            members.push(Member::new_with_metadata(
+                SocketAddr::from(([0, 0, 0, 0], port)),
+                metadata,
+            ));
+        }
+        let leaving_addr = members.lookup_member("foo").unwrap().addr;
+
+        // The leaving member stays in the members, but the shards are placed on the others.
+        let mut member = members.get(&leaving_addr).unwrap().clone();
+        member.metadata.as_mut().unwrap().leaving = true;
+        assert!(members.push(member.clone()).is_some());
+        assert_eq!(members.len(), 3);
+        assert!(members.is_leaving(&leaving_addr));
+        for key in ["foo", "bar", "baz", "qux"] {
+            let addrs = members
+                .lookup_members(key, 3)
+                .map(|member| member.addr)
+                .collect::<Vec<_>>();
+            assert_eq!(addrs.len(), 2);
+            assert!(!addrs.contains(&leaving_addr));
+        }
+
+        // The down notification of the previous identity does not remove the member.
+        let mut previous = member.clone();
+        previous.metadata.as_mut().unwrap().leaving = false;
+        assert!(members.remove_member(&previous).is_none());
+        assert!(members.remove_member(&member).is_some());
+        assert!(!members.contains(&leaving_addr));
+    }
 }
@@ -14,12 +14,13 @@ use tokio::{
     sync::{mpsc, watch, RwLock, RwLockReadGuard},
 };
 use tokio_stream::wrappers::WatchStream;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::common::write_file;
 
 use super::{
     broadcast::MessageHandler,
+    envelope::{Envelope, Keyring, ENVELOPE_OVERHEAD},
     member::Member,
     members::Members,
     message::{Input, Message},
@@ -34,6 +35,7 @@ const MEMBERS_FILE: &str = "members.json";
 pub enum MembershipErrorKind {
     SocketBindingFailure,
     MembersSerializationFailure,
+    IdentityChangeFailure,
     BroadcastFailure,
     FileWriteFailure,
 }
@@ -85,7 +87,8 @@ async fn save_members(path: &Path, members: Members) -> Result<(), MembershipErr
 
 pub struct Membership {
     foca: Arc<RwLock<Foca<Member, PostcardCodec, StdRng, MessageHandler>>>,
-    local_member: Member,
+    local_member: Arc<RwLock<Member>>,
+    tx_foca: mpsc::Sender<Input<Member>>,
     members: Arc<RwLock<Members>>,
     members_receiver: watch::Receiver<Members>,
     message_receiver: watch::Receiver<Message>,
@@ -98,11 +101,19 @@ impl Membership {
         metadata: Metadata,
         data_directory: PathBuf,
         seed_address: Option<SocketAddr>,
+        keyring: Option<Keyring>,
+        compression: bool,
     ) -> Result<Self, MembershipError> {
         // Create a config specifies the parameters Foca will use for the SWIM protocol.
         let mut config = Config::simple();
         config.max_packet_size = NonZeroUsize::new(1024 * 1024).unwrap();
 
+        // Create the envelope of the datagrams.
+        if keyring.is_none() {
+            warn!("The gossip datagrams are not encrypted. Any host can join the cluster.");
+        }
+        let envelope = Envelope::new(keyring, compression, config.max_packet_size.get());
+
         // Create a node identity.
         let local_member = Member::new_with_metadata(advertise_address, metadata);
 
@@ -138,16 +149,20 @@ impl Membership {
 
         // The socket writing task
         let write_socket = Arc::clone(&socket);
+        let write_envelope = envelope.clone();
         tokio::spawn(async move {
             info!(?write_socket, "Starting socket writing task.");
             while let Some((dst, data)) = rx_data.recv().await {
-                // A more reasonable implementation would do some more stuff
-                // here before sending, like:
-                //  * zlib or something else to compress the data
-                //  * encryption (shared key, AES most likely)
-                //  * an envelope with tag+version+checksum to allow
-                //    protocol evolution
-                let _ignored_send_result = write_socket.send_to(&data, &dst).await;
+                // Compress, encrypt and wrap the data in the envelope with the version and
+                // the checksum.
+                let sealed = match write_envelope.seal(&data) {
+                    Ok(sealed) => sealed,
+                    Err(error) => {
+                        error!(?error, "Failed to seal data.");
+                        continue;
+                    }
+                };
+                let _ignored_send_result = write_socket.send_to(&sealed, &dst).await;
             }
         });
 
@@ -166,6 +181,7 @@ impl Membership {
         tokio::spawn(async move {
             while let Some(input) = rx_foca.recv().await {
                 debug_assert_eq!(0, runtime.backlog());
+                let mut active_list_has_changed = false;
 
                 let result = match input {
                     Input::Event(timer) => {
@@ -180,6 +196,18 @@ impl Membership {
                         debug!(?dst, "Received announce.");
                         foca_task.write().await.announce(dst, &mut runtime)
                     }
+                    Input::ChangeIdentity(member) => {
+                        info!(?member, "Changing identity.");
+                        let result = foca_task
+                            .write()
+                            .await
+                            .change_identity(member.clone(), &mut runtime);
+                        if result.is_ok() {
+                            members_task.write().await.push(member);
+                            active_list_has_changed = true;
+                        }
+                        result
+                    }
                 };
 
                 // Every public foca result yields `()` on success, so there's
@@ -225,7 +253,6 @@ impl Membership {
                 // But since this is an agent, we simply write to a file
                 // so other proccesses periodically open()/read()/close()
                 // to figure out the cluster members.
-                let mut active_list_has_changed = false;
                 while let Some(notification) = runtime.notifications.pop() {
                     match notification {
                         Notification::MemberUp(member) => {
@@ -236,7 +263,7 @@ impl Membership {
                         Notification::MemberDown(member) => {
                             info!(?member, "Member down.");
                             active_list_has_changed |=
-                                members_task.write().await.remove(&member.addr).is_some()
+                                members_task.write().await.remove_member(&member).is_some()
                         }
                         other => {
                             info!(notification = ?other, "Receive membership notification.");
@@ -272,6 +299,9 @@ impl Membership {
             }
         });
 
+        // Keep a sender to change the identity of the local member later.
+        let tx_identity = tx_foca.clone();
+
         // Foca is running, we can tell it to announce to our target
         if let Some(seed_address) = seed_address {
             let seed_member = Member::new(seed_address);
@@ -284,12 +314,12 @@ impl Membership {
         }
 
         // And finally, we receive forever
-        let mut recv_buf = vec![0u8; config.max_packet_size.get()];
+        let mut recv_buf = vec![0u8; config.max_packet_size.get() + ENVELOPE_OVERHEAD];
         tokio::spawn(async move {
             info!(?socket, "Listening on.");
             let mut databuf = BytesMut::new();
             loop {
-                let (len, _from_addr) = match socket.recv_from(&mut recv_buf).await {
+                let (len, from_addr) = match socket.recv_from(&mut recv_buf).await {
                     Ok(result) => result,
                     Err(error) => {
                         error!(?error, "Error receiving.");
@@ -297,9 +327,16 @@ impl Membership {
                     }
                 };
 
-                // Accordinly, we would undo everything that's done prior to
-                // sending: decompress, decrypt, remove the envelope
-                databuf.put_slice(&recv_buf[..len]);
+                // Undo everything that's done prior to sending. The datagrams that are not
+                // authenticated are dropped before they reach foca.
+                let data = match envelope.open(&recv_buf[..len]) {
+                    Ok(data) => data,
+                    Err(error) => {
+                        warn!(?from_addr, ?error, "Dropped invalid datagram.");
+                        continue;
+                    }
+                };
+                databuf.put_slice(&data);
 
                 // And simply forward it to foca
                 let _ignored_send_error = tx_foca.send(Input::Data(databuf.split().freeze())).await;
@@ -308,7 +345,8 @@ impl Membership {
 
         let membership = Self {
             foca,
-            local_member,
+            local_member: Arc::new(RwLock::new(local_member)),
+            tx_foca: tx_identity,
             members,
             members_receiver,
             message_receiver,
@@ -326,19 +364,43 @@ impl Membership {
     }
 
     pub async fn local_member(&self) -> Member {
-        self.local_member.clone()
+        self.local_member.read().await.clone()
     }
 
     pub async fn remote_members(&self) -> Vec<Member> {
+        let local_addr = self.local_member.read().await.addr;
         self.members
             .read()
             .await
             .iter()
             .cloned()
-            .filter(|id| id != &self.local_member)
+            .filter(|member| member.addr != local_addr)
             .collect::<Vec<Member>>()
     }
 
+    /// Marks the local member as leaving in its gossip metadata, which excludes the node from the
+    /// shard placement. Foca declares the previous identity down and gossips the new one.
+    pub async fn set_leaving(&self) -> Result<(), MembershipError> {
+        let mut local_member = self.local_member.write().await;
+        let mut metadata = local_member.metadata.clone().unwrap_or_default();
+        if metadata.leaving {
+            return Ok(());
+        }
+        metadata.leaving = true;
+
+        let new_member = Member::new_with_metadata(local_member.addr, metadata);
+        self.tx_foca
+            .send(Input::ChangeIdentity(new_member.clone()))
+            .await
+            .map_err(|error| {
+                MembershipErrorKind::IdentityChangeFailure
+                    .with_error(anyhow::anyhow!(error.to_string()))
+            })?;
+        *local_member = new_member;
+
+        Ok(())
+    }
+
     pub async fn members(&self) -> RwLockReadGuard<Members> {
         self.members.read().await
     }
@@ -53,14 +53,12 @@ pub enum Input<T> {
     Event(Timer<T>),
     Data(Bytes),
     Announce(T),
+    ChangeIdentity(T),
 }
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub enum MessageKind {
     Unknown,
-    CreateIndex,
-    DeleteIndex,
-    ModifyIndex,
 }
 
 impl FromStr for MessageKind {
@@ -69,9 +67,6 @@ impl FromStr for MessageKind {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "unknown" => Ok(MessageKind::Unknown),
-            "create_index" => Ok(MessageKind::CreateIndex),
-            "delete_index" => Ok(MessageKind::DeleteIndex),
-            "modify_index" => Ok(MessageKind::ModifyIndex),
             _ => Err(MessageErrorKind::InvalidMessageKind
                 .with_error(anyhow::format_err!("Unknown message kind: {}", s))),
         }
@@ -82,9 +77,6 @@ impl fmt::Debug for MessageKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             MessageKind::Unknown => write!(f, "unknown"),
-            MessageKind::CreateIndex => write!(f, "create_index"),
-            MessageKind::DeleteIndex => write!(f, "delete_index"),
-            MessageKind::ModifyIndex => write!(f, "modify_index"),
         }
     }
 }
@@ -93,9 +85,6 @@ impl MessageKind {
     pub fn from_u8(n: u8) -> Result<Self, MessageError> {
         match n {
             0 => Ok(MessageKind::Unknown),
-            3 => Ok(MessageKind::CreateIndex),
-            4 => Ok(MessageKind::DeleteIndex),
-            5 => Ok(MessageKind::ModifyIndex),
             _ => Err(MessageErrorKind::InvalidMessageKind
                 .with_error(anyhow::format_err!("Unknown message kind: {:?}", n))),
         }
@@ -104,16 +93,10 @@ impl MessageKind {
     pub fn to_u8(&self) -> u8 {
         match self {
             MessageKind::Unknown => 0,
-            MessageKind::CreateIndex => 3,
-            MessageKind::DeleteIndex => 4,
-            MessageKind::ModifyIndex => 5,
         }
     }
 }
 
-pub const MESSAGE_NAME_FIELD: &str = "name";
-pub const MESSAGE_METADATA_FIELD: &str = "metadata";
-
 #[derive(Clone, PartialEq)]
 pub struct Message {
     data: Vec<u8>,
@@ -264,163 +247,4 @@ mod tests {
         assert_eq!(message.body(), body);
         assert_eq!(message.version(), version);
     }
-
-    #[test]
-    fn test_modify_index_message() {
-        let modify_index_message_json_str = r#"
-        {
-            "name": "example",
-            "index_metadata": {
-                "schema": [
-                    {
-                        "name": "_id",
-                        "type": "text",
-                        "options": {
-                            "indexing": {
-                                "record": "basic",
-                                "fieldnorms": true,
-                                "tokenizer": "raw"
-                            },
-                            "stored": true,
-                            "fast": false
-                        }
-                    },
-                    {
-                        "name": "_timestamp",
-                        "type": "date",
-                        "options": {
-                            "indexed": true,
-                            "fieldnorms": true,
-                            "fast": "single",
-                            "stored": true
-                        }
-                    },
-                    {
-                        "name": "url",
-                        "type": "text",
-                        "options": {
-                            "indexing": {
-                                "record": "freq",
-                                "fieldnorms": false,
-                                "tokenizer": "raw"
-                            },
-                            "stored": true,
-                            "fast": false
-                        }
-                    },
-                    {
-                        "name": "name",
-                        "type": "text",
-                        "options": {
-                            "indexing": {
-                                "record": "position",
-                                "fieldnorms": false,
-                                "tokenizer": "default"
-                            },
-                            "stored": true,
-                            "fast": false
-                        }
-                    },
-                    {
-                        "name": "description",
-                        "type": "text",
-                        "options": {
-                            "indexing": {
-                                "record": "position",
-                                "fieldnorms": false,
-                                "tokenizer": "default"
-                            },
-                            "stored": true,
-                            "fast": false
-                        }
-                    },
-                    {
-                        "name": "popularity",
-                        "type": "u64",
-                        "options": {
-                            "indexed": true,
-                            "fieldnorms": true,
-                            "fast": "single",
-                            "stored": true
-                        }
-                    },
-                    {
-                        "name": "category",
-                        "type": "facet",
-                        "options": {
-                            "stored": true
-                        }
-                    },
-                    {
-                        "name": "publish_date",
-                        "type": "date",
-                        "options": {
-                            "indexed": true,
-                            "fieldnorms": true,
-                            "fast": "single",
-                            "stored": true
-                        }
-                    }
-                ],
-                "index_settings": {
-                    "docstore_compression": "none",
-                    "docstore_blocksize": 16384
-                },
-                "analyzers": {
-                    "default": {
-                        "filters":[
-                            {
-                                "args":{
-                                    "length_limit": 40
-                                },
-                                "name": "remove_long"
-                            },
-                            {
-                                "name": "ascii_folding"
-                            },
-                            {
-                                "name": "lower_case"
-                            }
-                        ],
-                        "tokenizer": {
-                            "name": "simple"
-                        }
-                    },
-                    "raw": {
-                        "tokenizer": {
-                            "name": "raw"
-                        }
-                    },
-                    "whitespace": {
-                        "tokenizer": {
-                            "name": "whitespace"
-                        }
-                    }
-                },
-                "writer_threads": 1,
-                "writer_mem_size": 500000000,
-                "num_replicas": 2,
-                "num_shards": 1,
-                "shards": {
-                    "shard_list": [
-                        {
-                            "id": "4UdJIxlX",
-                            "state": "serving",
-                            "version": 1660049618
-                        },
-                        {
-                            "id": "QMcs7uHm",
-                            "state": "draining",
-                            "version": 1660049618
-                        }
-                    ]
-                }
-            }
-        }
-        "#;
-        let modify_index_message_bytes = modify_index_message_json_str.as_bytes();
-        let modify_index_message =
-            serde_json::from_slice::<serde_json::Value>(modify_index_message_bytes).unwrap();
-        let _modify_index_message_vec = serde_json::to_vec(&modify_index_message).unwrap();
-    }
 }
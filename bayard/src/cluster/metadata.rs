@@ -1,9 +1,72 @@
-use std::{fmt, net::SocketAddr};
+use std::{fmt, net::SocketAddr, path::Path, str::FromStr};
 
-#[derive(Copy, Clone, Default, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub const DEFAULT_CAPACITY: u32 = 1;
+
+const GIB: f64 = (1024 * 1024 * 1024) as f64;
+
+#[derive(Debug, Clone, Copy)]
+pub enum MetadataErrorKind {
+    InvalidCapacity,
+    CapacityComputationFailure,
+}
+
+impl MetadataErrorKind {
+    pub fn with_error<E>(self, source: E) -> MetadataError
+    where
+        anyhow::Error: From<E>,
+    {
+        MetadataError {
+            kind: self,
+            source: From::from(source),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("MetadataError(kind={kind:?}, source={source})")]
+pub struct MetadataError {
+    pub kind: MetadataErrorKind,
+    #[source]
+    source: anyhow::Error,
+}
+
+impl MetadataError {
+    pub fn add_context<C>(self, ctx: C) -> Self
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        MetadataError {
+            kind: self.kind,
+            source: self.source.context(ctx),
+        }
+    }
+
+    pub fn kind(&self) -> MetadataErrorKind {
+        self.kind
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Metadata {
     pub grpc_address: Option<SocketAddr>,
     pub http_address: Option<SocketAddr>,
+    /// The zone or rack of the node. The replicas of a shard are spread across the zones.
+    #[serde(default)]
+    pub zone: Option<String>,
+    /// The relative weight of the node in the shard placement.
+    /// The nodes are assigned shards in proportion to their capacities.
+    #[serde(default)]
+    pub capacity: Option<u32>,
+    /// Whether the node is being decommissioned. A leaving node is excluded from the shard
+    /// placement and hands its shards off to the new owners before it stops.
+    #[serde(default)]
+    pub leaving: bool,
+}
+
+impl Metadata {
+    pub fn capacity(&self) -> u32 {
+        self.capacity.unwrap_or(DEFAULT_CAPACITY)
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -12,6 +75,85 @@ impl fmt::Debug for Metadata {
             .debug_struct("Metadata")
             .field("grpc_address", &self.grpc_address)
             .field("http_address", &self.http_address)
+            .field("zone", &self.zone)
+            .field("capacity", &self.capacity)
+            .field("leaving", &self.leaving)
             .finish()
     }
 }
+
+/// How the capacity of a node is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapacitySetting {
+    Fixed(u32),
+    /// Computed from the CPUs and the disk with [`compute_capacity`].
+    Auto,
+}
+
+impl FromStr for CapacitySetting {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(CapacitySetting::Auto);
+        }
+        match s.parse::<u32>() {
+            Ok(capacity) if capacity > 0 => Ok(CapacitySetting::Fixed(capacity)),
+            _ => Err(
+                MetadataErrorKind::InvalidCapacity.with_error(anyhow::anyhow!(
+                    "Capacity must be a positive integer or auto: {:?}",
+                    s
+                )),
+            ),
+        }
+    }
+}
+
+/// Compute the capacity of the node as the geometric mean of the number of CPUs and the size
+/// of the disk of the directory in GiB. So a node that has twice as many CPUs and twice as
+/// large a disk has twice the capacity.
+pub fn compute_capacity(dir: &Path) -> Result<u32, MetadataError> {
+    let cpus = std::thread::available_parallelism()
+        .map_err(|error| MetadataErrorKind::CapacityComputationFailure.with_error(error))?
+        .get() as f64;
+
+    // The directory may not have been created yet.
+    let existing_dir = dir.ancestors().find(|path| path.exists()).ok_or_else(|| {
+        MetadataErrorKind::CapacityComputationFailure
+            .with_error(anyhow::anyhow!("Directory {:?} does not exist.", dir))
+    })?;
+    let disk = fs2::total_space(existing_dir)
+        .map_err(|error| MetadataErrorKind::CapacityComputationFailure.with_error(error))?
+        as f64
+        / GIB;
+
+    Ok(((cpus * disk).sqrt().round() as u32).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::cluster::metadata::{compute_capacity, CapacitySetting};
+
+    #[test]
+    fn test_capacity_setting_from_str() {
+        assert_eq!(
+            "10".parse::<CapacitySetting>().unwrap(),
+            CapacitySetting::Fixed(10)
+        );
+        assert_eq!(
+            "auto".parse::<CapacitySetting>().unwrap(),
+            CapacitySetting::Auto
+        );
+        assert!("0".parse::<CapacitySetting>().is_err());
+        assert!("-1".parse::<CapacitySetting>().is_err());
+        assert!("large".parse::<CapacitySetting>().is_err());
+    }
+
+    #[test]
+    fn test_compute_capacity() {
+        let capacity = compute_capacity(&env::temp_dir().join("not_created_yet")).unwrap();
+        assert!(capacity >= 1);
+    }
+}
@@ -3,12 +3,19 @@ use http::StatusCode;
 use tonic::{transport::Channel, Request};
 use tracing::error;
 
-use crate::proto::cluster::{cluster_service_client::ClusterServiceClient, GetNodesRequest};
+use crate::{
+    auth::Credentials,
+    proto::cluster::{
+        cluster_service_client::ClusterServiceClient, DecommissionNodeRequest, GetNodesRequest,
+        GetPlacementViolationsRequest,
+    },
+};
 
 pub async fn nodes(
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let mut client = ClusterServiceClient::new(channel);
+    let mut client = ClusterServiceClient::with_interceptor(channel, credentials);
 
     let req = GetNodesRequest {};
     let resp = match client.get_nodes(Request::new(req)).await {
@@ -29,3 +36,55 @@ pub async fn nodes(
 
     Ok((StatusCode::OK, Json(json)))
 }
+
+pub async fn placement_violations(
+    Extension(channel): Extension<Channel>,
+    credentials: Credentials,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut client = ClusterServiceClient::with_interceptor(channel, credentials);
+
+    let req = GetPlacementViolationsRequest {};
+    let resp = match client.get_placement_violations(Request::new(req)).await {
+        Ok(resp) => resp.into_inner(),
+        Err(error) => {
+            error!("{}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let json = match serde_json::to_value(resp) {
+        Ok(json) => json,
+        Err(error) => {
+            error!("{}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok((StatusCode::OK, Json(json)))
+}
+
+pub async fn decommission_node(
+    Extension(channel): Extension<Channel>,
+    credentials: Credentials,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut client = ClusterServiceClient::with_interceptor(channel, credentials);
+
+    let req = DecommissionNodeRequest {};
+    let resp = match client.decommission_node(Request::new(req)).await {
+        Ok(resp) => resp.into_inner(),
+        Err(error) => {
+            error!("{}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let json = match serde_json::to_value(resp) {
+        Ok(json) => json,
+        Err(error) => {
+            error!("{}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok((StatusCode::OK, Json(json)))
+}
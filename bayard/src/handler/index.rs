@@ -7,16 +7,19 @@ use axum::{
 use http::StatusCode;
 use serde_json::Value;
 use tokio::io::AsyncBufReadExt;
-use tonic::{transport::Channel, Request};
+use tonic::{transport::Channel, Code, Request};
 use tracing::error;
 
 use crate::{
-    index::metadata::Metadata,
+    auth::Credentials,
+    index::{geo_point::GeoPoint, metadata::Metadata},
     proto::index::{
-        index_service_client::IndexServiceClient, query::Kind, sort::Order, CollectionKind,
-        CommitRequest, CreateIndexRequest, DeleteDocumentsRequest, DeleteIndexRequest,
-        GetIndexRequest, ModifyIndexRequest, PutDocumentsRequest, Query, RollbackRequest,
-        SearchRequest, Sort,
+        index_service_client::IndexServiceClient, query::Kind, sort::Order, AnalyzeRequest,
+        CollectionKind, CommitRequest, CreateIndexRequest, DeleteDocumentsRequest,
+        DeleteIndexRequest, DeleteUserDictionaryRequest, GeoPoint as ProtoGeoPoint,
+        GetCacheStatsRequest, GetIndexRequest, ModifyIndexRequest, PutDocumentsRequest,
+        PutUserDictionaryRequest, Query, ReloadAnalyzersRequest, RollbackRequest, SearchRequest,
+        Sort,
     },
 };
 
@@ -30,6 +33,7 @@ struct JsonQuery {
 struct JsonSort {
     field: String,
     order: Order,
+    origin: Option<Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -40,6 +44,15 @@ pub struct JsonSearchRequest {
     fields: Vec<String>,
     offset: i32,
     hits: i32,
+    post_filter: Option<JsonQuery>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JsonAnalyzeRequest {
+    analyzer: Option<String>,
+    field: Option<String>,
+    definition: Option<Value>,
+    text: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -61,6 +74,7 @@ pub async fn create_index(
     Path(index): Path<String>,
     Json(request): Json<Metadata>,
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
     let metadata_bytes = serde_json::to_vec(&request).map_err(|error| {
         error!(?error, "Failed to serialize schema.");
@@ -72,7 +86,7 @@ pub async fn create_index(
         metadata: metadata_bytes,
     };
 
-    let mut client = IndexServiceClient::new(channel);
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
     let resp = client
         .create_index(Request::new(req))
         .await
@@ -88,10 +102,11 @@ pub async fn create_index(
 pub async fn delete_index(
     Path(index): Path<String>,
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
     let req = DeleteIndexRequest { name: index };
 
-    let mut client = IndexServiceClient::new(channel);
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
     let resp = client
         .delete_index(Request::new(req))
         .await
@@ -107,10 +122,11 @@ pub async fn delete_index(
 pub async fn get_index(
     Path(index): Path<String>,
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
     let req = GetIndexRequest { name: index };
 
-    let mut client = IndexServiceClient::new(channel);
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
     let resp = client
         .get_index(Request::new(req))
         .await
@@ -129,10 +145,31 @@ pub async fn get_index(
     Ok((StatusCode::OK, Json(metadata)))
 }
 
+pub async fn get_cache_stats(
+    Path(index): Path<String>,
+    Extension(channel): Extension<Channel>,
+    credentials: Credentials,
+) -> Result<impl IntoResponse, StatusCode> {
+    let req = GetCacheStatsRequest { name: index };
+
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
+    let resp = client
+        .get_cache_stats(Request::new(req))
+        .await
+        .map_err(|error| {
+            error!(?error, "Failed to get cache stats.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_inner();
+
+    Ok((StatusCode::OK, Json(resp)))
+}
+
 pub async fn modify_index(
     Path(index): Path<String>,
     Json(request): Json<Metadata>,
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
     let metadata_bytes = serde_json::to_vec(&request).map_err(|error| {
         error!(?error, "Failed to serialize index config.");
@@ -144,7 +181,7 @@ pub async fn modify_index(
         metadata: metadata_bytes,
     };
 
-    let mut client = IndexServiceClient::new(channel);
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
     let resp = client
         .modify_index(Request::new(req))
         .await
@@ -161,6 +198,7 @@ pub async fn put_documents(
     Path(index): Path<String>,
     ContentLengthLimit(bytes): ContentLengthLimit<Bytes, 5_000_000>,
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
     let docs_bytes = bytes.to_vec();
 
@@ -179,7 +217,7 @@ pub async fn put_documents(
         docs,
     };
 
-    let mut client = IndexServiceClient::new(channel);
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
     let resp = client
         .put_documents(Request::new(req))
         .await
@@ -196,6 +234,7 @@ pub async fn delete_documents(
     Path(index): Path<String>,
     ContentLengthLimit(bytes): ContentLengthLimit<Bytes, 5_000_000>,
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
     let docs_bytes = bytes.to_vec();
 
@@ -218,7 +257,7 @@ pub async fn delete_documents(
         doc_ids,
     };
 
-    let mut client = IndexServiceClient::new(channel);
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
     let resp = client
         .delete_documents(Request::new(req))
         .await
@@ -234,13 +273,14 @@ pub async fn delete_documents(
 pub async fn commit(
     Path(index): Path<String>,
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
     let req = CommitRequest {
         name: index,
         shard_id: "".to_string(),
     };
 
-    let mut client = IndexServiceClient::new(channel);
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
     let resp = client
         .commit(Request::new(req))
         .await
@@ -256,13 +296,14 @@ pub async fn commit(
 pub async fn rollback(
     Path(index): Path<String>,
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
     let req = RollbackRequest {
         name: index,
         shard_id: "".to_string(),
     };
 
-    let mut client = IndexServiceClient::new(channel);
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
     let resp = client
         .rollback(Request::new(req))
         .await
@@ -279,6 +320,7 @@ pub async fn search(
     Path(index): Path<String>,
     Json(request): Json<JsonSearchRequest>,
     Extension(channel): Extension<Channel>,
+    credentials: Credentials,
 ) -> Result<impl IntoResponse, StatusCode> {
     let options_bytes = serde_json::to_vec(&request.query.options).map_err(|error| {
         error!(?error, "Failed to deserialize query options.");
@@ -290,11 +332,42 @@ pub async fn search(
         options: options_bytes,
     };
 
+    let post_filter = match request.post_filter {
+        Some(post_filter) => {
+            let options_bytes = serde_json::to_vec(&post_filter.options).map_err(|error| {
+                error!(?error, "Failed to deserialize post filter options.");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            Some(Query {
+                kind: post_filter.kind as i32,
+                options: options_bytes,
+            })
+        }
+        None => None,
+    };
+
     let sort = match request.sort {
-        Some(sort) => Some(Sort {
-            field: sort.field,
-            order: sort.order as i32,
-        }),
+        Some(sort) => {
+            // The origin of the geo distance sort.
+            let origin = match sort.origin {
+                Some(origin) => {
+                    let point = GeoPoint::from_json(&origin).ok_or_else(|| {
+                        error!(?origin, "Failed to parse sort origin.");
+                        StatusCode::BAD_REQUEST
+                    })?;
+                    Some(ProtoGeoPoint {
+                        lat: point.lat,
+                        lon: point.lon,
+                    })
+                }
+                None => None,
+            };
+            Some(Sort {
+                field: sort.field,
+                order: sort.order as i32,
+                origin,
+            })
+        }
         None => None,
     };
 
@@ -307,16 +380,20 @@ pub async fn search(
         fields: request.fields,
         offset: request.offset,
         hits: request.hits,
+        post_filter,
     };
 
-    let mut client = IndexServiceClient::new(channel);
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
 
     let resp = client
         .search(Request::new(req))
         .await
         .map_err(|error| {
             error!(?error, "Failed to search index.");
-            StatusCode::INTERNAL_SERVER_ERROR
+            match error.code() {
+                Code::InvalidArgument => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            }
         })?
         .into_inner();
 
@@ -345,3 +422,129 @@ pub async fn search(
 
     Ok((StatusCode::OK, Json(json)))
 }
+
+pub async fn analyze(
+    Path(index): Path<String>,
+    Json(request): Json<JsonAnalyzeRequest>,
+    Extension(channel): Extension<Channel>,
+    credentials: Credentials,
+) -> Result<impl IntoResponse, StatusCode> {
+    if request.analyzer.is_none() && request.field.is_none() && request.definition.is_none() {
+        error!("One of analyzer, field or definition must be specified.");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let definition_bytes = match request.definition {
+        Some(definition) => serde_json::to_vec(&definition).map_err(|error| {
+            error!(?error, "Failed to serialize analyzer definition.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        None => Vec::new(),
+    };
+
+    let req = AnalyzeRequest {
+        name: index,
+        analyzer: request.analyzer.unwrap_or_default(),
+        field: request.field.unwrap_or_default(),
+        definition: definition_bytes,
+        text: request.text,
+    };
+
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
+    let resp = client
+        .analyze(Request::new(req))
+        .await
+        .map_err(|error| {
+            error!(?error, "Failed to analyze text.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_inner();
+
+    Ok((StatusCode::OK, Json(resp)))
+}
+
+pub async fn reload_analyzers(
+    Path(index): Path<String>,
+    body: Bytes,
+    Extension(channel): Extension<Channel>,
+    credentials: Credentials,
+) -> Result<impl IntoResponse, StatusCode> {
+    // The body is optional. If it is empty, the current analyzers are reloaded.
+    let analyzers_bytes = if body.is_empty() {
+        Vec::new()
+    } else {
+        let analyzers = serde_json::from_slice::<Value>(&body).map_err(|error| {
+            error!(?error, "Failed to deserialize analyzers.");
+            StatusCode::BAD_REQUEST
+        })?;
+        serde_json::to_vec(&analyzers).map_err(|error| {
+            error!(?error, "Failed to serialize analyzers.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    let req = ReloadAnalyzersRequest {
+        name: index,
+        analyzers: analyzers_bytes,
+    };
+
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
+    let resp = client
+        .reload_analyzers(Request::new(req))
+        .await
+        .map_err(|error| {
+            error!(?error, "Failed to reload analyzers.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_inner();
+
+    Ok((StatusCode::OK, Json(resp)))
+}
+
+pub async fn put_user_dictionary(
+    Path((index, file_name)): Path<(String, String)>,
+    body: Bytes,
+    Extension(channel): Extension<Channel>,
+    credentials: Credentials,
+) -> Result<impl IntoResponse, StatusCode> {
+    let req = PutUserDictionaryRequest {
+        name: index,
+        file_name,
+        dictionary: body.to_vec(),
+    };
+
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
+    let resp = client
+        .put_user_dictionary(Request::new(req))
+        .await
+        .map_err(|error| {
+            error!(?error, "Failed to put user dictionary.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_inner();
+
+    Ok((StatusCode::OK, Json(resp)))
+}
+
+pub async fn delete_user_dictionary(
+    Path((index, file_name)): Path<(String, String)>,
+    Extension(channel): Extension<Channel>,
+    credentials: Credentials,
+) -> Result<impl IntoResponse, StatusCode> {
+    let req = DeleteUserDictionaryRequest {
+        name: index,
+        file_name,
+    };
+
+    let mut client = IndexServiceClient::with_interceptor(channel, credentials);
+    let resp = client
+        .delete_user_dictionary(Request::new(req))
+        .await
+        .map_err(|error| {
+            error!(?error, "Failed to delete user dictionary.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_inner();
+
+    Ok((StatusCode::OK, Json(resp)))
+}
@@ -1,19 +1,35 @@
 pub mod analyzer;
+pub mod char_filter;
+pub mod geo_point;
 pub mod metadata;
 pub mod metastore;
+pub mod processor;
+pub mod search_tokenizer;
 pub mod shard;
 pub mod shards;
 pub mod token_filter;
 pub mod tokenizer;
 
+use std::path::{Component, Path, PathBuf};
+
 pub const DOC_ID_FIELD_NAME: &str = "_id";
 pub const DOC_TIMESTAMP_FIELD_NAME: &str = "_timestamp";
 
-#[cfg(not(target_os = "windows"))]
-const INDEX_METADATA_FILE_PATTERN: &str = r"^.*/indices/([^/]+)/meta.json$";
-#[cfg(target_os = "windows")]
-const INDEX_METADATA_FILE_PATTERN: &str = r"^.*\indices\([^\]+)\meta.json$";
-
 pub const INDICES_DIR: &str = "indices";
 pub const INDEX_METADATA_FILE: &str = "meta.json";
 pub const SHARDS_DIR: &str = "shards";
+
+/// Resolve the path of a file placed in the index directory, such as a synonym file or
+/// a user dictionary. The path must be relative to the index directory and must not
+/// point outside of it.
+pub fn resolve_index_file(index_dir: &Path, path: &Path) -> anyhow::Result<PathBuf> {
+    if path.as_os_str().is_empty()
+        || !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        anyhow::bail!("Path must be relative to the index directory: {:?}", path);
+    }
+
+    Ok(index_dir.join(path))
+}
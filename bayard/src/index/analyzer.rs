@@ -1,22 +1,41 @@
-use std::{collections::HashMap, fmt, path::Path, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use serde::{
     de::{self, Deserialize, Deserializer},
     ser::{Serialize, Serializer},
 };
 use serde_json::Value;
-use tantivy::tokenizer::{TextAnalyzer, TokenizerManager};
+use tantivy::{
+    schema::{FieldType, Schema},
+    tokenizer::{TextAnalyzer, Token, TokenizerManager},
+};
 use tokio::fs;
 
 use super::{
+    char_filter::{
+        html_strip::create_html_strip_char_filter, mapping::create_mapping_char_filter,
+        pattern_replace::create_pattern_replace_char_filter,
+        unicode_normalize::create_unicode_normalize_char_filter, CharFilterKind,
+        CharFilteredTokenizer,
+    },
     token_filter::{
         alpha_num_only::create_alpha_num_only_filter, ascii_folding::create_ascii_folding_filter,
-        lower_case::create_lower_case_filter, remove_long::create_remove_long_filter,
-        stemming::create_stemming_filter, stop_word::create_stop_word_filter, FilterKind,
+        edge_ngram::create_edge_ngram_filter, elision::create_elision_filter,
+        length::create_length_filter, lower_case::create_lower_case_filter,
+        remove_long::create_remove_long_filter, shingle::create_shingle_filter,
+        stemming::create_stemming_filter, stop_word::create_stop_word_filter,
+        synonym::create_synonym_filter, unique::create_unique_filter,
+        word_delimiter::create_word_delimiter_filter, FilterKind,
     },
     tokenizer::{
-        lindera::create_lindera_analyzer, ngram::create_ngram_analyzer, raw::create_raw_analyzer,
-        simple::create_simple_analyzer, whitespace::create_whitespace_analyzer, TokenizerKind,
+        lindera::create_lindera_analyzer, ngram::create_ngram_analyzer,
+        pattern::create_pattern_analyzer, raw::create_raw_analyzer, simple::create_simple_analyzer,
+        unicode::create_unicode_analyzer, whitespace::create_whitespace_analyzer, TokenizerKind,
     },
 };
 
@@ -25,6 +44,7 @@ pub enum AnalyzerErrorKind {
     ParseFailure,
     InitializationFailure,
     InvalidTokenizerKind,
+    IncompatibleChange,
 }
 
 impl AnalyzerErrorKind {
@@ -67,6 +87,7 @@ impl AnalyzerError {
 pub struct Analyzers {
     source: Value,
     analyzers: HashMap<String, TextAnalyzer>,
+    base_dir: Option<PathBuf>,
 }
 
 impl Analyzers {
@@ -89,6 +110,23 @@ impl Analyzers {
     pub fn contains_key(&self, key: &str) -> bool {
         self.analyzers.contains_key(key)
     }
+
+    /// Returns the setting of the analyzer.
+    pub fn setting(&self, name: &str) -> Option<&Value> {
+        self.source.get(name)
+    }
+
+    /// Returns the analyzers rebuilt with the files under the index directory.
+    pub fn with_base_dir(&self, base_dir: &Path) -> Result<Analyzers, AnalyzerError> {
+        build_analyzers(&self.source, Some(base_dir))
+    }
+
+    /// Returns the text analyzers of each stage of the analyzer.
+    pub fn stages(&self, name: &str) -> Option<Result<Vec<(String, TextAnalyzer)>, AnalyzerError>> {
+        self.source
+            .get(name)
+            .map(|setting| build_analyzer_stages(setting, self.base_dir.as_deref()))
+    }
 }
 
 impl Serialize for Analyzers {
@@ -106,13 +144,13 @@ impl<'de> Deserialize<'de> for Analyzers {
         D: Deserializer<'de>,
     {
         let inner = Value::deserialize(deserializer)?;
-        let text_analyzers = build_analyzers(&inner).map_err(de::Error::custom)?;
+        let text_analyzers = build_analyzers(&inner, None).map_err(de::Error::custom)?;
 
         Ok(text_analyzers)
     }
 }
 
-fn build_analyzers(source: &Value) -> Result<Analyzers, AnalyzerError> {
+fn build_analyzers(source: &Value, base_dir: Option<&Path>) -> Result<Analyzers, AnalyzerError> {
     let mut analyzers = HashMap::new();
 
     let inner_map = source.as_object().ok_or_else(|| {
@@ -120,109 +158,284 @@ fn build_analyzers(source: &Value) -> Result<Analyzers, AnalyzerError> {
     })?;
 
     for (analyzer_name, analyzer_setting) in inner_map.iter() {
-        let analyzer_map = analyzer_setting.as_object().ok_or_else(|| {
-            AnalyzerErrorKind::ParseFailure
-                .with_error(anyhow::anyhow!("analyzer setting must be a map"))
+        let analyzer = build_analyzer(analyzer_setting, base_dir)?;
+        analyzers.insert(analyzer_name.to_string(), analyzer);
+    }
+
+    Ok(Analyzers {
+        source: source.clone(),
+        analyzers,
+        base_dir: base_dir.map(Path::to_path_buf),
+    })
+}
+
+/// Build a text analyzer from the analyzer setting.
+/// The files referred to by the filters are resolved relative to the base directory.
+pub fn build_analyzer(
+    analyzer_setting: &Value,
+    base_dir: Option<&Path>,
+) -> Result<TextAnalyzer, AnalyzerError> {
+    let (_stage_name, analyzer) = build_analyzer_stages(analyzer_setting, base_dir)?
+        .pop()
+        .ok_or_else(|| {
+            AnalyzerErrorKind::ParseFailure.with_error(anyhow::anyhow!("analyzer is empty"))
         })?;
 
-        // Initialize tokenizer.
-        let tokenizer_map = analyzer_map["tokenizer"].as_object().ok_or_else(|| {
+    Ok(analyzer)
+}
+
+/// Build the text analyzers of each stage of the analyzer setting.
+/// The first one consists only of the tokenizer, and each following one adds the next filter.
+pub fn build_analyzer_stages(
+    analyzer_setting: &Value,
+    base_dir: Option<&Path>,
+) -> Result<Vec<(String, TextAnalyzer)>, AnalyzerError> {
+    let mut stages = Vec::new();
+
+    let analyzer_map = analyzer_setting.as_object().ok_or_else(|| {
+        AnalyzerErrorKind::ParseFailure
+            .with_error(anyhow::anyhow!("analyzer setting must be a map"))
+    })?;
+
+    // Initialize tokenizer.
+    let tokenizer_map = analyzer_map
+        .get("tokenizer")
+        .and_then(|tokenizer| tokenizer.as_object())
+        .ok_or_else(|| {
             AnalyzerErrorKind::ParseFailure
                 .with_error(anyhow::anyhow!("tokenizer setting must be a map"))
         })?;
 
-        let tokenizer_name = tokenizer_map["name"].as_str().ok_or_else(|| {
+    let tokenizer_name = tokenizer_map
+        .get("name")
+        .and_then(|name| name.as_str())
+        .ok_or_else(|| {
             AnalyzerErrorKind::ParseFailure
                 .with_error(anyhow::anyhow!("tokenizer name must be a string"))
         })?;
 
-        let tokenizer_args_bytes = if tokenizer_map.contains_key("args") {
-            serde_json::to_vec(&tokenizer_map["args"])
-                .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?
-        } else {
-            Vec::new()
-        };
-
-        // Make text analyzer based on tokenizer.
-        let mut analyzer = match TokenizerKind::from_str(tokenizer_name)
+    let tokenizer_args_bytes = if tokenizer_map.contains_key("args") {
+        serde_json::to_vec(&tokenizer_map["args"])
             .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?
-        {
-            TokenizerKind::Lindera => create_lindera_analyzer(&tokenizer_args_bytes)
-                .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
-            TokenizerKind::Ngram => create_ngram_analyzer(&tokenizer_args_bytes)
-                .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
-            TokenizerKind::Raw => create_raw_analyzer(),
-            TokenizerKind::Simple => create_simple_analyzer(),
-            TokenizerKind::Whitespace => create_whitespace_analyzer(),
-        };
+    } else {
+        Vec::new()
+    };
 
-        // Initialize filters.
-        if analyzer_map.contains_key("filters") {
-            let filters = analyzer_map["filters"].as_array().ok_or_else(|| {
+    // Make text analyzer based on tokenizer.
+    let mut analyzer = match TokenizerKind::from_str(tokenizer_name)
+        .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?
+    {
+        TokenizerKind::Lindera => create_lindera_analyzer(&tokenizer_args_bytes, base_dir)
+            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+        TokenizerKind::Ngram => create_ngram_analyzer(&tokenizer_args_bytes)
+            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+        TokenizerKind::Pattern => create_pattern_analyzer(&tokenizer_args_bytes)
+            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+        TokenizerKind::Raw => create_raw_analyzer(),
+        TokenizerKind::Simple => create_simple_analyzer(),
+        TokenizerKind::Unicode => create_unicode_analyzer(),
+        TokenizerKind::Whitespace => create_whitespace_analyzer(),
+    };
+
+    // Initialize char filters.
+    // They are applied to the text before the tokenizer, so they are a part of the tokenizer stage.
+    if analyzer_map.contains_key("char_filters") {
+        let char_filter_settings = analyzer_map["char_filters"].as_array().ok_or_else(|| {
+            AnalyzerErrorKind::ParseFailure
+                .with_error(anyhow::anyhow!("char_filters must be an array"))
+        })?;
+
+        let mut char_filters = Vec::new();
+        for char_filter in char_filter_settings {
+            let char_filter_setting_map = char_filter.as_object().ok_or_else(|| {
                 AnalyzerErrorKind::ParseFailure
-                    .with_error(anyhow::anyhow!("filters must be an array"))
+                    .with_error(anyhow::anyhow!("char filter setting must be a map"))
             })?;
 
-            for filter in filters {
-                let filter_setting_map = filter.as_object().ok_or_else(|| {
+            let char_filter_name = char_filter_setting_map
+                .get("name")
+                .and_then(|name| name.as_str())
+                .ok_or_else(|| {
                     AnalyzerErrorKind::ParseFailure
-                        .with_error(anyhow::anyhow!("filter setting must be a map"))
+                        .with_error(anyhow::anyhow!("char filter name must be a string"))
                 })?;
 
-                let filter_name = filter_setting_map["name"].as_str().ok_or_else(|| {
+            let char_filter_args_bytes = if char_filter_setting_map.contains_key("args") {
+                serde_json::to_vec(&char_filter_setting_map["args"])
+                    .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?
+            } else {
+                Vec::new()
+            };
+
+            let char_filter = match CharFilterKind::from_str(char_filter_name)
+                .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?
+            {
+                CharFilterKind::HtmlStrip => create_html_strip_char_filter(&char_filter_args_bytes),
+                CharFilterKind::Mapping => create_mapping_char_filter(&char_filter_args_bytes),
+                CharFilterKind::PatternReplace => {
+                    create_pattern_replace_char_filter(&char_filter_args_bytes)
+                }
+                CharFilterKind::UnicodeNormalize => {
+                    create_unicode_normalize_char_filter(&char_filter_args_bytes)
+                }
+            }
+            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?;
+            char_filters.push(char_filter);
+        }
+
+        if !char_filters.is_empty() {
+            analyzer = TextAnalyzer::from(CharFilteredTokenizer::new(char_filters, analyzer));
+        }
+    }
+    stages.push((format!("tokenizer:{}", tokenizer_name), analyzer.clone()));
+
+    // Initialize filters.
+    if analyzer_map.contains_key("filters") {
+        let filters = analyzer_map["filters"].as_array().ok_or_else(|| {
+            AnalyzerErrorKind::ParseFailure.with_error(anyhow::anyhow!("filters must be an array"))
+        })?;
+
+        for filter in filters {
+            let filter_setting_map = filter.as_object().ok_or_else(|| {
+                AnalyzerErrorKind::ParseFailure
+                    .with_error(anyhow::anyhow!("filter setting must be a map"))
+            })?;
+
+            let filter_name = filter_setting_map
+                .get("name")
+                .and_then(|name| name.as_str())
+                .ok_or_else(|| {
                     AnalyzerErrorKind::ParseFailure
                         .with_error(anyhow::anyhow!("filter name must be a string"))
                 })?;
 
-                let filter_args_bytes = if filter_setting_map.contains_key("args") {
-                    serde_json::to_vec(&filter_setting_map["args"])
-                        .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?
-                } else {
-                    Vec::new()
-                };
-
-                match FilterKind::from_str(filter_name)
+            let filter_args_bytes = if filter_setting_map.contains_key("args") {
+                serde_json::to_vec(&filter_setting_map["args"])
                     .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?
-                {
-                    FilterKind::AlphaNumOnly => {
-                        analyzer = analyzer.filter(create_alpha_num_only_filter());
-                    }
-                    FilterKind::AsciiFolding => {
-                        analyzer = analyzer.filter(create_ascii_folding_filter());
-                    }
-                    FilterKind::LowerCase => {
-                        analyzer = analyzer.filter(create_lower_case_filter());
-                    }
-                    FilterKind::RemoveLong => {
-                        analyzer = analyzer.filter(
-                            create_remove_long_filter(&filter_args_bytes).map_err(|error| {
-                                AnalyzerErrorKind::ParseFailure.with_error(error)
-                            })?,
-                        );
-                    }
-                    FilterKind::Stemming => {
-                        analyzer =
-                            analyzer.filter(create_stemming_filter(&filter_args_bytes).map_err(
-                                |error| AnalyzerErrorKind::ParseFailure.with_error(error),
-                            )?);
-                    }
-                    FilterKind::StopWord => {
-                        analyzer =
-                            analyzer.filter(create_stop_word_filter(&filter_args_bytes).map_err(
-                                |error| AnalyzerErrorKind::ParseFailure.with_error(error),
-                            )?);
-                    }
+            } else {
+                Vec::new()
+            };
+
+            match FilterKind::from_str(filter_name)
+                .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?
+            {
+                FilterKind::AlphaNumOnly => {
+                    analyzer = analyzer.filter(create_alpha_num_only_filter());
+                }
+                FilterKind::AsciiFolding => {
+                    analyzer = analyzer.filter(create_ascii_folding_filter());
+                }
+                FilterKind::EdgeNgram => {
+                    analyzer = analyzer.filter(
+                        create_edge_ngram_filter(&filter_args_bytes)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
+                }
+                FilterKind::Elision => {
+                    analyzer = analyzer.filter(
+                        create_elision_filter(&filter_args_bytes)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
+                }
+                FilterKind::Length => {
+                    analyzer = analyzer.filter(
+                        create_length_filter(&filter_args_bytes)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
+                }
+                FilterKind::LowerCase => {
+                    analyzer = analyzer.filter(create_lower_case_filter());
+                }
+                FilterKind::RemoveLong => {
+                    analyzer = analyzer.filter(
+                        create_remove_long_filter(&filter_args_bytes)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
+                }
+                FilterKind::Shingle => {
+                    analyzer = analyzer.filter(
+                        create_shingle_filter(&filter_args_bytes)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
+                }
+                FilterKind::Stemming => {
+                    analyzer = analyzer.filter(
+                        create_stemming_filter(&filter_args_bytes)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
+                }
+                FilterKind::StopWord => {
+                    analyzer = analyzer.filter(
+                        create_stop_word_filter(&filter_args_bytes)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
+                }
+                FilterKind::Synonym => {
+                    analyzer = analyzer.filter(
+                        create_synonym_filter(&filter_args_bytes, base_dir)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
+                }
+                FilterKind::Unique => {
+                    analyzer = analyzer.filter(
+                        create_unique_filter(&filter_args_bytes)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
+                }
+                FilterKind::WordDelimiter => {
+                    analyzer = analyzer.filter(
+                        create_word_delimiter_filter(&filter_args_bytes)
+                            .map_err(|error| AnalyzerErrorKind::ParseFailure.with_error(error))?,
+                    );
                 }
             }
+            stages.push((format!("filter:{}", filter_name), analyzer.clone()));
         }
+    }
 
-        analyzers.insert(analyzer_name.to_string(), analyzer);
+    Ok(stages)
+}
+
+/// Returns the names of the analyzers used to index the text fields.
+pub fn indexing_analyzer_names(schema: &Schema) -> HashSet<String> {
+    schema
+        .fields()
+        .filter_map(|(_field, field_entry)| match field_entry.field_type() {
+            FieldType::Str(options) => options.get_indexing_options(),
+            FieldType::JsonObject(options) => options.get_text_indexing_options(),
+            _ => None,
+        })
+        .map(|indexing_options| indexing_options.tokenizer().to_string())
+        .collect()
+}
+
+/// Checks that the new analyzers produce the same tokens as the current ones for the indexed
+/// fields. Only the analyzers that are not used at index time can be changed.
+pub fn check_analyzers_compatibility(
+    analyzers: &Analyzers,
+    new_analyzers: &Analyzers,
+    schema: &Schema,
+) -> Result<(), AnalyzerError> {
+    for name in indexing_analyzer_names(schema) {
+        if analyzers.setting(&name) != new_analyzers.setting(&name) {
+            return Err(
+                AnalyzerErrorKind::IncompatibleChange.with_error(anyhow::anyhow!(
+                    "Analyzer {} is used at index time and cannot be changed",
+                    name
+                )),
+            );
+        }
     }
 
-    Ok(Analyzers {
-        source: source.clone(),
-        analyzers,
-    })
+    Ok(())
+}
+
+/// Analyze the text and returns the tokens.
+pub fn analyze_text(analyzer: &TextAnalyzer, text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut token_stream = analyzer.token_stream(text);
+    token_stream.process(&mut |token: &Token| tokens.push(token.clone()));
+    tokens
 }
 
 pub fn register(manager: &TokenizerManager, analyzers: &Analyzers) -> Result<(), AnalyzerError> {
@@ -233,13 +446,35 @@ pub fn register(manager: &TokenizerManager, analyzers: &Analyzers) -> Result<(),
     Ok(())
 }
 
+/// Registers the analyzers that are not used at index time.
+/// The index-time analyzers keep the files loaded when the shard was opened, so that reloading
+/// the changed files cannot make the indexed tokens incompatible.
+pub fn register_search_time_analyzers(
+    manager: &TokenizerManager,
+    analyzers: &Analyzers,
+    schema: &Schema,
+) -> Result<(), AnalyzerError> {
+    let indexing_analyzer_names = indexing_analyzer_names(schema);
+    for (name, analyzer) in analyzers.iter() {
+        if !indexing_analyzer_names.contains(name) {
+            manager.register(name, analyzer.clone());
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs, path::PathBuf};
 
     use tantivy::tokenizer::TokenizerManager;
+    use tempdir::TempDir;
 
-    use crate::index::analyzer::{register, Analyzers};
+    use crate::index::analyzer::{
+        analyze_text, build_analyzer, build_analyzer_stages, check_analyzers_compatibility,
+        indexing_analyzer_names, register, register_search_time_analyzers, Analyzers,
+    };
 
     #[test]
     fn test_register() {
@@ -285,4 +520,219 @@ mod tests {
         let expected = 5018;
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_build_analyzer_stages() {
+        let analyzer_setting = serde_json::json!({
+            "tokenizer": {
+                "name": "simple"
+            },
+            "filters": [
+                {
+                    "name": "ascii_folding"
+                },
+                {
+                    "name": "lower_case"
+                }
+            ]
+        });
+
+        let stages = build_analyzer_stages(&analyzer_setting, None).unwrap();
+        let stage_names = stages
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            stage_names,
+            vec![
+                "tokenizer:simple",
+                "filter:ascii_folding",
+                "filter:lower_case"
+            ]
+        );
+
+        let texts = stages
+            .iter()
+            .map(|(_, analyzer)| {
+                analyze_text(analyzer, "Café Latte")
+                    .into_iter()
+                    .map(|token| token.text)
+                    .collect::<Vec<String>>()
+            })
+            .collect::<Vec<Vec<String>>>();
+        assert_eq!(texts[0], vec!["Café", "Latte"]);
+        assert_eq!(texts[1], vec!["Cafe", "Latte"]);
+        assert_eq!(texts[2], vec!["cafe", "latte"]);
+
+        let tokens = analyze_text(&stages[2].1, "Café Latte");
+        assert_eq!(tokens[1].offset_from, 6);
+        assert_eq!(tokens[1].offset_to, 11);
+        assert_eq!(tokens[1].position, 1);
+        assert_eq!(tokens[1].position_length, 1);
+    }
+
+    #[test]
+    fn test_build_analyzer_with_char_filters() {
+        let analyzer_setting = serde_json::json!({
+            "char_filters": [
+                {
+                    "name": "html_strip"
+                },
+                {
+                    "name": "unicode_normalize",
+                    "args": {
+                        "form": "nfkc_cf"
+                    }
+                }
+            ],
+            "tokenizer": {
+                "name": "simple"
+            }
+        });
+
+        let analyzer = build_analyzer(&analyzer_setting, None).unwrap();
+        let text = "<p>Ｔｏｋｙｏ</p>";
+        let tokens = analyze_text(&analyzer, text);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].text, "tokyo");
+        assert_eq!(
+            &text[tokens[0].offset_from..tokens[0].offset_to],
+            "Ｔｏｋｙｏ"
+        );
+
+        let analyzer_setting = serde_json::json!({
+            "char_filters": [
+                {
+                    "name": "unknown"
+                }
+            ],
+            "tokenizer": {
+                "name": "simple"
+            }
+        });
+        assert!(build_analyzer(&analyzer_setting, None).is_err());
+    }
+
+    #[test]
+    fn test_analyzers_stages() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../examples")
+            .join("analyzers.json");
+
+        let analyzers_bytes = fs::read(&path).unwrap();
+        let analyzers = serde_json::from_slice::<Analyzers>(&analyzers_bytes).unwrap();
+
+        let stages = analyzers.stages("default").unwrap().unwrap();
+        assert_eq!(stages.len(), 4);
+        assert!(analyzers.stages("unknown").is_none());
+    }
+
+    #[test]
+    fn test_check_analyzers_compatibility() {
+        let schema = serde_json::from_value(serde_json::json!([
+            {
+                "name": "title",
+                "type": "text",
+                "options": {
+                    "indexing": {
+                        "record": "position",
+                        "fieldnorms": true,
+                        "tokenizer": "default"
+                    },
+                    "stored": true
+                }
+            }
+        ]))
+        .unwrap();
+        assert_eq!(
+            indexing_analyzer_names(&schema)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["default".to_string()]
+        );
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../examples")
+            .join("analyzers.json");
+        let analyzers_bytes = fs::read(&path).unwrap();
+        let analyzers = serde_json::from_slice::<Analyzers>(&analyzers_bytes).unwrap();
+
+        // Analyzers that are not used at index time can be changed.
+        let mut source = serde_json::to_value(&analyzers).unwrap();
+        source["lang_en"]["filters"] = serde_json::json!([{"name": "lower_case"}]);
+        let new_analyzers = serde_json::from_value::<Analyzers>(source).unwrap();
+        assert!(check_analyzers_compatibility(&analyzers, &new_analyzers, &schema).is_ok());
+
+        // Analyzers that are used at index time cannot be changed.
+        let mut source = serde_json::to_value(&analyzers).unwrap();
+        source["default"]["filters"] = serde_json::json!([{"name": "lower_case"}]);
+        let new_analyzers = serde_json::from_value::<Analyzers>(source).unwrap();
+        assert!(check_analyzers_compatibility(&analyzers, &new_analyzers, &schema).is_err());
+    }
+
+    #[test]
+    fn test_register_search_time_analyzers() {
+        let index_dir = TempDir::new("analyzer").unwrap();
+        let synonyms_path = index_dir.path().join("synonyms.txt");
+        fs::write(&synonyms_path, "fast, quick\n").unwrap();
+
+        let analyzer = serde_json::json!({
+            "tokenizer": {
+                "name": "simple"
+            },
+            "filters": [
+                {
+                    "name": "synonym",
+                    "args": {
+                        "synonyms_path": "synonyms.txt"
+                    }
+                }
+            ]
+        });
+        let analyzers = serde_json::from_value::<Analyzers>(serde_json::json!({
+            "index_time": analyzer,
+            "search_time": analyzer,
+        }))
+        .unwrap();
+        let schema = serde_json::from_value(serde_json::json!([
+            {
+                "name": "title",
+                "type": "text",
+                "options": {
+                    "indexing": {
+                        "record": "position",
+                        "fieldnorms": true,
+                        "tokenizer": "index_time"
+                    },
+                    "stored": true
+                }
+            }
+        ]))
+        .unwrap();
+
+        let manager = TokenizerManager::default();
+        register(
+            &manager,
+            &analyzers.with_base_dir(index_dir.path()).unwrap(),
+        )
+        .unwrap();
+
+        // Reloading the changed file affects only the analyzers used at search time.
+        fs::write(&synonyms_path, "fast, rapid\n").unwrap();
+        register_search_time_analyzers(
+            &manager,
+            &analyzers.with_base_dir(index_dir.path()).unwrap(),
+            &schema,
+        )
+        .unwrap();
+
+        let texts = |name: &str| {
+            analyze_text(&manager.get(name).unwrap(), "fast")
+                .into_iter()
+                .map(|token| token.text)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts("index_time"), vec!["fast", "quick"]);
+        assert_eq!(texts("search_time"), vec!["fast", "rapid"]);
+    }
 }
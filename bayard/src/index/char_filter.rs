@@ -0,0 +1,258 @@
+pub mod html_strip;
+pub mod mapping;
+pub mod pattern_replace;
+pub mod unicode_normalize;
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use tantivy::tokenizer::{BoxTokenStream, TextAnalyzer, Token, Tokenizer};
+
+use crate::index::token_filter::BufferedTokenStream;
+
+#[derive(Debug, Clone, Copy)]
+pub enum CharFilterFactoryErrorKind {
+    InvalidArgument,
+    InvalidCharFilterKind,
+}
+
+impl CharFilterFactoryErrorKind {
+    pub fn with_error<E>(self, source: E) -> CharFilterFactoryError
+    where
+        anyhow::Error: From<E>,
+    {
+        CharFilterFactoryError {
+            kind: self,
+            source: From::from(source),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("CharFilterFactoryError(kind={kind:?}, source={source})")]
+pub struct CharFilterFactoryError {
+    pub kind: CharFilterFactoryErrorKind,
+    #[source]
+    source: anyhow::Error,
+}
+
+impl CharFilterFactoryError {
+    pub fn add_context<C>(self, ctx: C) -> Self
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        CharFilterFactoryError {
+            kind: self.kind,
+            source: self.source.context(ctx),
+        }
+    }
+
+    pub fn kind(&self) -> CharFilterFactoryErrorKind {
+        self.kind
+    }
+}
+
+pub enum CharFilterKind {
+    HtmlStrip,
+    Mapping,
+    PatternReplace,
+    UnicodeNormalize,
+}
+
+impl FromStr for CharFilterKind {
+    type Err = CharFilterFactoryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html_strip" => Ok(CharFilterKind::HtmlStrip),
+            "mapping" => Ok(CharFilterKind::Mapping),
+            "pattern_replace" => Ok(CharFilterKind::PatternReplace),
+            "unicode_normalize" => Ok(CharFilterKind::UnicodeNormalize),
+            _ => Err(CharFilterFactoryErrorKind::InvalidCharFilterKind
+                .with_error(anyhow::format_err!("Unknown char filter kind: {}", s))),
+        }
+    }
+}
+
+/// The text produced by char filters.
+/// Each byte of the text keeps the range of the original text it comes from,
+/// so that the token offsets can be mapped back to the original text.
+pub struct MappedText {
+    text: String,
+    spans: Vec<(usize, usize)>,
+    original_len: usize,
+}
+
+impl MappedText {
+    /// Creates an empty text to be built from an original text of the given length.
+    pub fn new(original_len: usize) -> Self {
+        MappedText {
+            text: String::with_capacity(original_len),
+            spans: Vec::with_capacity(original_len),
+            original_len,
+        }
+    }
+
+    /// Creates a text identical to the original text.
+    pub fn identity(text: &str) -> Self {
+        let mut mapped = MappedText::new(text.len());
+        mapped.push_original(text, 0);
+        mapped
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Appends a part of the original text starting at the offset as is.
+    pub fn push_original(&mut self, s: &str, offset: usize) {
+        self.text.push_str(s);
+        self.spans
+            .extend((offset..offset + s.len()).map(|offset| (offset, offset + 1)));
+    }
+
+    /// Appends the replacement of the range of the original text.
+    pub fn push_replaced(&mut self, s: &str, offset_from: usize, offset_to: usize) {
+        self.text.push_str(s);
+        self.spans
+            .extend(std::iter::repeat_n((offset_from, offset_to), s.len()));
+    }
+
+    /// Maps the range of the text back to the range of the original text.
+    pub fn original_offsets(&self, offset_from: usize, offset_to: usize) -> (usize, usize) {
+        let original_from = self
+            .spans
+            .get(offset_from)
+            .map(|span| span.0)
+            .unwrap_or(self.original_len);
+        if offset_to <= offset_from {
+            return (original_from, original_from);
+        }
+        let original_to = self
+            .spans
+            .get(offset_to - 1)
+            .map(|span| span.1)
+            .unwrap_or(self.original_len);
+
+        (original_from, original_to.max(original_from))
+    }
+
+    /// Composes the mapping of this text, produced from the previous text,
+    /// with the mapping of the previous text.
+    fn compose(self, previous: &MappedText) -> MappedText {
+        MappedText {
+            text: self.text,
+            spans: self
+                .spans
+                .into_iter()
+                .map(|(from, to)| previous.original_offsets(from, to))
+                .collect(),
+            original_len: previous.original_len,
+        }
+    }
+}
+
+pub trait CharFilter: 'static + Send + Sync {
+    /// Filters the text and returns the filtered text mapped to the given text.
+    fn filter(&self, text: &str) -> MappedText;
+}
+
+#[derive(Clone)]
+pub struct BoxCharFilter(Arc<dyn CharFilter>);
+
+impl<T: CharFilter> From<T> for BoxCharFilter {
+    fn from(char_filter: T) -> BoxCharFilter {
+        BoxCharFilter(Arc::new(char_filter))
+    }
+}
+
+/// Applies the char filters to the text in order.
+pub fn apply_char_filters(char_filters: &[BoxCharFilter], text: &str) -> MappedText {
+    let mut mapped = MappedText::identity(text);
+    for char_filter in char_filters {
+        mapped = char_filter.0.filter(mapped.text()).compose(&mapped);
+    }
+    mapped
+}
+
+/// A tokenizer that applies the char filters before the analyzer.
+/// The offsets of the tokens refer to the original text.
+#[derive(Clone)]
+pub struct CharFilteredTokenizer {
+    char_filters: Vec<BoxCharFilter>,
+    analyzer: TextAnalyzer,
+}
+
+impl CharFilteredTokenizer {
+    pub fn new(char_filters: Vec<BoxCharFilter>, analyzer: TextAnalyzer) -> Self {
+        CharFilteredTokenizer {
+            char_filters,
+            analyzer,
+        }
+    }
+}
+
+impl Tokenizer for CharFilteredTokenizer {
+    fn token_stream<'a>(&self, text: &'a str) -> BoxTokenStream<'a> {
+        let mapped = apply_char_filters(&self.char_filters, text);
+
+        let mut tokens = Vec::new();
+        self.analyzer
+            .token_stream(mapped.text())
+            .process(&mut |token: &Token| {
+                let (offset_from, offset_to) =
+                    mapped.original_offsets(token.offset_from, token.offset_to);
+                tokens.push(Token {
+                    offset_from,
+                    offset_to,
+                    ..token.clone()
+                });
+            });
+
+        BoxTokenStream::from(BufferedTokenStream::new(tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::{SimpleTokenizer, TextAnalyzer, Token};
+
+    use crate::index::char_filter::{
+        html_strip::create_html_strip_char_filter, mapping::create_mapping_char_filter,
+        CharFilteredTokenizer,
+    };
+
+    #[test]
+    fn test_char_filtered_tokenizer() {
+        let html_strip = create_html_strip_char_filter(&[]).unwrap();
+        let mapping = create_mapping_char_filter(
+            r#"
+            {
+                "mappings": ["&& => and"]
+            }
+            "#
+            .as_bytes(),
+        )
+        .unwrap();
+        let analyzer = TextAnalyzer::from(CharFilteredTokenizer::new(
+            vec![html_strip, mapping],
+            TextAnalyzer::from(SimpleTokenizer),
+        ));
+
+        let text = "<b>rock</b> &amp;&amp; roll";
+        let mut tokens = vec![];
+        analyzer
+            .token_stream(text)
+            .process(&mut |token: &Token| tokens.push(token.clone()));
+
+        assert_eq!(
+            vec![("rock", "rock"), ("and", "&amp;&amp;"), ("roll", "roll")],
+            tokens
+                .iter()
+                .map(|token| (
+                    token.text.as_str(),
+                    &text[token.offset_from..token.offset_to]
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashSet, sync::Arc};
+
+use crate::index::char_filter::{
+    BoxCharFilter, CharFilter, CharFilterFactoryError, CharFilterFactoryErrorKind, MappedText,
+};
+
+// The tags that are replaced with a line break so that the words around them are not joined.
+const BLOCK_TAGS: [&str; 30] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+// The tags whose contents are removed as well.
+const SKIPPED_TAGS: [&str; 2] = ["script", "style"];
+
+// The longest entity to look for. e.g. "&#x10FFFF;"
+const MAX_ENTITY_LEN: usize = 10;
+
+#[derive(Deserialize, Serialize, Default)]
+struct HtmlStripCharFilterArgs {
+    #[serde(default)]
+    escaped_tags: Vec<String>,
+}
+
+pub fn create_html_strip_char_filter(args: &[u8]) -> Result<BoxCharFilter, CharFilterFactoryError> {
+    let html_strip_args = if args.is_empty() {
+        HtmlStripCharFilterArgs::default()
+    } else {
+        serde_json::from_slice::<HtmlStripCharFilterArgs>(args)
+            .map_err(|e| CharFilterFactoryErrorKind::InvalidArgument.with_error(e))?
+    };
+
+    Ok(BoxCharFilter::from(HtmlStripCharFilter {
+        escaped_tags: Arc::new(
+            html_strip_args
+                .escaped_tags
+                .iter()
+                .map(|tag| tag.to_lowercase())
+                .collect(),
+        ),
+    }))
+}
+
+/// Removes the HTML tags and comments, and decodes the character references.
+#[derive(Clone)]
+pub struct HtmlStripCharFilter {
+    escaped_tags: Arc<HashSet<String>>,
+}
+
+impl HtmlStripCharFilter {
+    /// Returns the length of the markup at the beginning of the text and its replacement,
+    /// or `None` if the text does not start with markup to be stripped.
+    fn parse_markup(&self, text: &str) -> Option<(usize, &'static str)> {
+        if let Some(comment) = text.strip_prefix("<!--") {
+            let len = comment
+                .find("-->")
+                .map(|end| "<!--".len() + end + "-->".len())
+                .unwrap_or(text.len());
+            return Some((len, ""));
+        }
+        if text.starts_with("<!") || text.starts_with("<?") {
+            return Some((text.find('>').map(|end| end + 1).unwrap_or(text.len()), ""));
+        }
+
+        let (closing, name_start) = match text.strip_prefix("</") {
+            Some(_) => (true, 2),
+            None => (false, 1),
+        };
+        let name = text[name_start..]
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .next()
+            .unwrap_or_default();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        let name = name.to_ascii_lowercase();
+        if self.escaped_tags.contains(&name) {
+            return None;
+        }
+        let mut len = text.find('>')? + 1;
+
+        if !closing && SKIPPED_TAGS.contains(&name.as_str()) {
+            let end_tag = format!("</{}", name);
+            len = text[len..]
+                .to_ascii_lowercase()
+                .find(&end_tag)
+                .and_then(|start| {
+                    let start = len + start;
+                    text[start..].find('>').map(|end| start + end + 1)
+                })
+                .unwrap_or(text.len());
+        }
+
+        if BLOCK_TAGS.contains(&name.as_str()) {
+            Some((len, "\n"))
+        } else {
+            Some((len, ""))
+        }
+    }
+}
+
+/// Returns the length of the character reference at the beginning of the text and
+/// the character it refers to.
+fn decode_entity(text: &str) -> Option<(usize, char)> {
+    let end = text
+        .char_indices()
+        .take(MAX_ENTITY_LEN)
+        .find(|&(_, c)| c == ';')?
+        .0;
+    let name = &text[1..end];
+
+    let decoded = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        _ => {
+            let code =
+                if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    u32::from_str_radix(hex, 16).ok()?
+                } else {
+                    name.strip_prefix('#')?.parse::<u32>().ok()?
+                };
+            char::from_u32(code)?
+        }
+    };
+
+    Some((end + 1, decoded))
+}
+
+impl CharFilter for HtmlStripCharFilter {
+    fn filter(&self, text: &str) -> MappedText {
+        let mut mapped = MappedText::new(text.len());
+
+        // The start of the part of the text that is kept as is.
+        let mut kept = 0;
+        let mut pos = 0;
+        while pos < text.len() {
+            let rest = &text[pos..];
+            let replaced = if rest.starts_with('<') {
+                self.parse_markup(rest)
+                    .map(|(len, replacement)| (len, replacement.to_string()))
+            } else if rest.starts_with('&') {
+                decode_entity(rest).map(|(len, decoded)| (len, decoded.to_string()))
+            } else {
+                None
+            };
+
+            match replaced {
+                Some((len, replacement)) => {
+                    mapped.push_original(&text[kept..pos], kept);
+                    mapped.push_replaced(&replacement, pos, pos + len);
+                    pos += len;
+                    kept = pos;
+                }
+                None => pos += rest.chars().next().map(char::len_utf8).unwrap_or(1),
+            }
+        }
+        mapped.push_original(&text[kept..], kept);
+
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::char_filter::{
+        apply_char_filters, html_strip::create_html_strip_char_filter,
+    };
+
+    fn helper(json_str: &str, text: &str) -> String {
+        let char_filter = create_html_strip_char_filter(json_str.as_bytes()).unwrap();
+
+        apply_char_filters(&[char_filter], text).text().to_string()
+    }
+
+    #[test]
+    fn test_html_strip_char_filter() {
+        assert_eq!(
+            "\nI&apos;m so happy!\n",
+            helper(
+                "",
+                "<p>I&amp;apos;m so <b>happy</b>!</p><!-- comment --><script>alert('x')</script>"
+            )
+        );
+        assert_eq!("caf\u{e9} <", helper("", "caf&#xE9; &lt;"));
+        assert_eq!("1 < 2 &unknown;", helper("", "1 < 2 &unknown;"));
+
+        let json_str = r#"
+            {
+                "escaped_tags": ["b"]
+            }
+            "#;
+        assert_eq!(
+            "\nI'm so <b>happy</b>!\n",
+            helper(json_str, "<p>I'm so <b>happy</b>!</p>")
+        );
+    }
+
+    #[test]
+    fn test_html_strip_char_filter_offsets() {
+        let char_filter = create_html_strip_char_filter(&[]).unwrap();
+
+        let text = "<b>hello</b> &lt;world&gt;";
+        let mapped = apply_char_filters(&[char_filter], text);
+        assert_eq!("hello <world>", mapped.text());
+
+        let (from, to) = mapped.original_offsets(0, 5);
+        assert_eq!("hello", &text[from..to]);
+        let (from, to) = mapped.original_offsets(6, 13);
+        assert_eq!("&lt;world&gt;", &text[from..to]);
+    }
+}
@@ -0,0 +1,117 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::index::char_filter::{
+    BoxCharFilter, CharFilter, CharFilterFactoryError, CharFilterFactoryErrorKind, MappedText,
+};
+
+const MAPPING_SEPARATOR: &str = "=>";
+
+#[derive(Deserialize, Serialize)]
+struct MappingCharFilterArgs {
+    mappings: Vec<String>,
+}
+
+pub fn create_mapping_char_filter(args: &[u8]) -> Result<BoxCharFilter, CharFilterFactoryError> {
+    let mapping_args = serde_json::from_slice::<MappingCharFilterArgs>(args)
+        .map_err(|e| CharFilterFactoryErrorKind::InvalidArgument.with_error(e))?;
+
+    let mut mappings = HashMap::new();
+    for mapping in mapping_args.mappings.iter() {
+        let (key, value) = mapping.split_once(MAPPING_SEPARATOR).ok_or_else(|| {
+            CharFilterFactoryErrorKind::InvalidArgument
+                .with_error(anyhow::anyhow!("Invalid mapping: {}", mapping))
+        })?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(
+                CharFilterFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                    "Mapping key must not be empty: {}",
+                    mapping
+                )),
+            );
+        }
+        mappings.insert(key.to_string(), value.trim().to_string());
+    }
+    let max_key_len = mappings.keys().map(|key| key.len()).max().unwrap_or(0);
+
+    Ok(BoxCharFilter::from(MappingCharFilter {
+        mappings: Arc::new(mappings),
+        max_key_len,
+    }))
+}
+
+/// Replaces the occurrences of the keys with the values. The longest key wins.
+#[derive(Clone)]
+pub struct MappingCharFilter {
+    mappings: Arc<HashMap<String, String>>,
+    max_key_len: usize,
+}
+
+impl CharFilter for MappingCharFilter {
+    fn filter(&self, text: &str) -> MappedText {
+        let mut mapped = MappedText::new(text.len());
+
+        // The start of the part of the text that is kept as is.
+        let mut kept = 0;
+        let mut pos = 0;
+        while pos < text.len() {
+            let rest = &text[pos..];
+            let matched = rest
+                .char_indices()
+                .map(|(i, c)| i + c.len_utf8())
+                .take_while(|&len| len <= self.max_key_len)
+                .filter_map(|len| self.mappings.get(&rest[..len]).map(|value| (len, value)))
+                .last();
+
+            match matched {
+                Some((len, value)) => {
+                    mapped.push_original(&text[kept..pos], kept);
+                    mapped.push_replaced(value, pos, pos + len);
+                    pos += len;
+                    kept = pos;
+                }
+                None => pos += rest.chars().next().map(char::len_utf8).unwrap_or(1),
+            }
+        }
+        mapped.push_original(&text[kept..], kept);
+
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::char_filter::{apply_char_filters, mapping::create_mapping_char_filter};
+
+    #[test]
+    fn test_mapping_char_filter() {
+        let json_str = r#"
+            {
+                "mappings": [
+                    "٠ => 0",
+                    "١ => 1",
+                    ":) => _happy_",
+                    ":)) => _very_happy_",
+                    "- =>"
+                ]
+            }
+            "#;
+        let char_filter = create_mapping_char_filter(json_str.as_bytes()).unwrap();
+
+        let text = "١٠ :)) e-mail :)";
+        let mapped = apply_char_filters(&[char_filter], text);
+        assert_eq!("10 _very_happy_ email _happy_", mapped.text());
+
+        let (from, to) = mapped.original_offsets(3, 15);
+        assert_eq!(":))", &text[from..to]);
+        let (from, to) = mapped.original_offsets(16, 21);
+        assert_eq!("e-mail", &text[from..to]);
+
+        let json_str = r#"
+            {
+                "mappings": ["a -> b"]
+            }
+            "#;
+        assert!(create_mapping_char_filter(json_str.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use regex::Regex;
+
+use crate::index::char_filter::{
+    BoxCharFilter, CharFilter, CharFilterFactoryError, CharFilterFactoryErrorKind, MappedText,
+};
+
+#[derive(Deserialize, Serialize)]
+struct PatternReplaceCharFilterArgs {
+    pattern: String,
+    #[serde(default)]
+    replacement: String,
+}
+
+pub fn create_pattern_replace_char_filter(
+    args: &[u8],
+) -> Result<BoxCharFilter, CharFilterFactoryError> {
+    let pattern_replace_args = serde_json::from_slice::<PatternReplaceCharFilterArgs>(args)
+        .map_err(|e| CharFilterFactoryErrorKind::InvalidArgument.with_error(e))?;
+
+    let pattern = Regex::new(&pattern_replace_args.pattern)
+        .map_err(|e| CharFilterFactoryErrorKind::InvalidArgument.with_error(e))?;
+
+    Ok(BoxCharFilter::from(PatternReplaceCharFilter {
+        pattern,
+        replacement: pattern_replace_args.replacement,
+    }))
+}
+
+/// Replaces the matches of the regular expression with the replacement.
+/// The replacement can refer to the capture groups as `$1` or `${name}`.
+#[derive(Clone)]
+pub struct PatternReplaceCharFilter {
+    pattern: Regex,
+    replacement: String,
+}
+
+impl CharFilter for PatternReplaceCharFilter {
+    fn filter(&self, text: &str) -> MappedText {
+        let mut mapped = MappedText::new(text.len());
+
+        // The start of the part of the text that is kept as is.
+        let mut kept = 0;
+        for captures in self.pattern.captures_iter(text) {
+            let matched = captures.get(0).unwrap();
+            let mut replacement = String::new();
+            captures.expand(&self.replacement, &mut replacement);
+
+            mapped.push_original(&text[kept..matched.start()], kept);
+            mapped.push_replaced(&replacement, matched.start(), matched.end());
+            kept = matched.end();
+        }
+        mapped.push_original(&text[kept..], kept);
+
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::char_filter::{
+        apply_char_filters, pattern_replace::create_pattern_replace_char_filter,
+    };
+
+    #[test]
+    fn test_pattern_replace_char_filter() {
+        let json_str = r#"
+            {
+                "pattern": "(\\d+)-(?=\\d)",
+                "replacement": "${1}_"
+            }
+            "#;
+        // Look-around is not supported.
+        assert!(create_pattern_replace_char_filter(json_str.as_bytes()).is_err());
+
+        let json_str = r#"
+            {
+                "pattern": "(\\d+)-(\\d+)",
+                "replacement": "${1}_$2"
+            }
+            "#;
+        let char_filter = create_pattern_replace_char_filter(json_str.as_bytes()).unwrap();
+
+        let text = "tel 123-456";
+        let mapped = apply_char_filters(&[char_filter], text);
+        assert_eq!("tel 123_456", mapped.text());
+
+        let (from, to) = mapped.original_offsets(4, 11);
+        assert_eq!("123-456", &text[from..to]);
+    }
+}
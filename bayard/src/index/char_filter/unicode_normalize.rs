@@ -0,0 +1,159 @@
+use unicode_normalization::{
+    char::{canonical_combining_class, compose},
+    UnicodeNormalization,
+};
+
+use crate::index::char_filter::{
+    BoxCharFilter, CharFilter, CharFilterFactoryError, CharFilterFactoryErrorKind, MappedText,
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizationForm {
+    Nfc,
+    #[default]
+    Nfkc,
+    NfkcCf,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct UnicodeNormalizeCharFilterArgs {
+    #[serde(default)]
+    form: NormalizationForm,
+}
+
+pub fn create_unicode_normalize_char_filter(
+    args: &[u8],
+) -> Result<BoxCharFilter, CharFilterFactoryError> {
+    let unicode_normalize_args = if args.is_empty() {
+        UnicodeNormalizeCharFilterArgs::default()
+    } else {
+        serde_json::from_slice::<UnicodeNormalizeCharFilterArgs>(args)
+            .map_err(|e| CharFilterFactoryErrorKind::InvalidArgument.with_error(e))?
+    };
+
+    Ok(BoxCharFilter::from(UnicodeNormalizeCharFilter {
+        form: unicode_normalize_args.form,
+    }))
+}
+
+/// Normalizes the text into the Unicode normalization form.
+#[derive(Clone)]
+pub struct UnicodeNormalizeCharFilter {
+    form: NormalizationForm,
+}
+
+impl UnicodeNormalizeCharFilter {
+    fn normalize(&self, text: &str) -> String {
+        match self.form {
+            NormalizationForm::Nfc => text.nfc().collect(),
+            NormalizationForm::Nfkc => text.nfkc().collect(),
+            NormalizationForm::NfkcCf => {
+                let folded = caseless::default_case_fold_str(&text.nfkc().collect::<String>());
+                folded.nfkc().collect()
+            }
+        }
+    }
+}
+
+/// Returns `true` if the normalization does not combine the character with the previous one,
+/// so that the text can be normalized separately before and after the character.
+fn is_boundary(prev: char, c: char) -> bool {
+    // The compatibility decomposition is used since it splits off more combining marks
+    // than the canonical one. e.g. The halfwidth voiced sound mark
+    let first = match std::iter::once(c).nfkd().next() {
+        Some(first) => first,
+        None => return true,
+    };
+    let prev_last = std::iter::once(prev).nfkd().last().unwrap_or(prev);
+
+    // Hangul trailing consonants are composed with the preceding syllable.
+    canonical_combining_class(first) == 0
+        && compose(prev_last, first).is_none()
+        && !('\u{11A8}'..='\u{11C2}').contains(&first)
+}
+
+impl CharFilter for UnicodeNormalizeCharFilter {
+    fn filter(&self, text: &str) -> MappedText {
+        let mut mapped = MappedText::new(text.len());
+
+        // Each segment is normalized separately so that the offsets are kept fine-grained.
+        let mut segment_start = 0;
+        let mut prev = None;
+        for (offset, c) in text
+            .char_indices()
+            .chain(std::iter::once((text.len(), '\0')))
+        {
+            let at_boundary = offset == text.len() || prev.is_some_and(|prev| is_boundary(prev, c));
+            if at_boundary && segment_start < offset {
+                let segment = &text[segment_start..offset];
+                let normalized = self.normalize(segment);
+                if normalized == segment {
+                    mapped.push_original(segment, segment_start);
+                } else {
+                    mapped.push_replaced(&normalized, segment_start, offset);
+                }
+                segment_start = offset;
+            }
+            prev = Some(c);
+        }
+
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::char_filter::{
+        apply_char_filters, unicode_normalize::create_unicode_normalize_char_filter,
+    };
+
+    fn helper(json_str: &str, text: &str) -> String {
+        let char_filter = create_unicode_normalize_char_filter(json_str.as_bytes()).unwrap();
+
+        apply_char_filters(&[char_filter], text).text().to_string()
+    }
+
+    #[test]
+    fn test_unicode_normalize_char_filter() {
+        let text = "Ｔｏｋｙｏ ｶﾀｶﾅ ①e\u{301} STRASSE Straße \u{1100}\u{1161}\u{11A8}";
+
+        let json_str = r#"
+            {
+                "form": "nfc"
+            }
+            "#;
+        assert_eq!(
+            "Ｔｏｋｙｏ ｶﾀｶﾅ ①\u{e9} STRASSE Straße \u{ac01}",
+            helper(json_str, text)
+        );
+        assert_eq!(
+            "Tokyo カタカナ 1\u{e9} STRASSE Straße \u{ac01}",
+            helper("", text)
+        );
+
+        let json_str = r#"
+            {
+                "form": "nfkc_cf"
+            }
+            "#;
+        assert_eq!(
+            "tokyo カタカナ 1\u{e9} strasse strasse \u{ac01}",
+            helper(json_str, text)
+        );
+    }
+
+    #[test]
+    fn test_unicode_normalize_char_filter_offsets() {
+        let char_filter = create_unicode_normalize_char_filter(&[]).unwrap();
+
+        let text = "ｶﾞｲﾄﾞ book";
+        let mapped = apply_char_filters(&[char_filter], text);
+        assert_eq!("ガイド book", mapped.text());
+
+        let (from, to) = mapped.original_offsets(0, "ガ".len());
+        assert_eq!("ｶﾞ", &text[from..to]);
+        let (from, to) = mapped.original_offsets("ガイド ".len(), "ガイド book".len());
+        assert_eq!("book", &text[from..to]);
+    }
+}
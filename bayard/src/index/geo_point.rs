@@ -0,0 +1,499 @@
+use serde_json::{Map, Value};
+use tantivy::schema::{
+    Cardinality, FieldEntry, FieldType, IndexRecordOption, NumericOptions, Schema,
+    TextFieldIndexing, TextOptions,
+};
+
+pub const GEO_POINT_TYPE: &str = "geo_point";
+pub const GEOHASH_FIELD_SUFFIX: &str = ".geohash";
+pub const LAT_FIELD_SUFFIX: &str = ".lat";
+pub const LON_FIELD_SUFFIX: &str = ".lon";
+pub const GEOHASH_MAX_PRECISION: usize = 12;
+
+const GEOHASH_BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+const EARTH_RADIUS: f64 = 6_371_008.8;
+// The shortest length of a degree of latitude in meters.
+const METERS_PER_DEGREE: f64 = 110_574.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl GeoPoint {
+    pub fn new(lat: f64, lon: f64) -> Option<Self> {
+        if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) {
+            Some(GeoPoint { lat, lon })
+        } else {
+            None
+        }
+    }
+
+    /// Parse a point given as `{"lat": <LAT>, "lon": <LON>}`, `"<LAT>,<LON>"` or `[<LON>, <LAT>]`.
+    pub fn from_json(value: &Value) -> Option<Self> {
+        match value {
+            Value::Object(object) => {
+                GeoPoint::new(object.get("lat")?.as_f64()?, object.get("lon")?.as_f64()?)
+            }
+            Value::String(string) => {
+                let (lat, lon) = string.split_once(',')?;
+                GeoPoint::new(lat.trim().parse().ok()?, lon.trim().parse().ok()?)
+            }
+            Value::Array(array) if array.len() == 2 => {
+                GeoPoint::new(array[1].as_f64()?, array[0].as_f64()?)
+            }
+            _ => None,
+        }
+    }
+
+    /// The great-circle distance to the other point in meters.
+    pub fn distance(&self, other: &GeoPoint) -> f64 {
+        let lat1 = self.lat.to_radians();
+        let lat2 = other.lat.to_radians();
+        let d_lat = (other.lat - self.lat).to_radians();
+        let d_lon = (other.lon - self.lon).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+}
+
+/// Encode the point into a geohash of the given precision.
+pub fn encode_geohash(point: &GeoPoint, precision: usize) -> String {
+    let (mut lat_min, mut lat_max) = (-90.0, 90.0);
+    let (mut lon_min, mut lon_max) = (-180.0, 180.0);
+
+    let mut geohash = String::with_capacity(precision);
+    let mut bits = 0;
+    let mut num_bits = 0;
+    let mut is_lon = true;
+    while geohash.len() < precision {
+        if is_lon {
+            let mid = (lon_min + lon_max) / 2.0;
+            if point.lon >= mid {
+                bits = bits * 2 + 1;
+                lon_min = mid;
+            } else {
+                bits *= 2;
+                lon_max = mid;
+            }
+        } else {
+            let mid = (lat_min + lat_max) / 2.0;
+            if point.lat >= mid {
+                bits = bits * 2 + 1;
+                lat_min = mid;
+            } else {
+                bits *= 2;
+                lat_max = mid;
+            }
+        }
+        is_lon = !is_lon;
+
+        num_bits += 1;
+        if num_bits == 5 {
+            geohash.push(GEOHASH_BASE32[bits] as char);
+            bits = 0;
+            num_bits = 0;
+        }
+    }
+
+    geohash
+}
+
+/// The geohashes of all precisions of the point, from the shortest to the longest.
+pub fn geohash_prefixes(point: &GeoPoint) -> Vec<String> {
+    let geohash = encode_geohash(point, GEOHASH_MAX_PRECISION);
+    (1..=GEOHASH_MAX_PRECISION)
+        .map(|precision| geohash[..precision].to_string())
+        .collect()
+}
+
+/// The height and the width of a geohash cell of the given precision in degrees.
+fn geohash_cell_size(precision: usize) -> (f64, f64) {
+    let num_bits = 5 * precision as i32;
+    let lat_bits = num_bits / 2;
+    let lon_bits = num_bits - lat_bits;
+    (180.0 / 2_f64.powi(lat_bits), 360.0 / 2_f64.powi(lon_bits))
+}
+
+/// The geohash cells that cover the circle of the given radius in meters around the origin.
+/// If the circle is too large to be covered by the cells around the origin, all the cells
+/// of precision 1 are returned.
+pub fn geohash_cover(origin: &GeoPoint, radius: f64) -> Vec<String> {
+    let all_cells = || {
+        GEOHASH_BASE32
+            .iter()
+            .map(|c| (*c as char).to_string())
+            .collect::<Vec<String>>()
+    };
+
+    // Use the width of the cells at the farthest latitude from the equator in the circle.
+    let max_lat = origin.lat.abs() + radius / METERS_PER_DEGREE;
+    if max_lat >= 90.0 {
+        return all_cells();
+    }
+    let lon_scale = max_lat.to_radians().cos();
+
+    // The 3x3 cells around the origin cover the circle if a cell is larger than the radius.
+    let precision = match (1..=GEOHASH_MAX_PRECISION).rev().find(|precision| {
+        let (height, width) = geohash_cell_size(*precision);
+        height * METERS_PER_DEGREE >= radius && width * METERS_PER_DEGREE * lon_scale >= radius
+    }) {
+        Some(precision) => precision,
+        None => return all_cells(),
+    };
+    let (height, width) = geohash_cell_size(precision);
+
+    let mut cells = Vec::with_capacity(9);
+    for d_lat in [-1.0, 0.0, 1.0] {
+        for d_lon in [-1.0, 0.0, 1.0] {
+            let lat = (origin.lat + d_lat * height).clamp(-90.0, 90.0);
+            let mut lon = origin.lon + d_lon * width;
+            if lon < -180.0 {
+                lon += 360.0;
+            } else if lon >= 180.0 {
+                lon -= 360.0;
+            }
+            let cell = encode_geohash(&GeoPoint { lat, lon }, precision);
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+    }
+
+    cells
+}
+
+/// Parse a distance such as `10km` into meters.
+/// Available units are `mm`, `cm`, `m`, `km`, `in`, `ft`, `yd`, `mi` and `nmi`.
+/// A number without a unit is taken as meters.
+pub fn parse_distance(value: &Value) -> Option<f64> {
+    if let Some(meters) = value.as_f64() {
+        return Some(meters);
+    }
+
+    let value = value.as_str()?.trim();
+    let pos = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(pos);
+    let number = number.parse::<f64>().ok()?;
+    let multiplier = match unit.trim() {
+        "mm" => 0.001,
+        "cm" => 0.01,
+        "" | "m" => 1.0,
+        "km" => 1_000.0,
+        "in" => 0.0254,
+        "ft" => 0.3048,
+        "yd" => 0.9144,
+        "mi" => 1_609.344,
+        "nmi" => 1_852.0,
+        _ => return None,
+    };
+    Some(number * multiplier)
+}
+
+/// Returns the names of the geo point fields in the schema.
+///
+/// A geo point field `<NAME>` consists of the stored text field `<NAME>`,
+/// the geohash field `<NAME>.geohash` and the coordinate fields `<NAME>.lat` and `<NAME>.lon`.
+pub fn geo_point_field_names(schema: &Schema) -> Vec<String> {
+    schema
+        .fields()
+        .filter_map(|(_field, field_entry)| {
+            let name = field_entry.name().strip_suffix(GEOHASH_FIELD_SUFFIX)?;
+            let is_f64 = |field_name: String| {
+                schema.get_field(&field_name).is_some_and(|field| {
+                    matches!(
+                        schema.get_field_entry(field).field_type(),
+                        FieldType::F64(_)
+                    )
+                })
+            };
+            if schema.get_field(name).is_some()
+                && is_f64(format!("{}{}", name, LAT_FIELD_SUFFIX))
+                && is_f64(format!("{}{}", name, LON_FIELD_SUFFIX))
+            {
+                Some(name.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn geo_point_field_entries(name: &str, stored: bool) -> Vec<FieldEntry> {
+    let mut text_options = TextOptions::default();
+    if stored {
+        text_options = text_options.set_stored();
+    }
+    let geohash_options = TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer("raw")
+            .set_index_option(IndexRecordOption::Basic)
+            .set_fieldnorms(false),
+    );
+    let coordinate_options = NumericOptions::default()
+        .set_indexed()
+        .set_fast(Cardinality::SingleValue);
+
+    vec![
+        FieldEntry::new_text(name.to_string(), text_options),
+        FieldEntry::new_text(format!("{}{}", name, GEOHASH_FIELD_SUFFIX), geohash_options),
+        FieldEntry::new_f64(
+            format!("{}{}", name, LAT_FIELD_SUFFIX),
+            coordinate_options.clone(),
+        ),
+        FieldEntry::new_f64(format!("{}{}", name, LON_FIELD_SUFFIX), coordinate_options),
+    ]
+}
+
+/// Expand the `geo_point` fields in the schema JSON into the native fields.
+pub fn expand_geo_point_fields(schema: Value) -> Result<Value, serde_json::Error> {
+    let entries = match schema {
+        Value::Array(entries) => entries,
+        schema => return Ok(schema),
+    };
+
+    let mut new_entries = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if entry["type"].as_str() != Some(GEO_POINT_TYPE) {
+            new_entries.push(entry);
+            continue;
+        }
+
+        let name = entry["name"]
+            .as_str()
+            .ok_or_else(|| <serde_json::Error as serde::de::Error>::missing_field("name"))?;
+        let stored = entry["options"]["stored"].as_bool().unwrap_or(false);
+        for field_entry in geo_point_field_entries(name, stored) {
+            new_entries.push(serde_json::to_value(field_entry)?);
+        }
+    }
+
+    Ok(Value::Array(new_entries))
+}
+
+/// Collapse the native fields of the geo point fields in the schema into the `geo_point` fields.
+pub fn collapse_geo_point_fields(schema: &Schema) -> Result<Value, serde_json::Error> {
+    let geo_point_names = geo_point_field_names(schema);
+    let entries = match serde_json::to_value(schema)? {
+        Value::Array(entries) => entries,
+        schema => return Ok(schema),
+    };
+
+    let mut new_entries = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let name = entry["name"].as_str().unwrap_or_default();
+        if geo_point_names.iter().any(|geo_point_name| {
+            [GEOHASH_FIELD_SUFFIX, LAT_FIELD_SUFFIX, LON_FIELD_SUFFIX]
+                .iter()
+                .any(|suffix| name == format!("{}{}", geo_point_name, suffix))
+        }) {
+            continue;
+        }
+        if geo_point_names
+            .iter()
+            .any(|geo_point_name| name == geo_point_name)
+        {
+            let stored = entry["options"]["stored"].as_bool().unwrap_or(false);
+            new_entries.push(serde_json::json!({
+                "name": name,
+                "type": GEO_POINT_TYPE,
+                "options": {
+                    "stored": stored
+                }
+            }));
+            continue;
+        }
+        new_entries.push(entry);
+    }
+
+    Ok(Value::Array(new_entries))
+}
+
+/// Replace the values of the geo point fields in the document fields with the values of the native fields.
+pub fn expand_geo_point_values(
+    fields: &mut Map<String, Value>,
+    geo_point_names: &[String],
+) -> anyhow::Result<()> {
+    for name in geo_point_names {
+        let value = match fields.remove(name) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        // Both a single point and an array of points are accepted.
+        let points = match &value {
+            Value::Array(values) if values.iter().all(|value| !value.is_number()) => {
+                values.iter().map(GeoPoint::from_json).collect()
+            }
+            value => vec![GeoPoint::from_json(value)],
+        }
+        .into_iter()
+        .collect::<Option<Vec<GeoPoint>>>()
+        .ok_or_else(|| anyhow::anyhow!("Invalid geo point {} for field {}", value, name))?;
+
+        fields.insert(
+            name.clone(),
+            Value::from(
+                points
+                    .iter()
+                    .map(|point| format!("{},{}", point.lat, point.lon))
+                    .collect::<Vec<String>>(),
+            ),
+        );
+        fields.insert(
+            format!("{}{}", name, GEOHASH_FIELD_SUFFIX),
+            Value::from(
+                points
+                    .iter()
+                    .flat_map(geohash_prefixes)
+                    .collect::<Vec<String>>(),
+            ),
+        );
+        // Only the first point is used for the distance calculation.
+        if let Some(point) = points.first() {
+            fields.insert(
+                format!("{}{}", name, LAT_FIELD_SUFFIX),
+                Value::from(point.lat),
+            );
+            fields.insert(
+                format!("{}{}", name, LON_FIELD_SUFFIX),
+                Value::from(point.lon),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use tantivy::schema::Schema;
+
+    use crate::index::geo_point::{
+        collapse_geo_point_fields, encode_geohash, expand_geo_point_fields,
+        expand_geo_point_values, geo_point_field_names, geohash_cover, parse_distance, GeoPoint,
+    };
+
+    #[test]
+    fn test_geo_point_from_json() {
+        let expected = GeoPoint::new(35.681, 139.767).unwrap();
+
+        let value = serde_json::json!({"lat": 35.681, "lon": 139.767});
+        assert_eq!(GeoPoint::from_json(&value), Some(expected));
+
+        let value = serde_json::json!("35.681,139.767");
+        assert_eq!(GeoPoint::from_json(&value), Some(expected));
+
+        let value = serde_json::json!([139.767, 35.681]);
+        assert_eq!(GeoPoint::from_json(&value), Some(expected));
+
+        let value = serde_json::json!({"lat": 135.681, "lon": 139.767});
+        assert_eq!(GeoPoint::from_json(&value), None);
+    }
+
+    #[test]
+    fn test_geo_point_distance() {
+        let tokyo = GeoPoint::new(35.681, 139.767).unwrap();
+        let osaka = GeoPoint::new(34.702, 135.496).unwrap();
+
+        let distance = tokyo.distance(&osaka);
+        assert!((distance - 403_000.0).abs() < 1_000.0, "{}", distance);
+    }
+
+    #[test]
+    fn test_encode_geohash() {
+        let point = GeoPoint::new(57.64911, 10.40744).unwrap();
+        assert_eq!(encode_geohash(&point, 11), "u4pruydqqvj");
+    }
+
+    #[test]
+    fn test_geohash_cover() {
+        let origin = GeoPoint::new(35.681, 139.767).unwrap();
+
+        let cells = geohash_cover(&origin, 1_000.0);
+        assert_eq!(cells.len(), 9);
+        assert!(cells.iter().all(|cell| cell.len() == 5));
+        assert!(cells.contains(&encode_geohash(&origin, 5)));
+
+        let cells = geohash_cover(&origin, 10_000_000.0);
+        assert_eq!(cells.len(), 32);
+    }
+
+    #[test]
+    fn test_parse_distance() {
+        assert_eq!(parse_distance(&serde_json::json!("10km")), Some(10_000.0));
+        assert_eq!(parse_distance(&serde_json::json!("1.5 m")), Some(1.5));
+        assert_eq!(parse_distance(&serde_json::json!(500)), Some(500.0));
+        assert_eq!(parse_distance(&serde_json::json!("10 parsecs")), None);
+    }
+
+    #[test]
+    fn test_expand_geo_point_fields() {
+        let schema_json_str = r#"
+            [
+                {
+                    "name": "name",
+                    "type": "text",
+                    "options": {
+                        "indexing": {
+                            "record": "position",
+                            "fieldnorms": false,
+                            "tokenizer": "default"
+                        },
+                        "stored": true
+                    }
+                },
+                {
+                    "name": "location",
+                    "type": "geo_point",
+                    "options": {
+                        "stored": true
+                    }
+                }
+            ]
+        "#;
+        let schema_json = serde_json::from_str::<Value>(schema_json_str).unwrap();
+
+        let expanded = expand_geo_point_fields(schema_json.clone()).unwrap();
+        let schema = serde_json::from_value::<Schema>(expanded).unwrap();
+        assert_eq!(schema.num_fields(), 5);
+        assert!(schema.get_field("location.geohash").is_some());
+        assert!(schema.get_field("location.lat").is_some());
+        assert!(schema.get_field("location.lon").is_some());
+        assert_eq!(geo_point_field_names(&schema), vec!["location".to_string()]);
+
+        let collapsed = collapse_geo_point_fields(&schema).unwrap();
+        assert_eq!(collapsed.as_array().unwrap().len(), 2);
+        assert_eq!(collapsed[1]["type"], "geo_point");
+        assert_eq!(collapsed[1]["options"]["stored"], true);
+    }
+
+    #[test]
+    fn test_expand_geo_point_values() {
+        let mut fields = serde_json::json!({
+            "name": "Tokyo Station",
+            "location": {"lat": 35.681, "lon": 139.767}
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+
+        expand_geo_point_values(&mut fields, &["location".to_string()]).unwrap();
+        assert_eq!(fields["location"], serde_json::json!(["35.681,139.767"]));
+        assert_eq!(fields["location.lat"], 35.681);
+        assert_eq!(fields["location.lon"], 139.767);
+        assert_eq!(fields["location.geohash"].as_array().unwrap().len(), 12);
+        assert_eq!(fields["location.geohash"][4], "xn76u");
+
+        let mut fields = serde_json::json!({"location": "invalid"})
+            .as_object()
+            .unwrap()
+            .clone();
+        assert!(expand_geo_point_values(&mut fields, &["location".to_string()]).is_err());
+    }
+}
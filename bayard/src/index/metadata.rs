@@ -1,5 +1,6 @@
 use std::{
     cmp::Ordering,
+    collections::HashMap,
     fmt,
     path::Path,
     sync::{Arc, RwLock},
@@ -20,7 +21,14 @@ use tracing::{error, info};
 
 use crate::{
     common::read_file,
-    index::{analyzer::Analyzers, shard::Shard, shards::Shards},
+    index::{
+        analyzer::Analyzers,
+        geo_point::{collapse_geo_point_fields, expand_geo_point_fields},
+        processor::Processors,
+        search_tokenizer::{extract_search_tokenizers, insert_search_tokenizers},
+        shard::Shard,
+        shards::Shards,
+    },
 };
 
 use super::{shard::State, DOC_ID_FIELD_NAME, DOC_TIMESTAMP_FIELD_NAME};
@@ -90,7 +98,9 @@ impl MetadataError {
 #[derive(Clone)]
 pub struct Metadata {
     schema: Arc<RwLock<Schema>>,
+    search_tokenizers: Arc<RwLock<HashMap<String, String>>>, // field_name -> tokenizer_name
     analyzers: Arc<RwLock<Analyzers>>,
+    processors: Arc<RwLock<Processors>>,
     index_settings: Arc<RwLock<IndexSettings>>,
     writer_threads: Arc<RwLock<usize>>,
     writer_mem_size: Arc<RwLock<usize>>,
@@ -116,7 +126,9 @@ impl Metadata {
 
         Metadata {
             schema: Arc::new(RwLock::new(schema)),
+            search_tokenizers: Arc::new(RwLock::new(HashMap::new())),
             analyzers: Arc::new(RwLock::new(analyzers)),
+            processors: Arc::new(RwLock::new(Processors::default())),
             index_settings: Arc::new(RwLock::new(index_settings)),
             writer_threads: Arc::new(RwLock::new(writer_threads)),
             writer_mem_size: Arc::new(RwLock::new(writer_mem_size)),
@@ -139,7 +151,9 @@ impl Metadata {
     ) -> Result<Self, MetadataError> {
         let mut metadata = Metadata {
             schema: Arc::new(RwLock::new(schema)),
+            search_tokenizers: Arc::new(RwLock::new(HashMap::new())),
             analyzers: Arc::new(RwLock::new(analyzers)),
+            processors: Arc::new(RwLock::new(Processors::default())),
             index_settings: Arc::new(RwLock::new(index_settings)),
             writer_threads: Arc::new(RwLock::new(writer_threads)),
             writer_mem_size: Arc::new(RwLock::new(writer_mem_size)),
@@ -177,6 +191,31 @@ impl Metadata {
         Ok(schema.clone())
     }
 
+    /// Returns the tokenizers used to analyze the query texts of the text fields,
+    /// if they differ from the ones used at index time.
+    pub fn search_tokenizers(&self) -> Result<HashMap<String, String>, MetadataError> {
+        Ok(self
+            .search_tokenizers
+            .read()
+            .map_err(|error| {
+                MetadataErrorKind::RwLockFailure.with_error(anyhow::anyhow!(error.to_string()))
+            })?
+            .clone())
+    }
+
+    pub fn set_search_tokenizers(
+        &mut self,
+        new_search_tokenizers: HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, MetadataError> {
+        let mut search_tokenizers = self.search_tokenizers.write().map_err(|error| {
+            MetadataErrorKind::RwLockFailure.with_error(anyhow::anyhow!(error.to_string()))
+        })?;
+
+        *search_tokenizers = new_search_tokenizers;
+
+        Ok(search_tokenizers.clone())
+    }
+
     pub fn analyzers(&self) -> Result<Analyzers, MetadataError> {
         Ok(self
             .analyzers
@@ -197,6 +236,28 @@ impl Metadata {
         Ok(analyzers.clone())
     }
 
+    pub fn processors(&self) -> Result<Processors, MetadataError> {
+        Ok(self
+            .processors
+            .read()
+            .map_err(|error| {
+                MetadataErrorKind::RwLockFailure.with_error(anyhow::anyhow!(error.to_string()))
+            })?
+            .clone())
+    }
+
+    pub fn set_processors(
+        &mut self,
+        new_processors: Processors,
+    ) -> Result<Processors, MetadataError> {
+        let mut processors = self.processors.write().map_err(|error| {
+            MetadataErrorKind::RwLockFailure.with_error(anyhow::anyhow!(error.to_string()))
+        })?;
+        *processors = new_processors;
+
+        Ok(processors.clone())
+    }
+
     pub fn index_settings(&self) -> Result<IndexSettings, MetadataError> {
         Ok(self
             .index_settings
@@ -400,13 +461,20 @@ impl Serialize for Metadata {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Meta", 7)?;
-        state.serialize_field(
-            "schema",
+        let mut state = serializer.serialize_struct("Meta", 9)?;
+        let mut schema = collapse_geo_point_fields(
             &self
                 .schema()
                 .map_err(|error| ser::Error::custom(error.to_string()))?,
-        )?;
+        )
+        .map_err(|error| ser::Error::custom(error.to_string()))?;
+        insert_search_tokenizers(
+            &mut schema,
+            &self
+                .search_tokenizers()
+                .map_err(|error| ser::Error::custom(error.to_string()))?,
+        );
+        state.serialize_field("schema", &schema)?;
         state.serialize_field(
             "index_settings",
             &self
@@ -419,6 +487,12 @@ impl Serialize for Metadata {
                 .analyzers()
                 .map_err(|error| ser::Error::custom(error.to_string()))?,
         )?;
+        state.serialize_field(
+            "processors",
+            &self
+                .processors()
+                .map_err(|error| ser::Error::custom(error.to_string()))?,
+        )?;
         state.serialize_field(
             "writer_threads",
             &self
@@ -465,6 +539,7 @@ impl<'de> Deserialize<'de> for Metadata {
             "schema",
             "index_settings",
             "analyzers",
+            "processors",
             "writer_threads",
             "writer_mem_size",
             "num_replicas",
@@ -476,6 +551,7 @@ impl<'de> Deserialize<'de> for Metadata {
             Schema,
             IndexSettings,
             Analyzers,
+            Processors,
             WriterThread,
             WriterMemSize,
             NumReplicas,
@@ -494,7 +570,7 @@ impl<'de> Deserialize<'de> for Metadata {
                     type Value = Field;
 
                     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                        formatter.write_str("`schema`, `analyzers`, `index_settings`, `writer_threads`, `writer_mem_size`, `num_replicas`, `num_shards` or `shards`")
+                        formatter.write_str("`schema`, `analyzers`, `processors`, `index_settings`, `writer_threads`, `writer_mem_size`, `num_replicas`, `num_shards` or `shards`")
                     }
 
                     fn visit_str<E>(self, value: &str) -> Result<Field, E>
@@ -504,6 +580,7 @@ impl<'de> Deserialize<'de> for Metadata {
                         match value {
                             "schema" => Ok(Field::Schema),
                             "analyzers" => Ok(Field::Analyzers),
+                            "processors" => Ok(Field::Processors),
                             "index_settings" => Ok(Field::IndexSettings),
                             "writer_threads" => Ok(Field::WriterThread),
                             "writer_mem_size" => Ok(Field::WriterMemSize),
@@ -575,7 +652,9 @@ impl<'de> Deserialize<'de> for Metadata {
                 V: MapAccess<'de>,
             {
                 let mut schema = None;
+                let mut search_tokenizers = HashMap::new();
                 let mut analyzers = None;
+                let mut processors = None;
                 let mut index_settings = None;
                 let mut writer_threads = None;
                 let mut writer_mem_size = None;
@@ -588,7 +667,16 @@ impl<'de> Deserialize<'de> for Metadata {
                             if schema.is_some() {
                                 return Err(de::Error::duplicate_field("schema"));
                             }
-                            schema = Some(map.next_value()?);
+                            // The `geo_point` fields are expanded into the native fields.
+                            let mut schema_value = expand_geo_point_fields(map.next_value()?)
+                                .map_err(|error| de::Error::custom(error.to_string()))?;
+                            // The search tokenizers are not part of the Tantivy schema.
+                            search_tokenizers = extract_search_tokenizers(&mut schema_value)
+                                .map_err(|error| de::Error::custom(error.to_string()))?;
+                            schema = Some(
+                                serde_json::from_value::<Schema>(schema_value)
+                                    .map_err(|error| de::Error::custom(error.to_string()))?,
+                            );
                         }
                         Field::Analyzers => {
                             if analyzers.is_some() {
@@ -596,6 +684,12 @@ impl<'de> Deserialize<'de> for Metadata {
                             }
                             analyzers = Some(map.next_value()?);
                         }
+                        Field::Processors => {
+                            if processors.is_some() {
+                                return Err(de::Error::duplicate_field("processors"));
+                            }
+                            processors = Some(map.next_value::<Processors>()?);
+                        }
                         Field::IndexSettings => {
                             if index_settings.is_some() {
                                 return Err(de::Error::duplicate_field("index_settings"));
@@ -642,8 +736,8 @@ impl<'de> Deserialize<'de> for Metadata {
                 let num_replicas = num_replicas.unwrap_or(DEFAULT_NUM_REPLICAS);
                 let num_shards = num_shards.unwrap_or(DEFAULT_NUM_SHARDS);
 
-                if let Some(shards) = shards {
-                    Ok(Metadata::new_with_shards(
+                let mut metadata = if let Some(shards) = shards {
+                    Metadata::new_with_shards(
                         schema,
                         analyzers,
                         index_settings,
@@ -653,9 +747,9 @@ impl<'de> Deserialize<'de> for Metadata {
                         num_shards,
                         shards,
                     )
-                    .map_err(|error| de::Error::custom(error.to_string()))?)
+                    .map_err(|error| de::Error::custom(error.to_string()))?
                 } else {
-                    Ok(Metadata::new(
+                    Metadata::new(
                         schema,
                         analyzers,
                         index_settings,
@@ -663,8 +757,26 @@ impl<'de> Deserialize<'de> for Metadata {
                         writer_mem_size,
                         num_replicas,
                         num_shards,
-                    ))
+                    )
+                };
+                metadata
+                    .set_search_tokenizers(search_tokenizers)
+                    .map_err(|error| de::Error::custom(error.to_string()))?;
+                if let Some(processors) = processors {
+                    // The processors must refer to the fields in the schema.
+                    processors
+                        .check(
+                            &metadata
+                                .schema()
+                                .map_err(|error| de::Error::custom(error.to_string()))?,
+                        )
+                        .map_err(|error| de::Error::custom(error.to_string()))?;
+                    metadata
+                        .set_processors(processors)
+                        .map_err(|error| de::Error::custom(error.to_string()))?;
                 }
+
+                Ok(metadata)
             }
         }
 
@@ -1,32 +1,41 @@
-use std::{collections::HashMap, fmt, path::PathBuf, sync::Arc};
-
-use crossbeam::channel::{unbounded, TryRecvError};
-use notify::{
-    event::{DataChange, ModifyKind, RemoveKind, RenameMode},
-    recommended_watcher, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
 };
-use regex::Regex;
+
 use tokio::{
     fs,
-    sync::{watch, RwLock},
-    time::{sleep, Duration},
+    sync::{broadcast, watch, RwLock},
 };
 use tokio_stream::wrappers::WatchStream;
-use tracing::{debug, error, info};
+use tracing::{error, info, warn};
 
 use crate::{
-    index::{metadata::load_index_metadata, INDEX_METADATA_FILE},
+    common::{remove_file, write_file},
+    index::{
+        metadata::{load_index_metadata, save_index_metadata},
+        resolve_index_file, INDEX_METADATA_FILE,
+    },
     node::index::extract_index_name,
 };
 
-use super::{metadata::Metadata, INDEX_METADATA_FILE_PATTERN};
+use super::metadata::Metadata;
 
-const SLEEP_DURATION: Duration = Duration::from_millis(100);
+const RELOAD_CHANNEL_CAPACITY: usize = 64;
 
 #[derive(Debug, Clone, Copy)]
 pub enum MetastoreErrorKind {
     DirectoryCreationFailure,
     DirectoryReadFailure,
+    IndexExists,
+    IndexNotFound,
+    MetadataSaveFailure,
+    FileWriteFailure,
+    FileRemoveFailure,
+    InvalidUserDictionary,
+    SnapshotSerializationFailure,
+    SnapshotDeserializationFailure,
 }
 
 impl MetastoreErrorKind {
@@ -65,9 +74,104 @@ impl MetastoreError {
     }
 }
 
+/// The commands that change the metastore. They are replicated by the Raft log and applied in
+/// the same order on all nodes.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Command {
+    CreateIndex {
+        name: String,
+        metadata: Metadata,
+    },
+    DeleteIndex {
+        name: String,
+    },
+    ModifyIndex {
+        name: String,
+        metadata: Metadata,
+    },
+    ReloadAnalyzers {
+        name: String,
+        metadata: Metadata,
+    },
+    PutUserDictionary {
+        name: String,
+        file_name: String,
+        /// The base64 encoded content of the user dictionary.
+        content: String,
+    },
+    DeleteUserDictionary {
+        name: String,
+        file_name: String,
+    },
+}
+
+impl Command {
+    pub fn index_name(&self) -> &str {
+        match self {
+            Command::CreateIndex { name, .. }
+            | Command::DeleteIndex { name }
+            | Command::ModifyIndex { name, .. }
+            | Command::ReloadAnalyzers { name, .. }
+            | Command::PutUserDictionary { name, .. }
+            | Command::DeleteUserDictionary { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    metadata: Metadata,
+    #[serde(default)]
+    user_dictionaries: BTreeMap<String, String>, // file name -> base64 encoded content
+    #[serde(default)]
+    version: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    indices: BTreeMap<String, IndexEntry>,
+    #[serde(default)]
+    tombstones: BTreeMap<String, u64>, // index name -> version of the deletion
+}
+
+/// The digest of an index in the replicated state. The version is the Raft log index of the
+/// last command that changed the index, so a higher version is always the newer one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    pub name: String,
+    pub version: u64,
+    pub deleted: bool,
+}
+
+/// The names of the indices that are newer in the remote digests than in the local digests.
+pub fn outdated_indices(local: &[Digest], remote: &[Digest]) -> Vec<String> {
+    let versions: HashMap<&str, u64> = local
+        .iter()
+        .map(|digest| (digest.name.as_str(), digest.version))
+        .collect();
+    remote
+        .iter()
+        .filter(|digest| digest.version > versions.get(digest.name.as_str()).copied().unwrap_or(0))
+        .map(|digest| digest.name.clone())
+        .collect()
+}
+
+/// The metastore holds the metadata and the user dictionaries of the indices.
+/// It is the state machine of the Raft log, so it is changed only by applying the committed
+/// commands or by restoring the snapshots. The applied changes are also written to the index
+/// directories, where the shards and the analyzers read them from.
 pub struct Metastore {
-    metadatas: Arc<RwLock<HashMap<String, Metadata>>>, // index name -> metadata
+    indices_dir: PathBuf,
+    indices: RwLock<BTreeMap<String, IndexEntry>>,
+    // The deleted indices, which are kept so that the nodes that missed the deletion remove them.
+    tombstones: RwLock<BTreeMap<String, u64>>,
+    // The indices found in the index directories of a node that has no Raft state yet.
+    // They are served until they have been imported into the replicated state.
+    pending_imports: RwLock<HashMap<String, Metadata>>,
+    metadatas_sender: watch::Sender<HashMap<String, Metadata>>,
     metadatas_receiver: watch::Receiver<HashMap<String, Metadata>>,
+    reload_sender: broadcast::Sender<String>,
 }
 
 impl Metastore {
@@ -79,10 +183,29 @@ impl Metastore {
                 .map_err(|error| MetastoreErrorKind::DirectoryCreationFailure.with_error(error))?;
         }
 
-        // Load existing index metadatas.
-        let metadatas: Arc<RwLock<HashMap<String, Metadata>>> =
-            Arc::new(RwLock::new(HashMap::new()));
-        let mut indices_read_dir = fs::read_dir(&indices_dir)
+        let (metadatas_sender, metadatas_receiver) =
+            watch::channel::<HashMap<String, Metadata>>(HashMap::new());
+        let (reload_sender, _) = broadcast::channel(RELOAD_CHANNEL_CAPACITY);
+
+        Ok(Metastore {
+            indices_dir,
+            indices: RwLock::new(BTreeMap::new()),
+            tombstones: RwLock::new(BTreeMap::new()),
+            pending_imports: RwLock::new(HashMap::new()),
+            metadatas_sender,
+            metadatas_receiver,
+            reload_sender,
+        })
+    }
+
+    /// Load the metadata files that have been left in the index directories, e.g. by a version
+    /// that did not replicate the metadata with Raft. They are served as they are until they
+    /// are imported with [`Command::CreateIndex`].
+    pub async fn load_pending_imports(&self) -> Result<(), MetastoreError> {
+        let tombstones = self.tombstones.read().await.clone();
+        let mut pending_imports = self.pending_imports.write().await;
+
+        let mut indices_read_dir = fs::read_dir(&self.indices_dir)
             .await
             .map_err(|error| MetastoreErrorKind::DirectoryReadFailure.with_error(error))?;
         while let Some(index_dir_entry) = indices_read_dir
@@ -90,8 +213,6 @@ impl Metastore {
             .await
             .map_err(|error| MetastoreErrorKind::DirectoryReadFailure.with_error(error))?
         {
-            let mut mut_metadatas = metadatas.write().await;
-
             // Get index directory path.
             let index_dir = index_dir_entry.path();
             if !index_dir.is_dir() {
@@ -110,145 +231,623 @@ impl Metastore {
 
             // Loading index metada.
             let index_metadata_path = index_dir.join(INDEX_METADATA_FILE);
-            info!(?index_metadata_path, "Loading index metadata.");
-            let metadata = match load_index_metadata(&index_metadata_path).await {
-                Ok(index_metadata) => index_metadata,
+            if !index_metadata_path.exists() {
+                continue;
+            }
+            // The index has been deleted while this node was down.
+            if tombstones.contains_key(&index_name) {
+                info!(
+                    ?index_metadata_path,
+                    "Deleting index metadata of deleted index."
+                );
+                if let Err(error) = remove_file(&index_metadata_path).await {
+                    error!(
+                        ?index_metadata_path,
+                        ?error,
+                        "Failed to delete index metadata file."
+                    );
+                }
+                continue;
+            }
+            info!(?index_metadata_path, "Loading index metadata to import.");
+            match load_index_metadata(&index_metadata_path).await {
+                Ok(metadata) => {
+                    pending_imports.insert(index_name, metadata);
+                }
                 Err(error) => {
                     error!(
                         ?index_metadata_path,
                         ?error,
                         "Failed to load index metadata."
                     );
+                }
+            }
+        }
+        drop(pending_imports);
+
+        self.publish().await;
+
+        Ok(())
+    }
+
+    /// The indices that have not been imported into the replicated state yet.
+    pub async fn pending_imports(&self) -> Vec<(String, Metadata)> {
+        let indices = self.indices.read().await;
+        self.pending_imports
+            .read()
+            .await
+            .iter()
+            .filter(|(name, _)| !indices.contains_key(*name))
+            .map(|(name, metadata)| (name.clone(), metadata.clone()))
+            .collect()
+    }
+
+    pub async fn metadatas(&self) -> HashMap<String, Metadata> {
+        let mut metadatas = self.pending_imports.read().await.clone();
+        for (name, entry) in self.indices.read().await.iter() {
+            metadatas.insert(name.clone(), entry.metadata.clone());
+        }
+        metadatas
+    }
+
+    /// The digests of the indices and the tombstones in the replicated state.
+    pub async fn digests(&self) -> Vec<Digest> {
+        let mut digests: Vec<Digest> = self
+            .indices
+            .read()
+            .await
+            .iter()
+            .map(|(name, entry)| Digest {
+                name: name.clone(),
+                version: entry.version,
+                deleted: false,
+            })
+            .collect();
+        digests.extend(
+            self.tombstones
+                .read()
+                .await
+                .iter()
+                .map(|(name, version)| Digest {
+                    name: name.clone(),
+                    version: *version,
+                    deleted: true,
+                }),
+        );
+        digests
+    }
+
+    pub fn watch(&self) -> WatchStream<HashMap<String, Metadata>> {
+        WatchStream::new(self.metadatas_receiver.clone())
+    }
+
+    /// Subscribe to the names of the indices whose analyzers have to be rebuilt because the
+    /// analyzers or the user dictionaries have been changed.
+    pub fn subscribe_reloads(&self) -> broadcast::Receiver<String> {
+        self.reload_sender.subscribe()
+    }
+
+    async fn publish(&self) {
+        let metadatas = self.metadatas().await;
+        if self.metadatas_sender.send(metadatas).is_err() {
+            error!("Failed to send index metadatas.");
+        }
+    }
+
+    fn notify_reload(&self, index_name: &str) {
+        // It fails only if there are no subscribers.
+        let _ = self.reload_sender.send(index_name.to_string());
+    }
+
+    /// Write the metadata to the index directory and read it back, so that the metadata in
+    /// memory has the reserved fields just like the one loaded at startup.
+    async fn save_metadata(
+        &self,
+        index_name: &str,
+        metadata: Metadata,
+    ) -> Result<Metadata, MetastoreError> {
+        let index_dir = self.indices_dir.join(index_name);
+        if !index_dir.exists() {
+            fs::create_dir_all(&index_dir)
+                .await
+                .map_err(|error| MetastoreErrorKind::DirectoryCreationFailure.with_error(error))?;
+        }
+
+        let meta_path = index_dir.join(INDEX_METADATA_FILE);
+        save_index_metadata(&meta_path, metadata)
+            .await
+            .map_err(|error| MetastoreErrorKind::MetadataSaveFailure.with_error(error))?;
+        let metadata = load_index_metadata(&meta_path)
+            .await
+            .map_err(|error| MetastoreErrorKind::MetadataSaveFailure.with_error(error))?;
+        info!(?meta_path, "Index metadata has been saved.");
+
+        Ok(metadata)
+    }
+
+    async fn write_user_dictionary(
+        &self,
+        index_name: &str,
+        file_name: &str,
+        content: &str,
+    ) -> Result<(), MetastoreError> {
+        let path = user_dictionary_path(&self.indices_dir.join(index_name), file_name)?;
+        let content = base64::decode(content)
+            .map_err(|error| MetastoreErrorKind::InvalidUserDictionary.with_error(error))?;
+        write_file(&path, &content)
+            .await
+            .map_err(|error| MetastoreErrorKind::FileWriteFailure.with_error(error))?;
+        info!(?path, "User dictionary has been saved.");
+
+        Ok(())
+    }
+
+    async fn remove_user_dictionary(
+        &self,
+        index_name: &str,
+        file_name: &str,
+    ) -> Result<(), MetastoreError> {
+        let path = user_dictionary_path(&self.indices_dir.join(index_name), file_name)?;
+        if path.exists() {
+            remove_file(&path)
+                .await
+                .map_err(|error| MetastoreErrorKind::FileRemoveFailure.with_error(error))?;
+            info!(?path, "User dictionary has been deleted.");
+        }
+
+        Ok(())
+    }
+
+    /// Apply a committed command. The version is the Raft log index of the command.
+    /// The commands are validated by the proposing node, so an error here means that the
+    /// command conflicts with a command committed before it, e.g. an index created twice.
+    pub async fn apply(&self, version: u64, command: &Command) -> Result<(), MetastoreError> {
+        let name = command.index_name();
+        let exists = self.indices.read().await.contains_key(name);
+
+        match command {
+            Command::CreateIndex { name, metadata } => {
+                if exists {
+                    return Err(MetastoreErrorKind::IndexExists
+                        .with_error(anyhow::anyhow!("Index {} already exists.", name)));
+                }
+                let metadata = self.save_metadata(name, metadata.clone()).await?;
+                self.indices.write().await.insert(
+                    name.clone(),
+                    IndexEntry {
+                        metadata,
+                        user_dictionaries: BTreeMap::new(),
+                        version,
+                    },
+                );
+                self.tombstones.write().await.remove(name);
+                self.pending_imports.write().await.remove(name);
+                self.publish().await;
+            }
+            Command::DeleteIndex { name } => {
+                let removed = self.indices.write().await.remove(name).is_some();
+                let pending = self.pending_imports.write().await.remove(name).is_some();
+                if !removed && !pending {
+                    return Err(MetastoreErrorKind::IndexNotFound
+                        .with_error(anyhow::anyhow!("Index {} does not exist.", name)));
+                }
+                self.tombstones.write().await.insert(name.clone(), version);
+
+                // The index directory is removed by the node when it receives the metadatas.
+                let meta_path = self.indices_dir.join(name).join(INDEX_METADATA_FILE);
+                if let Err(error) = remove_file(&meta_path).await {
+                    error!(?meta_path, ?error, "Failed to delete index metadata file.");
+                }
+                self.publish().await;
+            }
+            Command::ModifyIndex { name, metadata }
+            | Command::ReloadAnalyzers { name, metadata } => {
+                if !exists {
+                    return Err(MetastoreErrorKind::IndexNotFound
+                        .with_error(anyhow::anyhow!("Index {} does not exist.", name)));
+                }
+                let metadata = self.save_metadata(name, metadata.clone()).await?;
+                if let Some(entry) = self.indices.write().await.get_mut(name) {
+                    entry.metadata = metadata;
+                    entry.version = version;
+                }
+                self.publish().await;
+
+                if matches!(command, Command::ReloadAnalyzers { .. }) {
+                    self.notify_reload(name);
+                }
+            }
+            Command::PutUserDictionary {
+                name,
+                file_name,
+                content,
+            } => {
+                if !exists {
+                    return Err(MetastoreErrorKind::IndexNotFound
+                        .with_error(anyhow::anyhow!("Index {} does not exist.", name)));
+                }
+                self.write_user_dictionary(name, file_name, content).await?;
+                if let Some(entry) = self.indices.write().await.get_mut(name) {
+                    entry
+                        .user_dictionaries
+                        .insert(file_name.clone(), content.clone());
+                    entry.version = version;
+                }
+
+                // The analyzers load the user dictionary when they are built.
+                self.notify_reload(name);
+            }
+            Command::DeleteUserDictionary { name, file_name } => {
+                if !exists {
+                    return Err(MetastoreErrorKind::IndexNotFound
+                        .with_error(anyhow::anyhow!("Index {} does not exist.", name)));
+                }
+                self.remove_user_dictionary(name, file_name).await?;
+                if let Some(entry) = self.indices.write().await.get_mut(name) {
+                    entry.user_dictionaries.remove(file_name);
+                    entry.version = version;
+                }
+                self.notify_reload(name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serialize the replicated state.
+    pub async fn snapshot(&self) -> Result<Vec<u8>, MetastoreError> {
+        let snapshot = Snapshot {
+            indices: self.indices.read().await.clone(),
+            tombstones: self.tombstones.read().await.clone(),
+        };
+        serde_json::to_vec(&snapshot)
+            .map_err(|error| MetastoreErrorKind::SnapshotSerializationFailure.with_error(error))
+    }
+
+    /// Replace the replicated state with a snapshot and write it to the index directories.
+    pub async fn restore(&self, data: &[u8]) -> Result<(), MetastoreError> {
+        let snapshot = serde_json::from_slice::<Snapshot>(data).map_err(|error| {
+            MetastoreErrorKind::SnapshotDeserializationFailure.with_error(error)
+        })?;
+
+        let current = self.indices.read().await.clone();
+        let mut indices = BTreeMap::new();
+        for (name, entry) in snapshot.indices {
+            let metadata = match self.save_metadata(&name, entry.metadata).await {
+                Ok(metadata) => metadata,
+                Err(error) => {
+                    error!(?name, ?error, "Failed to restore index metadata.");
                     continue;
                 }
             };
 
-            mut_metadatas.insert(index_name, metadata);
+            // Remove the user dictionaries that have been deleted since the current state.
+            if let Some(current_entry) = current.get(&name) {
+                for file_name in current_entry.user_dictionaries.keys() {
+                    if !entry.user_dictionaries.contains_key(file_name) {
+                        if let Err(error) = self.remove_user_dictionary(&name, file_name).await {
+                            warn!(
+                                ?name,
+                                ?file_name,
+                                ?error,
+                                "Failed to delete user dictionary."
+                            );
+                        }
+                    }
+                }
+            }
+            for (file_name, content) in entry.user_dictionaries.iter() {
+                if let Err(error) = self.write_user_dictionary(&name, file_name, content).await {
+                    warn!(
+                        ?name,
+                        ?file_name,
+                        ?error,
+                        "Failed to restore user dictionary."
+                    );
+                }
+            }
+
+            indices.insert(
+                name,
+                IndexEntry {
+                    metadata,
+                    user_dictionaries: entry.user_dictionaries,
+                    version: entry.version,
+                },
+            );
         }
 
-        let (metadatas_sender, metadatas_receiver) =
-            watch::channel::<HashMap<String, Metadata>>(metadatas.read().await.clone());
-
-        let indices_dir_task = indices_dir.clone();
-        let metadatas_task = Arc::clone(&metadatas);
-
-        tokio::spawn(async move {
-            let index_meta_re = Regex::new(INDEX_METADATA_FILE_PATTERN).unwrap();
-
-            let (tx_filesystem, rx_filesystem) = unbounded();
-
-            let mut watcher: RecommendedWatcher =
-                recommended_watcher(tx_filesystem).expect("Watch error.");
-            watcher
-                .watch(indices_dir_task.as_path(), RecursiveMode::Recursive)
-                .expect("Watch error.");
-
-            loop {
-                match rx_filesystem.try_recv() {
-                    Ok(event) => {
-                        let event = match event {
-                            Ok(event) => event,
-                            Err(error) => {
-                                error!(?error, "Receive error event.");
-                                continue;
-                            }
-                        };
+        // The indices that have been deleted since the current state must not be imported again.
+        for name in current.keys().chain(snapshot.tombstones.keys()) {
+            let meta_path = self.indices_dir.join(name).join(INDEX_METADATA_FILE);
+            if !indices.contains_key(name) && meta_path.exists() {
+                if let Err(error) = remove_file(&meta_path).await {
+                    error!(?meta_path, ?error, "Failed to delete index metadata file.");
+                }
+            }
+        }
 
-                        let path = match event.paths.first() {
-                            Some(path) => path,
-                            None => {
-                                error!(?event, "No path in event.");
-                                continue;
-                            }
-                        };
-
-                        if index_meta_re.is_match(path.to_str().unwrap_or("")) {
-                            // Extract index name from path.
-                            let index_name = match extract_index_name(path) {
-                                Ok(name) => name,
-                                Err(error) => {
-                                    error!(?path, ?error, "Failed to extract index name.");
-                                    continue;
-                                }
-                            };
-
-                            match event.kind {
-                                EventKind::Modify(ModifyKind::Name(RenameMode::To))
-                                | EventKind::Modify(ModifyKind::Data(DataChange::Any)) => {
-                                    // Load index metadata.
-                                    info!(?path, "Load index metadata.");
-                                    let index_metadata = match load_index_metadata(path).await {
-                                        Ok(index_metadata) => index_metadata,
-                                        Err(error) => {
-                                            error!(?path, ?error, "Failed to load metadata.");
-                                            continue;
-                                        }
-                                    };
-
-                                    // Update index metadatas.
-                                    info!(?index_name, "Insert index metadata.");
-                                    let mut mut_metadatas = metadatas_task.write().await;
-                                    mut_metadatas
-                                        .insert(index_name.clone(), index_metadata.clone());
-
-                                    match metadatas_sender.send(mut_metadatas.clone()) {
-                                        Ok(_) => info!("Sent index metadatas to the stream."),
-                                        Err(_error) => {
-                                            error!(
-                                                ?index_name,
-                                                // ?error,
-                                                "Failed to send index metadatas."
-                                            );
-                                        }
-                                    }
-                                }
-                                EventKind::Remove(RemoveKind::File) => {
-                                    info!(?index_name, "Remove index metadata.");
-                                    let mut mut_metadatas = metadatas_task.write().await;
-                                    mut_metadatas.remove(&index_name);
-
-                                    match metadatas_sender.send(mut_metadatas.clone()) {
-                                        Ok(_) => info!("Sent index metadatas to the stream."),
-                                        Err(_error) => {
-                                            error!(
-                                                ?index_name,
-                                                // ?error,
-                                                "Failed to send index metadatas."
-                                            );
-                                        }
-                                    }
-                                }
-                                _ => {
-                                    debug!(?event, "Unexpected event kind.");
-                                    continue;
-                                }
-                            }
-                        } else {
-                            debug!(?path, "Ignore path.");
-                            continue;
+        let mut pending_imports = self.pending_imports.write().await;
+        for name in indices.keys().chain(snapshot.tombstones.keys()) {
+            pending_imports.remove(name);
+        }
+        drop(pending_imports);
+
+        let restored: Vec<String> = indices.keys().cloned().collect();
+        *self.indices.write().await = indices;
+        *self.tombstones.write().await = snapshot.tombstones;
+        self.publish().await;
+
+        for name in restored.iter() {
+            self.notify_reload(name);
+        }
+
+        Ok(())
+    }
+}
+
+fn user_dictionary_path(index_dir: &Path, file_name: &str) -> Result<PathBuf, MetastoreError> {
+    resolve_index_file(index_dir, Path::new(file_name))
+        .map_err(|error| MetastoreErrorKind::InvalidUserDictionary.with_error(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read, path::Path};
+
+    use tempdir::TempDir;
+
+    use crate::index::{
+        metadata::Metadata,
+        metastore::{outdated_indices, Command, Digest, Metastore},
+        INDEX_METADATA_FILE,
+    };
+
+    fn create_metadata() -> Metadata {
+        serde_json::from_str::<Metadata>(
+            r#"
+            {
+                "schema": [
+                    {
+                        "name": "title",
+                        "type": "text",
+                        "options": {
+                            "indexing": {
+                                "record": "position",
+                                "fieldnorms": true,
+                                "tokenizer": "default"
+                            },
+                            "stored": true
                         }
                     }
-                    Err(TryRecvError::Empty) => {
-                        sleep(SLEEP_DURATION).await;
-                    }
-                    Err(TryRecvError::Disconnected) => {
-                        break;
+                ],
+                "analyzers": {
+                    "default": {
+                        "tokenizer": {
+                            "name": "simple"
+                        },
+                        "filters": [
+                            {
+                                "name": "lower_case"
+                            }
+                        ]
                     }
                 }
             }
-        });
+            "#,
+        )
+        .unwrap()
+    }
 
-        Ok(Metastore {
-            metadatas,
-            metadatas_receiver,
-        })
+    fn read_file(path: &Path) -> String {
+        let mut content = String::new();
+        File::open(path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
     }
 
-    pub async fn metadatas(&self) -> HashMap<String, Metadata> {
-        self.metadatas.read().await.clone()
+    #[tokio::test]
+    async fn test_apply() {
+        let tmp_dir = TempDir::new("test_apply").unwrap();
+        let indices_dir = tmp_dir.path().join("indices");
+
+        let metastore = Metastore::new(indices_dir.clone()).await.unwrap();
+        let mut reloads = metastore.subscribe_reloads();
+
+        metastore
+            .apply(
+                1,
+                &Command::CreateIndex {
+                    name: "foo".to_string(),
+                    metadata: create_metadata(),
+                },
+            )
+            .await
+            .unwrap();
+        assert!(indices_dir.join("foo").join(INDEX_METADATA_FILE).exists());
+        let metadata = metastore.metadatas().await.get("foo").cloned().unwrap();
+        // The reserved fields are added as the metadata is loaded from the file.
+        assert!(metadata.schema().unwrap().get_field("_id").is_some());
+
+        // An index cannot be created twice.
+        assert!(metastore
+            .apply(
+                2,
+                &Command::CreateIndex {
+                    name: "foo".to_string(),
+                    metadata: create_metadata(),
+                }
+            )
+            .await
+            .is_err());
+
+        metastore
+            .apply(3, &Command::PutUserDictionary {
+                name: "foo".to_string(),
+                file_name: "userdic.csv".to_string(),
+                content: base64::encode("東京スカイツリー,1288,1288,-1000,名詞,固有名詞,一般,*,*,*,東京スカイツリー,トウキョウスカイツリー,トウキョウスカイツリー"),
+            })
+            .await
+            .unwrap();
+        assert!(read_file(&indices_dir.join("foo").join("userdic.csv")).starts_with("東京"));
+        assert_eq!(reloads.recv().await.unwrap(), "foo");
+
+        // The user dictionary must be placed in the index directory.
+        assert!(metastore
+            .apply(
+                4,
+                &Command::PutUserDictionary {
+                    name: "foo".to_string(),
+                    file_name: "../userdic.csv".to_string(),
+                    content: String::new(),
+                }
+            )
+            .await
+            .is_err());
+
+        let snapshot = metastore.snapshot().await.unwrap();
+
+        metastore
+            .apply(
+                5,
+                &Command::DeleteIndex {
+                    name: "foo".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        assert!(metastore.metadatas().await.is_empty());
+        assert!(!indices_dir.join("foo").join(INDEX_METADATA_FILE).exists());
+        assert!(metastore
+            .apply(
+                6,
+                &Command::DeleteIndex {
+                    name: "foo".to_string(),
+                }
+            )
+            .await
+            .is_err());
+
+        // Restore the state before the index was deleted.
+        metastore.restore(&snapshot).await.unwrap();
+        assert!(metastore.metadatas().await.contains_key("foo"));
+        assert!(indices_dir.join("foo").join(INDEX_METADATA_FILE).exists());
+        assert!(indices_dir.join("foo").join("userdic.csv").exists());
+        assert_eq!(
+            metastore.digests().await,
+            vec![Digest {
+                name: "foo".to_string(),
+                version: 3,
+                deleted: false,
+            }]
+        );
     }
 
-    pub fn watch(&self) -> WatchStream<HashMap<String, Metadata>> {
-        WatchStream::new(self.metadatas_receiver.clone())
+    #[tokio::test]
+    async fn test_tombstones() {
+        let tmp_dir = TempDir::new("test_tombstones").unwrap();
+        let indices_dir = tmp_dir.path().join("indices");
+
+        let metastore = Metastore::new(indices_dir.clone()).await.unwrap();
+        metastore
+            .apply(
+                1,
+                &Command::CreateIndex {
+                    name: "foo".to_string(),
+                    metadata: create_metadata(),
+                },
+            )
+            .await
+            .unwrap();
+        metastore
+            .apply(
+                2,
+                &Command::DeleteIndex {
+                    name: "foo".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        let snapshot = metastore.snapshot().await.unwrap();
+        assert_eq!(
+            metastore.digests().await,
+            vec![Digest {
+                name: "foo".to_string(),
+                version: 2,
+                deleted: true,
+            }]
+        );
+
+        // A node that missed the deletion removes the index when it restores the snapshot.
+        let metastore = Metastore::new(indices_dir.clone()).await.unwrap();
+        metastore
+            .apply(
+                1,
+                &Command::CreateIndex {
+                    name: "foo".to_string(),
+                    metadata: create_metadata(),
+                },
+            )
+            .await
+            .unwrap();
+        let local = metastore.digests().await;
+        let remote = Metastore::new(tmp_dir.path().join("remote")).await.unwrap();
+        remote.restore(&snapshot).await.unwrap();
+        assert_eq!(
+            outdated_indices(&local, &remote.digests().await),
+            vec!["foo".to_string()]
+        );
+        assert!(outdated_indices(&remote.digests().await, &local).is_empty());
+
+        metastore.restore(&snapshot).await.unwrap();
+        assert!(metastore.metadatas().await.is_empty());
+        assert!(!indices_dir.join("foo").join(INDEX_METADATA_FILE).exists());
+
+        // The deleted index is not imported again.
+        let metastore = Metastore::new(indices_dir.clone()).await.unwrap();
+        metastore
+            .save_metadata("foo", create_metadata())
+            .await
+            .unwrap();
+        metastore.restore(&snapshot).await.unwrap();
+        metastore.load_pending_imports().await.unwrap();
+        assert!(metastore.metadatas().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pending_imports() {
+        let tmp_dir = TempDir::new("test_pending_imports").unwrap();
+        let indices_dir = tmp_dir.path().join("indices");
+
+        let metastore = Metastore::new(indices_dir.clone()).await.unwrap();
+        metastore
+            .apply(
+                1,
+                &Command::CreateIndex {
+                    name: "foo".to_string(),
+                    metadata: create_metadata(),
+                },
+            )
+            .await
+            .unwrap();
+
+        // The metadata files that are left without the replicated state are served until they
+        // are imported.
+        let metastore = Metastore::new(indices_dir.clone()).await.unwrap();
+        assert!(metastore.metadatas().await.is_empty());
+        metastore.load_pending_imports().await.unwrap();
+        assert!(metastore.metadatas().await.contains_key("foo"));
+
+        let pending_imports = metastore.pending_imports().await;
+        assert_eq!(pending_imports.len(), 1);
+        let (name, metadata) = pending_imports.into_iter().next().unwrap();
+        metastore
+            .apply(1, &Command::CreateIndex { name, metadata })
+            .await
+            .unwrap();
+        assert!(metastore.pending_imports().await.is_empty());
+        assert!(metastore.metadatas().await.contains_key("foo"));
     }
 }
@@ -0,0 +1,242 @@
+pub mod language_detection;
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use serde::{
+    de::{self, Deserialize, Deserializer},
+    ser::{Serialize, Serializer},
+};
+use serde_json::{Map, Value};
+use tantivy::schema::Schema;
+
+use crate::index::processor::language_detection::create_language_detection_processor;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ProcessorErrorKind {
+    ParseFailure,
+    InvalidArgument,
+    InvalidProcessorKind,
+    FieldNotFound,
+    ProcessFailure,
+}
+
+impl ProcessorErrorKind {
+    pub fn with_error<E>(self, source: E) -> ProcessorError
+    where
+        anyhow::Error: From<E>,
+    {
+        ProcessorError {
+            kind: self,
+            source: From::from(source),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("ProcessorError(kind={kind:?}, source={source})")]
+pub struct ProcessorError {
+    pub kind: ProcessorErrorKind,
+    #[source]
+    source: anyhow::Error,
+}
+
+impl ProcessorError {
+    pub fn add_context<C>(self, ctx: C) -> Self
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        ProcessorError {
+            kind: self.kind,
+            source: self.source.context(ctx),
+        }
+    }
+
+    pub fn kind(&self) -> ProcessorErrorKind {
+        self.kind
+    }
+}
+
+pub enum ProcessorKind {
+    LanguageDetection,
+}
+
+impl FromStr for ProcessorKind {
+    type Err = ProcessorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "language_detection" => Ok(ProcessorKind::LanguageDetection),
+            _ => Err(ProcessorErrorKind::InvalidProcessorKind
+                .with_error(anyhow::format_err!("Unknown processor kind: {}", s))),
+        }
+    }
+}
+
+pub trait Processor: 'static + Send + Sync {
+    /// Returns the names of the fields the processor reads or writes.
+    fn field_names(&self) -> Vec<&str>;
+
+    /// Processes the fields of the document before it is indexed.
+    fn process(&self, fields: &mut Map<String, Value>) -> Result<(), ProcessorError>;
+}
+
+#[derive(Clone)]
+pub struct BoxProcessor(Arc<dyn Processor>);
+
+impl<T: Processor> From<T> for BoxProcessor {
+    fn from(processor: T) -> BoxProcessor {
+        BoxProcessor(Arc::new(processor))
+    }
+}
+
+/// The processors applied in order to the documents at ingest time.
+#[derive(Clone)]
+pub struct Processors {
+    source: Value,
+    processors: Vec<BoxProcessor>,
+}
+
+impl Default for Processors {
+    fn default() -> Self {
+        Processors {
+            source: Value::Array(Vec::new()),
+            processors: Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for Processors {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Processors {
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// Checks that the fields the processors refer to exist in the schema.
+    pub fn check(&self, schema: &Schema) -> Result<(), ProcessorError> {
+        for processor in self.processors.iter() {
+            for field_name in processor.0.field_names() {
+                if schema.get_field(field_name).is_none() {
+                    return Err(ProcessorErrorKind::FieldNotFound
+                        .with_error(anyhow::anyhow!("Field {:?} not found.", field_name)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn process(&self, fields: &mut Map<String, Value>) -> Result<(), ProcessorError> {
+        for processor in self.processors.iter() {
+            processor.0.process(fields)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for Processors {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.source.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Processors {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let inner = Value::deserialize(deserializer)?;
+        let processors = build_processors(&inner).map_err(de::Error::custom)?;
+
+        Ok(processors)
+    }
+}
+
+fn build_processors(source: &Value) -> Result<Processors, ProcessorError> {
+    let processor_settings = source.as_array().ok_or_else(|| {
+        ProcessorErrorKind::ParseFailure.with_error(anyhow::anyhow!("processors must be a list"))
+    })?;
+
+    let mut processors = Vec::new();
+    for processor_setting in processor_settings.iter() {
+        processors.push(build_processor(processor_setting)?);
+    }
+
+    Ok(Processors {
+        source: source.clone(),
+        processors,
+    })
+}
+
+fn build_processor(processor_setting: &Value) -> Result<BoxProcessor, ProcessorError> {
+    let name = processor_setting
+        .get("name")
+        .and_then(|name| name.as_str())
+        .ok_or_else(|| {
+            ProcessorErrorKind::ParseFailure
+                .with_error(anyhow::anyhow!("processor name is missing"))
+        })?;
+    let args = match processor_setting.get("args") {
+        Some(args) => serde_json::to_vec(args)
+            .map_err(|error| ProcessorErrorKind::ParseFailure.with_error(error))?,
+        None => Vec::new(),
+    };
+
+    match ProcessorKind::from_str(name)? {
+        ProcessorKind::LanguageDetection => create_language_detection_processor(&args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::schema::{Schema, STRING, TEXT};
+
+    use crate::index::processor::Processors;
+
+    #[test]
+    fn test_processors() {
+        let json_str = r#"
+            [
+                {
+                    "name": "language_detection",
+                    "args": {
+                        "field": "body",
+                        "target_field": "language",
+                        "languages": {
+                            "eng": "body_en",
+                            "jpn": "body_ja"
+                        }
+                    }
+                }
+            ]
+            "#;
+        let processors = serde_json::from_str::<Processors>(json_str).unwrap();
+        assert!(!processors.is_empty());
+
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("body", TEXT);
+        schema_builder.add_text_field("language", STRING);
+        schema_builder.add_text_field("body_en", TEXT);
+        let schema = schema_builder.build();
+        assert!(processors.check(&schema).is_err());
+
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("body", TEXT);
+        schema_builder.add_text_field("language", STRING);
+        schema_builder.add_text_field("body_en", TEXT);
+        schema_builder.add_text_field("body_ja", TEXT);
+        let schema = schema_builder.build();
+        assert!(processors.check(&schema).is_ok());
+
+        assert!(serde_json::from_str::<Processors>(r#"[{"name": "unknown"}]"#).is_err());
+        assert!(serde_json::from_str::<Processors>("[]").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+use whatlang::{Detector, Lang};
+
+use crate::index::processor::{BoxProcessor, Processor, ProcessorError, ProcessorErrorKind};
+
+#[derive(Deserialize, Serialize)]
+struct LanguageDetectionProcessorArgs {
+    field: String,
+    #[serde(default)]
+    target_field: Option<String>,
+    #[serde(default)]
+    languages: BTreeMap<String, String>,
+    #[serde(default)]
+    default_language: Option<String>,
+    #[serde(default)]
+    min_confidence: f64,
+}
+
+fn parse_language(code: &str) -> Result<Lang, ProcessorError> {
+    Lang::from_code(code).ok_or_else(|| {
+        ProcessorErrorKind::InvalidArgument
+            .with_error(anyhow::anyhow!("Unknown language code: {}", code))
+    })
+}
+
+pub fn create_language_detection_processor(args: &[u8]) -> Result<BoxProcessor, ProcessorError> {
+    let language_detection_args = serde_json::from_slice::<LanguageDetectionProcessorArgs>(args)
+        .map_err(|e| ProcessorErrorKind::InvalidArgument.with_error(e))?;
+
+    if language_detection_args.target_field.is_none()
+        && language_detection_args.languages.is_empty()
+    {
+        return Err(
+            ProcessorErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                "Either target_field or languages must be specified."
+            )),
+        );
+    }
+    if !(0.0..=1.0).contains(&language_detection_args.min_confidence) {
+        return Err(
+            ProcessorErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                "min_confidence must be between 0.0 and 1.0."
+            )),
+        );
+    }
+
+    let mut languages = Vec::new();
+    for (code, field) in language_detection_args.languages.iter() {
+        languages.push((parse_language(code)?, field.clone()));
+    }
+    let default_language = language_detection_args
+        .default_language
+        .as_deref()
+        .map(parse_language)
+        .transpose()?;
+
+    // The languages that have no sub-field cannot be routed, so they are not detected.
+    let detector = if languages.is_empty() {
+        Detector::new()
+    } else {
+        Detector::with_allowlist(languages.iter().map(|(lang, _)| *lang).collect())
+    };
+
+    Ok(BoxProcessor::from(LanguageDetectionProcessor {
+        field: language_detection_args.field,
+        target_field: language_detection_args.target_field,
+        languages,
+        default_language,
+        min_confidence: language_detection_args.min_confidence,
+        detector,
+    }))
+}
+
+/// Detects the language of the text field, writes the language code to the target field,
+/// and copies the text to the sub-field for the language.
+/// The language codes are ISO 639-3 codes, e.g. `eng`, `jpn`, `kor` and `cmn`.
+pub struct LanguageDetectionProcessor {
+    field: String,
+    target_field: Option<String>,
+    languages: Vec<(Lang, String)>,
+    default_language: Option<Lang>,
+    min_confidence: f64,
+    detector: Detector,
+}
+
+impl LanguageDetectionProcessor {
+    fn detect(&self, text: &str) -> Option<Lang> {
+        self.detector
+            .detect(text)
+            .filter(|info| info.confidence() >= self.min_confidence)
+            .map(|info| info.lang())
+            .or(self.default_language)
+    }
+}
+
+impl Processor for LanguageDetectionProcessor {
+    fn field_names(&self) -> Vec<&str> {
+        let mut field_names = vec![self.field.as_str()];
+        field_names.extend(self.target_field.as_deref());
+        field_names.extend(self.languages.iter().map(|(_, field)| field.as_str()));
+        field_names
+    }
+
+    fn process(&self, fields: &mut Map<String, Value>) -> Result<(), ProcessorError> {
+        let value = match fields.get(&self.field) {
+            Some(value) => value.clone(),
+            None => return Ok(()),
+        };
+        let text = match &value {
+            Value::String(text) => text.clone(),
+            Value::Array(values) => values
+                .iter()
+                .map(|value| {
+                    value.as_str().ok_or_else(|| {
+                        ProcessorErrorKind::ProcessFailure
+                            .with_error(anyhow::anyhow!("Field {:?} must be a text.", self.field))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .join("\n"),
+            _ => {
+                return Err(ProcessorErrorKind::ProcessFailure
+                    .with_error(anyhow::anyhow!("Field {:?} must be a text.", self.field)))
+            }
+        };
+
+        let lang = match self.detect(&text) {
+            Some(lang) => lang,
+            None => return Ok(()),
+        };
+
+        if let Some(target_field) = &self.target_field {
+            fields.insert(target_field.clone(), Value::String(lang.code().to_string()));
+        }
+        if let Some((_, field)) = self.languages.iter().find(|(l, _)| *l == lang) {
+            fields.insert(field.clone(), value);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Map, Value};
+
+    use crate::index::processor::language_detection::create_language_detection_processor;
+
+    fn helper(json_str: &str, fields: Value) -> Map<String, Value> {
+        let processor = create_language_detection_processor(json_str.as_bytes()).unwrap();
+
+        let mut fields = fields.as_object().unwrap().clone();
+        processor.0.process(&mut fields).unwrap();
+        fields
+    }
+
+    #[test]
+    fn test_language_detection_processor() {
+        let json_str = r#"
+            {
+                "field": "body",
+                "target_field": "language",
+                "languages": {
+                    "eng": "body_en",
+                    "jpn": "body_ja",
+                    "kor": "body_ko",
+                    "cmn": "body_zh"
+                }
+            }
+            "#;
+
+        let fields = helper(
+            json_str,
+            json!({"body": "The quick brown fox jumps over the lazy dog."}),
+        );
+        assert_eq!(fields["language"], "eng");
+        assert_eq!(
+            fields["body_en"],
+            "The quick brown fox jumps over the lazy dog."
+        );
+
+        let fields = helper(
+            json_str,
+            json!({"body": "東京は日本の首都であり、多くの人々が暮らしています。"}),
+        );
+        assert_eq!(fields["language"], "jpn");
+        assert!(fields.contains_key("body_ja"));
+
+        let fields = helper(
+            json_str,
+            json!({"body": ["서울은 대한민국의 수도입니다.", "많은 사람들이 살고 있습니다."]}),
+        );
+        assert_eq!(fields["language"], "kor");
+        assert_eq!(fields["body_ko"].as_array().unwrap().len(), 2);
+
+        let fields = helper(json_str, json!({"title": "no body"}));
+        assert!(!fields.contains_key("language"));
+    }
+
+    #[test]
+    fn test_language_detection_processor_default_language() {
+        let json_str = r#"
+            {
+                "field": "body",
+                "target_field": "language",
+                "default_language": "eng",
+                "min_confidence": 1.0
+            }
+            "#;
+        let fields = helper(json_str, json!({"body": "ok"}));
+        assert_eq!(fields["language"], "eng");
+
+        assert!(create_language_detection_processor(r#"{"field": "body"}"#.as_bytes()).is_err());
+        assert!(create_language_detection_processor(
+            r#"{"field": "body", "languages": {"xx": "body_xx"}}"#.as_bytes()
+        )
+        .is_err());
+    }
+}
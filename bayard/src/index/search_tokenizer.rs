@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+use tantivy::{
+    tokenizer::{TextAnalyzer, TokenizerManager},
+    Searcher,
+};
+
+pub const SEARCH_TOKENIZER_OPTION: &str = "search_tokenizer";
+const SEARCH_ANALYZER_PREFIX: &str = "_search.";
+
+/// Returns the name under which the search-time analyzer of the field is registered.
+pub fn search_analyzer_name(field_name: &str) -> String {
+    format!("{}{}", SEARCH_ANALYZER_PREFIX, field_name)
+}
+
+/// Remove the `search_tokenizer` options from the schema entries and returns them
+/// as a map from the field name to the tokenizer name.
+pub fn extract_search_tokenizers(schema: &mut Value) -> anyhow::Result<HashMap<String, String>> {
+    let mut search_tokenizers = HashMap::new();
+
+    let entries = match schema {
+        Value::Array(entries) => entries,
+        _ => return Ok(search_tokenizers),
+    };
+
+    for entry in entries.iter_mut() {
+        let name = entry["name"].as_str().unwrap_or_default().to_string();
+        let is_text = entry["type"].as_str() == Some("text");
+        let indexing = match entry
+            .get_mut("options")
+            .and_then(|options| options.get_mut("indexing"))
+            .and_then(|indexing| indexing.as_object_mut())
+        {
+            Some(indexing) => indexing,
+            None => continue,
+        };
+        let search_tokenizer = match indexing.remove(SEARCH_TOKENIZER_OPTION) {
+            Some(search_tokenizer) => search_tokenizer,
+            None => continue,
+        };
+
+        if !is_text {
+            anyhow::bail!(
+                "Search tokenizer is only available for text fields: {}",
+                name
+            );
+        }
+        let search_tokenizer = search_tokenizer
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Search tokenizer of {} must be a string", name))?;
+        search_tokenizers.insert(name, search_tokenizer.to_string());
+    }
+
+    Ok(search_tokenizers)
+}
+
+/// Put the `search_tokenizer` options back into the schema entries.
+pub fn insert_search_tokenizers(schema: &mut Value, search_tokenizers: &HashMap<String, String>) {
+    let entries = match schema {
+        Value::Array(entries) => entries,
+        _ => return,
+    };
+
+    for entry in entries.iter_mut() {
+        let search_tokenizer = match entry["name"]
+            .as_str()
+            .and_then(|name| search_tokenizers.get(name))
+        {
+            Some(search_tokenizer) => search_tokenizer.clone(),
+            None => continue,
+        };
+        if let Some(indexing) = entry
+            .get_mut("options")
+            .and_then(|options| options.get_mut("indexing"))
+            .and_then(|indexing| indexing.as_object_mut())
+        {
+            indexing.insert(
+                SEARCH_TOKENIZER_OPTION.to_string(),
+                Value::String(search_tokenizer),
+            );
+        }
+    }
+}
+
+/// Register the search-time analyzers of the fields.
+/// The analyzers referred to must already be registered in the manager.
+pub fn register_search_analyzers(
+    manager: &TokenizerManager,
+    search_tokenizers: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+    for (field_name, tokenizer_name) in search_tokenizers.iter() {
+        let analyzer = manager.get(tokenizer_name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Search tokenizer {} of {} not found",
+                tokenizer_name,
+                field_name
+            )
+        })?;
+        manager.register(&search_analyzer_name(field_name), analyzer);
+    }
+
+    Ok(())
+}
+
+/// Returns the search-time analyzer of the field if it has one.
+pub fn search_analyzer(searcher: &Searcher, field_name: &str) -> Option<TextAnalyzer> {
+    searcher
+        .index()
+        .tokenizers()
+        .get(&search_analyzer_name(field_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tantivy::tokenizer::TokenizerManager;
+
+    use crate::index::search_tokenizer::{
+        extract_search_tokenizers, insert_search_tokenizers, register_search_analyzers,
+        search_analyzer_name,
+    };
+
+    #[test]
+    fn test_extract_search_tokenizers() {
+        let schema = serde_json::json!([
+            {
+                "name": "title",
+                "type": "text",
+                "options": {
+                    "indexing": {
+                        "record": "position",
+                        "fieldnorms": true,
+                        "tokenizer": "edge_ngram",
+                        "search_tokenizer": "default"
+                    },
+                    "stored": true
+                }
+            },
+            {
+                "name": "body",
+                "type": "text",
+                "options": {
+                    "indexing": {
+                        "record": "position",
+                        "fieldnorms": true,
+                        "tokenizer": "default"
+                    },
+                    "stored": true
+                }
+            }
+        ]);
+
+        let mut extracted = schema.clone();
+        let search_tokenizers = extract_search_tokenizers(&mut extracted).unwrap();
+        assert_eq!(
+            search_tokenizers,
+            HashMap::from([("title".to_string(), "default".to_string())])
+        );
+        assert!(extracted[0]["options"]["indexing"]
+            .get("search_tokenizer")
+            .is_none());
+
+        insert_search_tokenizers(&mut extracted, &search_tokenizers);
+        assert_eq!(extracted, schema);
+
+        let mut schema = serde_json::json!([
+            {
+                "name": "count",
+                "type": "u64",
+                "options": {
+                    "indexing": {
+                        "search_tokenizer": "default"
+                    }
+                }
+            }
+        ]);
+        assert!(extract_search_tokenizers(&mut schema).is_err());
+    }
+
+    #[test]
+    fn test_register_search_analyzers() {
+        let manager = TokenizerManager::default();
+
+        let search_tokenizers = HashMap::from([("title".to_string(), "default".to_string())]);
+        register_search_analyzers(&manager, &search_tokenizers).unwrap();
+        assert!(manager.get(&search_analyzer_name("title")).is_some());
+
+        let search_tokenizers = HashMap::from([("title".to_string(), "unknown".to_string())]);
+        assert!(register_search_analyzers(&manager, &search_tokenizers).is_err());
+    }
+}
@@ -1,16 +1,26 @@
 pub mod alpha_num_only;
 pub mod ascii_folding;
+pub mod edge_ngram;
+pub mod elision;
+pub mod length;
 pub mod lower_case;
 pub mod remove_long;
+pub mod shingle;
 pub mod stemming;
 pub mod stop_word;
+pub mod synonym;
+pub mod unique;
+pub mod word_delimiter;
 
 use std::{fmt, str::FromStr};
 
+use tantivy::tokenizer::{Token, TokenStream};
+
 #[derive(Debug, Clone, Copy)]
 pub enum FilterFactoryErrorKind {
     InvalidArgument,
     InvalidFilterKind,
+    FileReadFailure,
 }
 
 impl FilterFactoryErrorKind {
@@ -52,10 +62,17 @@ impl FilterFactoryError {
 pub enum FilterKind {
     AlphaNumOnly,
     AsciiFolding,
+    EdgeNgram,
+    Elision,
+    Length,
     LowerCase,
     RemoveLong,
+    Shingle,
     Stemming,
     StopWord,
+    Synonym,
+    Unique,
+    WordDelimiter,
 }
 
 impl FromStr for FilterKind {
@@ -65,12 +82,50 @@ impl FromStr for FilterKind {
         match s {
             "alpha_num_only" => Ok(FilterKind::AlphaNumOnly),
             "ascii_folding" => Ok(FilterKind::AsciiFolding),
+            "edge_ngram" => Ok(FilterKind::EdgeNgram),
+            "elision" => Ok(FilterKind::Elision),
+            "length" => Ok(FilterKind::Length),
             "lower_case" => Ok(FilterKind::LowerCase),
             "remove_long" => Ok(FilterKind::RemoveLong),
+            "shingle" => Ok(FilterKind::Shingle),
             "stemming" => Ok(FilterKind::Stemming),
             "stop_word" => Ok(FilterKind::StopWord),
+            "synonym" => Ok(FilterKind::Synonym),
+            "unique" => Ok(FilterKind::Unique),
+            "word_delimiter" => Ok(FilterKind::WordDelimiter),
             _ => Err(FilterFactoryErrorKind::InvalidFilterKind
                 .with_error(anyhow::format_err!("Unknown filter kind: {}", s))),
         }
     }
 }
+
+/// A token stream that emits the tokens buffered by a filter.
+/// It is used by the filters that need to look at several tokens at once.
+pub struct BufferedTokenStream {
+    tokens: Vec<Token>,
+    cursor: usize,
+}
+
+impl BufferedTokenStream {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        BufferedTokenStream { tokens, cursor: 0 }
+    }
+}
+
+impl TokenStream for BufferedTokenStream {
+    fn advance(&mut self) -> bool {
+        if self.cursor >= self.tokens.len() {
+            return false;
+        }
+        self.cursor += 1;
+        true
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.cursor - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.cursor - 1]
+    }
+}
@@ -0,0 +1,159 @@
+use tantivy::tokenizer::{BoxTokenFilter, BoxTokenStream, Token, TokenFilter};
+
+use crate::index::token_filter::{BufferedTokenStream, FilterFactoryError, FilterFactoryErrorKind};
+
+fn default_min_gram() -> usize {
+    1
+}
+
+fn default_max_gram() -> usize {
+    2
+}
+
+#[derive(Deserialize, Serialize)]
+struct EdgeNgramFilterArgs {
+    #[serde(default = "default_min_gram")]
+    min_gram: usize,
+    #[serde(default = "default_max_gram")]
+    max_gram: usize,
+    #[serde(default)]
+    preserve_original: bool,
+}
+
+impl Default for EdgeNgramFilterArgs {
+    fn default() -> Self {
+        EdgeNgramFilterArgs {
+            min_gram: default_min_gram(),
+            max_gram: default_max_gram(),
+            preserve_original: false,
+        }
+    }
+}
+
+pub fn create_edge_ngram_filter(args: &[u8]) -> Result<BoxTokenFilter, FilterFactoryError> {
+    let edge_ngram_args = if args.is_empty() {
+        EdgeNgramFilterArgs::default()
+    } else {
+        serde_json::from_slice::<EdgeNgramFilterArgs>(args)
+            .map_err(|e| FilterFactoryErrorKind::InvalidArgument.with_error(e))?
+    };
+
+    if edge_ngram_args.min_gram == 0 || edge_ngram_args.min_gram > edge_ngram_args.max_gram {
+        return Err(
+            FilterFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                "min_gram must be between 1 and max_gram: min_gram={}, max_gram={}",
+                edge_ngram_args.min_gram,
+                edge_ngram_args.max_gram
+            )),
+        );
+    }
+
+    Ok(BoxTokenFilter::from(EdgeNgramFilter {
+        min_gram: edge_ngram_args.min_gram,
+        max_gram: edge_ngram_args.max_gram,
+        preserve_original: edge_ngram_args.preserve_original,
+    }))
+}
+
+/// Emits the prefixes of each token at the position of the token.
+#[derive(Clone)]
+pub struct EdgeNgramFilter {
+    min_gram: usize,
+    max_gram: usize,
+    preserve_original: bool,
+}
+
+impl TokenFilter for EdgeNgramFilter {
+    fn transform<'a>(&self, mut token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        let mut tokens = Vec::new();
+        token_stream.process(&mut |token: &Token| {
+            // Byte offsets of the ends of the prefixes in characters.
+            let ends = token
+                .text
+                .char_indices()
+                .map(|(i, c)| i + c.len_utf8())
+                .skip(self.min_gram - 1)
+                .take(self.max_gram - self.min_gram + 1);
+
+            let mut emitted_original = false;
+            for end in ends {
+                let mut prefix = token.clone();
+                prefix.text.truncate(end);
+                emitted_original |= end == token.text.len();
+                tokens.push(prefix);
+            }
+            if self.preserve_original && !emitted_original {
+                tokens.push(token.clone());
+            }
+        });
+
+        BoxTokenStream::from(BufferedTokenStream::new(tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::{SimpleTokenizer, TextAnalyzer, Token};
+
+    use crate::index::token_filter::edge_ngram::create_edge_ngram_filter;
+
+    fn helper(json_str: &str, text: &str) -> Vec<(String, usize)> {
+        let filter = create_edge_ngram_filter(json_str.as_bytes()).unwrap();
+
+        let mut tokens = vec![];
+        let mut token_stream = TextAnalyzer::from(SimpleTokenizer)
+            .filter(filter)
+            .token_stream(text);
+        token_stream
+            .process(&mut |token: &Token| tokens.push((token.text.clone(), token.position)));
+        tokens
+    }
+
+    #[test]
+    fn test_edge_ngram_filter() {
+        let json_str = r#"
+            {
+                "min_gram": 2,
+                "max_gram": 4
+            }
+            "#;
+        assert_eq!(
+            vec![
+                ("qu".to_string(), 0),
+                ("qui".to_string(), 0),
+                ("quic".to_string(), 0),
+                ("fo".to_string(), 1),
+                ("fox".to_string(), 1),
+            ],
+            helper(json_str, "quick fox a")
+        );
+
+        let json_str = r#"
+            {
+                "min_gram": 2,
+                "max_gram": 3,
+                "preserve_original": true
+            }
+            "#;
+        assert_eq!(
+            vec![
+                ("ちょ".to_string(), 0),
+                ("ちょう".to_string(), 0),
+                ("ちょうちょ".to_string(), 0),
+                ("a".to_string(), 1),
+            ],
+            helper(json_str, "ちょうちょ a")
+        );
+    }
+
+    #[test]
+    fn test_edge_ngram_filter_invalid_args() {
+        let json_str = r#"
+            {
+                "min_gram": 3,
+                "max_gram": 2
+            }
+            "#;
+        assert!(create_edge_ngram_filter(json_str.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::{collections::HashSet, sync::Arc};
+
+use tantivy::tokenizer::{BoxTokenFilter, BoxTokenStream, Token, TokenFilter, TokenStream};
+
+use crate::index::token_filter::{FilterFactoryError, FilterFactoryErrorKind};
+
+const DEFAULT_ARTICLES: [&str; 13] = [
+    "l", "m", "t", "qu", "n", "s", "j", "d", "c", "jusqu", "quoiqu", "lorsqu", "puisqu",
+];
+
+const APOSTROPHES: [char; 2] = ['\'', '\u{2019}'];
+
+fn default_articles() -> Vec<String> {
+    DEFAULT_ARTICLES
+        .iter()
+        .map(|article| article.to_string())
+        .collect()
+}
+
+fn default_ignore_case() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize)]
+struct ElisionFilterArgs {
+    #[serde(default = "default_articles")]
+    articles: Vec<String>,
+    #[serde(default = "default_ignore_case")]
+    ignore_case: bool,
+}
+
+impl Default for ElisionFilterArgs {
+    fn default() -> Self {
+        ElisionFilterArgs {
+            articles: default_articles(),
+            ignore_case: default_ignore_case(),
+        }
+    }
+}
+
+pub fn create_elision_filter(args: &[u8]) -> Result<BoxTokenFilter, FilterFactoryError> {
+    let elision_args = if args.is_empty() {
+        ElisionFilterArgs::default()
+    } else {
+        serde_json::from_slice::<ElisionFilterArgs>(args)
+            .map_err(|e| FilterFactoryErrorKind::InvalidArgument.with_error(e))?
+    };
+
+    let articles = elision_args
+        .articles
+        .into_iter()
+        .map(|article| {
+            if elision_args.ignore_case {
+                article.to_lowercase()
+            } else {
+                article
+            }
+        })
+        .collect();
+
+    Ok(BoxTokenFilter::from(ElisionFilter {
+        articles: Arc::new(articles),
+        ignore_case: elision_args.ignore_case,
+    }))
+}
+
+/// Removes the elided articles from the beginning of tokens. e.g. "l'avion" to "avion"
+#[derive(Clone)]
+pub struct ElisionFilter {
+    articles: Arc<HashSet<String>>,
+    ignore_case: bool,
+}
+
+impl TokenFilter for ElisionFilter {
+    fn transform<'a>(&self, token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(ElisionTokenStream {
+            filter: self.clone(),
+            tail: token_stream,
+        })
+    }
+}
+
+pub struct ElisionTokenStream<'a> {
+    filter: ElisionFilter,
+    tail: BoxTokenStream<'a>,
+}
+
+impl<'a> ElisionTokenStream<'a> {
+    fn elide(&mut self) {
+        let token = self.tail.token_mut();
+        let apostrophe = match token.text.find(APOSTROPHES) {
+            Some(apostrophe) => apostrophe,
+            None => return,
+        };
+        let article = &token.text[..apostrophe];
+        let is_article = if self.filter.ignore_case {
+            self.filter.articles.contains(&article.to_lowercase())
+        } else {
+            self.filter.articles.contains(article)
+        };
+        if !is_article {
+            return;
+        }
+
+        let elided_len = apostrophe + token.text[apostrophe..].chars().next().unwrap().len_utf8();
+        if elided_len == token.text.len() {
+            return;
+        }
+        token.text.drain(..elided_len);
+        if token.offset_to - token.offset_from > elided_len {
+            token.offset_from += elided_len;
+        }
+    }
+}
+
+impl<'a> TokenStream for ElisionTokenStream<'a> {
+    fn advance(&mut self) -> bool {
+        if !self.tail.advance() {
+            return false;
+        }
+        self.elide();
+        true
+    }
+
+    fn token(&self) -> &Token {
+        self.tail.token()
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.tail.token_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::{TextAnalyzer, Token, WhitespaceTokenizer};
+
+    use crate::index::token_filter::elision::create_elision_filter;
+
+    fn helper(json_str: &str, text: &str) -> Vec<String> {
+        let filter = create_elision_filter(json_str.as_bytes()).unwrap();
+
+        let mut tokens = vec![];
+        let mut token_stream = TextAnalyzer::from(WhitespaceTokenizer)
+            .filter(filter)
+            .token_stream(text);
+        token_stream.process(&mut |token: &Token| tokens.push(token.text.clone()));
+        tokens
+    }
+
+    #[test]
+    fn test_elision_filter() {
+        assert_eq!(
+            vec![
+                "avion".to_string(),
+                "arbre".to_string(),
+                "aujourd'hui".to_string(),
+                "l'".to_string(),
+            ],
+            helper("", "l'avion L\u{2019}arbre aujourd'hui l'")
+        );
+
+        let json_str = r#"
+            {
+                "articles": ["dell", "l"],
+                "ignore_case": false
+            }
+            "#;
+        assert_eq!(
+            vec![
+                "isola".to_string(),
+                "L'acqua".to_string(),
+                "amico".to_string(),
+            ],
+            helper(json_str, "dell'isola L'acqua l'amico")
+        );
+    }
+}
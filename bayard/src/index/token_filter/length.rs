@@ -0,0 +1,137 @@
+use tantivy::tokenizer::{BoxTokenFilter, BoxTokenStream, Token, TokenFilter, TokenStream};
+
+use crate::index::token_filter::{FilterFactoryError, FilterFactoryErrorKind};
+
+fn default_max() -> usize {
+    usize::MAX
+}
+
+#[derive(Deserialize, Serialize)]
+struct LengthFilterArgs {
+    #[serde(default)]
+    min: usize,
+    #[serde(default = "default_max")]
+    max: usize,
+}
+
+impl Default for LengthFilterArgs {
+    fn default() -> Self {
+        LengthFilterArgs {
+            min: 0,
+            max: default_max(),
+        }
+    }
+}
+
+pub fn create_length_filter(args: &[u8]) -> Result<BoxTokenFilter, FilterFactoryError> {
+    let length_args = if args.is_empty() {
+        LengthFilterArgs::default()
+    } else {
+        serde_json::from_slice::<LengthFilterArgs>(args)
+            .map_err(|e| FilterFactoryErrorKind::InvalidArgument.with_error(e))?
+    };
+
+    if length_args.min > length_args.max {
+        return Err(
+            FilterFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                "min must not be greater than max: min={}, max={}",
+                length_args.min,
+                length_args.max
+            )),
+        );
+    }
+
+    Ok(BoxTokenFilter::from(LengthFilter {
+        min: length_args.min,
+        max: length_args.max,
+    }))
+}
+
+/// Removes the tokens whose number of characters is out of the range.
+#[derive(Clone)]
+pub struct LengthFilter {
+    min: usize,
+    max: usize,
+}
+
+impl TokenFilter for LengthFilter {
+    fn transform<'a>(&self, token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(LengthTokenStream {
+            min: self.min,
+            max: self.max,
+            tail: token_stream,
+        })
+    }
+}
+
+pub struct LengthTokenStream<'a> {
+    min: usize,
+    max: usize,
+    tail: BoxTokenStream<'a>,
+}
+
+impl<'a> TokenStream for LengthTokenStream<'a> {
+    fn advance(&mut self) -> bool {
+        while self.tail.advance() {
+            let length = self.tail.token().text.chars().count();
+            if self.min <= length && length <= self.max {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn token(&self) -> &Token {
+        self.tail.token()
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.tail.token_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::{SimpleTokenizer, TextAnalyzer, Token};
+
+    use crate::index::token_filter::length::create_length_filter;
+
+    fn helper(json_str: &str, text: &str) -> Vec<String> {
+        let filter = create_length_filter(json_str.as_bytes()).unwrap();
+
+        let mut tokens = vec![];
+        let mut token_stream = TextAnalyzer::from(SimpleTokenizer)
+            .filter(filter)
+            .token_stream(text);
+        token_stream.process(&mut |token: &Token| tokens.push(token.text.clone()));
+        tokens
+    }
+
+    #[test]
+    fn test_length_filter() {
+        let json_str = r#"
+            {
+                "min": 2,
+                "max": 4
+            }
+            "#;
+        assert_eq!(
+            vec![
+                "to".to_string(),
+                "be".to_string(),
+                "or".to_string(),
+                "not".to_string(),
+                "東京都".to_string(),
+            ],
+            helper(json_str, "to be or not a question 東京都")
+        );
+
+        let json_str = r#"
+            {
+                "min": 5,
+                "max": 4
+            }
+            "#;
+        assert!(create_length_filter(json_str.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use tantivy::tokenizer::{BoxTokenFilter, BoxTokenStream, Token, TokenFilter};
+
+use crate::index::token_filter::{BufferedTokenStream, FilterFactoryError, FilterFactoryErrorKind};
+
+fn default_shingle_size() -> usize {
+    2
+}
+
+fn default_output_unigrams() -> bool {
+    true
+}
+
+fn default_token_separator() -> String {
+    " ".to_string()
+}
+
+#[derive(Deserialize, Serialize)]
+struct ShingleFilterArgs {
+    #[serde(default = "default_shingle_size")]
+    min_shingle_size: usize,
+    #[serde(default = "default_shingle_size")]
+    max_shingle_size: usize,
+    #[serde(default = "default_output_unigrams")]
+    output_unigrams: bool,
+    #[serde(default = "default_token_separator")]
+    token_separator: String,
+}
+
+impl Default for ShingleFilterArgs {
+    fn default() -> Self {
+        ShingleFilterArgs {
+            min_shingle_size: default_shingle_size(),
+            max_shingle_size: default_shingle_size(),
+            output_unigrams: default_output_unigrams(),
+            token_separator: default_token_separator(),
+        }
+    }
+}
+
+pub fn create_shingle_filter(args: &[u8]) -> Result<BoxTokenFilter, FilterFactoryError> {
+    let shingle_args = if args.is_empty() {
+        ShingleFilterArgs::default()
+    } else {
+        serde_json::from_slice::<ShingleFilterArgs>(args)
+            .map_err(|e| FilterFactoryErrorKind::InvalidArgument.with_error(e))?
+    };
+
+    if shingle_args.min_shingle_size < 2
+        || shingle_args.min_shingle_size > shingle_args.max_shingle_size
+    {
+        return Err(
+            FilterFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                "min_shingle_size must be between 2 and max_shingle_size: min_shingle_size={}, max_shingle_size={}",
+                shingle_args.min_shingle_size,
+                shingle_args.max_shingle_size
+            )),
+        );
+    }
+
+    Ok(BoxTokenFilter::from(ShingleFilter {
+        min_shingle_size: shingle_args.min_shingle_size,
+        max_shingle_size: shingle_args.max_shingle_size,
+        output_unigrams: shingle_args.output_unigrams,
+        token_separator: shingle_args.token_separator,
+    }))
+}
+
+/// Emits the word n-grams starting at each token at the position of the token.
+#[derive(Clone)]
+pub struct ShingleFilter {
+    min_shingle_size: usize,
+    max_shingle_size: usize,
+    output_unigrams: bool,
+    token_separator: String,
+}
+
+impl TokenFilter for ShingleFilter {
+    fn transform<'a>(&self, mut token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        let mut tokens = Vec::new();
+        token_stream.process(&mut |token: &Token| tokens.push(token.clone()));
+
+        let mut output = Vec::new();
+        for start in 0..tokens.len() {
+            if self.output_unigrams {
+                output.push(tokens[start].clone());
+            }
+            let max_end = tokens.len().min(start + self.max_shingle_size);
+            for end in (start + self.min_shingle_size)..=max_end {
+                let words = &tokens[start..end];
+                let last = &words[words.len() - 1];
+                output.push(Token {
+                    offset_from: words[0].offset_from,
+                    offset_to: last.offset_to,
+                    position: words[0].position,
+                    text: words
+                        .iter()
+                        .map(|word| word.text.as_str())
+                        .collect::<Vec<_>>()
+                        .join(&self.token_separator),
+                    position_length: last.position + last.position_length - words[0].position,
+                });
+            }
+        }
+
+        BoxTokenStream::from(BufferedTokenStream::new(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::{SimpleTokenizer, TextAnalyzer, Token};
+
+    use crate::index::token_filter::shingle::create_shingle_filter;
+
+    fn helper(json_str: &str, text: &str) -> Vec<(String, usize, usize)> {
+        let filter = create_shingle_filter(json_str.as_bytes()).unwrap();
+
+        let mut tokens = vec![];
+        let mut token_stream = TextAnalyzer::from(SimpleTokenizer)
+            .filter(filter)
+            .token_stream(text);
+        token_stream.process(&mut |token: &Token| {
+            tokens.push((token.text.clone(), token.position, token.position_length))
+        });
+        tokens
+    }
+
+    #[test]
+    fn test_shingle_filter() {
+        assert_eq!(
+            vec![
+                ("quick".to_string(), 0, 1),
+                ("quick brown".to_string(), 0, 2),
+                ("brown".to_string(), 1, 1),
+                ("brown fox".to_string(), 1, 2),
+                ("fox".to_string(), 2, 1),
+            ],
+            helper("", "quick brown fox")
+        );
+
+        let json_str = r#"
+            {
+                "min_shingle_size": 2,
+                "max_shingle_size": 3,
+                "output_unigrams": false,
+                "token_separator": "_"
+            }
+            "#;
+        assert_eq!(
+            vec![
+                ("quick_brown".to_string(), 0, 2),
+                ("quick_brown_fox".to_string(), 0, 3),
+                ("brown_fox".to_string(), 1, 2),
+            ],
+            helper(json_str, "quick brown fox")
+        );
+    }
+
+    #[test]
+    fn test_shingle_filter_invalid_args() {
+        let json_str = r#"
+            {
+                "min_shingle_size": 1
+            }
+            "#;
+        assert!(create_shingle_filter(json_str.as_bytes()).is_err());
+    }
+}
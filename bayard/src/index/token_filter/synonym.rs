@@ -0,0 +1,494 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use tantivy::tokenizer::{BoxTokenFilter, BoxTokenStream, Token, TokenFilter};
+
+use crate::index::{
+    resolve_index_file,
+    token_filter::{BufferedTokenStream, FilterFactoryError, FilterFactoryErrorKind},
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SynonymFormat {
+    #[default]
+    Solr,
+    Wordnet,
+}
+
+fn default_expand() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize)]
+struct SynonymFilterArgs {
+    #[serde(default)]
+    synonyms: Vec<String>,
+    synonyms_path: Option<String>,
+    #[serde(default)]
+    format: SynonymFormat,
+    #[serde(default = "default_expand")]
+    expand: bool,
+    #[serde(default)]
+    ignore_case: bool,
+}
+
+/// Synonym rules keyed by the sequence of words to be replaced.
+#[derive(Debug, Default)]
+struct SynonymMap {
+    rules: HashMap<Vec<String>, Vec<Vec<String>>>,
+    max_words: usize,
+    ignore_case: bool,
+}
+
+impl SynonymMap {
+    fn new(ignore_case: bool) -> Self {
+        SynonymMap {
+            ignore_case,
+            ..Default::default()
+        }
+    }
+
+    fn words(&self, phrase: &str) -> Vec<String> {
+        phrase
+            .split_whitespace()
+            .map(|word| self.normalize(word))
+            .collect()
+    }
+
+    fn normalize(&self, word: &str) -> String {
+        if self.ignore_case {
+            word.to_lowercase()
+        } else {
+            word.to_string()
+        }
+    }
+
+    fn add(&mut self, input: Vec<String>, output: Vec<String>) {
+        if input.is_empty() || output.is_empty() {
+            return;
+        }
+        self.max_words = self.max_words.max(input.len());
+        let outputs = self.rules.entry(input).or_default();
+        if !outputs.contains(&output) {
+            outputs.push(output);
+        }
+    }
+
+    fn add_equivalence(&mut self, phrases: &[&str], expand: bool) {
+        let phrases = phrases
+            .iter()
+            .map(|phrase| self.words(phrase))
+            .filter(|words| !words.is_empty())
+            .collect::<Vec<Vec<String>>>();
+        for input in phrases.iter() {
+            if expand {
+                for output in phrases.iter() {
+                    self.add(input.clone(), output.clone());
+                }
+            } else if let Some(output) = phrases.first() {
+                self.add(input.clone(), output.clone());
+            }
+        }
+    }
+
+    /// Parses the rules in Solr format.
+    /// `a, b, c` defines equivalent synonyms and `a, b => c` defines an explicit mapping.
+    fn parse_solr(&mut self, rules: &str, expand: bool) -> Result<(), FilterFactoryError> {
+        for line in rules.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let sides = line.split("=>").collect::<Vec<&str>>();
+            match sides.as_slice() {
+                [phrases] => {
+                    let phrases = phrases.split(',').collect::<Vec<&str>>();
+                    self.add_equivalence(&phrases, expand);
+                }
+                [inputs, outputs] => {
+                    let outputs = outputs
+                        .split(',')
+                        .map(|phrase| self.words(phrase))
+                        .filter(|words| !words.is_empty())
+                        .collect::<Vec<Vec<String>>>();
+                    for input in inputs.split(',') {
+                        let input = self.words(input);
+                        for output in outputs.iter() {
+                            self.add(input.clone(), output.clone());
+                        }
+                    }
+                }
+                _ => {
+                    return Err(FilterFactoryErrorKind::InvalidArgument
+                        .with_error(anyhow::anyhow!("Invalid synonym rule: {}", line)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the rules in WordNet prolog format.
+    /// The words in the same synset are equivalent synonyms.
+    /// e.g. `s(100000001,1,'word',n,1,0).`
+    fn parse_wordnet(&mut self, rules: &str, expand: bool) -> Result<(), FilterFactoryError> {
+        let mut synsets: Vec<(String, Vec<String>)> = Vec::new();
+
+        for line in rules.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid_rule = || {
+                FilterFactoryErrorKind::InvalidArgument
+                    .with_error(anyhow::anyhow!("Invalid synonym rule: {}", line))
+            };
+
+            let body = line
+                .strip_prefix("s(")
+                .and_then(|body| body.strip_suffix(")."))
+                .ok_or_else(invalid_rule)?;
+            let synset_id = body.split(',').next().ok_or_else(invalid_rule)?;
+            let word_start = body.find('\'').ok_or_else(invalid_rule)?;
+            let word_end = body.rfind('\'').ok_or_else(invalid_rule)?;
+            if word_end <= word_start {
+                return Err(invalid_rule());
+            }
+            let word = body[word_start + 1..word_end].replace("''", "'");
+
+            match synsets.last_mut() {
+                Some((id, words)) if id == synset_id => words.push(word),
+                _ => synsets.push((synset_id.to_string(), vec![word])),
+            }
+        }
+
+        for (_, words) in synsets.iter() {
+            let phrases = words
+                .iter()
+                .map(|word| word.as_str())
+                .collect::<Vec<&str>>();
+            self.add_equivalence(&phrases, expand);
+        }
+
+        Ok(())
+    }
+
+    fn parse(
+        &mut self,
+        rules: &str,
+        format: SynonymFormat,
+        expand: bool,
+    ) -> Result<(), FilterFactoryError> {
+        match format {
+            SynonymFormat::Solr => self.parse_solr(rules, expand),
+            SynonymFormat::Wordnet => self.parse_wordnet(rules, expand),
+        }
+    }
+}
+
+/// Reads the synonym file. The path must be relative to the index directory.
+fn read_synonyms_file(path: &str, base_dir: &Path) -> Result<String, FilterFactoryError> {
+    let path = resolve_index_file(base_dir, Path::new(path))
+        .map_err(|error| FilterFactoryErrorKind::InvalidArgument.with_error(error))?;
+
+    fs::read_to_string(path)
+        .map_err(|error| FilterFactoryErrorKind::FileReadFailure.with_error(error))
+}
+
+/// Create a synonym filter.
+/// The rules in `synonyms_path` are loaded only if the index directory is given.
+pub fn create_synonym_filter(
+    args: &[u8],
+    base_dir: Option<&Path>,
+) -> Result<BoxTokenFilter, FilterFactoryError> {
+    if args.is_empty() {
+        return Ok(BoxTokenFilter::from(SynonymFilter {
+            synonyms: Arc::new(SynonymMap::default()),
+        }));
+    }
+
+    let synonym_args = serde_json::from_slice::<SynonymFilterArgs>(args)
+        .map_err(|e| FilterFactoryErrorKind::InvalidArgument.with_error(e))?;
+
+    let mut synonyms = SynonymMap::new(synonym_args.ignore_case);
+    synonyms.parse(
+        &synonym_args.synonyms.join("\n"),
+        synonym_args.format,
+        synonym_args.expand,
+    )?;
+    if let (Some(path), Some(base_dir)) = (&synonym_args.synonyms_path, base_dir) {
+        let rules = read_synonyms_file(path, base_dir)?;
+        synonyms.parse(&rules, synonym_args.format, synonym_args.expand)?;
+    }
+
+    Ok(BoxTokenFilter::from(SynonymFilter {
+        synonyms: Arc::new(synonyms),
+    }))
+}
+
+/// Token filter that injects synonyms.
+/// Multi-word synonyms occupy consecutive positions, and the positions of the following
+/// tokens are shifted by the extra positions, so that phrase queries analyzed with the same
+/// filter still match.
+#[derive(Clone)]
+pub struct SynonymFilter {
+    synonyms: Arc<SynonymMap>,
+}
+
+impl TokenFilter for SynonymFilter {
+    fn transform<'a>(&self, mut token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        let mut tokens = Vec::new();
+        token_stream.process(&mut |token: &Token| tokens.push(token.clone()));
+
+        BoxTokenStream::from(BufferedTokenStream::new(apply_synonyms(
+            &self.synonyms,
+            tokens,
+        )))
+    }
+}
+
+fn apply_synonyms(synonyms: &SynonymMap, tokens: Vec<Token>) -> Vec<Token> {
+    if synonyms.rules.is_empty() {
+        return tokens;
+    }
+
+    let mut output = Vec::with_capacity(tokens.len());
+    let mut position_shift = 0;
+    let mut start = 0;
+
+    while start < tokens.len() {
+        // Find the longest rule that matches the tokens from the start.
+        let matched = (1..=synonyms.max_words.min(tokens.len() - start))
+            .rev()
+            .find_map(|num_words| {
+                let input = tokens[start..start + num_words]
+                    .iter()
+                    .map(|token| synonyms.normalize(&token.text))
+                    .collect::<Vec<String>>();
+                synonyms
+                    .rules
+                    .get(&input)
+                    .map(|outputs| (input, outputs, num_words))
+            });
+
+        let (input, outputs, num_words) = match matched {
+            Some(matched) => matched,
+            None => {
+                let mut token = tokens[start].clone();
+                token.position += position_shift;
+                output.push(token);
+                start += 1;
+                continue;
+            }
+        };
+
+        let matched_tokens = &tokens[start..start + num_words];
+        let first = &matched_tokens[0];
+        let last = &matched_tokens[num_words - 1];
+        let input_span = last.position - first.position + 1;
+        let span = outputs
+            .iter()
+            .map(|words| words.len())
+            .max()
+            .unwrap_or(0)
+            .max(input_span);
+        let base_position = first.position + position_shift;
+
+        let mut synonym_tokens = Vec::new();
+        for words in outputs.iter() {
+            if *words == input {
+                // Keep the original tokens.
+                for token in matched_tokens.iter() {
+                    let relative_position = token.position - first.position;
+                    let mut token = token.clone();
+                    token.position = base_position + relative_position;
+                    if relative_position + 1 == input_span {
+                        token.position_length = span - relative_position;
+                    }
+                    synonym_tokens.push(token);
+                }
+            } else {
+                for (i, word) in words.iter().enumerate() {
+                    synonym_tokens.push(Token {
+                        offset_from: first.offset_from,
+                        offset_to: last.offset_to,
+                        position: base_position + i,
+                        text: word.clone(),
+                        position_length: if i + 1 == words.len() { span - i } else { 1 },
+                    });
+                }
+            }
+        }
+        synonym_tokens.sort_by_key(|token| token.position);
+        output.extend(synonym_tokens);
+
+        position_shift += span - input_span;
+        start += num_words;
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tantivy::tokenizer::{SimpleTokenizer, TextAnalyzer, Token};
+    use tempdir::TempDir;
+
+    use crate::index::token_filter::synonym::create_synonym_filter;
+
+    fn helper(json_str: &str, text: &str) -> Vec<Token> {
+        let filter = create_synonym_filter(json_str.as_bytes(), None).unwrap();
+
+        let mut tokens = vec![];
+        let mut token_stream = TextAnalyzer::from(SimpleTokenizer)
+            .filter(filter)
+            .token_stream(text);
+        token_stream.process(&mut |token: &Token| tokens.push(token.clone()));
+        tokens
+    }
+
+    fn summary(tokens: &[Token]) -> Vec<(String, usize, usize)> {
+        tokens
+            .iter()
+            .map(|token| (token.text.clone(), token.position, token.position_length))
+            .collect()
+    }
+
+    #[test]
+    fn test_synonym_filter_equivalence() {
+        let json_str = r#"
+            {
+                "synonyms": [
+                    "fast, quick"
+                ]
+            }
+            "#;
+
+        assert_eq!(
+            summary(&helper(json_str, "a quick fox")),
+            vec![
+                ("a".to_string(), 0, 1),
+                ("fast".to_string(), 1, 1),
+                ("quick".to_string(), 1, 1),
+                ("fox".to_string(), 2, 1),
+            ]
+        );
+
+        let json_str = r#"
+            {
+                "synonyms": [
+                    "fast, quick"
+                ],
+                "expand": false
+            }
+            "#;
+
+        assert_eq!(
+            summary(&helper(json_str, "a quick fox")),
+            vec![
+                ("a".to_string(), 0, 1),
+                ("fast".to_string(), 1, 1),
+                ("fox".to_string(), 2, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_synonym_filter_mapping() {
+        let json_str = r##"
+            {
+                "synonyms": [
+                    "# comment",
+                    "ny, nyc => new york"
+                ]
+            }
+            "##;
+
+        let tokens = helper(json_str, "ny city");
+        assert_eq!(
+            summary(&tokens),
+            vec![
+                ("new".to_string(), 0, 1),
+                ("york".to_string(), 1, 1),
+                ("city".to_string(), 2, 1),
+            ]
+        );
+        assert_eq!(tokens[1].offset_from, 0);
+        assert_eq!(tokens[1].offset_to, 2);
+    }
+
+    #[test]
+    fn test_synonym_filter_multi_word() {
+        let json_str = r#"
+            {
+                "synonyms": [
+                    "NY, New York"
+                ],
+                "ignore_case": true
+            }
+            "#;
+
+        assert_eq!(
+            summary(&helper(json_str, "ny city")),
+            vec![
+                ("ny".to_string(), 0, 2),
+                ("new".to_string(), 0, 1),
+                ("york".to_string(), 1, 1),
+                ("city".to_string(), 2, 1),
+            ]
+        );
+
+        assert_eq!(
+            summary(&helper(json_str, "New York city")),
+            vec![
+                ("ny".to_string(), 0, 2),
+                ("New".to_string(), 0, 1),
+                ("York".to_string(), 1, 1),
+                ("city".to_string(), 2, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_synonym_filter_wordnet() {
+        let dir = TempDir::new("synonym").unwrap();
+        fs::write(
+            dir.path().join("wn_s.pl"),
+            "s(100000001,1,'car',n,1,0).\ns(100000001,2,'automobile',n,1,0).\ns(100000002,1,'dog',n,1,0).\n",
+        )
+        .unwrap();
+
+        let json_str = r#"
+            {
+                "synonyms_path": "wn_s.pl",
+                "format": "wordnet"
+            }
+            "#;
+        let filter = create_synonym_filter(json_str.as_bytes(), Some(dir.path())).unwrap();
+
+        let mut tokens = vec![];
+        let mut token_stream = TextAnalyzer::from(SimpleTokenizer)
+            .filter(filter)
+            .token_stream("my car");
+        token_stream.process(&mut |token: &Token| tokens.push(token.clone()));
+        assert_eq!(
+            summary(&tokens),
+            vec![
+                ("my".to_string(), 0, 1),
+                ("car".to_string(), 1, 1),
+                ("automobile".to_string(), 1, 1),
+            ]
+        );
+
+        let json_str = r#"
+            {
+                "synonyms_path": "../wn_s.pl"
+            }
+            "#;
+        assert!(create_synonym_filter(json_str.as_bytes(), Some(dir.path())).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+
+use tantivy::tokenizer::{BoxTokenFilter, BoxTokenStream, Token, TokenFilter, TokenStream};
+
+use crate::index::token_filter::{FilterFactoryError, FilterFactoryErrorKind};
+
+fn default_only_on_same_position() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize)]
+struct UniqueFilterArgs {
+    #[serde(default = "default_only_on_same_position")]
+    only_on_same_position: bool,
+}
+
+impl Default for UniqueFilterArgs {
+    fn default() -> Self {
+        UniqueFilterArgs {
+            only_on_same_position: default_only_on_same_position(),
+        }
+    }
+}
+
+pub fn create_unique_filter(args: &[u8]) -> Result<BoxTokenFilter, FilterFactoryError> {
+    let unique_args = if args.is_empty() {
+        UniqueFilterArgs::default()
+    } else {
+        serde_json::from_slice::<UniqueFilterArgs>(args)
+            .map_err(|e| FilterFactoryErrorKind::InvalidArgument.with_error(e))?
+    };
+
+    Ok(BoxTokenFilter::from(UniqueFilter {
+        only_on_same_position: unique_args.only_on_same_position,
+    }))
+}
+
+/// Removes the duplicate tokens at the same position, or in the whole stream.
+#[derive(Clone)]
+pub struct UniqueFilter {
+    only_on_same_position: bool,
+}
+
+impl TokenFilter for UniqueFilter {
+    fn transform<'a>(&self, token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(UniqueTokenStream {
+            only_on_same_position: self.only_on_same_position,
+            seen: HashSet::new(),
+            tail: token_stream,
+        })
+    }
+}
+
+pub struct UniqueTokenStream<'a> {
+    only_on_same_position: bool,
+    seen: HashSet<(usize, String)>,
+    tail: BoxTokenStream<'a>,
+}
+
+impl<'a> TokenStream for UniqueTokenStream<'a> {
+    fn advance(&mut self) -> bool {
+        while self.tail.advance() {
+            let token = self.tail.token();
+            let position = if self.only_on_same_position {
+                token.position
+            } else {
+                0
+            };
+            if self.seen.insert((position, token.text.clone())) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn token(&self) -> &Token {
+        self.tail.token()
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.tail.token_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::{SimpleTokenizer, TextAnalyzer, Token};
+
+    use crate::index::token_filter::{
+        synonym::create_synonym_filter, unique::create_unique_filter,
+    };
+
+    fn helper(json_str: &str, text: &str) -> Vec<(String, usize)> {
+        let synonym_json_str = r#"
+            {
+                "synonyms": ["tv => tv, television, tv"]
+            }
+            "#;
+        let synonym_filter = create_synonym_filter(synonym_json_str.as_bytes(), None).unwrap();
+        let filter = create_unique_filter(json_str.as_bytes()).unwrap();
+
+        let mut tokens = vec![];
+        let mut token_stream = TextAnalyzer::from(SimpleTokenizer)
+            .filter(synonym_filter)
+            .filter(filter)
+            .token_stream(text);
+        token_stream
+            .process(&mut |token: &Token| tokens.push((token.text.clone(), token.position)));
+        tokens
+    }
+
+    #[test]
+    fn test_unique_filter() {
+        assert_eq!(
+            vec![
+                ("tv".to_string(), 0),
+                ("television".to_string(), 0),
+                ("tv".to_string(), 1),
+                ("television".to_string(), 1),
+            ],
+            helper("", "tv tv")
+        );
+
+        let json_str = r#"
+            {
+                "only_on_same_position": false
+            }
+            "#;
+        assert_eq!(
+            vec![("tv".to_string(), 0), ("television".to_string(), 0)],
+            helper(json_str, "tv tv")
+        );
+    }
+}
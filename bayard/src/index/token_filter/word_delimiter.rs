@@ -0,0 +1,232 @@
+use tantivy::tokenizer::{BoxTokenFilter, BoxTokenStream, Token, TokenFilter};
+
+use crate::index::token_filter::{BufferedTokenStream, FilterFactoryError, FilterFactoryErrorKind};
+
+fn default_split() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize)]
+struct WordDelimiterFilterArgs {
+    #[serde(default = "default_split")]
+    split_on_case_change: bool,
+    #[serde(default = "default_split")]
+    split_on_numerics: bool,
+    #[serde(default)]
+    preserve_original: bool,
+}
+
+impl Default for WordDelimiterFilterArgs {
+    fn default() -> Self {
+        WordDelimiterFilterArgs {
+            split_on_case_change: default_split(),
+            split_on_numerics: default_split(),
+            preserve_original: false,
+        }
+    }
+}
+
+pub fn create_word_delimiter_filter(args: &[u8]) -> Result<BoxTokenFilter, FilterFactoryError> {
+    let word_delimiter_args = if args.is_empty() {
+        WordDelimiterFilterArgs::default()
+    } else {
+        serde_json::from_slice::<WordDelimiterFilterArgs>(args)
+            .map_err(|e| FilterFactoryErrorKind::InvalidArgument.with_error(e))?
+    };
+
+    Ok(BoxTokenFilter::from(WordDelimiterFilter {
+        split_on_case_change: word_delimiter_args.split_on_case_change,
+        split_on_numerics: word_delimiter_args.split_on_numerics,
+        preserve_original: word_delimiter_args.preserve_original,
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Lower,
+    Upper,
+    Digit,
+    Delimiter,
+}
+
+fn classify(c: char) -> CharClass {
+    if c.is_uppercase() {
+        CharClass::Upper
+    } else if c.is_numeric() {
+        CharClass::Digit
+    } else if c.is_alphanumeric() {
+        // Letters without case are treated as lower case letters.
+        CharClass::Lower
+    } else {
+        CharClass::Delimiter
+    }
+}
+
+/// Splits tokens into subwords on non-alphanumeric characters, case changes and
+/// letter-digit boundaries.
+#[derive(Clone)]
+pub struct WordDelimiterFilter {
+    split_on_case_change: bool,
+    split_on_numerics: bool,
+    preserve_original: bool,
+}
+
+impl WordDelimiterFilter {
+    fn is_boundary(&self, prev: CharClass, current: CharClass, next: Option<CharClass>) -> bool {
+        match (prev, current) {
+            (CharClass::Lower, CharClass::Upper) => self.split_on_case_change,
+            // The last upper case letter of an acronym starts the next word. e.g. "XMLParser"
+            (CharClass::Upper, CharClass::Upper) => {
+                self.split_on_case_change && next == Some(CharClass::Lower)
+            }
+            (CharClass::Digit, CharClass::Lower | CharClass::Upper)
+            | (CharClass::Lower | CharClass::Upper, CharClass::Digit) => self.split_on_numerics,
+            _ => false,
+        }
+    }
+
+    /// Returns the byte ranges of the subwords in the text.
+    fn split(&self, text: &str) -> Vec<(usize, usize)> {
+        let chars = text
+            .char_indices()
+            .map(|(offset, c)| (offset, classify(c)))
+            .collect::<Vec<_>>();
+
+        let mut parts = Vec::new();
+        let mut start = None;
+        for (i, &(offset, class)) in chars.iter().enumerate() {
+            if class == CharClass::Delimiter {
+                if let Some(start) = start.take() {
+                    parts.push((start, offset));
+                }
+                continue;
+            }
+            match start {
+                Some(part_start) => {
+                    let next = chars.get(i + 1).map(|&(_, class)| class);
+                    if self.is_boundary(chars[i - 1].1, class, next) {
+                        parts.push((part_start, offset));
+                        start = Some(offset);
+                    }
+                }
+                None => start = Some(offset),
+            }
+        }
+        if let Some(start) = start {
+            parts.push((start, text.len()));
+        }
+
+        parts
+    }
+}
+
+impl TokenFilter for WordDelimiterFilter {
+    fn transform<'a>(&self, mut token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        let mut tokens = Vec::new();
+        token_stream.process(&mut |token: &Token| tokens.push(token.clone()));
+
+        let mut output = Vec::with_capacity(tokens.len());
+        // The subwords occupy consecutive positions, so the following tokens are shifted.
+        let mut position_shift = 0;
+        for token in tokens {
+            let parts = self.split(&token.text);
+            if parts.is_empty() {
+                continue;
+            }
+
+            let position = token.position + position_shift;
+            // The offsets can only be mapped into the subwords if the text has not been
+            // modified by the preceding filters.
+            let maps_offsets = token.offset_to - token.offset_from == token.text.len();
+            if self.preserve_original && parts.len() > 1 {
+                output.push(Token {
+                    position,
+                    position_length: parts.len(),
+                    ..token.clone()
+                });
+            }
+            for (i, &(start, end)) in parts.iter().enumerate() {
+                let (offset_from, offset_to) = if maps_offsets {
+                    (token.offset_from + start, token.offset_from + end)
+                } else {
+                    (token.offset_from, token.offset_to)
+                };
+                output.push(Token {
+                    offset_from,
+                    offset_to,
+                    position: position + i,
+                    text: token.text[start..end].to_string(),
+                    position_length: 1,
+                });
+            }
+            position_shift += parts.len() - 1;
+        }
+
+        BoxTokenStream::from(BufferedTokenStream::new(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::{TextAnalyzer, Token, WhitespaceTokenizer};
+
+    use crate::index::token_filter::word_delimiter::create_word_delimiter_filter;
+
+    fn helper(json_str: &str, text: &str) -> Vec<Token> {
+        let filter = create_word_delimiter_filter(json_str.as_bytes()).unwrap();
+
+        let mut tokens = vec![];
+        let mut token_stream = TextAnalyzer::from(WhitespaceTokenizer)
+            .filter(filter)
+            .token_stream(text);
+        token_stream.process(&mut |token: &Token| tokens.push(token.clone()));
+        tokens
+    }
+
+    fn texts_and_positions(tokens: &[Token]) -> Vec<(&str, usize)> {
+        tokens
+            .iter()
+            .map(|token| (token.text.as_str(), token.position))
+            .collect()
+    }
+
+    #[test]
+    fn test_word_delimiter_filter() {
+        let tokens = helper("", "PowerShot SD500 wi-fi XMLParser");
+        assert_eq!(
+            vec![
+                ("Power", 0),
+                ("Shot", 1),
+                ("SD", 2),
+                ("500", 3),
+                ("wi", 4),
+                ("fi", 5),
+                ("XML", 6),
+                ("Parser", 7),
+            ],
+            texts_and_positions(&tokens)
+        );
+        assert_eq!((tokens[1].offset_from, tokens[1].offset_to), (5, 9));
+        assert_eq!((tokens[3].offset_from, tokens[3].offset_to), (12, 15));
+
+        let json_str = r#"
+            {
+                "split_on_case_change": false,
+                "split_on_numerics": false,
+                "preserve_original": true
+            }
+            "#;
+        let tokens = helper(json_str, "PowerShot SD500 wi-fi");
+        assert_eq!(
+            vec![
+                ("PowerShot", 0),
+                ("SD500", 1),
+                ("wi-fi", 2),
+                ("wi", 2),
+                ("fi", 3)
+            ],
+            texts_and_positions(&tokens)
+        );
+        assert_eq!(tokens[2].position_length, 2);
+    }
+}
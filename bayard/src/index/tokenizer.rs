@@ -1,7 +1,9 @@
 pub mod lindera;
 pub mod ngram;
+pub mod pattern;
 pub mod raw;
 pub mod simple;
+pub mod unicode;
 pub mod whitespace;
 
 use std::{fmt, str::FromStr};
@@ -52,8 +54,10 @@ impl TokenizerFactoryError {
 pub enum TokenizerKind {
     Lindera,
     Ngram,
+    Pattern,
     Raw,
     Simple,
+    Unicode,
     Whitespace,
 }
 
@@ -64,8 +68,10 @@ impl FromStr for TokenizerKind {
         match s {
             "lindera" => Ok(TokenizerKind::Lindera),
             "ngram" => Ok(TokenizerKind::Ngram),
+            "pattern" => Ok(TokenizerKind::Pattern),
             "raw" => Ok(TokenizerKind::Raw),
             "simple" => Ok(TokenizerKind::Simple),
+            "unicode" => Ok(TokenizerKind::Unicode),
             "whitespace" => Ok(TokenizerKind::Whitespace),
             _ => Err(TokenizerFactoryErrorKind::InvalidTokenizerKind
                 .with_error(anyhow::format_err!("Unknown tokenizer kind: {}", s))),
@@ -1,20 +1,43 @@
+use std::path::Path;
+
 use lindera_tantivy::tokenizer::{LinderaTokenizer, TokenizerConfig};
 use tantivy::tokenizer::TextAnalyzer;
 
-use crate::index::tokenizer::{TokenizerFactoryError, TokenizerFactoryErrorKind};
+use crate::index::{
+    resolve_index_file,
+    tokenizer::{TokenizerFactoryError, TokenizerFactoryErrorKind},
+};
 
 type LinderaTokenizerArgs = TokenizerConfig;
 
-pub fn create_lindera_analyzer(args: &[u8]) -> Result<TextAnalyzer, TokenizerFactoryError> {
+/// Create a Lindera analyzer.
+/// A relative user dictionary path is resolved against the index directory, and the
+/// user dictionary is loaded only if the index directory is given.
+pub fn create_lindera_analyzer(
+    args: &[u8],
+    base_dir: Option<&Path>,
+) -> Result<TextAnalyzer, TokenizerFactoryError> {
     if args.is_empty() {
         return Ok(TextAnalyzer::from(LinderaTokenizer::new().map_err(
             |e| TokenizerFactoryErrorKind::CreateError.with_error(e),
         )?));
     }
 
-    let lindera_args = serde_json::from_slice::<LinderaTokenizerArgs>(args)
+    let mut lindera_args = serde_json::from_slice::<LinderaTokenizerArgs>(args)
         .map_err(|e| TokenizerFactoryErrorKind::InvalidArgument.with_error(e))?;
 
+    if let Some(mut user_dictionary) = lindera_args.user_dictionary.take() {
+        if user_dictionary.path.is_relative() {
+            if let Some(base_dir) = base_dir {
+                user_dictionary.path = resolve_index_file(base_dir, &user_dictionary.path)
+                    .map_err(|e| TokenizerFactoryErrorKind::InvalidArgument.with_error(e))?;
+                lindera_args.user_dictionary = Some(user_dictionary);
+            }
+        } else {
+            lindera_args.user_dictionary = Some(user_dictionary);
+        }
+    }
+
     Ok(TextAnalyzer::from(
         LinderaTokenizer::with_config(lindera_args)
             .map_err(|e| TokenizerFactoryErrorKind::CreateError.with_error(e))?,
@@ -0,0 +1,176 @@
+use regex::Regex;
+use tantivy::tokenizer::{BoxTokenStream, TextAnalyzer, Token, Tokenizer};
+
+use crate::index::{
+    token_filter::BufferedTokenStream,
+    tokenizer::{TokenizerFactoryError, TokenizerFactoryErrorKind},
+};
+
+const DEFAULT_PATTERN: &str = r"\W+";
+
+fn default_pattern() -> String {
+    DEFAULT_PATTERN.to_string()
+}
+
+#[derive(Deserialize, Serialize)]
+struct PatternTokenizerArgs {
+    #[serde(default = "default_pattern")]
+    pattern: String,
+    #[serde(default)]
+    group: Option<usize>,
+}
+
+pub fn create_pattern_analyzer(args: &[u8]) -> Result<TextAnalyzer, TokenizerFactoryError> {
+    let pattern_args = if args.is_empty() {
+        PatternTokenizerArgs {
+            pattern: default_pattern(),
+            group: None,
+        }
+    } else {
+        serde_json::from_slice::<PatternTokenizerArgs>(args)
+            .map_err(|e| TokenizerFactoryErrorKind::InvalidArgument.with_error(e))?
+    };
+
+    let pattern = Regex::new(&pattern_args.pattern)
+        .map_err(|e| TokenizerFactoryErrorKind::InvalidArgument.with_error(e))?;
+    if let Some(group) = pattern_args.group {
+        if group >= pattern.captures_len() {
+            return Err(
+                TokenizerFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                    "Pattern {} has no capture group {}",
+                    pattern_args.pattern,
+                    group
+                )),
+            );
+        }
+    }
+
+    Ok(TextAnalyzer::from(PatternTokenizer {
+        pattern,
+        group: pattern_args.group,
+    }))
+}
+
+/// Tokenize the text by splitting it on the matches of the regular expression,
+/// or by extracting the capture group from each match.
+#[derive(Clone)]
+pub struct PatternTokenizer {
+    pattern: Regex,
+    group: Option<usize>,
+}
+
+impl PatternTokenizer {
+    fn spans(&self, text: &str) -> Vec<(usize, usize)> {
+        match self.group {
+            Some(group) => self
+                .pattern
+                .captures_iter(text)
+                .filter_map(|captures| captures.get(group))
+                .map(|matched| (matched.start(), matched.end()))
+                .collect(),
+            None => {
+                let mut spans = Vec::new();
+                let mut start = 0;
+                for matched in self.pattern.find_iter(text) {
+                    spans.push((start, matched.start()));
+                    start = matched.end();
+                }
+                spans.push((start, text.len()));
+                spans
+            }
+        }
+    }
+}
+
+impl Tokenizer for PatternTokenizer {
+    fn token_stream<'a>(&self, text: &'a str) -> BoxTokenStream<'a> {
+        let tokens = self
+            .spans(text)
+            .into_iter()
+            .filter(|(start, end)| start < end)
+            .enumerate()
+            .map(|(position, (start, end))| Token {
+                offset_from: start,
+                offset_to: end,
+                position,
+                text: text[start..end].to_string(),
+                position_length: 1,
+            })
+            .collect();
+
+        BoxTokenStream::from(BufferedTokenStream::new(tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::Token;
+
+    use crate::index::tokenizer::pattern::create_pattern_analyzer;
+
+    fn helper(json_str: &str, text: &str) -> Vec<Token> {
+        let tokenizer = create_pattern_analyzer(json_str.as_bytes()).unwrap();
+
+        let mut tokens = vec![];
+        tokenizer
+            .token_stream(text)
+            .process(&mut |token: &Token| tokens.push(token.clone()));
+        tokens
+    }
+
+    #[test]
+    fn test_pattern_tokenizer() {
+        let tokens = helper("", "Hello, world! 東京");
+        assert_eq!(
+            vec!["Hello", "world", "東京"],
+            tokens
+                .iter()
+                .map(|token| token.text.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(tokens[2].position, 2);
+        assert_eq!(tokens[2].offset_from, 14);
+        assert_eq!(tokens[2].offset_to, 20);
+
+        let json_str = r#"
+            {
+                "pattern": ","
+            }
+            "#;
+        let tokens = helper(json_str, "red,,blue green");
+        assert_eq!(
+            vec!["red", "blue green"],
+            tokens
+                .iter()
+                .map(|token| token.text.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_pattern_tokenizer_group() {
+        let json_str = r#"
+            {
+                "pattern": "\"((?:\\\\\"|[^\"])+)\"",
+                "group": 1
+            }
+            "#;
+        let tokens = helper(json_str, r#""value", "value with embedded \" quote""#);
+        assert_eq!(
+            vec!["value", r#"value with embedded \" quote"#],
+            tokens
+                .iter()
+                .map(|token| token.text.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(tokens[1].offset_from, 10);
+
+        let json_str = r#"
+            {
+                "pattern": "a(b)",
+                "group": 2
+            }
+            "#;
+        assert!(create_pattern_analyzer(json_str.as_bytes()).is_err());
+    }
+}
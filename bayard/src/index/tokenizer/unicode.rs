@@ -0,0 +1,71 @@
+use tantivy::tokenizer::{BoxTokenStream, TextAnalyzer, Token, Tokenizer};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::index::token_filter::BufferedTokenStream;
+
+pub fn create_unicode_analyzer() -> TextAnalyzer {
+    TextAnalyzer::from(UnicodeTokenizer {})
+}
+
+/// Tokenize the text on the word boundaries defined by Unicode Standard Annex #29.
+/// The segments that contain no letters or digits, such as spaces and punctuation, are dropped.
+#[derive(Clone)]
+pub struct UnicodeTokenizer;
+
+impl Tokenizer for UnicodeTokenizer {
+    fn token_stream<'a>(&self, text: &'a str) -> BoxTokenStream<'a> {
+        let tokens = text
+            .split_word_bound_indices()
+            .filter(|(_, word)| word.chars().any(char::is_alphanumeric))
+            .enumerate()
+            .map(|(position, (offset, word))| Token {
+                offset_from: offset,
+                offset_to: offset + word.len(),
+                position,
+                text: word.to_string(),
+                position_length: 1,
+            })
+            .collect();
+
+        BoxTokenStream::from(BufferedTokenStream::new(tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::tokenizer::Token;
+
+    use crate::index::tokenizer::unicode::create_unicode_analyzer;
+
+    fn helper(text: &str) -> Vec<String> {
+        let tokenizer = create_unicode_analyzer();
+
+        let mut tokens = vec![];
+        tokenizer
+            .token_stream(text)
+            .process(&mut |token: &Token| tokens.push(token.text.clone()));
+        tokens
+    }
+
+    #[test]
+    fn test_unicode_tokenizer() {
+        assert_eq!(
+            vec!["The", "quick", "brown", "fox", "can't", "jump", "32.3", "feet", "right"],
+            helper("The quick (\"brown\") fox can't jump 32.3 feet, right?")
+        );
+        assert_eq!(
+            vec!["Rust", "は", "安", "全", "Москва", "café"],
+            helper("Rustは安全 Москва, café!")
+        );
+
+        let tokenizer = create_unicode_analyzer();
+        let mut stream = tokenizer.token_stream("hello, мир");
+        stream.advance();
+        stream.advance();
+        let token = stream.token();
+        assert_eq!(token.text, "мир");
+        assert_eq!(token.offset_from, 7);
+        assert_eq!(token.offset_to, 13);
+        assert_eq!(token.position, 1);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod audit;
+pub mod auth;
 pub mod client;
 pub mod cluster;
 pub mod common;
@@ -5,11 +7,13 @@ pub mod handler;
 pub mod index;
 pub mod node;
 pub mod proto;
+pub mod raft;
 pub mod rendezvous;
 pub mod search;
 pub mod server;
 pub mod service;
 pub mod signal;
+pub mod tls;
 
 #[macro_use]
 extern crate serde;
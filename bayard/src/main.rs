@@ -1,18 +1,33 @@
 use std::{net::SocketAddr, path::PathBuf, sync::Arc};
 
 use bayard::{
+    audit::{
+        AuditLevel, AuditLog, AUDIT_DIR, DEFAULT_AUDIT_LOG_MAX_FILES,
+        DEFAULT_AUDIT_LOG_MAX_FILE_SIZE,
+    },
+    auth::Authenticator,
     client::Client,
-    cluster::{membership::Membership, metadata::Metadata},
+    cluster::{
+        envelope::Keyring,
+        membership::Membership,
+        metadata::{compute_capacity, CapacitySetting, Metadata},
+    },
     index::{metastore::Metastore, INDICES_DIR},
     node::Node,
+    raft::{Raft, RaftMember, DEFAULT_SNAPSHOT_THRESHOLD, RAFT_DIR},
+    search::cache::{DEFAULT_FILTER_CACHE_SIZE, DEFAULT_REQUEST_CACHE_SIZE},
     server::{handle_grpc_server, handle_http_server},
-    signal::handle_signals,
+    signal::{handle_signals, raise_stop_signal},
+    tls::TlsConfig,
 };
 use clap::Parser;
 use tokio::{
+    signal::unix::{signal, SignalKind},
     sync::{watch, RwLock},
     time::{sleep, Duration},
 };
+use tokio_stream::StreamExt;
+use tracing::{error, info};
 
 #[derive(Parser, Debug)]
 #[clap(version, about, long_about = None)]
@@ -56,6 +71,18 @@ struct Args {
     )]
     http_address: String,
 
+    /// The zone or rack of the node, such as an availability zone. The replicas of a shard are placed in different zones as far as possible.
+    #[clap(long = "zone", value_name = "ZONE")]
+    zone: Option<String>,
+
+    /// The capacity of the node, which is a positive integer, or auto to compute it from the number of CPUs and the disk size of the data directory. The shards are assigned to the nodes in proportion to their capacities. If it omitted, the capacity is 1.
+    #[clap(long = "capacity", value_name = "CAPACITY")]
+    capacity: Option<CapacitySetting>,
+
+    /// Start the node as leaving, which excludes it from the shard placement. The node hands its shards off to the other nodes and stops once all of them serve the shards.
+    #[clap(long = "decommission")]
+    decommission: bool,
+
     /// The data directory that should be store node list, indices and etc.
     #[clap(
         short = 'd',
@@ -64,6 +91,106 @@ struct Args {
         value_name = "DATA_DIRECTORY"
     )]
     data_directory: PathBuf,
+
+    /// The memory budget in bytes of the filter cache for each shard.
+    #[clap(
+        long = "filter-cache-size",
+        default_value_t = DEFAULT_FILTER_CACHE_SIZE,
+        value_name = "FILTER_CACHE_SIZE"
+    )]
+    filter_cache_size: usize,
+
+    /// The memory budget in bytes of the request cache for each shard.
+    #[clap(
+        long = "request-cache-size",
+        default_value_t = DEFAULT_REQUEST_CACHE_SIZE,
+        value_name = "REQUEST_CACHE_SIZE"
+    )]
+    request_cache_size: usize,
+
+    /// The keyring file that contains the shared keys to encrypt and authenticate the gossip datagrams. If it omitted, the datagrams are not encrypted.
+    #[clap(long = "gossip-keyring-file", value_name = "GOSSIP_KEYRING_FILE")]
+    gossip_keyring_file: Option<PathBuf>,
+
+    /// Compress the gossip datagrams.
+    #[clap(long = "gossip-compression")]
+    gossip_compression: bool,
+
+    /// The PEM encoded certificate chain of the node that is used to serve gRPC and HTTP over TLS. If it omitted, the servers are served in plaintext.
+    #[clap(
+        long = "tls-cert-file",
+        value_name = "TLS_CERT_FILE",
+        requires_all = ["tls_key_file", "tls_ca_file", "tls_server_name"]
+    )]
+    tls_cert_file: Option<PathBuf>,
+
+    /// The PEM encoded private key of the node certificate.
+    #[clap(
+        long = "tls-key-file",
+        value_name = "TLS_KEY_FILE",
+        requires = "tls_cert_file"
+    )]
+    tls_key_file: Option<PathBuf>,
+
+    /// The PEM encoded CA bundle that is used to verify the certificates of the other nodes.
+    #[clap(
+        long = "tls-ca-file",
+        value_name = "TLS_CA_FILE",
+        requires = "tls_cert_file"
+    )]
+    tls_ca_file: Option<PathBuf>,
+
+    /// The DNS name that the certificates of all nodes contain. The other nodes are verified against it.
+    #[clap(
+        long = "tls-server-name",
+        value_name = "TLS_SERVER_NAME",
+        requires = "tls_cert_file"
+    )]
+    tls_server_name: Option<String>,
+
+    /// Require the gRPC clients, including the other nodes, to present a certificate signed by the CA.
+    #[clap(long = "tls-client-auth", requires = "tls_cert_file")]
+    tls_client_auth: bool,
+
+    /// The auth file that contains the roles, the users and the API keys. It is reloaded on SIGHUP and requires --tls-client-auth. If it omitted, the requests are not authenticated.
+    #[clap(long = "auth-file", value_name = "AUTH_FILE")]
+    auth_file: Option<PathBuf>,
+
+    /// The operations that are recorded in the audit log under the data directory. Available values are admin, write and all. If it omitted, the audit log is disabled.
+    #[clap(long = "audit-level", value_name = "AUDIT_LEVEL")]
+    audit_level: Option<AuditLevel>,
+
+    /// The size in bytes at which the audit log file is rotated.
+    #[clap(
+        long = "audit-log-max-file-size",
+        default_value_t = DEFAULT_AUDIT_LOG_MAX_FILE_SIZE,
+        value_name = "AUDIT_LOG_MAX_FILE_SIZE"
+    )]
+    audit_log_max_file_size: u64,
+
+    /// The number of the rotated audit log files that are kept.
+    #[clap(
+        long = "audit-log-max-files",
+        default_value_t = DEFAULT_AUDIT_LOG_MAX_FILES,
+        value_name = "AUDIT_LOG_MAX_FILES"
+    )]
+    audit_log_max_files: usize,
+
+    /// The gRPC addresses of the nodes that elect the leader of the index metadata, separated by commas. If it is omitted, the node is the only voter, or a learner that follows the leader if the seed address is given.
+    #[clap(
+        long = "raft-voters",
+        value_name = "RAFT_VOTERS",
+        value_delimiter = ','
+    )]
+    raft_voters: Vec<String>,
+
+    /// The number of the applied Raft log entries after which a snapshot of the index metadata is taken.
+    #[clap(
+        long = "raft-snapshot-threshold",
+        default_value_t = DEFAULT_SNAPSHOT_THRESHOLD,
+        value_name = "RAFT_SNAPSHOT_THRESHOLD"
+    )]
+    raft_snapshot_threshold: u64,
 }
 
 #[tokio::main]
@@ -74,6 +201,14 @@ async fn main() -> anyhow::Result<()> {
     // Parse the command line arguments.
     let args = Args::parse();
 
+    // The Raft RPCs and the shard hand-offs between the nodes are not subject to the auth file,
+    // and they are served only to the client certificates that contain the server name.
+    if args.auth_file.is_some() && !args.tls_client_auth {
+        anyhow::bail!(
+            "--auth-file requires --tls-client-auth to authenticate the Raft RPCs and the shard hand-offs between the nodes."
+        );
+    }
+
     let bind_address = args.bind_address.parse::<SocketAddr>()?;
 
     let advertise_address = args
@@ -90,6 +225,18 @@ async fn main() -> anyhow::Result<()> {
     let grpc_address = args.grpc_address.parse::<SocketAddr>()?;
     let http_address = args.http_address.parse::<SocketAddr>()?;
 
+    let raft_voters = if args.raft_voters.is_empty() {
+        match seed_address {
+            Some(_) => Vec::new(),
+            None => vec![grpc_address.to_string()],
+        }
+    } else {
+        args.raft_voters
+            .iter()
+            .map(|voter| voter.parse::<SocketAddr>().map(|addr| addr.to_string()))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
     // Create the signal handler.
     let (tx_signal, rx_signal) = watch::channel(());
     let signal_handler = tokio::spawn(async move {
@@ -97,9 +244,76 @@ async fn main() -> anyhow::Result<()> {
     });
 
     // Create the node metadata.
+    let capacity = match args.capacity {
+        Some(CapacitySetting::Fixed(capacity)) => Some(capacity),
+        Some(CapacitySetting::Auto) => Some(compute_capacity(&args.data_directory)?),
+        None => None,
+    };
     let metadata = Metadata {
         grpc_address: Some(grpc_address),
         http_address: Some(http_address),
+        zone: args.zone,
+        capacity,
+        leaving: args.decommission,
+    };
+
+    // Load the gossip keyring.
+    let keyring = match args.gossip_keyring_file {
+        Some(path) => Some(Keyring::load(&path).await?),
+        None => None,
+    };
+
+    // Load the TLS certificates.
+    let tls_config = match (
+        args.tls_cert_file,
+        args.tls_key_file,
+        args.tls_ca_file,
+        args.tls_server_name,
+    ) {
+        (Some(cert_file), Some(key_file), Some(ca_file), Some(server_name)) => Some(
+            TlsConfig::load(
+                &cert_file,
+                &key_file,
+                &ca_file,
+                &server_name,
+                args.tls_client_auth,
+            )
+            .await?,
+        ),
+        _ => None,
+    };
+
+    // Load the auth file.
+    let authenticator = match &args.auth_file {
+        Some(path) => Some(Arc::new(Authenticator::load(path).await?)),
+        None => None,
+    };
+
+    // Reload the auth file on SIGHUP.
+    if let (Some(authenticator), Some(path)) = (authenticator.clone(), args.auth_file.clone()) {
+        let mut hangup = signal(SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                match authenticator.reload(&path).await {
+                    Ok(()) => info!(?path, "The auth file has been reloaded."),
+                    Err(error) => error!(?path, ?error, "Failed to reload the auth file."),
+                }
+            }
+        });
+    }
+
+    // Create the audit log.
+    let audit_log = match args.audit_level {
+        Some(level) => Some(Arc::new(
+            AuditLog::new(
+                args.data_directory.join(AUDIT_DIR),
+                level,
+                args.audit_log_max_file_size,
+                args.audit_log_max_files,
+            )
+            .await?,
+        )),
+        None => None,
     };
 
     // Create membership.
@@ -110,6 +324,8 @@ async fn main() -> anyhow::Result<()> {
             metadata,
             args.data_directory.clone(),
             seed_address,
+            keyring,
+            args.gossip_compression,
         )
         .await?,
     );
@@ -117,25 +333,105 @@ async fn main() -> anyhow::Result<()> {
     // Create index watcher.
     let metastore = Arc::new(Metastore::new(args.data_directory.join(INDICES_DIR)).await?);
 
+    // Create the Raft node that replicates the index metadata.
+    let members = membership.watch_members().map(|members| {
+        members
+            .iter()
+            .filter_map(|member| {
+                member.metadata.as_ref().and_then(|metadata| {
+                    metadata.grpc_address.map(|grpc_address| RaftMember {
+                        id: grpc_address.to_string(),
+                        leaving: metadata.leaving,
+                    })
+                })
+            })
+            .collect::<Vec<_>>()
+    });
+    let raft = Arc::new(
+        Raft::new(
+            &args.data_directory.join(RAFT_DIR),
+            grpc_address.to_string(),
+            raft_voters,
+            args.raft_snapshot_threshold,
+            Arc::clone(&metastore),
+            members,
+            tls_config.clone(),
+        )
+        .await?,
+    );
+
     // Create the client.
-    let client = Arc::new(Client::new(Arc::clone(&membership), Arc::clone(&metastore)).await);
+    let client = Arc::new(
+        Client::new(
+            Arc::clone(&membership),
+            Arc::clone(&metastore),
+            tls_config.clone(),
+        )
+        .await,
+    );
 
     // Create the node.
     let node = Arc::new(RwLock::new(
-        Node::new(membership, metastore, args.data_directory.join(INDICES_DIR)).await?,
+        Node::new(
+            membership,
+            metastore,
+            Arc::clone(&raft),
+            args.data_directory.join(INDICES_DIR),
+            args.filter_cache_size,
+            args.request_cache_size,
+            tls_config.clone(),
+        )
+        .await?,
     ));
 
+    // Stop the node once it has been decommissioned.
+    let mut decommissioned = node.read().await.watch_decommissioned();
+    tokio::spawn(async move {
+        loop {
+            if *decommissioned.borrow() {
+                info!("Stopping the decommissioned node.");
+                if let Err(error) = raise_stop_signal() {
+                    error!(?error, "Failed to stop the decommissioned node.");
+                }
+                break;
+            }
+            if decommissioned.changed().await.is_err() {
+                break;
+            }
+        }
+    });
+
     // Create the gRPC server handler.
     let rx_grpc_signal = rx_signal.clone();
+    let grpc_tls_config = tls_config.clone();
+    let grpc_authenticator = authenticator.clone();
+    let grpc_audit_log = audit_log.clone();
     let grpc_server_handler = tokio::spawn(async move {
-        handle_grpc_server(grpc_address, node, client, rx_grpc_signal).await;
+        handle_grpc_server(
+            grpc_address,
+            node,
+            client,
+            raft,
+            grpc_tls_config,
+            grpc_authenticator,
+            grpc_audit_log,
+            rx_grpc_signal,
+        )
+        .await;
     });
 
     sleep(Duration::from_secs(1)).await;
 
     // Create the HTTP server handler.
     let http_server_handler = tokio::spawn(async move {
-        handle_http_server(http_address, grpc_address, rx_signal).await;
+        handle_http_server(
+            http_address,
+            grpc_address,
+            tls_config,
+            authenticator,
+            rx_signal,
+        )
+        .await;
     });
 
     // Wait for handlers to finish.
@@ -143,5 +439,10 @@ async fn main() -> anyhow::Result<()> {
     http_server_handler.await?;
     signal_handler.await?;
 
+    // The records of the last requests are written before exiting.
+    if let Some(audit_log) = audit_log {
+        audit_log.flush().await;
+    }
+
     Ok(())
 }
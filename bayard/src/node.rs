@@ -1,53 +1,96 @@
 pub mod index;
 pub mod search;
-
-use std::{fmt, path::PathBuf, sync::Arc};
+pub mod transfer;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::Duration,
+};
 
 use dashmap::DashMap;
-use tantivy::{schema::FieldType, DateTime, Index, IndexReader, IndexWriter, Term};
+use tantivy::{
+    query::{BooleanQuery, Occur, Query},
+    schema::{FieldType, Schema},
+    tokenizer::TokenizerManager,
+    DateTime, Index, IndexReader, IndexWriter, Term, Warmer,
+};
 use time::OffsetDateTime;
-use tokio::fs;
+use tokio::{
+    fs,
+    sync::{broadcast::error::RecvError, watch},
+    time::interval,
+};
 use tokio_stream::{wrappers::WatchStream, StreamExt};
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    cluster::{
-        member::Member,
-        message::{Message, MessageKind},
-    },
-    cluster::{
-        members::Members,
-        membership::Membership,
-        message::{MESSAGE_METADATA_FIELD, MESSAGE_NAME_FIELD},
-    },
-    common::{read_file, remove_file},
+    cluster::{member::Member, members::Members, membership::Membership},
+    common::read_file,
     index::{
-        metadata::{save_index_metadata, Metadata},
-        metastore::Metastore,
+        analyzer::{
+            analyze_text, build_analyzer_stages, check_analyzers_compatibility, register,
+            register_search_time_analyzers, Analyzers,
+        },
+        geo_point::{expand_geo_point_values, geo_point_field_names},
+        metadata::Metadata,
+        metastore::{Command, Metastore},
+        processor::Processors,
+        search_tokenizer::register_search_analyzers,
         shard::Shard,
         shards::Shards,
         DOC_ID_FIELD_NAME, DOC_TIMESTAMP_FIELD_NAME, INDEX_METADATA_FILE, SHARDS_DIR,
     },
     node::index::delete_index,
+    proto::cluster::{
+        DecommissionNodeRequest, DecommissionNodeResponse, GetPlacementViolationsRequest,
+        GetPlacementViolationsResponse, PlacementViolation, Replica,
+    },
     proto::index::{
-        sort::Order, CollectionKind, CommitRequest, CommitResponse, CreateIndexRequest,
-        CreateIndexResponse, DeleteDocumentsRequest, DeleteDocumentsResponse, DeleteIndexRequest,
-        DeleteIndexResponse, GetIndexRequest, GetIndexResponse, ModifyIndexRequest,
-        ModifyIndexResponse, PutDocumentsRequest, PutDocumentsResponse, RollbackRequest,
-        RollbackResponse, SearchRequest, SearchResponse,
+        query::Kind as ProtoQueryKind, sort::Order, AnalyzeRequest, AnalyzeResponse, AnalyzeStage,
+        AnalyzedToken, CacheStats as ProtoCacheStats, CollectionKind, CommitRequest,
+        CommitResponse, CreateIndexRequest, CreateIndexResponse, DeleteDocumentsRequest,
+        DeleteDocumentsResponse, DeleteIndexRequest, DeleteIndexResponse,
+        DeleteUserDictionaryRequest, DeleteUserDictionaryResponse, GetCacheStatsRequest,
+        GetCacheStatsResponse, GetIndexRequest, GetIndexResponse, ModifyIndexRequest,
+        ModifyIndexResponse, PutDocumentsRequest, PutDocumentsResponse, PutUserDictionaryRequest,
+        PutUserDictionaryResponse, ReloadAnalyzersRequest, ReloadAnalyzersResponse,
+        RollbackRequest, RollbackResponse, SearchRequest, SearchResponse, ShardCacheStats,
     },
-    search::query::create_query,
+    proto::shard::{DownloadShardRequest, GetShardStatusRequest, GetShardStatusResponse},
+    raft::Raft,
+    search::{
+        cache::{canonical_query_key, CachedFilterQuery, ShardCache},
+        query::{
+            boolean::create_filter_query,
+            create_query,
+            function_score::{create_function_score, FunctionScoreQueryArgs},
+        },
+    },
+    tls::TlsConfig,
 };
 
 use self::{
     index::{create_index, index_exists, open_index},
     search::{
         search_order_by_date_asc, search_order_by_date_desc, search_order_by_f64_asc,
-        search_order_by_f64_desc, search_order_by_i64_asc, search_order_by_i64_desc,
-        search_order_by_score_desc, search_order_by_u64_asc, search_order_by_u64_desc,
+        search_order_by_f64_desc, search_order_by_function_score_desc,
+        search_order_by_geo_distance_asc, search_order_by_geo_distance_desc,
+        search_order_by_i64_asc, search_order_by_i64_desc, search_order_by_score_desc,
+        search_order_by_u64_asc, search_order_by_u64_desc,
     },
+    transfer::{download_shard, get_shard_statuses, is_handed_off, HandoffAttempts, ShardSnapshot},
 };
 
+// The interval to retry the shard hand-offs that are waiting for the other nodes.
+const HANDOFF_INTERVAL: Duration = Duration::from_secs(5);
+
+// User dictionaries are kept in the Raft log and the snapshots of the metastore.
+const MAX_USER_DICTIONARY_SIZE: usize = 1024 * 1024;
+
 #[derive(Debug, Clone, Copy)]
 pub enum NodeErrorKind {
     MetadataError,
@@ -82,6 +125,15 @@ pub enum NodeErrorKind {
     IndexDirectoryCreationFailure,
     AnalyzerRegistrationFailure,
     IndexConfigDoesNotExist,
+    AnalyzerNotFound,
+    AnalyzeFailure,
+    InvalidUserDictionary,
+    InvalidProcessor,
+    DocumentProcessFailure,
+    ShardSnapshotFailure,
+    ShardDownloadFailure,
+    ShardStatusFailure,
+    DecommissionFailure,
 }
 
 impl NodeErrorKind {
@@ -123,17 +175,28 @@ impl NodeError {
 pub struct Node {
     membership: Arc<Membership>,
     metastore: Arc<Metastore>,
+    raft: Arc<Raft>,
     indices_dir: PathBuf,
     indices: Arc<DashMap<String, DashMap<String, Index>>>, // index_name -> shard_id -> index
     index_writers: Arc<DashMap<String, DashMap<String, IndexWriter>>>, // index_name -> shard_id -> index_writer
     index_readers: Arc<DashMap<String, DashMap<String, IndexReader>>>, // index_name -> shard_id -> index_reader
+    index_caches: Arc<DashMap<String, DashMap<String, Arc<ShardCache>>>>, // index_name -> shard_id -> shard_cache
+    downloaders: Arc<DashMap<(String, String), HashSet<String>>>, // (index_name, shard_id) -> gRPC addresses of the nodes that downloaded the shard
+    filter_cache_size: usize,
+    request_cache_size: usize,
+    tls_config: Option<TlsConfig>,
+    decommissioned_receiver: watch::Receiver<bool>,
 }
 
 impl Node {
     pub async fn new(
         membership: Arc<Membership>,
         metastore: Arc<Metastore>,
+        raft: Arc<Raft>,
         indices_dir: PathBuf,
+        filter_cache_size: usize,
+        request_cache_size: usize,
+        tls_config: Option<TlsConfig>,
     ) -> Result<Self, NodeError> {
         if !indices_dir.exists() {
             info!(?indices_dir, "Creating indices directory.");
@@ -145,36 +208,88 @@ impl Node {
         let indices: DashMap<String, DashMap<String, Index>> = DashMap::new();
         let index_writers: DashMap<String, DashMap<String, IndexWriter>> = DashMap::new();
         let index_readers: DashMap<String, DashMap<String, IndexReader>> = DashMap::new();
+        let index_caches: DashMap<String, DashMap<String, Arc<ShardCache>>> = DashMap::new();
+        let (decommissioned_sender, decommissioned_receiver) = watch::channel(false);
 
         let node = Self {
             membership,
             metastore,
+            raft,
             indices_dir,
             indices: Arc::new(indices),
             index_writers: Arc::new(index_writers),
             index_readers: Arc::new(index_readers),
+            index_caches: Arc::new(index_caches),
+            downloaders: Arc::new(DashMap::new()),
+            filter_cache_size,
+            request_cache_size,
+            tls_config,
+            decommissioned_receiver,
         };
 
-        node.handle_metadatas().await;
-        node.handle_messages().await;
+        node.handle_metadatas(decommissioned_sender).await;
+        node.handle_reloads().await;
 
         Ok(node)
     }
 
-    async fn handle_metadatas(&self) {
+    async fn handle_metadatas(&self, decommissioned_sender: watch::Sender<bool>) {
         let mut stream = self.metastore.watch();
+        let mut members_stream = self.membership.watch_members();
+        let raft = Arc::clone(&self.raft);
+        let tls_config = self.tls_config.clone();
         let membership = Arc::clone(&self.membership);
         let metastore = Arc::clone(&self.metastore);
         let indices = Arc::clone(&self.indices);
         let index_writers = Arc::clone(&self.index_writers);
         let index_readers = Arc::clone(&self.index_readers);
+        let index_caches = Arc::clone(&self.index_caches);
+        let downloaders = Arc::clone(&self.downloaders);
+        let filter_cache_size = self.filter_cache_size;
+        let request_cache_size = self.request_cache_size;
         let indices_dir = Arc::new(self.indices_dir.clone());
 
         tokio::spawn(async move {
-            let local_addr = membership.local_member().await.addr;
+            let local_member = membership.local_member().await;
+            let local_addr = local_member.addr;
+            let node_id = local_member
+                .metadata
+                .and_then(|metadata| metadata.grpc_address)
+                .map(|grpc_address| grpc_address.to_string())
+                .unwrap_or_default();
+
+            // The shards are re-assigned when the members change as well, and the hand-offs that
+            // are waiting for the other nodes are retried periodically.
+            let mut handoff_interval = interval(HANDOFF_INTERVAL);
+            let mut has_pending_handoffs = false;
+            let mut handed_off_shards: HashSet<(String, String)> = HashSet::new();
+            let mut handoff_attempts = HandoffAttempts::default();
+
+            loop {
+                let metadatas = tokio::select! {
+                    metadatas = stream.next() => match metadatas {
+                        Some(metadatas) => {
+                            info!("Received metadatas.");
+                            metadatas
+                        }
+                        None => break,
+                    },
+                    Some(_) = members_stream.next() => {
+                        info!("Received members.");
+                        metastore.metadatas().await
+                    }
+                    _ = handoff_interval.tick(), if has_pending_handoffs => {
+                        debug!("Retrying pending shard hand-offs.");
+                        metastore.metadatas().await
+                    }
+                };
+                has_pending_handoffs = false;
 
-            while let Some(metadatas) = stream.next().await {
-                info!("Received metadatas.");
+                let is_leaving = membership
+                    .local_member()
+                    .await
+                    .metadata
+                    .is_some_and(|metadata| metadata.leaving);
 
                 for (index_name, metadata) in metadatas.iter() {
                     let shards = match metadata.shards() {
@@ -194,14 +309,31 @@ impl Node {
                     };
 
                     for shard in shards.iter() {
-                        let is_assigned_shard = membership
-                            .members()
-                            .await
-                            .lookup_members(&shard.id, num_replicas)
-                            .map(|members| members.addr)
-                            .any(|addr| addr == local_addr);
+                        let (is_assigned_shard, owner_addresses, other_addresses) = {
+                            let members = membership.members().await;
+                            let owners = members
+                                .lookup_members(&shard.id, num_replicas)
+                                .map(|member| member.addr)
+                                .collect::<HashSet<_>>();
+                            let grpc_addresses = |is_owner: bool| {
+                                members
+                                    .iter()
+                                    .filter(|member| member.addr != local_addr)
+                                    .filter(|member| owners.contains(&member.addr) == is_owner)
+                                    .filter_map(|member| member.metadata.as_ref())
+                                    .filter_map(|metadata| metadata.grpc_address)
+                                    .collect::<Vec<SocketAddr>>()
+                            };
+                            (
+                                owners.contains(&local_addr),
+                                grpc_addresses(true),
+                                grpc_addresses(false),
+                            )
+                        };
+                        let shard_key = (index_name.clone(), shard.id.clone());
                         if is_assigned_shard {
                             info!(?index_name, shard_id = ?shard.id, "Shard is assigned to this node.");
+                            handed_off_shards.remove(&shard_key);
 
                             // Check index object existence.
                             let index = if indices
@@ -237,6 +369,71 @@ impl Node {
                                     }
                                 };
 
+                                // Copy the shard from a node that serves it, e.g. the node that
+                                // hands it off, before creating an empty one. The owners are
+                                // asked first because they keep their copies up to date.
+                                let mut is_shard_index_exist = is_shard_index_exist;
+                                if !is_shard_index_exist {
+                                    let grpc_addresses = owner_addresses
+                                        .iter()
+                                        .chain(other_addresses.iter())
+                                        .copied()
+                                        .collect::<Vec<_>>();
+                                    let statuses = get_shard_statuses(
+                                        &grpc_addresses,
+                                        tls_config.as_ref(),
+                                        index_name,
+                                        &shard.id,
+                                    )
+                                    .await;
+                                    let mut has_failures = false;
+                                    for (grpc_address, status) in
+                                        grpc_addresses.iter().zip(statuses)
+                                    {
+                                        match status {
+                                            Ok(Some(_)) => (),
+                                            Ok(None) => continue,
+                                            Err(error) => {
+                                                warn!(?grpc_address, ?index_name, shard_id = ?shard.id, ?error, "Failed to get shard status.");
+                                                has_failures = true;
+                                                continue;
+                                            }
+                                        }
+                                        match download_shard(
+                                            grpc_address,
+                                            tls_config.as_ref(),
+                                            &node_id,
+                                            index_name,
+                                            &shard.id,
+                                            &shard_dir,
+                                        )
+                                        .await
+                                        {
+                                            Ok(true) => {
+                                                is_shard_index_exist = true;
+                                                break;
+                                            }
+                                            Ok(false) => (),
+                                            Err(error) => {
+                                                warn!(?grpc_address, ?index_name, shard_id = ?shard.id, ?error, "Failed to download shard.");
+                                                has_failures = true;
+                                            }
+                                        }
+                                    }
+
+                                    // Creating an empty shard could discard the documents of a
+                                    // node that is not reachable for now, so it is retried a few
+                                    // times before the shard is created without that node.
+                                    if !is_shard_index_exist
+                                        && has_failures
+                                        && handoff_attempts.retry(&shard_key)
+                                    {
+                                        has_pending_handoffs = true;
+                                        continue;
+                                    }
+                                    handoff_attempts.remove(&shard_key);
+                                }
+
                                 // Create index object.
                                 let index = if is_shard_index_exist {
                                     // Get analyzers.
@@ -247,6 +444,15 @@ impl Node {
                                             continue;
                                         }
                                     };
+                                    let analyzers = match analyzers
+                                        .with_base_dir(&indices_dir.join(index_name))
+                                    {
+                                        Ok(analyzers) => analyzers,
+                                        Err(error) => {
+                                            error!(?index_name, ?shard.id, ?error, "Failed to load analyzer files.");
+                                            continue;
+                                        }
+                                    };
 
                                     // Open index.
                                     info!(?shard_dir, "Opening shard index.");
@@ -288,6 +494,15 @@ impl Node {
                                             continue;
                                         }
                                     };
+                                    let analyzers = match analyzers
+                                        .with_base_dir(&indices_dir.join(index_name))
+                                    {
+                                        Ok(analyzers) => analyzers,
+                                        Err(error) => {
+                                            error!(?index_name, ?shard.id, ?error, "Failed to load analyzer files.");
+                                            continue;
+                                        }
+                                    };
 
                                     // Create index.
                                     info!(?shard_dir, "Creating shard index.");
@@ -311,6 +526,22 @@ impl Node {
                                     }
                                 };
 
+                                // Register the search-time analyzers of the fields.
+                                let search_tokenizers = match metadata.search_tokenizers() {
+                                    Ok(search_tokenizers) => search_tokenizers,
+                                    Err(error) => {
+                                        error!(?index_name, ?shard.id, ?error, "Failed to get search tokenizers.");
+                                        continue;
+                                    }
+                                };
+                                if let Err(error) = register_search_analyzers(
+                                    index.tokenizers(),
+                                    &search_tokenizers,
+                                ) {
+                                    error!(?index_name, ?shard.id, ?error, "Failed to register search analyzers.");
+                                    continue;
+                                }
+
                                 // Add index object to indices.
                                 info!(?index_name, shard_id = ?shard.id, "Insert assigned index.");
                                 indices
@@ -394,25 +625,32 @@ impl Node {
                             {
                                 debug!(?index_name, shard_id = ?shard.id, "Index reader already exists.");
                             } else {
-                                let index_reader = match index.reader() {
-                                    Ok(index_reader) => {
-                                        info!(
-                                            ?index_name,
-                                            shard_id = ?shard.id,
-                                            "Opened index reader."
-                                        );
-                                        index_reader
-                                    }
-                                    Err(error) => {
-                                        error!(
-                                            ?index_name,
-                                            shard_id = ?shard.id,
-                                            ?error,
-                                            "Failed to open index reader."
-                                        );
-                                        continue;
-                                    }
-                                };
+                                // The shard cache is registered as a warmer so that it is invalidated on reload.
+                                let shard_cache = Arc::new(ShardCache::new(
+                                    filter_cache_size,
+                                    request_cache_size,
+                                ));
+                                let warmer: Weak<dyn Warmer> = Arc::downgrade(&shard_cache) as _;
+                                let index_reader =
+                                    match index.reader_builder().warmers(vec![warmer]).try_into() {
+                                        Ok(index_reader) => {
+                                            info!(
+                                                ?index_name,
+                                                shard_id = ?shard.id,
+                                                "Opened index reader."
+                                            );
+                                            index_reader
+                                        }
+                                        Err(error) => {
+                                            error!(
+                                                ?index_name,
+                                                shard_id = ?shard.id,
+                                                ?error,
+                                                "Failed to open index reader."
+                                            );
+                                            continue;
+                                        }
+                                    };
 
                                 // Add index reader to index readers.
                                 info!(?index_name, shard_id = ?shard.id, "Insert assigned index reader.");
@@ -420,10 +658,121 @@ impl Node {
                                     .entry(index_name.clone())
                                     .or_insert_with(DashMap::new)
                                     .insert(shard.id.clone(), index_reader);
+                                index_caches
+                                    .entry(index_name.clone())
+                                    .or_default()
+                                    .insert(shard.id.clone(), shard_cache);
                             }
                         } else {
                             info!(?index_name, shard_id = ?shard.id, "Shard is not assigned to this node.");
 
+                            let mut is_open_shard = indices
+                                .get(index_name)
+                                .is_some_and(|shard_indices| shard_indices.contains_key(&shard.id));
+
+                            // A leaving node opens the shards left on its disk, so that the owners
+                            // can copy them even if the node has been restarted.
+                            if !is_open_shard
+                                && is_leaving
+                                && !handed_off_shards.contains(&shard_key)
+                            {
+                                let shard_dir = indices_dir
+                                    .join(index_name)
+                                    .join(SHARDS_DIR)
+                                    .join(&shard.id);
+                                if index_exists(&shard_dir).await.unwrap_or(false) {
+                                    let analyzers = match metadata.analyzers() {
+                                        Ok(analyzers) => analyzers,
+                                        Err(error) => {
+                                            error!(?index_name, ?shard.id, ?error, "Failed to get analyzers.");
+                                            has_pending_handoffs = true;
+                                            continue;
+                                        }
+                                    };
+                                    let analyzers = match analyzers
+                                        .with_base_dir(&indices_dir.join(index_name))
+                                    {
+                                        Ok(analyzers) => analyzers,
+                                        Err(error) => {
+                                            error!(?index_name, ?shard.id, ?error, "Failed to load analyzer files.");
+                                            has_pending_handoffs = true;
+                                            continue;
+                                        }
+                                    };
+                                    match open_index(&shard_dir, &analyzers).await {
+                                        Ok(index) => {
+                                            info!(?shard_dir, "Opened shard index to hand off.");
+                                            indices
+                                                .entry(index_name.clone())
+                                                .or_default()
+                                                .insert(shard.id.clone(), index);
+                                            is_open_shard = true;
+                                        }
+                                        Err(error) => {
+                                            error!(
+                                                ?shard_dir,
+                                                ?error,
+                                                "Failed to open shard index to hand off."
+                                            );
+                                            has_pending_handoffs = true;
+                                            continue;
+                                        }
+                                    }
+                                }
+                            }
+
+                            // Keep serving the shard until all of its owners serve it, because
+                            // the owners that do not have it copy it from this node.
+                            if is_open_shard {
+                                let committed_opstamp = match indices.get(index_name).and_then(
+                                    |shard_indices| {
+                                        shard_indices.get(&shard.id).map(|index| index.load_metas())
+                                    },
+                                ) {
+                                    Some(Ok(metas)) => metas.opstamp,
+                                    Some(Err(error)) => {
+                                        error!(?index_name, shard_id = ?shard.id, ?error, "Failed to load shard metas.");
+                                        has_pending_handoffs = true;
+                                        continue;
+                                    }
+                                    None => 0,
+                                };
+                                let statuses = get_shard_statuses(
+                                    &owner_addresses,
+                                    tls_config.as_ref(),
+                                    index_name,
+                                    &shard.id,
+                                )
+                                .await;
+                                let owner_opstamps = owner_addresses
+                                    .iter()
+                                    .zip(statuses)
+                                    .map(|(grpc_address, status)| {
+                                        let opstamp = status.unwrap_or_else(|error| {
+                                            warn!(?grpc_address, ?index_name, shard_id = ?shard.id, ?error, "Failed to get shard status.");
+                                            None
+                                        });
+                                        (*grpc_address, opstamp)
+                                    })
+                                    .collect::<Vec<_>>();
+                                let is_handed_off = is_handed_off(
+                                    &owner_opstamps,
+                                    &downloaders
+                                        .get(&shard_key)
+                                        .map(|downloaders| downloaders.clone())
+                                        .unwrap_or_default(),
+                                    committed_opstamp,
+                                );
+                                if !is_handed_off {
+                                    info!(?index_name, shard_id = ?shard.id, "Waiting for the owners to serve the shard.");
+                                    has_pending_handoffs = true;
+                                    continue;
+                                }
+                                info!(?index_name, shard_id = ?shard.id, "Handed off shard to the owners.");
+                                downloaders.remove(&shard_key);
+                                handed_off_shards.insert(shard_key);
+                            }
+
                             // Remove unassigned index writer.
                             info!(?index_name, shard_id = ?shard.id, "Remove unassigned index writer.");
                             index_writers
@@ -437,6 +786,10 @@ impl Node {
                                 .entry(index_name.clone())
                                 .or_insert_with(DashMap::new)
                                 .remove(&shard.id);
+                            index_caches
+                                .entry(index_name.clone())
+                                .or_default()
+                                .remove(&shard.id);
 
                             // Remove unassigned index.
                             info!(?index_name, shard_id = ?shard.id, "Remove unassigned index.");
@@ -494,6 +847,9 @@ impl Node {
                                         "Remove index reader for shard that no longer exist."
                                     );
                                     shard_readers.remove(shard_id);
+                                    if let Some(shard_caches) = index_caches.get_mut(index_name) {
+                                        shard_caches.remove(shard_id);
+                                    }
                                 }
                                 None => {
                                     error!(
@@ -603,6 +959,29 @@ impl Node {
                     }
                 }
 
+                // A leaving node can be stopped once it has handed off all of its shards, and the
+                // leader has removed it from the Raft voters.
+                if is_leaving {
+                    let num_open_shards = indices
+                        .iter()
+                        .map(|shard_indices| shard_indices.len())
+                        .sum::<usize>();
+                    let status = raft.status();
+                    if is_decommissioned(
+                        num_open_shards,
+                        has_pending_handoffs,
+                        status.leader.is_some(),
+                        status.is_voter,
+                    ) {
+                        if !*decommissioned_sender.borrow() {
+                            info!("Handed off all shards. The node is decommissioned.");
+                            decommissioned_sender.send_replace(true);
+                        }
+                    } else {
+                        has_pending_handoffs = true;
+                    }
+                }
+
                 // Remove index directories that do not exist in the metadatas.
                 let mut indices_read_dir = match fs::read_dir(indices_dir.as_ref()).await {
                     Ok(dir) => dir,
@@ -671,6 +1050,10 @@ impl Node {
                                 .entry(index_name.clone())
                                 .or_insert_with(DashMap::new)
                                 .remove(&shard_id);
+                            index_caches
+                                .entry(index_name.clone())
+                                .or_default()
+                                .remove(&shard_id);
 
                             // Remove indices that do not exist in the metadatas.
                             info!(
@@ -698,207 +1081,56 @@ impl Node {
         });
     }
 
-    async fn handle_messages(&self) {
-        let mut receiver = self.membership.watch_message();
+    async fn handle_reloads(&self) {
+        let mut receiver = self.metastore.subscribe_reloads();
+        let metastore = Arc::clone(&self.metastore);
+        let indices = Arc::clone(&self.indices);
+        let index_caches = Arc::clone(&self.index_caches);
         let indices_dir = self.indices_dir.clone();
 
         tokio::spawn(async move {
-            while let Some(message) = receiver.next().await {
-                let kind = match message.kind() {
-                    Ok(kind) => kind,
-                    Err(error) => {
-                        error!(?error, "Failed to get message kind.");
+            loop {
+                let index_name = match receiver.recv().await {
+                    Ok(index_name) => index_name,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Missed analyzer reloads.");
                         continue;
                     }
+                    Err(RecvError::Closed) => break,
                 };
+                info!(?index_name, "Reloading analyzers.");
 
-                info!(?kind, "Receive message.");
-                match kind {
-                    MessageKind::CreateIndex => {
-                        // Settings are extracted from the received messages and written to a file.
-                        // No actual creating an index is performed.
-                        let json_value =
-                            match serde_json::from_slice::<serde_json::Value>(message.body()) {
-                                Ok(value) => value,
-                                Err(error) => {
-                                    error!(?error, "Failed to deserialize message body.");
-                                    continue;
-                                }
-                            };
-
-                        let index_name = match json_value.get("name").and_then(|name| name.as_str())
-                        {
-                            Some(name) => name.to_string(),
-                            None => {
-                                error!("Request does not contain index name.");
-                                continue;
-                            }
-                        };
-
-                        // Create the index directory
-                        let index_dir = indices_dir.join(&index_name);
-                        if index_dir.exists() {
-                            warn!(?index_dir, "Index directory already exists.");
-                        } else {
-                            match fs::create_dir_all(&index_dir).await {
-                                Ok(_) => info!(?index_dir, "Index directory has been created."),
-                                Err(error) => {
-                                    error!(?index_dir, ?error, "Failed to create index directory.");
-                                    continue;
-                                }
-                            }
-                        }
-
-                        let index_metadata_map = match json_value
-                            .get("metadata")
-                            .and_then(|metadata| metadata.as_object())
-                        {
-                            Some(map) => map,
-                            None => {
-                                error!("Request does not contain index metadata.");
-                                continue;
-                            }
-                        };
-                        let index_metadata_bytes = match serde_json::to_vec(&index_metadata_map) {
-                            Ok(bytes) => bytes,
-                            Err(error) => {
-                                error!(?error, "Failed to serialize index metadata.");
-                                continue;
-                            }
-                        };
-                        let index_metadata =
-                            match serde_json::from_slice::<Metadata>(&index_metadata_bytes) {
-                                Ok(metadata) => metadata,
-                                Err(error) => {
-                                    error!(?error, "Failed to deserialize index metadata.");
-                                    continue;
-                                }
-                            };
-
-                        // Save meta.json
-                        let meta_path = index_dir.join(INDEX_METADATA_FILE);
-                        if meta_path.exists() {
-                            warn!(?meta_path, "File already exists.");
-                        } else {
-                            match save_index_metadata(&meta_path, index_metadata).await {
-                                Ok(_) => info!(?meta_path, "File have been saved."),
-                                Err(error) => {
-                                    error!(?meta_path, ?error, "Failed to write file.");
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                    MessageKind::DeleteIndex => {
-                        let json_value =
-                            match serde_json::from_slice::<serde_json::Value>(message.body()) {
-                                Ok(value) => value,
-                                Err(error) => {
-                                    error!(?error, "Failed to deserialize message body.");
-                                    continue;
-                                }
-                            };
-
-                        let index_name = match json_value.get("name").and_then(|name| name.as_str())
-                        {
-                            Some(name) => name.to_string(),
-                            None => {
-                                error!("Request does not contain index name.");
-                                continue;
-                            }
-                        };
-
-                        // Create the index directory
-                        let index_dir = indices_dir.join(&index_name);
-
-                        // Delete index metadata file.
-                        let index_metadata_path = index_dir.join(INDEX_METADATA_FILE);
-                        if index_metadata_path.exists() {
-                            match remove_file(&index_metadata_path).await {
-                                Ok(_) => {
-                                    info!(
-                                        ?index_metadata_path,
-                                        "Index config file have been deleted."
-                                    )
-                                }
-                                Err(error) => {
-                                    error!(
-                                        ?index_metadata_path,
-                                        ?error,
-                                        "Failed to delete index config file."
-                                    );
-                                }
-                            }
-                        }
+                let index_metadata = match metastore.metadatas().await.get(&index_name).cloned() {
+                    Some(metadata) => metadata,
+                    None => {
+                        warn!(?index_name, "Index metadata does not exist.");
+                        continue;
                     }
-                    MessageKind::ModifyIndex => {
-                        let json_value =
-                            match serde_json::from_slice::<serde_json::Value>(message.body()) {
-                                Ok(value) => value,
-                                Err(error) => {
-                                    error!(?error, "Failed to deserialize message body.");
-                                    continue;
-                                }
-                            };
-
-                        let index_name = match json_value.get("name").and_then(|name| name.as_str())
-                        {
-                            Some(name) => name.to_string(),
-                            None => {
-                                error!("Request does not contain index name.");
-                                continue;
-                            }
-                        };
-
-                        let index_dir = indices_dir.join(&index_name);
-
-                        // Check index directory existence.
-                        if !index_dir.exists() {
-                            warn!(?index_dir, "Index directory does not exists.");
+                };
+                let (analyzers, search_tokenizers, schema) =
+                    match index_metadata.analyzers().and_then(|analyzers| {
+                        Ok((
+                            analyzers,
+                            index_metadata.search_tokenizers()?,
+                            index_metadata.schema()?,
+                        ))
+                    }) {
+                        Ok(result) => result,
+                        Err(error) => {
+                            error!(?index_name, ?error, "Failed to get analyzers.");
                             continue;
                         }
+                    };
 
-                        let index_metadata_map = match json_value
-                            .get("metadata")
-                            .and_then(|metadata| metadata.as_object())
-                        {
-                            Some(map) => map,
-                            None => {
-                                error!("Request does not contain index metadata.");
-                                continue;
-                            }
-                        };
-                        let index_metadata_bytes = match serde_json::to_vec(&index_metadata_map) {
-                            Ok(bytes) => bytes,
-                            Err(error) => {
-                                error!(?error, "Failed to serialize index metadata.");
-                                continue;
-                            }
-                        };
-                        let index_metadata =
-                            match serde_json::from_slice::<Metadata>(&index_metadata_bytes) {
-                                Ok(metadata) => metadata,
-                                Err(error) => {
-                                    error!(?error, "Failed to deserialize index metadata.");
-                                    continue;
-                                }
-                            };
-
-                        // Save meta.json
-                        let meta_path = index_dir.join(INDEX_METADATA_FILE);
-                        match save_index_metadata(&meta_path, index_metadata).await {
-                            Ok(_) => info!(?meta_path, "File have been saved."),
-                            Err(error) => {
-                                error!(?meta_path, ?error, "Failed to write file.");
-                                continue;
-                            }
-                        }
-                    }
-                    _ => {
-                        debug!(?kind, "Unhandled message kind.");
-                        continue;
-                    }
-                }
+                reload_index_analyzers(
+                    &indices,
+                    &index_caches,
+                    &index_name,
+                    &indices_dir.join(&index_name),
+                    &analyzers,
+                    &search_tokenizers,
+                    &schema,
+                );
             }
         });
     }
@@ -919,6 +1151,222 @@ impl Node {
         self.membership.members().await.iter().cloned().collect()
     }
 
+    /// The gRPC address of the node that accepts the changes of the index metadata.
+    pub fn leader(&self) -> Option<String> {
+        self.raft.leader()
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.raft.is_leader()
+    }
+
+    /// Returns the shards whose replicas are placed in the same zone, e.g. because the cluster
+    /// has fewer zones than the replicas, or a node has no zone.
+    pub async fn placement_violations(
+        &self,
+        _request: GetPlacementViolationsRequest,
+    ) -> Result<GetPlacementViolationsResponse, NodeError> {
+        let mut metadatas = self
+            .metastore
+            .metadatas()
+            .await
+            .into_iter()
+            .collect::<Vec<_>>();
+        metadatas.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
+
+        let members = self.membership.members().await;
+        let num_zones = members.num_zones();
+        let mut violations = Vec::new();
+
+        // The replicas are not placed by zone unless the nodes have zones.
+        if num_zones > 0 {
+            for (index_name, metadata) in metadatas {
+                let shards = metadata
+                    .shards()
+                    .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+                let num_replicas = metadata
+                    .num_replicas()
+                    .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+
+                for shard in shards.iter() {
+                    let replicas = members
+                        .lookup_members(&shard.id, num_replicas)
+                        .map(|member| Replica {
+                            address: member.addr.to_string(),
+                            zone: member
+                                .metadata
+                                .as_ref()
+                                .and_then(|metadata| metadata.zone.clone())
+                                .unwrap_or_default(),
+                        })
+                        .collect::<Vec<_>>();
+                    let zones = replicas
+                        .iter()
+                        .map(|replica| replica.zone.as_str())
+                        .collect::<HashSet<_>>();
+                    if zones.len() < replicas.len() {
+                        violations.push(PlacementViolation {
+                            index_name: index_name.clone(),
+                            shard_id: shard.id.clone(),
+                            replicas,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(GetPlacementViolationsResponse {
+            num_zones: num_zones as u32,
+            violations,
+        })
+    }
+
+    /// Marks this node as leaving, which excludes it from the shard placement. The node keeps
+    /// serving its shards until their new owners serve them, and then it is decommissioned.
+    pub async fn decommission(
+        &self,
+        _request: DecommissionNodeRequest,
+    ) -> Result<DecommissionNodeResponse, NodeError> {
+        self.membership
+            .set_leaving()
+            .await
+            .map_err(|error| NodeErrorKind::DecommissionFailure.with_error(error))?;
+
+        Ok(DecommissionNodeResponse {})
+    }
+
+    /// Watches whether this node has handed off all of its shards after it has been marked as
+    /// leaving, i.e. it can be stopped without losing any replicas.
+    pub fn watch_decommissioned(&self) -> watch::Receiver<bool> {
+        self.decommissioned_receiver.clone()
+    }
+
+    pub async fn get_shard_status(
+        &self,
+        request: GetShardStatusRequest,
+    ) -> Result<GetShardStatusResponse, NodeError> {
+        let index = self
+            .indices
+            .get(&request.index_name)
+            .and_then(|shard_indices| {
+                shard_indices
+                    .get(&request.shard_id)
+                    .map(|index| index.clone())
+            });
+        let opstamp = match index.as_ref() {
+            Some(index) => {
+                index
+                    .load_metas()
+                    .map_err(|error| NodeErrorKind::ShardStatusFailure.with_error(error))?
+                    .opstamp
+            }
+            None => 0,
+        };
+
+        Ok(GetShardStatusResponse {
+            serving: index.is_some(),
+            opstamp,
+        })
+    }
+
+    /// Takes a snapshot of the last commit of the shard, which another node downloads.
+    pub async fn download_shard(
+        &self,
+        request: DownloadShardRequest,
+    ) -> Result<ShardSnapshot, NodeError> {
+        let index = self
+            .indices
+            .get(&request.index_name)
+            .and_then(|shard_indices| {
+                shard_indices
+                    .get(&request.shard_id)
+                    .map(|index| index.clone())
+            })
+            .ok_or_else(|| {
+                NodeErrorKind::ShardNotFound.with_error(anyhow::anyhow!(
+                    "Shard {:?} of index {:?} is not served by this node.",
+                    request.shard_id,
+                    request.index_name
+                ))
+            })?;
+
+        // The node that hands the shard off commits the documents that it has received, because
+        // only the last commit is copied.
+        if !self
+            .is_assigned_shard(&request.index_name, &request.shard_id)
+            .await
+        {
+            if let Some(shard_writers) = self.index_writers.get(&request.index_name) {
+                if let Some(mut index_writer) = shard_writers.get_mut(&request.shard_id) {
+                    index_writer
+                        .commit()
+                        .map_err(|error| NodeErrorKind::IndexCommitFailure.with_error(error))?;
+                }
+            }
+        }
+
+        let shard_dir = self
+            .indices_dir
+            .join(&request.index_name)
+            .join(SHARDS_DIR)
+            .join(&request.shard_id);
+        let snapshot = ShardSnapshot::take(&index, &shard_dir).await?;
+
+        // Only the owners of the shard count towards the hand-off.
+        let node_id = request.node_id.as_str();
+        if self
+            .is_shard_owner(&request.index_name, &request.shard_id, |member| {
+                member
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.grpc_address)
+                    .is_some_and(|grpc_address| grpc_address.to_string() == node_id)
+            })
+            .await
+        {
+            self.downloaders
+                .entry((request.index_name, request.shard_id))
+                .or_default()
+                .insert(request.node_id);
+        } else {
+            warn!(
+                ?node_id,
+                index_name = ?request.index_name,
+                shard_id = ?request.shard_id,
+                "Shard has been downloaded by a node that does not own it."
+            );
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Returns whether this node is one of the owners of the shard.
+    async fn is_assigned_shard(&self, index_name: &str, shard_id: &str) -> bool {
+        let local_addr = self.membership.local_member().await.addr;
+        self.is_shard_owner(index_name, shard_id, |member| member.addr == local_addr)
+            .await
+    }
+
+    /// Returns whether the member that matches the predicate is one of the owners of the shard.
+    async fn is_shard_owner<F>(&self, index_name: &str, shard_id: &str, predicate: F) -> bool
+    where
+        F: Fn(&Member) -> bool,
+    {
+        let num_replicas = match self
+            .index_metadata(index_name)
+            .await
+            .map(|metadata| metadata.num_replicas())
+        {
+            Some(Ok(num_replicas)) => num_replicas,
+            _ => return false,
+        };
+        let members = self.membership.members().await;
+        let is_owner = members
+            .lookup_members(shard_id, num_replicas)
+            .any(predicate);
+        is_owner
+    }
+
     pub async fn index_metadata(&self, name: &str) -> Option<Metadata> {
         self.metastore.metadatas().await.get(name).cloned()
     }
@@ -946,20 +1394,12 @@ impl Node {
         let metadata = serde_json::from_slice::<Metadata>(&request.metadata)
             .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
 
-        let kind = MessageKind::CreateIndex;
-        let message = serde_json::json!({
-            MESSAGE_NAME_FIELD: request.name,
-            MESSAGE_METADATA_FIELD: metadata,
-        });
-        let body = serde_json::to_vec(&message)
-            .map_err(|error| NodeErrorKind::MessageSerializationFailure.with_error(error))?;
-        let version = OffsetDateTime::now_utc().unix_timestamp();
+        let command = Command::CreateIndex {
+            name: request.name,
+            metadata,
+        };
 
-        match self
-            .membership
-            .broadcast(Message::with_body_version(kind, body.as_slice(), version))
-            .await
-        {
+        match self.raft.propose(command).await {
             Ok(_) => Ok(CreateIndexResponse {}),
             Err(error) => Err(NodeErrorKind::IndexCreationFailure.with_error(error)),
         }
@@ -969,19 +1409,9 @@ impl Node {
         &self,
         request: DeleteIndexRequest,
     ) -> Result<DeleteIndexResponse, NodeError> {
-        let kind = MessageKind::DeleteIndex;
-        let message = serde_json::json!({
-            MESSAGE_NAME_FIELD: request.name,
-        });
-        let body = serde_json::to_vec(&message)
-            .map_err(|error| NodeErrorKind::MessageSerializationFailure.with_error(error))?;
-        let version = OffsetDateTime::now_utc().unix_timestamp();
+        let command = Command::DeleteIndex { name: request.name };
 
-        match self
-            .membership
-            .broadcast(Message::with_body_version(kind, body.as_slice(), version))
-            .await
-        {
+        match self.raft.propose(command).await {
             Ok(_) => Ok(DeleteIndexResponse {}),
             Err(error) => Err(NodeErrorKind::IndexDeletionFailure.with_error(error)),
         }
@@ -1068,6 +1498,27 @@ impl Node {
             changed = true;
         }
 
+        let processors = metadata
+            .processors()
+            .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+        let new_processors = new_metadata
+            .processors()
+            .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+        if processors != new_processors {
+            // The processors must refer to the fields in the current schema.
+            new_processors
+                .check(
+                    &metadata
+                        .schema()
+                        .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?,
+                )
+                .map_err(|error| NodeErrorKind::InvalidProcessor.with_error(error))?;
+            metadata
+                .set_processors(new_processors)
+                .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+            changed = true;
+        }
+
         let num_shards = metadata
             .num_shards()
             .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
@@ -1086,29 +1537,148 @@ impl Node {
             return Ok(ModifyIndexResponse {});
         }
 
-        let kind = MessageKind::ModifyIndex;
-        let message = serde_json::json!({
-            MESSAGE_NAME_FIELD: request.name,
-            MESSAGE_METADATA_FIELD: metadata,
-        });
-        let body = serde_json::to_vec(&message)
-            .map_err(|error| NodeErrorKind::MessageSerializationFailure.with_error(error))?;
-        let version = OffsetDateTime::now_utc().unix_timestamp();
+        let command = Command::ModifyIndex {
+            name: request.name,
+            metadata,
+        };
 
-        match self
-            .membership
-            .broadcast(Message::with_body_version(kind, body.as_slice(), version))
-            .await
-        {
+        match self.raft.propose(command).await {
             Ok(_) => Ok(ModifyIndexResponse {}),
             Err(error) => Err(NodeErrorKind::ModifyIndexFailure.with_error(error)),
         }
     }
 
+    pub async fn reload_analyzers(
+        &self,
+        request: ReloadAnalyzersRequest,
+    ) -> Result<ReloadAnalyzersResponse, NodeError> {
+        let mut metadata = self.index_metadata(&request.name).await.ok_or_else(|| {
+            NodeErrorKind::IndexConfigDoesNotExist.with_error(anyhow::anyhow!(
+                "Index config for {} does not exist.",
+                request.name
+            ))
+        })?;
+
+        let analyzers = metadata
+            .analyzers()
+            .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+
+        // If no analyzers are given, the current ones are reloaded, e.g. to pick up the
+        // updated synonym files.
+        let new_analyzers = if request.analyzers.is_empty() {
+            analyzers.clone()
+        } else {
+            serde_json::from_slice::<Analyzers>(&request.analyzers)
+                .map_err(|error| NodeErrorKind::AnalyzerRegistrationFailure.with_error(error))?
+        };
+
+        // Changing the analyzers used at index time would make the indexed tokens incompatible.
+        let schema = metadata
+            .schema()
+            .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+        check_analyzers_compatibility(&analyzers, &new_analyzers, &schema)
+            .map_err(|error| NodeErrorKind::AnalyzerRegistrationFailure.with_error(error))?;
+
+        // Make sure that the files referred to by the analyzers can be loaded and that the
+        // search tokenizers of the fields still exist.
+        let manager = TokenizerManager::default();
+        register(
+            &manager,
+            &new_analyzers
+                .with_base_dir(&self.indices_dir.join(&request.name))
+                .map_err(|error| NodeErrorKind::AnalyzerRegistrationFailure.with_error(error))?,
+        )
+        .map_err(|error| NodeErrorKind::AnalyzerRegistrationFailure.with_error(error))?;
+        let search_tokenizers = metadata
+            .search_tokenizers()
+            .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+        register_search_analyzers(&manager, &search_tokenizers)
+            .map_err(|error| NodeErrorKind::AnalyzerRegistrationFailure.with_error(error))?;
+
+        metadata
+            .set_analyzers(new_analyzers)
+            .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+
+        let command = Command::ReloadAnalyzers {
+            name: request.name,
+            metadata,
+        };
+
+        match self.raft.propose(command).await {
+            Ok(_) => Ok(ReloadAnalyzersResponse {}),
+            Err(error) => Err(NodeErrorKind::AnalyzerRegistrationFailure.with_error(error)),
+        }
+    }
+
+    pub async fn put_user_dictionary(
+        &self,
+        request: PutUserDictionaryRequest,
+    ) -> Result<PutUserDictionaryResponse, NodeError> {
+        self.index_metadata(&request.name).await.ok_or_else(|| {
+            NodeErrorKind::IndexConfigDoesNotExist.with_error(anyhow::anyhow!(
+                "Index config for {} does not exist.",
+                request.name
+            ))
+        })?;
+
+        check_user_dictionary_file_name(&request.file_name)?;
+        if request.dictionary.len() > MAX_USER_DICTIONARY_SIZE {
+            return Err(
+                NodeErrorKind::InvalidUserDictionary.with_error(anyhow::anyhow!(
+                    "User dictionary must not be larger than {} bytes.",
+                    MAX_USER_DICTIONARY_SIZE
+                )),
+            );
+        }
+
+        let command = Command::PutUserDictionary {
+            name: request.name,
+            file_name: request.file_name,
+            content: base64::encode(&request.dictionary),
+        };
+
+        match self.raft.propose(command).await {
+            Ok(_) => Ok(PutUserDictionaryResponse {}),
+            Err(error) => Err(NodeErrorKind::InvalidUserDictionary.with_error(error)),
+        }
+    }
+
+    pub async fn delete_user_dictionary(
+        &self,
+        request: DeleteUserDictionaryRequest,
+    ) -> Result<DeleteUserDictionaryResponse, NodeError> {
+        self.index_metadata(&request.name).await.ok_or_else(|| {
+            NodeErrorKind::IndexConfigDoesNotExist.with_error(anyhow::anyhow!(
+                "Index config for {} does not exist.",
+                request.name
+            ))
+        })?;
+
+        check_user_dictionary_file_name(&request.file_name)?;
+
+        let command = Command::DeleteUserDictionary {
+            name: request.name,
+            file_name: request.file_name,
+        };
+
+        match self.raft.propose(command).await {
+            Ok(_) => Ok(DeleteUserDictionaryResponse {}),
+            Err(error) => Err(NodeErrorKind::InvalidUserDictionary.with_error(error)),
+        }
+    }
+
     pub async fn put_documents(
         &self,
         request: PutDocumentsRequest,
     ) -> Result<PutDocumentsResponse, NodeError> {
+        // Ingest processors.
+        let processors = match self.index_metadata(&request.name).await {
+            Some(metadata) => metadata
+                .processors()
+                .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?,
+            None => Processors::default(),
+        };
+
         // Index.
         let shards = self.indices.get(&request.name).ok_or_else(|| {
             NodeErrorKind::IndexNotFound
@@ -1138,6 +1708,9 @@ impl Node {
             ))
         })?;
 
+        // Geo point fields.
+        let geo_point_names = geo_point_field_names(&schema);
+
         // Index writer.
         let shard_indexers = self.index_writers.get(&request.name).ok_or_else(|| {
             NodeErrorKind::IndexNotFound
@@ -1167,10 +1740,23 @@ impl Node {
                     .with_error(anyhow::anyhow!("Document ID not found."))
             })?;
 
-            let doc_fields = doc_value_map["fields"].as_object().ok_or_else(|| {
-                NodeErrorKind::DocumentDeserializationFailure
-                    .with_error(anyhow::anyhow!("Document fields not found."))
-            })?;
+            let mut doc_fields = doc_value_map["fields"]
+                .as_object()
+                .ok_or_else(|| {
+                    NodeErrorKind::DocumentDeserializationFailure
+                        .with_error(anyhow::anyhow!("Document fields not found."))
+                })?
+                .clone();
+
+            // Apply the ingest processors to the fields as given by the client.
+            processors
+                .process(&mut doc_fields)
+                .map_err(|error| NodeErrorKind::DocumentProcessFailure.with_error(error))?;
+
+            // Convert the geo points to the values of the native fields.
+            expand_geo_point_values(&mut doc_fields, &geo_point_names)
+                .map_err(|error| NodeErrorKind::DocumentParseFailure.with_error(error))?;
+
             let doc_fields_json = serde_json::to_string(&doc_fields)
                 .map_err(|error| NodeErrorKind::DocumentSerializationFailure.with_error(error))?;
 
@@ -1294,6 +1880,30 @@ impl Node {
         })?;
         let searcher = index_reader.searcher();
 
+        // Get shard cache.
+        let shard_cache = self
+            .index_caches
+            .get(&request.name)
+            .and_then(|shard_caches| {
+                shard_caches
+                    .get(&request.shard_id)
+                    .map(|shard_cache| Arc::clone(shard_cache.value()))
+            })
+            .ok_or_else(|| {
+                NodeErrorKind::IndexNotFound.with_error(anyhow::anyhow!(
+                    "Cache not found for {}.",
+                    &request.shard_id
+                ))
+            })?;
+        let filter_cache = shard_cache.filter_cache();
+
+        // Return the cached response if the same request has already been served by this reader generation.
+        let generation_id = searcher.generation().generation_id();
+        if let Some(response) = shard_cache.request_cache().get(generation_id, &request) {
+            return Ok(response);
+        }
+        let cache_request = request.clone();
+
         // Get schema.
         let schema = searcher.schema();
 
@@ -1301,15 +1911,93 @@ impl Node {
         let query = request.query.ok_or_else(|| {
             NodeErrorKind::SearchFailre.with_error(anyhow::anyhow!("Query not found."))
         })?;
-        let query = create_query(&query, &searcher)
+
+        // The function score is applied to the scores of the wrapped query by the collector.
+        let (query, function_score) = if query.kind == ProtoQueryKind::FunctionScore as i32 {
+            if request.sort.is_some() {
+                return Err(NodeErrorKind::SearchFailre.with_error(anyhow::anyhow!(
+                    "Function score query cannot be combined with sort."
+                )));
+            }
+            let args = serde_json::from_slice::<FunctionScoreQueryArgs>(query.options.as_slice())
+                .map_err(|error| NodeErrorKind::SearchFailre.with_error(error))?;
+            let function_score = create_function_score(&args, schema)
+                .map_err(|error| NodeErrorKind::SearchFailre.with_error(error))?;
+            (args.query().clone(), Some(Arc::new(function_score)))
+        } else {
+            (query, None)
+        };
+        let query = create_query(&query, &searcher, Some(filter_cache))
             .map_err(|error| NodeErrorKind::SearchFailre.with_error(error))?;
 
+        // Apply post filter.
+        // The post filter narrows down the hits without affecting the scores.
+        let query: Box<dyn Query> = match request.post_filter {
+            Some(post_filter) => {
+                let post_filter_query =
+                    create_query(&post_filter, &searcher, Some(filter_cache))
+                        .map_err(|error| NodeErrorKind::SearchFailre.with_error(error))?;
+                let post_filter_query = Box::new(CachedFilterQuery::new(
+                    canonical_query_key(&post_filter),
+                    post_filter_query,
+                    Arc::clone(filter_cache),
+                ));
+                Box::new(BooleanQuery::new(vec![
+                    (Occur::Must, query),
+                    (Occur::Must, create_filter_query(post_filter_query)),
+                ]))
+            }
+            None => query,
+        };
+
         // Get collection kind.
         let kind = CollectionKind::from_i32(request.collection_kind).ok_or_else(|| {
             NodeErrorKind::SearchFailre.with_error(anyhow::anyhow!("Collection kind not found."))
         })?;
 
         let result = match request.sort {
+            // Sort by the distance from the origin.
+            Some(sort) if sort.origin.is_some() => {
+                // Sort order.
+                let sort_order = Order::from_i32(sort.order).ok_or_else(|| {
+                    NodeErrorKind::SearchFailre.with_error(anyhow::anyhow!(
+                        "Sort order {:?} is not supported.",
+                        sort.order
+                    ))
+                })?;
+
+                let (total_hits, documents) = match sort_order {
+                    Order::Asc => search_order_by_geo_distance_asc(
+                        &searcher,
+                        query,
+                        request.hits,
+                        request.offset,
+                        sort,
+                        request.fields,
+                        kind,
+                    )?,
+                    Order::Desc => search_order_by_geo_distance_desc(
+                        &searcher,
+                        query,
+                        request.hits,
+                        request.offset,
+                        sort,
+                        request.fields,
+                        kind,
+                    )?,
+                    _ => {
+                        return Err(NodeErrorKind::SearchFailre.with_error(anyhow::anyhow!(
+                            "Sort order {:?} is not supported.",
+                            sort.order
+                        )));
+                    }
+                };
+
+                SearchResponse {
+                    total_hits,
+                    documents,
+                }
+            }
             Some(sort) => {
                 // Sort field.
                 let sort_field = schema.get_field(&sort.field).ok_or_else(|| {
@@ -1425,14 +2113,25 @@ impl Node {
                 }
             }
             None => {
-                let (total_hits, documents) = search_order_by_score_desc(
-                    &searcher,
-                    query,
-                    request.hits,
-                    request.offset,
-                    request.fields,
-                    kind,
-                )?;
+                let (total_hits, documents) = match function_score {
+                    Some(function_score) => search_order_by_function_score_desc(
+                        &searcher,
+                        query,
+                        function_score,
+                        request.hits,
+                        request.offset,
+                        request.fields,
+                        kind,
+                    )?,
+                    None => search_order_by_score_desc(
+                        &searcher,
+                        query,
+                        request.hits,
+                        request.offset,
+                        request.fields,
+                        kind,
+                    )?,
+                };
 
                 SearchResponse {
                     total_hits,
@@ -1441,15 +2140,250 @@ impl Node {
             }
         };
 
+        shard_cache
+            .request_cache()
+            .put(generation_id, &cache_request, result.clone());
+
         Ok(result)
     }
+
+    pub async fn get_cache_stats(
+        &self,
+        request: GetCacheStatsRequest,
+    ) -> Result<GetCacheStatsResponse, NodeError> {
+        let shard_caches = self.index_caches.get(&request.name).ok_or_else(|| {
+            NodeErrorKind::IndexNotFound
+                .with_error(anyhow::anyhow!("Cache not found for {}.", &request.name))
+        })?;
+
+        let mut shards = shard_caches
+            .iter()
+            .map(|item| {
+                let filter_cache = item.value().filter_cache();
+                let request_cache = item.value().request_cache();
+                ShardCacheStats {
+                    shard_id: item.key().clone(),
+                    filter_cache: Some(ProtoCacheStats {
+                        hits: filter_cache.stats().hits(),
+                        misses: filter_cache.stats().misses(),
+                        hit_rate: filter_cache.stats().hit_rate(),
+                        entries: filter_cache.len() as u64,
+                        memory_usage: filter_cache.memory_usage() as u64,
+                    }),
+                    request_cache: Some(ProtoCacheStats {
+                        hits: request_cache.stats().hits(),
+                        misses: request_cache.stats().misses(),
+                        hit_rate: request_cache.stats().hit_rate(),
+                        entries: request_cache.len() as u64,
+                        memory_usage: request_cache.memory_usage() as u64,
+                    }),
+                }
+            })
+            .collect::<Vec<ShardCacheStats>>();
+        shards.sort_by(|a, b| a.shard_id.cmp(&b.shard_id));
+
+        Ok(GetCacheStatsResponse { shards })
+    }
+
+    pub async fn analyze(&self, request: AnalyzeRequest) -> Result<AnalyzeResponse, NodeError> {
+        let stages = if !request.definition.is_empty() {
+            // Build the analyzer from the inline definition.
+            let definition = serde_json::from_slice::<serde_json::Value>(&request.definition)
+                .map_err(|error| NodeErrorKind::AnalyzeFailure.with_error(error))?;
+            build_analyzer_stages(&definition, Some(&self.indices_dir.join(&request.name)))
+                .map_err(|error| NodeErrorKind::AnalyzeFailure.with_error(error))?
+        } else {
+            let metadata = self.index_metadata(&request.name).await.ok_or_else(|| {
+                NodeErrorKind::IndexNotFound
+                    .with_error(anyhow::anyhow!("Index {} not found.", &request.name))
+            })?;
+
+            // If the field is specified, use the analyzer with which the field is indexed.
+            let analyzer_name = if !request.field.is_empty() {
+                let schema = metadata
+                    .schema()
+                    .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?;
+                let field = schema.get_field(&request.field).ok_or_else(|| {
+                    NodeErrorKind::FieldNotFound
+                        .with_error(anyhow::anyhow!("Field {} not found.", &request.field))
+                })?;
+                let indexing_options = match schema.get_field_entry(field).field_type() {
+                    FieldType::Str(options) => options.get_indexing_options(),
+                    FieldType::JsonObject(options) => options.get_text_indexing_options(),
+                    _ => None,
+                };
+                indexing_options
+                    .map(|options| options.tokenizer().to_string())
+                    .ok_or_else(|| {
+                        NodeErrorKind::AnalyzerNotFound.with_error(anyhow::anyhow!(
+                            "Field {} is not an indexed text field.",
+                            &request.field
+                        ))
+                    })?
+            } else {
+                request.analyzer.clone()
+            };
+
+            let analyzers = metadata
+                .analyzers()
+                .map_err(|error| NodeErrorKind::MetadataError.with_error(error))?
+                .with_base_dir(&self.indices_dir.join(&request.name))
+                .map_err(|error| NodeErrorKind::AnalyzeFailure.with_error(error))?;
+            match analyzers.stages(&analyzer_name) {
+                Some(stages) => {
+                    stages.map_err(|error| NodeErrorKind::AnalyzeFailure.with_error(error))?
+                }
+                None => {
+                    // Fall back to the built-in analyzers of Tantivy.
+                    let analyzer =
+                        TokenizerManager::default()
+                            .get(&analyzer_name)
+                            .ok_or_else(|| {
+                                NodeErrorKind::AnalyzerNotFound.with_error(anyhow::anyhow!(
+                                    "Analyzer {} not found.",
+                                    &analyzer_name
+                                ))
+                            })?;
+                    vec![(analyzer_name, analyzer)]
+                }
+            }
+        };
+
+        let stages = stages
+            .iter()
+            .map(|(stage_name, analyzer)| AnalyzeStage {
+                name: stage_name.clone(),
+                tokens: analyze_text(analyzer, &request.text)
+                    .into_iter()
+                    .map(|token| AnalyzedToken {
+                        text: token.text,
+                        offset_from: token.offset_from as u64,
+                        offset_to: token.offset_to as u64,
+                        position: token.position as u64,
+                        position_length: token.position_length as u64,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(AnalyzeResponse { stages })
+    }
+}
+
+/// Re-register the analyzers to the shard indices of the index that are already open.
+fn reload_index_analyzers(
+    indices: &DashMap<String, DashMap<String, Index>>,
+    index_caches: &DashMap<String, DashMap<String, Arc<ShardCache>>>,
+    index_name: &str,
+    index_dir: &Path,
+    analyzers: &Analyzers,
+    search_tokenizers: &HashMap<String, String>,
+    schema: &Schema,
+) {
+    let analyzers = match analyzers.with_base_dir(index_dir) {
+        Ok(analyzers) => analyzers,
+        Err(error) => {
+            error!(?index_name, ?error, "Failed to load analyzer files.");
+            return;
+        }
+    };
+    if let Some(shards) = indices.get(index_name) {
+        for item in shards.iter() {
+            // The analyzers used at index time are not reloaded, even if their files have changed.
+            // The search-time analyzers refer to the reloaded analyzers.
+            let result =
+                register_search_time_analyzers(item.value().tokenizers(), &analyzers, schema)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|_| {
+                        register_search_analyzers(item.value().tokenizers(), search_tokenizers)
+                    });
+            match result {
+                Ok(_) => {
+                    info!(?index_name, shard_id = ?item.key(), "Analyzers have been reloaded.")
+                }
+                Err(error) => {
+                    error!(?index_name, shard_id = ?item.key(), ?error, "Failed to reload analyzers.")
+                }
+            }
+        }
+    }
+
+    // The cached results may have been produced with the previous analyzers.
+    if let Some(shard_caches) = index_caches.get(index_name) {
+        for item in shard_caches.iter() {
+            item.value().clear();
+        }
+    }
+}
+
+/// User dictionaries are placed directly in the index directory, and must not be mistaken for
+/// the files of the index itself. The temporary files share the stem of the file.
+fn check_user_dictionary_file_name(file_name: &str) -> Result<(), NodeError> {
+    let path = Path::new(file_name);
+    let reserved_stems = [
+        Path::new(INDEX_METADATA_FILE).file_stem(),
+        Some(SHARDS_DIR.as_ref()),
+    ];
+    if path.components().count() != 1
+        || path.file_name().is_none()
+        || reserved_stems.contains(&path.file_stem())
+    {
+        return Err(
+            NodeErrorKind::InvalidUserDictionary.with_error(anyhow::anyhow!(
+                "Invalid user dictionary file name: {}",
+                file_name
+            )),
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether a leaving node can be stopped without losing any shard replicas or Raft voters.
+fn is_decommissioned(
+    num_open_shards: usize,
+    has_pending_handoffs: bool,
+    has_leader: bool,
+    is_voter: bool,
+) -> bool {
+    num_open_shards == 0 && !has_pending_handoffs && has_leader && !is_voter
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::node::index::extract_index_name;
+    use crate::node::{
+        check_user_dictionary_file_name, index::extract_index_name, is_decommissioned,
+    };
     use std::path::Path;
 
+    #[test]
+    fn test_check_user_dictionary_file_name() {
+        assert!(check_user_dictionary_file_name("userdic.csv").is_ok());
+        assert!(check_user_dictionary_file_name("userdic.bin").is_ok());
+
+        assert!(check_user_dictionary_file_name("").is_err());
+        assert!(check_user_dictionary_file_name("../userdic.csv").is_err());
+        assert!(check_user_dictionary_file_name("dict/userdic.csv").is_err());
+        assert!(check_user_dictionary_file_name("/tmp/userdic.csv").is_err());
+        assert!(check_user_dictionary_file_name("meta.json").is_err());
+        assert!(check_user_dictionary_file_name("meta.csv").is_err());
+        assert!(check_user_dictionary_file_name("shards").is_err());
+    }
+
+    #[test]
+    fn test_is_decommissioned() {
+        assert!(is_decommissioned(0, false, true, false));
+
+        // The shards that are open or waiting for their new owners are not handed off yet.
+        assert!(!is_decommissioned(1, false, true, false));
+        assert!(!is_decommissioned(0, true, true, false));
+
+        // The node must still be a member of the Raft cluster that has removed it from the
+        // voters.
+        assert!(!is_decommissioned(0, false, false, false));
+        assert!(!is_decommissioned(0, false, true, true));
+    }
+
     #[test]
     fn test_extract_index_name() {
         let path = Path::new("/tmp/indices/test_index");
@@ -1,15 +1,21 @@
-use std::cmp::Reverse;
+use std::{cmp::Reverse, collections::HashMap, sync::Arc};
 
 use tantivy::{
     collector::{Count, MultiCollector, TopDocs},
-    fastfield::FastFieldReader,
+    fastfield::{DynamicFastFieldReader, FastFieldReader},
     query::Query,
-    DateTime, DocAddress, DocId, Document as TDocument, LeasedItem, Searcher, SegmentReader,
+    schema::{Field, Type},
+    DateTime, DocAddress, DocId, Document as TDocument, LeasedItem, Score, Searcher, SegmentReader,
+    TantivyError,
 };
 
 use crate::{
-    index::{DOC_ID_FIELD_NAME, DOC_TIMESTAMP_FIELD_NAME},
+    index::{
+        geo_point::{GeoPoint, LAT_FIELD_SUFFIX, LON_FIELD_SUFFIX},
+        DOC_ID_FIELD_NAME, DOC_TIMESTAMP_FIELD_NAME,
+    },
     proto::index::{CollectionKind, Document, Sort},
+    search::query::function_score::FunctionScore,
 };
 
 use super::{NodeError, NodeErrorKind};
@@ -152,6 +158,213 @@ pub fn search_order_by_score_desc(
     Ok((total_hits, documents))
 }
 
+/// Reads the value of a numeric or date fast field as `f64`.
+/// Dates are read as the UNIX timestamp in seconds.
+enum FastFieldValueReader {
+    I64(DynamicFastFieldReader<i64>),
+    U64(DynamicFastFieldReader<u64>),
+    F64(DynamicFastFieldReader<f64>),
+    Date(DynamicFastFieldReader<DateTime>),
+}
+
+impl FastFieldValueReader {
+    fn open(
+        segment_reader: &SegmentReader,
+        field: Field,
+        value_type: Type,
+    ) -> tantivy::Result<Self> {
+        let fast_fields = segment_reader.fast_fields();
+        match value_type {
+            Type::I64 => Ok(FastFieldValueReader::I64(fast_fields.i64(field)?)),
+            Type::U64 => Ok(FastFieldValueReader::U64(fast_fields.u64(field)?)),
+            Type::F64 => Ok(FastFieldValueReader::F64(fast_fields.f64(field)?)),
+            Type::Date => Ok(FastFieldValueReader::Date(fast_fields.date(field)?)),
+            _ => Err(TantivyError::SchemaError(format!(
+                "Field type {:?} is not supported.",
+                value_type
+            ))),
+        }
+    }
+
+    fn get(&self, doc: DocId) -> f64 {
+        match self {
+            FastFieldValueReader::I64(reader) => reader.get(doc) as f64,
+            FastFieldValueReader::U64(reader) => reader.get(doc) as f64,
+            FastFieldValueReader::F64(reader) => reader.get(doc),
+            FastFieldValueReader::Date(reader) => reader.get(doc).into_unix_timestamp() as f64,
+        }
+    }
+}
+
+pub fn search_order_by_function_score_desc(
+    searcher: &LeasedItem<Searcher>,
+    query: Box<dyn Query>,
+    function_score: Arc<FunctionScore>,
+    hits: i32,
+    offset: i32,
+    fields: Vec<String>,
+    kind: CollectionKind,
+) -> Result<(i64, Vec<Document>), NodeError> {
+    // Get schema.
+    let schema = searcher.schema();
+
+    // Open the fast field readers of all segments up front, so that a failure is returned
+    // as an error instead of panicking in the collector.
+    let mut fast_field_readers = HashMap::new();
+    for segment_reader in searcher.segment_readers() {
+        let readers = function_score
+            .functions()
+            .iter()
+            .map(|function| {
+                FastFieldValueReader::open(segment_reader, function.field(), function.value_type())
+            })
+            .collect::<tantivy::Result<Vec<FastFieldValueReader>>>()
+            .map_err(|error| NodeErrorKind::IndexSearchFailure.with_error(error))?;
+        fast_field_readers.insert(segment_reader.segment_id(), Arc::new(readers));
+    }
+
+    // Create multi collector.
+    let mut multi_collector = MultiCollector::new();
+
+    // Create count collector.
+    let count_handle = match kind {
+        CollectionKind::CountAndTopDocs | CollectionKind::Count => {
+            Some(multi_collector.add_collector(Count))
+        }
+        _ => None,
+    };
+
+    // Create top docs collector.
+    let top_docs_handle = match kind {
+        CollectionKind::CountAndTopDocs | CollectionKind::TopDocs => Some(
+            multi_collector.add_collector(
+                TopDocs::with_limit(hits as usize)
+                    .and_offset(offset as usize)
+                    .tweak_score(move |segment_reader: &SegmentReader| {
+                        let readers = fast_field_readers
+                            .get(&segment_reader.segment_id())
+                            .cloned();
+                        let function_score = Arc::clone(&function_score);
+
+                        move |doc: DocId, original_score: Score| match &readers {
+                            Some(readers) => {
+                                function_score.score(original_score, |i| readers[i].get(doc))
+                            }
+                            None => original_score,
+                        }
+                    }),
+            ),
+        ),
+        _ => None,
+    };
+
+    // Search index.
+    let mut multi_fruit = searcher
+        .search(&query, &multi_collector)
+        .map_err(|error| NodeErrorKind::IndexSearchFailure.with_error(error))?;
+
+    // Get total hits count.
+    let total_hits = if let Some(handle) = count_handle {
+        handle.extract(&mut multi_fruit) as i64
+    } else {
+        // If no count is requested, return -1.
+        -1
+    };
+
+    // Get top docs.
+    let top_docs = if let Some(handle) = top_docs_handle {
+        handle.extract(&mut multi_fruit)
+    } else {
+        // If no top docs are requested, return None.
+        Vec::new()
+    };
+
+    // Get document ID field.
+    let doc_id_field = schema.get_field(DOC_ID_FIELD_NAME).ok_or_else(|| {
+        NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+            "{:?} field does not exist.",
+            DOC_ID_FIELD_NAME
+        ))
+    })?;
+
+    // Get document timestamp field.
+    let doc_timestamp_field = schema.get_field(DOC_TIMESTAMP_FIELD_NAME).ok_or_else(|| {
+        NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+            "{:?} field does not exist.",
+            DOC_TIMESTAMP_FIELD_NAME
+        ))
+    })?;
+
+    // Create documents.
+    let mut documents = Vec::new();
+    let top_docs: Vec<(f32, DocAddress)> = top_docs;
+    for (score, doc_addr) in top_docs {
+        let doc = searcher
+            .doc(doc_addr)
+            .map_err(|error| NodeErrorKind::IndexSearchFailure.with_error(error))?;
+
+        let id = doc
+            .get_first(doc_id_field)
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure
+                    .with_error(anyhow::anyhow!("{:?} field does not exist.", doc_id_field))
+            })?
+            .as_text()
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                    "{:?} field is not text type.",
+                    doc_id_field
+                ))
+            })?
+            .to_string();
+
+        let timestamp = doc
+            .get_first(doc_timestamp_field)
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                    "{:?} field does not exist.",
+                    doc_timestamp_field
+                ))
+            })?
+            .as_date()
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                    "{:?} field is not text type.",
+                    doc_id_field
+                ))
+            })?
+            .into_utc()
+            .unix_timestamp();
+
+        let mut new_doc = TDocument::new();
+        for field in fields.iter() {
+            for (doc_field, doc_field_values) in doc.get_sorted_field_values() {
+                if field == schema.get_field_name(doc_field)
+                    && (field != DOC_ID_FIELD_NAME || field != DOC_TIMESTAMP_FIELD_NAME)
+                {
+                    for doc_field_value in doc_field_values.into_iter().cloned() {
+                        new_doc.add_field_value(doc_field, doc_field_value);
+                    }
+                }
+            }
+        }
+
+        let doc_json = schema.to_json(&new_doc);
+
+        let doc = Document {
+            id,
+            score,
+            timestamp,
+            sort_value: 0.0,
+            fields: doc_json.as_bytes().to_vec(),
+        };
+
+        documents.push(doc);
+    }
+
+    Ok((total_hits, documents))
+}
+
 pub fn search_order_by_i64_asc(
     searcher: &LeasedItem<Searcher>,
     query: Box<dyn Query>,
@@ -772,6 +985,384 @@ pub fn search_order_by_f64_desc(
     Ok((total_hits, documents))
 }
 
+pub fn search_order_by_geo_distance_asc(
+    searcher: &LeasedItem<Searcher>,
+    query: Box<dyn Query>,
+    hits: i32,
+    offset: i32,
+    sort: Sort,
+    fields: Vec<String>,
+    kind: CollectionKind,
+) -> Result<(i64, Vec<Document>), NodeError> {
+    // Get schema.
+    let schema = searcher.schema();
+
+    // Coordinate fields of the geo point field.
+    let lat_field = schema
+        .get_field(&format!("{}{}", sort.field, LAT_FIELD_SUFFIX))
+        .ok_or_else(|| {
+            NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                "Sort field {:?} is not a geo point field.",
+                sort.field
+            ))
+        })?;
+    let lon_field = schema
+        .get_field(&format!("{}{}", sort.field, LON_FIELD_SUFFIX))
+        .ok_or_else(|| {
+            NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                "Sort field {:?} is not a geo point field.",
+                sort.field
+            ))
+        })?;
+
+    // Origin of the distance.
+    let origin = sort
+        .origin
+        .map(|origin| GeoPoint {
+            lat: origin.lat,
+            lon: origin.lon,
+        })
+        .ok_or_else(|| {
+            NodeErrorKind::IndexSearchFailure
+                .with_error(anyhow::anyhow!("Sort origin does not exist."))
+        })?;
+
+    // Create multi collector.
+    let mut multi_collector = MultiCollector::new();
+
+    // Create count collector.
+    let count_handle = match kind {
+        CollectionKind::CountAndTopDocs | CollectionKind::Count => {
+            Some(multi_collector.add_collector(Count))
+        }
+        _ => None,
+    };
+
+    // Create top docs collector.
+    let top_docs_handle = match kind {
+        CollectionKind::CountAndTopDocs | CollectionKind::TopDocs => Some(
+            multi_collector.add_collector(
+                TopDocs::with_limit(hits as usize)
+                    .and_offset(offset as usize)
+                    .custom_score(move |segment_reader: &SegmentReader| {
+                        let lat_reader = segment_reader
+                            .fast_fields()
+                            .f64(lat_field)
+                            .expect("field exists");
+                        let lon_reader = segment_reader
+                            .fast_fields()
+                            .f64(lon_field)
+                            .expect("field exists");
+
+                        move |doc: DocId| {
+                            let point = GeoPoint {
+                                lat: lat_reader.get(doc),
+                                lon: lon_reader.get(doc),
+                            };
+                            Reverse(origin.distance(&point))
+                        }
+                    }),
+            ),
+        ),
+        _ => None,
+    };
+
+    // Search index.
+    let mut multi_fruit = searcher
+        .search(&query, &multi_collector)
+        .map_err(|error| NodeErrorKind::IndexSearchFailure.with_error(error))?;
+
+    // Get total hits count.
+    let total_hits = if let Some(handle) = count_handle {
+        handle.extract(&mut multi_fruit) as i64
+    } else {
+        // If no count is requested, return -1.
+        -1
+    };
+
+    // Get top docs.
+    let top_docs = if let Some(handle) = top_docs_handle {
+        handle.extract(&mut multi_fruit)
+    } else {
+        // If no top docs are requested, return None.
+        Vec::new()
+    };
+
+    // Get document ID field.
+    let doc_id_field = schema.get_field(DOC_ID_FIELD_NAME).ok_or_else(|| {
+        NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+            "{:?} field does not exist.",
+            DOC_ID_FIELD_NAME
+        ))
+    })?;
+
+    // Get document timestamp field.
+    let doc_timestamp_field = schema.get_field(DOC_TIMESTAMP_FIELD_NAME).ok_or_else(|| {
+        NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+            "{:?} field does not exist.",
+            DOC_TIMESTAMP_FIELD_NAME
+        ))
+    })?;
+
+    // Create documents.
+    let mut documents = Vec::new();
+    let top_docs: Vec<(Reverse<f64>, DocAddress)> = top_docs;
+    for (sort_value, doc_addr) in top_docs {
+        let doc = searcher
+            .doc(doc_addr)
+            .map_err(|error| NodeErrorKind::IndexSearchFailure.with_error(error))?;
+
+        let id = doc
+            .get_first(doc_id_field)
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure
+                    .with_error(anyhow::anyhow!("{:?} field does not exist.", doc_id_field))
+            })?
+            .as_text()
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                    "{:?} field is not text type.",
+                    doc_id_field
+                ))
+            })?
+            .to_string();
+
+        let timestamp = doc
+            .get_first(doc_timestamp_field)
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                    "{:?} field does not exist.",
+                    doc_timestamp_field
+                ))
+            })?
+            .as_date()
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                    "{:?} field is not text type.",
+                    doc_id_field
+                ))
+            })?
+            .into_utc()
+            .unix_timestamp();
+
+        let mut new_doc = TDocument::new();
+        for field in fields.iter() {
+            for (doc_field, doc_field_values) in doc.get_sorted_field_values() {
+                if field == schema.get_field_name(doc_field)
+                    && (field != DOC_ID_FIELD_NAME || field != DOC_TIMESTAMP_FIELD_NAME)
+                {
+                    for doc_field_value in doc_field_values.into_iter().cloned() {
+                        new_doc.add_field_value(doc_field, doc_field_value);
+                    }
+                }
+            }
+        }
+
+        let doc_json = schema.to_json(&new_doc);
+
+        let doc = Document {
+            id,
+            score: 0.0,
+            timestamp,
+            sort_value: sort_value.0,
+            fields: doc_json.as_bytes().to_vec(),
+        };
+
+        documents.push(doc);
+    }
+
+    Ok((total_hits, documents))
+}
+
+pub fn search_order_by_geo_distance_desc(
+    searcher: &LeasedItem<Searcher>,
+    query: Box<dyn Query>,
+    hits: i32,
+    offset: i32,
+    sort: Sort,
+    fields: Vec<String>,
+    kind: CollectionKind,
+) -> Result<(i64, Vec<Document>), NodeError> {
+    // Get schema.
+    let schema = searcher.schema();
+
+    // Coordinate fields of the geo point field.
+    let lat_field = schema
+        .get_field(&format!("{}{}", sort.field, LAT_FIELD_SUFFIX))
+        .ok_or_else(|| {
+            NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                "Sort field {:?} is not a geo point field.",
+                sort.field
+            ))
+        })?;
+    let lon_field = schema
+        .get_field(&format!("{}{}", sort.field, LON_FIELD_SUFFIX))
+        .ok_or_else(|| {
+            NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                "Sort field {:?} is not a geo point field.",
+                sort.field
+            ))
+        })?;
+
+    // Origin of the distance.
+    let origin = sort
+        .origin
+        .map(|origin| GeoPoint {
+            lat: origin.lat,
+            lon: origin.lon,
+        })
+        .ok_or_else(|| {
+            NodeErrorKind::IndexSearchFailure
+                .with_error(anyhow::anyhow!("Sort origin does not exist."))
+        })?;
+
+    // Create multi collector.
+    let mut multi_collector = MultiCollector::new();
+
+    // Create count collector.
+    let count_handle = match kind {
+        CollectionKind::CountAndTopDocs | CollectionKind::Count => {
+            Some(multi_collector.add_collector(Count))
+        }
+        _ => None,
+    };
+
+    // Create top docs collector.
+    let top_docs_handle = match kind {
+        CollectionKind::CountAndTopDocs | CollectionKind::TopDocs => Some(
+            multi_collector.add_collector(
+                TopDocs::with_limit(hits as usize)
+                    .and_offset(offset as usize)
+                    .custom_score(move |segment_reader: &SegmentReader| {
+                        let lat_reader = segment_reader
+                            .fast_fields()
+                            .f64(lat_field)
+                            .expect("field exists");
+                        let lon_reader = segment_reader
+                            .fast_fields()
+                            .f64(lon_field)
+                            .expect("field exists");
+
+                        move |doc: DocId| {
+                            let point = GeoPoint {
+                                lat: lat_reader.get(doc),
+                                lon: lon_reader.get(doc),
+                            };
+                            origin.distance(&point)
+                        }
+                    }),
+            ),
+        ),
+        _ => None,
+    };
+
+    // Search index.
+    let mut multi_fruit = searcher
+        .search(&query, &multi_collector)
+        .map_err(|error| NodeErrorKind::IndexSearchFailure.with_error(error))?;
+
+    // Get total hits count.
+    let total_hits = if let Some(handle) = count_handle {
+        handle.extract(&mut multi_fruit) as i64
+    } else {
+        // If no count is requested, return -1.
+        -1
+    };
+
+    // Get top docs.
+    let top_docs = if let Some(handle) = top_docs_handle {
+        handle.extract(&mut multi_fruit)
+    } else {
+        // If no top docs are requested, return None.
+        Vec::new()
+    };
+
+    // Get document ID field.
+    let doc_id_field = schema.get_field(DOC_ID_FIELD_NAME).ok_or_else(|| {
+        NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+            "{:?} field does not exist.",
+            DOC_ID_FIELD_NAME
+        ))
+    })?;
+
+    // Get document timestamp field.
+    let doc_timestamp_field = schema.get_field(DOC_TIMESTAMP_FIELD_NAME).ok_or_else(|| {
+        NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+            "{:?} field does not exist.",
+            DOC_TIMESTAMP_FIELD_NAME
+        ))
+    })?;
+
+    // Create documents.
+    let mut documents = Vec::new();
+    let top_docs: Vec<(f64, DocAddress)> = top_docs;
+    for (sort_value, doc_addr) in top_docs {
+        let doc = searcher
+            .doc(doc_addr)
+            .map_err(|error| NodeErrorKind::IndexSearchFailure.with_error(error))?;
+
+        let id = doc
+            .get_first(doc_id_field)
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure
+                    .with_error(anyhow::anyhow!("{:?} field does not exist.", doc_id_field))
+            })?
+            .as_text()
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                    "{:?} field is not text type.",
+                    doc_id_field
+                ))
+            })?
+            .to_string();
+
+        let timestamp = doc
+            .get_first(doc_timestamp_field)
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                    "{:?} field does not exist.",
+                    doc_timestamp_field
+                ))
+            })?
+            .as_date()
+            .ok_or_else(|| {
+                NodeErrorKind::IndexSearchFailure.with_error(anyhow::anyhow!(
+                    "{:?} field is not text type.",
+                    doc_id_field
+                ))
+            })?
+            .into_utc()
+            .unix_timestamp();
+
+        let mut new_doc = TDocument::new();
+        for field in fields.iter() {
+            for (doc_field, doc_field_values) in doc.get_sorted_field_values() {
+                if field == schema.get_field_name(doc_field)
+                    && (field != DOC_ID_FIELD_NAME || field != DOC_TIMESTAMP_FIELD_NAME)
+                {
+                    for doc_field_value in doc_field_values.into_iter().cloned() {
+                        new_doc.add_field_value(doc_field, doc_field_value);
+                    }
+                }
+            }
+        }
+
+        let doc_json = schema.to_json(&new_doc);
+
+        let doc = Document {
+            id,
+            score: 0.0,
+            timestamp,
+            sort_value,
+            fields: doc_json.as_bytes().to_vec(),
+        };
+
+        documents.push(doc);
+    }
+
+    Ok((total_hits, documents))
+}
+
 pub fn search_order_by_u64_asc(
     searcher: &LeasedItem<Searcher>,
     query: Box<dyn Query>,
@@ -0,0 +1,483 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::SocketAddr,
+    path::Path,
+    time::Duration,
+};
+
+use futures_util::{future::join_all, stream, Stream, StreamExt, TryStreamExt};
+use tantivy::Index;
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncWriteExt},
+    time::timeout,
+};
+use tonic::Code;
+use tracing::{info, warn};
+
+use crate::{
+    client::create_channel,
+    common::write_file,
+    proto::shard::{
+        shard_service_client::ShardServiceClient, DownloadShardRequest, GetShardStatusRequest,
+        ShardFileChunk,
+    },
+    tls::TlsConfig,
+};
+
+use super::{NodeError, NodeErrorKind};
+
+// The file that tantivy writes at the end of a commit.
+const META_FILE_NAME: &str = "meta.json";
+const CHUNK_SIZE: usize = 1024 * 1024;
+const SNAPSHOT_ATTEMPTS: usize = 3;
+// The time to wait for a response, or for the next chunk of a download.
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+// The number of the attempts to copy a shard while some of the nodes fail to respond.
+const MAX_HANDOFF_ATTEMPTS: usize = 3;
+
+/// The files of the last commit of a shard. The files are kept open, so that they can be read
+/// even if the index writer removes them in the meantime.
+pub struct ShardSnapshot {
+    files: Vec<(String, File)>,
+    meta_bytes: Vec<u8>,
+}
+
+impl ShardSnapshot {
+    /// Opens the files of the segments of the last commit in the shard directory.
+    pub async fn take(index: &Index, shard_dir: &Path) -> Result<Self, NodeError> {
+        for _ in 0..SNAPSHOT_ATTEMPTS {
+            let metas = index
+                .load_metas()
+                .map_err(|error| NodeErrorKind::ShardSnapshotFailure.with_error(error))?;
+            let paths = metas
+                .segments
+                .iter()
+                .flat_map(|segment| segment.list_files())
+                .collect::<HashSet<_>>();
+
+            let mut files = Vec::new();
+            for path in paths.iter() {
+                match File::open(shard_dir.join(path)).await {
+                    Ok(file) => files.push((path.to_string_lossy().into_owned(), file)),
+                    // Some of the files of a segment may not exist, e.g. the deletes.
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                    Err(error) => {
+                        return Err(NodeErrorKind::ShardSnapshotFailure.with_error(error));
+                    }
+                }
+            }
+
+            // The files are consistent only if no commit or merge has removed them while opening.
+            let current_paths = index
+                .load_metas()
+                .map_err(|error| NodeErrorKind::ShardSnapshotFailure.with_error(error))?
+                .segments
+                .iter()
+                .flat_map(|segment| segment.list_files())
+                .collect::<HashSet<_>>();
+            if current_paths != paths {
+                warn!(
+                    ?shard_dir,
+                    "The shard has been committed while taking a snapshot. Retrying."
+                );
+                continue;
+            }
+
+            let meta_bytes = serde_json::to_vec_pretty(&metas)
+                .map_err(|error| NodeErrorKind::ShardSnapshotFailure.with_error(error))?;
+
+            return Ok(Self { files, meta_bytes });
+        }
+
+        Err(
+            NodeErrorKind::ShardSnapshotFailure.with_error(anyhow::anyhow!(
+                "The shard is committed too often to take a snapshot."
+            )),
+        )
+    }
+
+    /// Reads the files in chunks. The `meta.json` is the last chunk, so that an interrupted
+    /// download does not leave an index that can be opened.
+    pub fn into_chunks(self) -> impl Stream<Item = io::Result<ShardFileChunk>> {
+        let meta_chunk = ShardFileChunk {
+            file_name: META_FILE_NAME.to_string(),
+            data: self.meta_bytes,
+        };
+
+        stream::iter(self.files)
+            .flat_map(|(file_name, file)| {
+                stream::unfold(Some((file, true)), move |state| {
+                    let file_name = file_name.clone();
+                    async move {
+                        let (mut file, is_first_chunk) = state?;
+                        let mut data = vec![0; CHUNK_SIZE];
+                        match file.read(&mut data).await {
+                            // An empty file is sent as a single empty chunk.
+                            Ok(0) if !is_first_chunk => None,
+                            Ok(len) => {
+                                data.truncate(len);
+                                let next_state = (len > 0).then_some((file, false));
+                                Some((Ok(ShardFileChunk { file_name, data }), next_state))
+                            }
+                            Err(error) => Some((Err(error), None)),
+                        }
+                    }
+                })
+            })
+            .chain(stream::once(async { Ok(meta_chunk) }))
+    }
+}
+
+/// Counts the failed attempts to copy the shards, so that a node that does not respond delays
+/// the creation of an empty shard only for a while.
+#[derive(Default)]
+pub struct HandoffAttempts {
+    attempts: HashMap<(String, String), usize>, // (index_name, shard_id) -> attempts
+}
+
+impl HandoffAttempts {
+    /// Records a failed attempt, and returns whether the shard should be copied again.
+    pub fn retry(&mut self, shard_key: &(String, String)) -> bool {
+        let attempts = self.attempts.entry(shard_key.clone()).or_default();
+        *attempts += 1;
+        if *attempts < MAX_HANDOFF_ATTEMPTS {
+            return true;
+        }
+        warn!(
+            ?shard_key,
+            attempts, "Giving up copying the shard from the nodes that do not respond."
+        );
+        self.attempts.remove(shard_key);
+        false
+    }
+
+    pub fn remove(&mut self, shard_key: &(String, String)) {
+        self.attempts.remove(shard_key);
+    }
+}
+
+/// Returns the opstamp of the last commit of the shard on the node at the gRPC address, or
+/// `None` if the node does not serve the shard.
+pub async fn get_shard_status(
+    grpc_address: &SocketAddr,
+    tls_config: Option<&TlsConfig>,
+    index_name: &str,
+    shard_id: &str,
+) -> Result<Option<u64>, NodeError> {
+    let channel = create_channel(grpc_address, tls_config)
+        .map_err(|error| NodeErrorKind::ShardStatusFailure.with_error(error))?;
+    let mut client = ShardServiceClient::new(channel);
+    let request = GetShardStatusRequest {
+        index_name: index_name.to_string(),
+        shard_id: shard_id.to_string(),
+    };
+    let response = timeout(RPC_TIMEOUT, client.get_shard_status(request))
+        .await
+        .map_err(|error| NodeErrorKind::ShardStatusFailure.with_error(error))?
+        .map_err(|error| NodeErrorKind::ShardStatusFailure.with_error(error))?
+        .into_inner();
+
+    Ok(response.serving.then_some(response.opstamp))
+}
+
+/// Asks the nodes at the gRPC addresses concurrently for the status of the shard.
+/// The results are in the order of the addresses.
+pub async fn get_shard_statuses(
+    grpc_addresses: &[SocketAddr],
+    tls_config: Option<&TlsConfig>,
+    index_name: &str,
+    shard_id: &str,
+) -> Vec<Result<Option<u64>, NodeError>> {
+    join_all(
+        grpc_addresses
+            .iter()
+            .map(|grpc_address| get_shard_status(grpc_address, tls_config, index_name, shard_id)),
+    )
+    .await
+}
+
+/// Returns whether the shard has been handed off to all of its owners, given the opstamps of
+/// their last commits, or `None` for the owners that do not serve it. The owners that have
+/// downloaded the shard from this node must have the last commit of this node, because the
+/// commits made after their downloads have not been copied.
+pub fn is_handed_off(
+    owner_opstamps: &[(SocketAddr, Option<u64>)],
+    downloaders: &HashSet<String>,
+    committed_opstamp: u64,
+) -> bool {
+    !owner_opstamps.is_empty()
+        && owner_opstamps
+            .iter()
+            .all(|(grpc_address, opstamp)| match opstamp {
+                Some(opstamp) => {
+                    *opstamp >= committed_opstamp
+                        || !downloaders.contains(&grpc_address.to_string())
+                }
+                None => false,
+            })
+}
+
+/// Writes the chunks of the files of a shard into the shard directory, which is removed if
+/// the chunks are not complete.
+pub async fn write_shard<S>(mut chunks: S, shard_dir: &Path) -> Result<(), NodeError>
+where
+    S: Stream<Item = Result<ShardFileChunk, NodeError>> + Unpin,
+{
+    fs::create_dir_all(shard_dir)
+        .await
+        .map_err(|error| NodeErrorKind::DirectoryCreationFailure.with_error(error))?;
+    let result = async {
+        let mut file: Option<(String, File)> = None;
+        let mut meta_bytes = Vec::new();
+        // The download is abandoned if the node stops sending the chunks.
+        while let Some(chunk) = timeout(RPC_TIMEOUT, chunks.next())
+            .await
+            .map_err(|error| NodeErrorKind::ShardDownloadFailure.with_error(error))?
+        {
+            let chunk = chunk?;
+
+            // The file names come from another node, so they must not point outside of the shard.
+            if Path::new(&chunk.file_name).file_name()
+                != Some(Path::new(&chunk.file_name).as_os_str())
+            {
+                return Err(NodeErrorKind::InvalidPath.with_error(anyhow::anyhow!(
+                    "Invalid shard file name: {:?}",
+                    chunk.file_name
+                )));
+            }
+
+            // The meta.json is written at last, so that the index is opened only if it is complete.
+            if chunk.file_name == META_FILE_NAME {
+                meta_bytes.extend_from_slice(&chunk.data);
+                continue;
+            }
+
+            if file.as_ref().map(|(file_name, _)| file_name) != Some(&chunk.file_name) {
+                let new_file = File::create(shard_dir.join(&chunk.file_name))
+                    .await
+                    .map_err(|error| NodeErrorKind::FileWriteFailure.with_error(error))?;
+                if let Some((_, previous_file)) = file.replace((chunk.file_name, new_file)) {
+                    previous_file
+                        .sync_all()
+                        .await
+                        .map_err(|error| NodeErrorKind::FileWriteFailure.with_error(error))?;
+                }
+            }
+            if let Some((_, current_file)) = file.as_mut() {
+                current_file
+                    .write_all(&chunk.data)
+                    .await
+                    .map_err(|error| NodeErrorKind::FileWriteFailure.with_error(error))?;
+            }
+        }
+        if let Some((_, last_file)) = file {
+            last_file
+                .sync_all()
+                .await
+                .map_err(|error| NodeErrorKind::FileWriteFailure.with_error(error))?;
+        }
+
+        if meta_bytes.is_empty() {
+            return Err(NodeErrorKind::ShardDownloadFailure
+                .with_error(anyhow::anyhow!("The shard has no meta.json.")));
+        }
+        write_file(&shard_dir.join(META_FILE_NAME), &meta_bytes)
+            .await
+            .map_err(|error| NodeErrorKind::FileWriteFailure.with_error(error))
+    }
+    .await;
+
+    if let Err(error) = result {
+        if let Err(remove_error) = fs::remove_dir_all(shard_dir).await {
+            warn!(
+                ?shard_dir,
+                ?remove_error,
+                "Failed to remove incomplete shard."
+            );
+        }
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+/// Downloads the shard from the node at the gRPC address into the shard directory. The node ID
+/// is the gRPC address of this node. Returns `false` if the node does not serve the shard.
+pub async fn download_shard(
+    grpc_address: &SocketAddr,
+    tls_config: Option<&TlsConfig>,
+    node_id: &str,
+    index_name: &str,
+    shard_id: &str,
+    shard_dir: &Path,
+) -> Result<bool, NodeError> {
+    let channel = create_channel(grpc_address, tls_config)
+        .map_err(|error| NodeErrorKind::ShardDownloadFailure.with_error(error))?;
+    let mut client = ShardServiceClient::new(channel);
+    let request = DownloadShardRequest {
+        index_name: index_name.to_string(),
+        shard_id: shard_id.to_string(),
+        node_id: node_id.to_string(),
+    };
+    let stream = match timeout(RPC_TIMEOUT, client.download_shard(request))
+        .await
+        .map_err(|error| NodeErrorKind::ShardDownloadFailure.with_error(error))?
+    {
+        Ok(response) => response.into_inner(),
+        Err(status) if status.code() == Code::NotFound => return Ok(false),
+        Err(status) => return Err(NodeErrorKind::ShardDownloadFailure.with_error(status)),
+    };
+
+    info!(?grpc_address, ?shard_dir, "Downloading shard.");
+    write_shard(
+        stream.map_err(|error| NodeErrorKind::ShardDownloadFailure.with_error(error)),
+        shard_dir,
+    )
+    .await?;
+
+    info!(?grpc_address, ?shard_dir, "Downloaded shard.");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, net::SocketAddr};
+
+    use futures_util::{stream, TryStreamExt};
+    use tantivy::{
+        collector::Count,
+        doc,
+        query::AllQuery,
+        schema::{Schema, STORED, TEXT},
+        Index,
+    };
+    use tempdir::TempDir;
+
+    use crate::{
+        node::{
+            transfer::{
+                is_handed_off, write_shard, HandoffAttempts, ShardSnapshot, MAX_HANDOFF_ATTEMPTS,
+                META_FILE_NAME,
+            },
+            NodeErrorKind,
+        },
+        proto::shard::ShardFileChunk,
+    };
+
+    #[tokio::test]
+    async fn test_shard_snapshot() {
+        let tmp_dir = TempDir::new("test_shard_snapshot").unwrap();
+        let src_dir = tmp_dir.path().join("src");
+        let dst_dir = tmp_dir.path().join("dst");
+        std::fs::create_dir_all(&src_dir).unwrap();
+
+        let mut schema_builder = Schema::builder();
+        let title = schema_builder.add_text_field("title", TEXT | STORED);
+        let index = Index::create_in_dir(&src_dir, schema_builder.build()).unwrap();
+        let mut index_writer = index.writer_with_num_threads(1, 10_000_000).unwrap();
+        index_writer.add_document(doc!(title => "foo")).unwrap();
+        index_writer.add_document(doc!(title => "bar")).unwrap();
+        let opstamp = index_writer.commit().unwrap();
+
+        // The documents that have not been committed are not copied.
+        index_writer.add_document(doc!(title => "baz")).unwrap();
+
+        let snapshot = ShardSnapshot::take(&index, &src_dir).await.unwrap();
+        let chunks = snapshot
+            .into_chunks()
+            .map_err(|error| NodeErrorKind::ShardSnapshotFailure.with_error(error));
+        write_shard(Box::pin(chunks), &dst_dir).await.unwrap();
+
+        let copied_index = Index::open_in_dir(&dst_dir).unwrap();
+        assert_eq!(copied_index.load_metas().unwrap().opstamp, opstamp);
+        let searcher = copied_index.reader().unwrap().searcher();
+        assert_eq!(searcher.search(&AllQuery, &Count).unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_write_shard_incomplete() {
+        let tmp_dir = TempDir::new("test_write_shard_incomplete").unwrap();
+        let shard_dir = tmp_dir.path().join("shard");
+        let chunk = |file_name: &str| {
+            Ok(ShardFileChunk {
+                file_name: file_name.to_string(),
+                data: b"data".to_vec(),
+            })
+        };
+
+        // A shard without the meta.json cannot be opened, so it is removed.
+        let result = write_shard(stream::iter(vec![chunk("foo.idx")]), &shard_dir).await;
+        assert!(result.is_err());
+        assert!(!shard_dir.exists());
+
+        // The file names must not point outside of the shard directory.
+        let result = write_shard(
+            stream::iter(vec![chunk("../foo.idx"), chunk(META_FILE_NAME)]),
+            &shard_dir,
+        )
+        .await;
+        assert!(matches!(
+            result.unwrap_err().kind(),
+            NodeErrorKind::InvalidPath
+        ));
+        assert!(!shard_dir.exists());
+        assert!(!tmp_dir.path().join("foo.idx").exists());
+    }
+
+    #[test]
+    fn test_is_handed_off() {
+        let owner1 = "127.0.0.1:5001".parse::<SocketAddr>().unwrap();
+        let owner2 = "127.0.0.1:5002".parse::<SocketAddr>().unwrap();
+        let mut downloaders = HashSet::new();
+
+        // All owners must serve the shard.
+        assert!(!is_handed_off(&[], &downloaders, 10));
+        assert!(!is_handed_off(
+            &[(owner1, Some(10)), (owner2, None)],
+            &downloaders,
+            10
+        ));
+        assert!(is_handed_off(
+            &[(owner1, Some(10)), (owner2, Some(3))],
+            &downloaders,
+            10
+        ));
+
+        // An owner that has downloaded the shard before the last commit of this node does
+        // not have all of the documents.
+        downloaders.insert(owner2.to_string());
+        assert!(!is_handed_off(
+            &[(owner1, Some(10)), (owner2, Some(3))],
+            &downloaders,
+            10
+        ));
+        assert!(is_handed_off(
+            &[(owner1, Some(10)), (owner2, Some(12))],
+            &downloaders,
+            10
+        ));
+    }
+
+    #[test]
+    fn test_handoff_attempts() {
+        let mut handoff_attempts = HandoffAttempts::default();
+        let foo = ("foo".to_string(), "shard1".to_string());
+        let bar = ("bar".to_string(), "shard1".to_string());
+
+        // The shard is copied again until the attempts run out.
+        for _ in 1..MAX_HANDOFF_ATTEMPTS {
+            assert!(handoff_attempts.retry(&foo));
+        }
+        assert!(handoff_attempts.retry(&bar));
+        assert!(!handoff_attempts.retry(&foo));
+
+        // The attempts start over once they have run out or the shard has been copied.
+        assert!(handoff_attempts.retry(&foo));
+        handoff_attempts.remove(&bar);
+        for _ in 1..MAX_HANDOFF_ATTEMPTS {
+            assert!(handoff_attempts.retry(&bar));
+        }
+    }
+}
@@ -2,3 +2,5 @@ pub mod cluster;
 pub mod common;
 pub mod healthcheck;
 pub mod index;
+pub mod raft;
+pub mod shard;
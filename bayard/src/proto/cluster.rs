@@ -6,6 +6,12 @@ pub struct Metadata {
     pub grpc_address: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub http_address: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub zone: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "4")]
+    pub capacity: u32,
+    #[prost(bool, tag = "5")]
+    pub leaving: bool,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -27,6 +33,47 @@ pub struct GetNodesResponse {
     #[prost(message, repeated, tag = "1")]
     pub nodes: ::prost::alloc::vec::Vec<Node>,
 }
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Replica {
+    #[prost(string, tag = "1")]
+    pub address: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub zone: ::prost::alloc::string::String,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PlacementViolation {
+    #[prost(string, tag = "1")]
+    pub index_name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub shard_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub replicas: ::prost::alloc::vec::Vec<Replica>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetPlacementViolationsRequest {}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetPlacementViolationsResponse {
+    #[prost(uint32, tag = "1")]
+    pub num_zones: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub violations: ::prost::alloc::vec::Vec<PlacementViolation>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DecommissionNodeRequest {}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DecommissionNodeResponse {}
 /// Generated client implementations.
 pub mod cluster_service_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -109,6 +156,37 @@ pub mod cluster_service_client {
             let path = http::uri::PathAndQuery::from_static("/cluster.ClusterService/GetNodes");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn get_placement_violations(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetPlacementViolationsRequest>,
+        ) -> Result<tonic::Response<super::GetPlacementViolationsResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/cluster.ClusterService/GetPlacementViolations",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn decommission_node(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DecommissionNodeRequest>,
+        ) -> Result<tonic::Response<super::DecommissionNodeResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/cluster.ClusterService/DecommissionNode");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -122,6 +200,14 @@ pub mod cluster_service_server {
             &self,
             request: tonic::Request<super::GetNodesRequest>,
         ) -> Result<tonic::Response<super::GetNodesResponse>, tonic::Status>;
+        async fn get_placement_violations(
+            &self,
+            request: tonic::Request<super::GetPlacementViolationsRequest>,
+        ) -> Result<tonic::Response<super::GetPlacementViolationsResponse>, tonic::Status>;
+        async fn decommission_node(
+            &self,
+            request: tonic::Request<super::DecommissionNodeRequest>,
+        ) -> Result<tonic::Response<super::DecommissionNodeResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct ClusterServiceServer<T: ClusterService> {
@@ -207,6 +293,75 @@ pub mod cluster_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/cluster.ClusterService/GetPlacementViolations" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetPlacementViolationsSvc<T: ClusterService>(pub Arc<T>);
+                    impl<T: ClusterService>
+                        tonic::server::UnaryService<super::GetPlacementViolationsRequest>
+                        for GetPlacementViolationsSvc<T>
+                    {
+                        type Response = super::GetPlacementViolationsResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetPlacementViolationsRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut =
+                                async move { (*inner).get_placement_violations(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetPlacementViolationsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/cluster.ClusterService/DecommissionNode" => {
+                    #[allow(non_camel_case_types)]
+                    struct DecommissionNodeSvc<T: ClusterService>(pub Arc<T>);
+                    impl<T: ClusterService>
+                        tonic::server::UnaryService<super::DecommissionNodeRequest>
+                        for DecommissionNodeSvc<T>
+                    {
+                        type Response = super::DecommissionNodeResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DecommissionNodeRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).decommission_node(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DecommissionNodeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
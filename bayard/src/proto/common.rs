@@ -17,6 +17,10 @@ impl Kind {
             "range" => Some(Kind::Range),
             "regex" => Some(Kind::Regex),
             "term" => Some(Kind::Term),
+            "more_like_this" => Some(Kind::MoreLikeThis),
+            "function_score" => Some(Kind::FunctionScore),
+            "geo_distance" => Some(Kind::GeoDistance),
+            "geo_bounding_box" => Some(Kind::GeoBoundingBox),
             _ => None,
         }
     }
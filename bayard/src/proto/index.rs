@@ -141,6 +141,14 @@ pub mod query {
         Regex = 8,
         #[serde(rename = "term")]
         Term = 9,
+        #[serde(rename = "more_like_this")]
+        MoreLikeThis = 10,
+        #[serde(rename = "function_score")]
+        FunctionScore = 11,
+        #[serde(rename = "geo_distance")]
+        GeoDistance = 12,
+        #[serde(rename = "geo_bounding_box")]
+        GeoBoundingBox = 13,
     }
     impl Kind {
         /// String value of the enum field names used in the ProtoBuf definition.
@@ -159,6 +167,10 @@ pub mod query {
                 Kind::Range => "RANGE",
                 Kind::Regex => "REGEX",
                 Kind::Term => "TERM",
+                Kind::MoreLikeThis => "MORE_LIKE_THIS",
+                Kind::FunctionScore => "FUNCTION_SCORE",
+                Kind::GeoDistance => "GEO_DISTANCE",
+                Kind::GeoBoundingBox => "GEO_BOUNDING_BOX",
             }
         }
     }
@@ -171,6 +183,8 @@ pub struct Sort {
     pub field: ::prost::alloc::string::String,
     #[prost(enumeration = "sort::Order", tag = "2")]
     pub order: i32,
+    #[prost(message, optional, tag = "3")]
+    pub origin: ::core::option::Option<GeoPoint>,
 }
 /// Nested message and enum types in `Sort`.
 pub mod sort {
@@ -203,6 +217,15 @@ pub mod sort {
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GeoPoint {
+    #[prost(double, tag = "1")]
+    pub lat: f64,
+    #[prost(double, tag = "2")]
+    pub lon: f64,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Document {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
@@ -235,6 +258,8 @@ pub struct SearchRequest {
     pub offset: i32,
     #[prost(int32, tag = "8")]
     pub hits: i32,
+    #[prost(message, optional, tag = "9")]
+    pub post_filter: ::core::option::Option<Query>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -247,6 +272,133 @@ pub struct SearchResponse {
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetCacheStatsRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CacheStats {
+    #[prost(uint64, tag = "1")]
+    pub hits: u64,
+    #[prost(uint64, tag = "2")]
+    pub misses: u64,
+    #[prost(double, tag = "3")]
+    pub hit_rate: f64,
+    #[prost(uint64, tag = "4")]
+    pub entries: u64,
+    #[prost(uint64, tag = "5")]
+    pub memory_usage: u64,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ShardCacheStats {
+    #[prost(string, tag = "1")]
+    pub shard_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub filter_cache: ::core::option::Option<CacheStats>,
+    #[prost(message, optional, tag = "3")]
+    pub request_cache: ::core::option::Option<CacheStats>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetCacheStatsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub shards: ::prost::alloc::vec::Vec<ShardCacheStats>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AnalyzeRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub analyzer: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub field: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "4")]
+    pub definition: ::prost::alloc::vec::Vec<u8>,
+    #[prost(string, tag = "5")]
+    pub text: ::prost::alloc::string::String,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AnalyzedToken {
+    #[prost(string, tag = "1")]
+    pub text: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub offset_from: u64,
+    #[prost(uint64, tag = "3")]
+    pub offset_to: u64,
+    #[prost(uint64, tag = "4")]
+    pub position: u64,
+    #[prost(uint64, tag = "5")]
+    pub position_length: u64,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AnalyzeStage {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub tokens: ::prost::alloc::vec::Vec<AnalyzedToken>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AnalyzeResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub stages: ::prost::alloc::vec::Vec<AnalyzeStage>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReloadAnalyzersRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub analyzers: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReloadAnalyzersResponse {}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PutUserDictionaryRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub file_name: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "3")]
+    pub dictionary: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PutUserDictionaryResponse {}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeleteUserDictionaryRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub file_name: ::prost::alloc::string::String,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeleteUserDictionaryResponse {}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum CollectionKind {
@@ -467,6 +619,78 @@ pub mod index_service_client {
             let path = http::uri::PathAndQuery::from_static("/index.IndexService/Search");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn get_cache_stats(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetCacheStatsRequest>,
+        ) -> Result<tonic::Response<super::GetCacheStatsResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/index.IndexService/GetCacheStats");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn analyze(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AnalyzeRequest>,
+        ) -> Result<tonic::Response<super::AnalyzeResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/index.IndexService/Analyze");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn reload_analyzers(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ReloadAnalyzersRequest>,
+        ) -> Result<tonic::Response<super::ReloadAnalyzersResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/index.IndexService/ReloadAnalyzers");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn put_user_dictionary(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PutUserDictionaryRequest>,
+        ) -> Result<tonic::Response<super::PutUserDictionaryResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/index.IndexService/PutUserDictionary");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn delete_user_dictionary(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DeleteUserDictionaryRequest>,
+        ) -> Result<tonic::Response<super::DeleteUserDictionaryResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/index.IndexService/DeleteUserDictionary");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -512,6 +736,26 @@ pub mod index_service_server {
             &self,
             request: tonic::Request<super::SearchRequest>,
         ) -> Result<tonic::Response<super::SearchResponse>, tonic::Status>;
+        async fn get_cache_stats(
+            &self,
+            request: tonic::Request<super::GetCacheStatsRequest>,
+        ) -> Result<tonic::Response<super::GetCacheStatsResponse>, tonic::Status>;
+        async fn analyze(
+            &self,
+            request: tonic::Request<super::AnalyzeRequest>,
+        ) -> Result<tonic::Response<super::AnalyzeResponse>, tonic::Status>;
+        async fn reload_analyzers(
+            &self,
+            request: tonic::Request<super::ReloadAnalyzersRequest>,
+        ) -> Result<tonic::Response<super::ReloadAnalyzersResponse>, tonic::Status>;
+        async fn put_user_dictionary(
+            &self,
+            request: tonic::Request<super::PutUserDictionaryRequest>,
+        ) -> Result<tonic::Response<super::PutUserDictionaryResponse>, tonic::Status>;
+        async fn delete_user_dictionary(
+            &self,
+            request: tonic::Request<super::DeleteUserDictionaryRequest>,
+        ) -> Result<tonic::Response<super::DeleteUserDictionaryResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct IndexServiceServer<T: IndexService> {
@@ -849,6 +1093,171 @@ pub mod index_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/index.IndexService/GetCacheStats" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetCacheStatsSvc<T: IndexService>(pub Arc<T>);
+                    impl<T: IndexService> tonic::server::UnaryService<super::GetCacheStatsRequest>
+                        for GetCacheStatsSvc<T>
+                    {
+                        type Response = super::GetCacheStatsResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetCacheStatsRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_cache_stats(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetCacheStatsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/index.IndexService/Analyze" => {
+                    #[allow(non_camel_case_types)]
+                    struct AnalyzeSvc<T: IndexService>(pub Arc<T>);
+                    impl<T: IndexService> tonic::server::UnaryService<super::AnalyzeRequest> for AnalyzeSvc<T> {
+                        type Response = super::AnalyzeResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AnalyzeRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).analyze(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = AnalyzeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/index.IndexService/ReloadAnalyzers" => {
+                    #[allow(non_camel_case_types)]
+                    struct ReloadAnalyzersSvc<T: IndexService>(pub Arc<T>);
+                    impl<T: IndexService> tonic::server::UnaryService<super::ReloadAnalyzersRequest>
+                        for ReloadAnalyzersSvc<T>
+                    {
+                        type Response = super::ReloadAnalyzersResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ReloadAnalyzersRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).reload_analyzers(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ReloadAnalyzersSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/index.IndexService/PutUserDictionary" => {
+                    #[allow(non_camel_case_types)]
+                    struct PutUserDictionarySvc<T: IndexService>(pub Arc<T>);
+                    impl<T: IndexService>
+                        tonic::server::UnaryService<super::PutUserDictionaryRequest>
+                        for PutUserDictionarySvc<T>
+                    {
+                        type Response = super::PutUserDictionaryResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PutUserDictionaryRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).put_user_dictionary(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = PutUserDictionarySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/index.IndexService/DeleteUserDictionary" => {
+                    #[allow(non_camel_case_types)]
+                    struct DeleteUserDictionarySvc<T: IndexService>(pub Arc<T>);
+                    impl<T: IndexService>
+                        tonic::server::UnaryService<super::DeleteUserDictionaryRequest>
+                        for DeleteUserDictionarySvc<T>
+                    {
+                        type Response = super::DeleteUserDictionaryResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DeleteUserDictionaryRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).delete_user_dictionary(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DeleteUserDictionarySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
@@ -0,0 +1,310 @@
+pub mod log;
+pub mod replica;
+
+use std::{fmt, path::Path, sync::Arc};
+
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    time::{sleep, Duration},
+};
+use tokio_stream::Stream;
+use tracing::{error, info, warn};
+
+use crate::{
+    index::metastore::{Command, Metastore},
+    proto::raft::{
+        AppendEntriesRequest, AppendEntriesResponse, GetDigestsRequest, GetDigestsResponse,
+        GetSnapshotRequest, GetSnapshotResponse, InstallSnapshotRequest, InstallSnapshotResponse,
+        RequestVoteRequest, RequestVoteResponse,
+    },
+    tls::TlsConfig,
+};
+
+use self::{
+    log::RaftLog,
+    replica::{Event, Replica},
+};
+
+pub const RAFT_DIR: &str = "raft";
+pub const DEFAULT_SNAPSHOT_THRESHOLD: u64 = 1000;
+
+const IMPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaftErrorKind {
+    StorageFailure,
+    SerializationFailure,
+    DeserializationFailure,
+    NotLeader,
+    LeadershipLost,
+    ApplyFailure,
+    Shutdown,
+}
+
+impl RaftErrorKind {
+    pub fn with_error<E>(self, source: E) -> RaftError
+    where
+        anyhow::Error: From<E>,
+    {
+        RaftError {
+            kind: self,
+            source: From::from(source),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("RaftError(kind={kind:?}, source={source})")]
+pub struct RaftError {
+    pub kind: RaftErrorKind,
+    #[source]
+    source: anyhow::Error,
+}
+
+impl RaftError {
+    pub fn add_context<C>(self, ctx: C) -> Self
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        RaftError {
+            kind: self.kind,
+            source: self.source.context(ctx),
+        }
+    }
+
+    pub fn kind(&self) -> RaftErrorKind {
+        self.kind
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub role: Role,
+    pub term: u64,
+    pub leader: Option<String>,
+    pub commit_index: u64,
+    pub applied_index: u64,
+    pub is_voter: bool,
+}
+
+/// A member of the cluster, which is known from the gossip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RaftMember {
+    pub id: String,
+    /// The leaving members are removed from the voters by the leader.
+    pub leaving: bool,
+}
+
+/// The Raft node that replicates the commands of the [`Metastore`].
+/// The nodes are identified by their gRPC addresses. Only the voters take part in the
+/// elections and the commitment, while the other members of the cluster are replicated to as
+/// the learners. The voters are given at startup, and the changes to them are replicated as
+/// entries of the log.
+pub struct Raft {
+    id: String,
+    sender: mpsc::UnboundedSender<Event>,
+    status_receiver: watch::Receiver<Status>,
+}
+
+impl Raft {
+    /// The members are the nodes in the cluster, which are replicated to as the learners unless
+    /// they are the voters.
+    pub async fn new<S>(
+        raft_dir: &Path,
+        id: String,
+        voters: Vec<String>,
+        snapshot_threshold: u64,
+        metastore: Arc<Metastore>,
+        members: S,
+        tls_config: Option<TlsConfig>,
+    ) -> Result<Self, RaftError>
+    where
+        S: Stream<Item = Vec<RaftMember>> + Send + Unpin + 'static,
+    {
+        let log = RaftLog::open(raft_dir).await?;
+
+        // Restore the committed state, so that the metastore is up to date before the leader
+        // is known.
+        if let Some(data) = log.snapshot_data() {
+            info!(index = log.snapshot_index(), "Restoring Raft snapshot.");
+            metastore
+                .restore(data)
+                .await
+                .map_err(|error| RaftErrorKind::ApplyFailure.with_error(error))?;
+        }
+        let commit_index = log.hard_state().commit_index.min(log.last_index());
+        for index in log.snapshot_index() + 1..=commit_index {
+            if let Some(command) = log.entry(index).and_then(|entry| entry.command.as_ref()) {
+                if let Err(error) = metastore.apply(index, command).await {
+                    warn!(index, ?error, "Failed to apply command.");
+                }
+            }
+        }
+        info!(commit_index, "Raft log has been replayed.");
+
+        // The indices that have been left in the index directories are imported by the leader.
+        if let Err(error) = metastore.load_pending_imports().await {
+            error!(?error, "Failed to load index metadatas to import.");
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (status_sender, status_receiver) = watch::channel(Status {
+            role: Role::Follower,
+            term: log.hard_state().current_term,
+            leader: None,
+            commit_index,
+            applied_index: commit_index,
+            is_voter: log.voters().unwrap_or(&voters).contains(&id),
+        });
+
+        let replica = Replica::new(
+            id.clone(),
+            voters,
+            snapshot_threshold,
+            log,
+            commit_index,
+            Arc::clone(&metastore),
+            tls_config,
+            sender.clone(),
+            status_sender,
+        );
+        tokio::spawn(replica.run(receiver, members));
+
+        let raft = Raft {
+            id,
+            sender,
+            status_receiver,
+        };
+        raft.import_indices(metastore);
+
+        Ok(raft)
+    }
+
+    fn import_indices(&self, metastore: Arc<Metastore>) {
+        let sender = self.sender.clone();
+        let mut status_receiver = self.status_receiver.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let pending_imports = metastore.pending_imports().await;
+                if pending_imports.is_empty() {
+                    break;
+                }
+
+                if status_receiver.borrow_and_update().role == Role::Leader {
+                    for (name, metadata) in pending_imports {
+                        info!(?name, "Importing index metadata.");
+                        let command = Command::CreateIndex {
+                            name: name.clone(),
+                            metadata,
+                        };
+                        if let Err(error) = propose(&sender, command).await {
+                            warn!(?name, ?error, "Failed to import index metadata.");
+                        }
+                    }
+                }
+
+                sleep(IMPORT_INTERVAL).await;
+            }
+        });
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn status(&self) -> Status {
+        self.status_receiver.borrow().clone()
+    }
+
+    pub fn leader(&self) -> Option<String> {
+        self.status_receiver.borrow().leader.clone()
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.status_receiver.borrow().role == Role::Leader
+    }
+
+    pub fn is_voter(&self) -> bool {
+        self.status_receiver.borrow().is_voter
+    }
+
+    /// Append a command to the log and wait until it has been committed and applied to the
+    /// metastore of this node. Only the leader accepts the commands.
+    pub async fn propose(&self, command: Command) -> Result<(), RaftError> {
+        propose(&self.sender, command).await
+    }
+
+    pub async fn request_vote(
+        &self,
+        request: RequestVoteRequest,
+    ) -> Result<RequestVoteResponse, RaftError> {
+        let (tx, rx) = oneshot::channel();
+        send(&self.sender, Event::RequestVote(request, tx))?;
+        rx.await
+            .map_err(|error| RaftErrorKind::Shutdown.with_error(error))
+    }
+
+    pub async fn append_entries(
+        &self,
+        request: AppendEntriesRequest,
+    ) -> Result<AppendEntriesResponse, RaftError> {
+        let (tx, rx) = oneshot::channel();
+        send(&self.sender, Event::AppendEntries(request, tx))?;
+        rx.await
+            .map_err(|error| RaftErrorKind::Shutdown.with_error(error))?
+    }
+
+    pub async fn install_snapshot(
+        &self,
+        request: InstallSnapshotRequest,
+    ) -> Result<InstallSnapshotResponse, RaftError> {
+        let (tx, rx) = oneshot::channel();
+        send(&self.sender, Event::InstallSnapshot(request, tx))?;
+        rx.await
+            .map_err(|error| RaftErrorKind::Shutdown.with_error(error))?
+    }
+
+    /// Exchange the digests of the index metadata with a peer. The peer pulls the snapshot
+    /// from this node if the digests show that it is behind.
+    pub async fn get_digests(
+        &self,
+        request: GetDigestsRequest,
+    ) -> Result<GetDigestsResponse, RaftError> {
+        let (tx, rx) = oneshot::channel();
+        send(&self.sender, Event::GetDigests(request, tx))?;
+        rx.await
+            .map_err(|error| RaftErrorKind::Shutdown.with_error(error))
+    }
+
+    /// Get a snapshot of the applied state, which is committed, so any node can serve it.
+    pub async fn get_snapshot(
+        &self,
+        _request: GetSnapshotRequest,
+    ) -> Result<GetSnapshotResponse, RaftError> {
+        let (tx, rx) = oneshot::channel();
+        send(&self.sender, Event::GetSnapshot(tx))?;
+        rx.await
+            .map_err(|error| RaftErrorKind::Shutdown.with_error(error))?
+    }
+}
+
+fn send(sender: &mpsc::UnboundedSender<Event>, event: Event) -> Result<(), RaftError> {
+    sender
+        .send(event)
+        .map_err(|_| RaftErrorKind::Shutdown.with_error(anyhow::anyhow!("Raft has been stopped.")))
+}
+
+async fn propose(sender: &mpsc::UnboundedSender<Event>, command: Command) -> Result<(), RaftError> {
+    let (tx, rx) = oneshot::channel();
+    send(sender, Event::Propose(command, tx))?;
+    rx.await
+        .map_err(|error| RaftErrorKind::Shutdown.with_error(error))?
+}
@@ -0,0 +1,588 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::AsyncWriteExt,
+};
+use tracing::{info, warn};
+
+use crate::{common::read_file, index::metastore::Command, proto::raft::Entry};
+
+use super::{RaftError, RaftErrorKind};
+
+const HARD_STATE_FILE: &str = "state.json";
+const LOG_FILE: &str = "log.jsonl";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const TMP_FILE_EXT: &str = "tmp";
+
+/// The state that must survive restarts. The commit index is persisted as well, so that the
+/// committed commands can be applied again at startup before the leader is known.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardState {
+    pub current_term: u64,
+    pub voted_for: Option<String>,
+    pub commit_index: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub term: u64,
+    pub index: u64,
+    /// The command is empty for the entries that the leaders append when they are elected.
+    pub command: Option<Command>,
+    /// The voters from this entry on, for the entries that change the voters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voters: Option<Vec<String>>,
+}
+
+impl LogEntry {
+    pub fn to_proto(&self) -> Result<Entry, RaftError> {
+        let command = match self.command.as_ref() {
+            Some(command) => serde_json::to_vec(command)
+                .map_err(|error| RaftErrorKind::SerializationFailure.with_error(error))?,
+            None => Vec::new(),
+        };
+        Ok(Entry {
+            term: self.term,
+            index: self.index,
+            command,
+            voters: self.voters.clone().unwrap_or_default(),
+        })
+    }
+
+    pub fn from_proto(entry: Entry) -> Result<Self, RaftError> {
+        let command = if entry.command.is_empty() {
+            None
+        } else {
+            Some(
+                serde_json::from_slice::<Command>(&entry.command)
+                    .map_err(|error| RaftErrorKind::DeserializationFailure.with_error(error))?,
+            )
+        };
+        Ok(LogEntry {
+            term: entry.term,
+            index: entry.index,
+            command,
+            voters: voters_from_proto(entry.voters),
+        })
+    }
+}
+
+/// The voters are sent as an empty list if they have never been changed.
+pub fn voters_from_proto(voters: Vec<String>) -> Option<Vec<String>> {
+    if voters.is_empty() {
+        None
+    } else {
+        Some(voters)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    last_index: u64,
+    last_term: u64,
+    data: serde_json::Value,
+    #[serde(default)]
+    voters: Option<Vec<String>>,
+}
+
+/// The Raft log that is stored in a directory.
+/// The entries are appended to a JSON lines file, which is rewritten when it is truncated or
+/// compacted. The entries up to the snapshot are discarded.
+pub struct RaftLog {
+    dir: PathBuf,
+    hard_state: HardState,
+    snapshot_index: u64,
+    snapshot_term: u64,
+    snapshot_data: Option<Vec<u8>>,
+    snapshot_voters: Option<Vec<String>>,
+    entries: Vec<LogEntry>, // the entries after the snapshot
+}
+
+impl RaftLog {
+    pub async fn open(dir: &Path) -> Result<Self, RaftError> {
+        if !dir.exists() {
+            info!(?dir, "Creating Raft directory.");
+            fs::create_dir_all(dir)
+                .await
+                .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+        }
+
+        let hard_state_path = dir.join(HARD_STATE_FILE);
+        let hard_state = if hard_state_path.exists() {
+            let bytes = read_file(&hard_state_path)
+                .await
+                .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+            serde_json::from_slice::<HardState>(&bytes)
+                .map_err(|error| RaftErrorKind::DeserializationFailure.with_error(error))?
+        } else {
+            HardState::default()
+        };
+
+        let snapshot_path = dir.join(SNAPSHOT_FILE);
+        let (snapshot_index, snapshot_term, snapshot_data, snapshot_voters) =
+            if snapshot_path.exists() {
+                let bytes = read_file(&snapshot_path)
+                    .await
+                    .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+                let snapshot = serde_json::from_slice::<SnapshotFile>(&bytes)
+                    .map_err(|error| RaftErrorKind::DeserializationFailure.with_error(error))?;
+                let data = serde_json::to_vec(&snapshot.data)
+                    .map_err(|error| RaftErrorKind::SerializationFailure.with_error(error))?;
+                (
+                    snapshot.last_index,
+                    snapshot.last_term,
+                    Some(data),
+                    snapshot.voters,
+                )
+            } else {
+                (0, 0, None, None)
+            };
+
+        let mut entries: Vec<LogEntry> = Vec::new();
+        let log_path = dir.join(LOG_FILE);
+        if log_path.exists() {
+            let bytes = read_file(&log_path)
+                .await
+                .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+            for line in bytes.split(|byte| *byte == b'\n') {
+                if line.is_empty() {
+                    continue;
+                }
+                let entry = match serde_json::from_slice::<LogEntry>(line) {
+                    Ok(entry) => entry,
+                    Err(error) => {
+                        // The last line may have been written partially.
+                        warn!(?error, "Ignoring the rest of the Raft log.");
+                        break;
+                    }
+                };
+                if entry.index <= snapshot_index {
+                    continue;
+                }
+                let expected_index = snapshot_index + entries.len() as u64 + 1;
+                if entry.index != expected_index {
+                    warn!(
+                        index = entry.index,
+                        expected_index, "Ignoring the rest of the Raft log."
+                    );
+                    break;
+                }
+                entries.push(entry);
+            }
+        }
+
+        Ok(RaftLog {
+            dir: dir.to_path_buf(),
+            hard_state,
+            snapshot_index,
+            snapshot_term,
+            snapshot_data,
+            snapshot_voters,
+            entries,
+        })
+    }
+
+    pub fn hard_state(&self) -> &HardState {
+        &self.hard_state
+    }
+
+    pub async fn save_hard_state(&mut self, hard_state: HardState) -> Result<(), RaftError> {
+        if self.hard_state == hard_state {
+            return Ok(());
+        }
+        let bytes = serde_json::to_vec(&hard_state)
+            .map_err(|error| RaftErrorKind::SerializationFailure.with_error(error))?;
+        write_file_durably(&self.dir.join(HARD_STATE_FILE), &bytes)
+            .await
+            .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+        self.hard_state = hard_state;
+        Ok(())
+    }
+
+    pub fn snapshot_index(&self) -> u64 {
+        self.snapshot_index
+    }
+
+    pub fn snapshot_term(&self) -> u64 {
+        self.snapshot_term
+    }
+
+    pub fn snapshot_data(&self) -> Option<&[u8]> {
+        self.snapshot_data.as_deref()
+    }
+
+    /// The voters of the latest configuration up to the index, which takes effect as soon as it
+    /// is appended. It is none if the voters have never been changed.
+    pub fn voters_at(&self, index: u64) -> Option<&[String]> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.index <= index)
+            .find_map(|entry| entry.voters.as_deref())
+            .or(self.snapshot_voters.as_deref())
+    }
+
+    pub fn voters(&self) -> Option<&[String]> {
+        self.voters_at(self.last_index())
+    }
+
+    pub fn last_index(&self) -> u64 {
+        self.snapshot_index + self.entries.len() as u64
+    }
+
+    pub fn last_term(&self) -> u64 {
+        self.entries
+            .last()
+            .map(|entry| entry.term)
+            .unwrap_or(self.snapshot_term)
+    }
+
+    /// The term of the entry at the index, which is known for the last entry of the snapshot
+    /// and the entries after it.
+    pub fn term(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot_index {
+            Some(self.snapshot_term)
+        } else {
+            self.entry(index).map(|entry| entry.term)
+        }
+    }
+
+    pub fn entry(&self, index: u64) -> Option<&LogEntry> {
+        if index <= self.snapshot_index {
+            return None;
+        }
+        self.entries.get((index - self.snapshot_index - 1) as usize)
+    }
+
+    pub fn entries_from(&self, index: u64, max_entries: usize) -> Vec<LogEntry> {
+        if index <= self.snapshot_index {
+            return Vec::new();
+        }
+        self.entries
+            .iter()
+            .skip((index - self.snapshot_index - 1) as usize)
+            .take(max_entries)
+            .cloned()
+            .collect()
+    }
+
+    /// Append the entries that follow the last entry.
+    pub async fn append(&mut self, entries: Vec<LogEntry>) -> Result<(), RaftError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut bytes = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.index != self.last_index() + i as u64 + 1 {
+                return Err(RaftErrorKind::StorageFailure.with_error(anyhow::anyhow!(
+                    "Entry {} does not follow the last entry {}.",
+                    entry.index,
+                    self.last_index()
+                )));
+            }
+            serde_json::to_writer(&mut bytes, entry)
+                .map_err(|error| RaftErrorKind::SerializationFailure.with_error(error))?;
+            bytes.push(b'\n');
+        }
+
+        let log_path = self.dir.join(LOG_FILE);
+        let is_new_file = !log_path.exists();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .await
+            .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+        file.write_all(&bytes)
+            .await
+            .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+        file.sync_data()
+            .await
+            .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+        if is_new_file {
+            sync_dir(&self.dir)
+                .await
+                .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+        }
+
+        self.entries.extend(entries);
+        Ok(())
+    }
+
+    /// Remove the entries from the index to the last one.
+    pub async fn truncate(&mut self, index: u64) -> Result<(), RaftError> {
+        if index <= self.snapshot_index || index > self.last_index() {
+            return Ok(());
+        }
+        self.entries
+            .truncate((index - self.snapshot_index - 1) as usize);
+        self.rewrite().await
+    }
+
+    /// Save the snapshot of the state up to the index and discard the entries up to it.
+    pub async fn compact(&mut self, index: u64, data: Vec<u8>) -> Result<(), RaftError> {
+        let term = self.term(index).ok_or_else(|| {
+            RaftErrorKind::StorageFailure
+                .with_error(anyhow::anyhow!("Entry {} is not in the log.", index))
+        })?;
+        let voters = self.voters_at(index).map(<[String]>::to_vec);
+        self.save_snapshot(index, term, data, voters).await?;
+
+        let retained = self
+            .entries
+            .split_off((index - self.snapshot_index) as usize);
+        self.entries = retained;
+        self.snapshot_index = index;
+        self.snapshot_term = term;
+        self.rewrite().await
+    }
+
+    /// Replace the log with a snapshot received from the leader. The entries following the
+    /// snapshot are kept if the log contains the last entry of the snapshot.
+    pub async fn install_snapshot(
+        &mut self,
+        index: u64,
+        term: u64,
+        data: Vec<u8>,
+        voters: Option<Vec<String>>,
+    ) -> Result<(), RaftError> {
+        let retained = if self.term(index) == Some(term) && index > self.snapshot_index {
+            self.entries
+                .split_off((index - self.snapshot_index) as usize)
+        } else {
+            Vec::new()
+        };
+        self.save_snapshot(index, term, data, voters).await?;
+
+        self.entries = retained;
+        self.snapshot_index = index;
+        self.snapshot_term = term;
+        self.rewrite().await
+    }
+
+    async fn save_snapshot(
+        &mut self,
+        index: u64,
+        term: u64,
+        data: Vec<u8>,
+        voters: Option<Vec<String>>,
+    ) -> Result<(), RaftError> {
+        let snapshot = SnapshotFile {
+            last_index: index,
+            last_term: term,
+            data: serde_json::from_slice(&data)
+                .map_err(|error| RaftErrorKind::DeserializationFailure.with_error(error))?,
+            voters: voters.clone(),
+        };
+        let bytes = serde_json::to_vec(&snapshot)
+            .map_err(|error| RaftErrorKind::SerializationFailure.with_error(error))?;
+        write_file_durably(&self.dir.join(SNAPSHOT_FILE), &bytes)
+            .await
+            .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))?;
+        info!(index, term, "Raft snapshot has been saved.");
+
+        self.snapshot_data = Some(data);
+        self.snapshot_voters = voters;
+        Ok(())
+    }
+
+    async fn rewrite(&self) -> Result<(), RaftError> {
+        let mut bytes = Vec::new();
+        for entry in self.entries.iter() {
+            serde_json::to_writer(&mut bytes, entry)
+                .map_err(|error| RaftErrorKind::SerializationFailure.with_error(error))?;
+            bytes.push(b'\n');
+        }
+        write_file_durably(&self.dir.join(LOG_FILE), &bytes)
+            .await
+            .map_err(|error| RaftErrorKind::StorageFailure.with_error(error))
+    }
+}
+
+/// Replaces the content of the file so that either the previous or the new content survives a
+/// crash. The voted candidate must never be forgotten, so the temporary file is synced before it
+/// is renamed over the file, and the directory is synced to persist the rename.
+async fn write_file_durably(path: &Path, content: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension(TMP_FILE_EXT);
+    let mut tmp_file = File::create(&tmp_path).await?;
+    tmp_file.write_all(content).await?;
+    tmp_file.sync_all().await?;
+    drop(tmp_file);
+
+    fs::rename(&tmp_path, path).await?;
+    match path.parent() {
+        Some(dir) => sync_dir(dir).await,
+        None => Ok(()),
+    }
+}
+
+async fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir).await?.sync_all().await
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::{
+        index::metastore::Command,
+        raft::log::{HardState, LogEntry, RaftLog},
+    };
+
+    fn entry(term: u64, index: u64) -> LogEntry {
+        LogEntry {
+            term,
+            index,
+            command: Some(Command::DeleteIndex {
+                name: format!("index{}", index),
+            }),
+            voters: None,
+        }
+    }
+
+    fn voters_entry(term: u64, index: u64, voters: &[&str]) -> LogEntry {
+        LogEntry {
+            term,
+            index,
+            command: None,
+            voters: Some(voters.iter().map(|voter| voter.to_string()).collect()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raft_log() {
+        let tmp_dir = TempDir::new("test_raft_log").unwrap();
+        let dir = tmp_dir.path().join("raft");
+
+        let mut log = RaftLog::open(&dir).await.unwrap();
+        assert_eq!(log.last_index(), 0);
+        assert_eq!(log.last_term(), 0);
+        assert_eq!(log.term(0), Some(0));
+
+        log.save_hard_state(HardState {
+            current_term: 2,
+            voted_for: Some("127.0.0.1:5000".to_string()),
+            commit_index: 3,
+        })
+        .await
+        .unwrap();
+        log.append(vec![entry(1, 1), entry(1, 2), entry(2, 3), entry(2, 4)])
+            .await
+            .unwrap();
+        // The entries must follow the last entry.
+        assert!(log.append(vec![entry(2, 6)]).await.is_err());
+
+        log.truncate(4).await.unwrap();
+        assert_eq!(log.last_index(), 3);
+
+        // Reopen the log.
+        let mut log = RaftLog::open(&dir).await.unwrap();
+        assert_eq!(log.hard_state().current_term, 2);
+        assert_eq!(log.hard_state().commit_index, 3);
+        assert_eq!(log.last_index(), 3);
+        assert_eq!(log.last_term(), 2);
+        assert_eq!(log.term(2), Some(1));
+        assert_eq!(log.entries_from(2, 10).len(), 2);
+
+        log.compact(2, br#"{"indices":{}}"#.to_vec()).await.unwrap();
+        assert_eq!(log.snapshot_index(), 2);
+        assert_eq!(log.term(2), Some(1));
+        assert!(log.entry(2).is_none());
+        assert_eq!(log.entry(3).unwrap().term, 2);
+
+        // Reopen the compacted log.
+        let mut log = RaftLog::open(&dir).await.unwrap();
+        assert_eq!(log.snapshot_index(), 2);
+        assert_eq!(log.last_index(), 3);
+        assert!(log.snapshot_data().is_some());
+
+        // A snapshot that does not match the log replaces the entries.
+        log.install_snapshot(5, 3, br#"{"indices":{}}"#.to_vec(), None)
+            .await
+            .unwrap();
+        assert_eq!(log.last_index(), 5);
+        assert_eq!(log.last_term(), 3);
+        log.append(vec![entry(3, 6)]).await.unwrap();
+
+        let log = RaftLog::open(&dir).await.unwrap();
+        assert_eq!(log.snapshot_index(), 5);
+        assert_eq!(log.last_index(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_voters() {
+        let tmp_dir = TempDir::new("test_voters").unwrap();
+        let dir = tmp_dir.path().join("raft");
+
+        let mut log = RaftLog::open(&dir).await.unwrap();
+        assert!(log.voters().is_none());
+        log.append(vec![
+            entry(1, 1),
+            voters_entry(1, 2, &["a", "b"]),
+            entry(1, 3),
+            voters_entry(1, 4, &["a"]),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(log.voters().unwrap(), ["a"]);
+        assert_eq!(log.voters_at(3).unwrap(), ["a", "b"]);
+        assert!(log.voters_at(1).is_none());
+
+        // The voters are reverted along with the truncated entries.
+        log.truncate(4).await.unwrap();
+        assert_eq!(log.voters().unwrap(), ["a", "b"]);
+
+        // The voters are kept in the snapshot.
+        log.compact(3, br#"{"indices":{}}"#.to_vec()).await.unwrap();
+        let mut log = RaftLog::open(&dir).await.unwrap();
+        assert_eq!(log.voters().unwrap(), ["a", "b"]);
+
+        log.install_snapshot(
+            5,
+            1,
+            br#"{"indices":{}}"#.to_vec(),
+            Some(vec!["b".to_string()]),
+        )
+        .await
+        .unwrap();
+        let log = RaftLog::open(&dir).await.unwrap();
+        assert_eq!(log.voters().unwrap(), ["b"]);
+    }
+
+    #[tokio::test]
+    async fn test_save_hard_state() {
+        let tmp_dir = TempDir::new("test_save_hard_state").unwrap();
+        let dir = tmp_dir.path().join("raft");
+
+        let mut log = RaftLog::open(&dir).await.unwrap();
+        for term in 1..=2 {
+            log.save_hard_state(HardState {
+                current_term: term,
+                voted_for: Some("127.0.0.1:5000".to_string()),
+                commit_index: 0,
+            })
+            .await
+            .unwrap();
+        }
+
+        // The hard state is replaced in place, without leaving the previous one aside.
+        let mut file_names = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        file_names.sort();
+        assert_eq!(file_names, vec!["state.json".to_string()]);
+
+        let log = RaftLog::open(&dir).await.unwrap();
+        assert_eq!(log.hard_state().current_term, 2);
+        assert_eq!(
+            log.hard_state().voted_for,
+            Some("127.0.0.1:5000".to_string())
+        );
+    }
+}
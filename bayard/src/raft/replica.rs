@@ -0,0 +1,2004 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use rand::Rng;
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    time::{sleep_until, timeout, Duration, Instant},
+};
+use tokio_stream::{Stream, StreamExt};
+use tonic::transport::Channel;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    client::create_channel,
+    index::metastore::{outdated_indices, Command, Digest, Metastore},
+    proto::raft::{
+        raft_service_client::RaftServiceClient, AppendEntriesRequest, AppendEntriesResponse,
+        GetDigestsRequest, GetDigestsResponse, GetSnapshotRequest, GetSnapshotResponse,
+        IndexDigest, InstallSnapshotRequest, InstallSnapshotResponse, RequestVoteRequest,
+        RequestVoteResponse,
+    },
+    tls::TlsConfig,
+};
+
+use super::{
+    log::{voters_from_proto, HardState, LogEntry, RaftLog},
+    RaftError, RaftErrorKind, RaftMember, Role, Status,
+};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+const ELECTION_TIMEOUT_MIN: Duration = Duration::from_millis(1500);
+const ELECTION_TIMEOUT_MAX: Duration = Duration::from_millis(3000);
+const RPC_TIMEOUT: Duration = Duration::from_millis(1000);
+const MAX_ENTRIES_PER_REQUEST: usize = 64;
+const ANTI_ENTROPY_INTERVAL: Duration = Duration::from_secs(10);
+
+type Reply<T> = oneshot::Sender<Result<T, RaftError>>;
+
+pub enum Event {
+    RequestVote(RequestVoteRequest, oneshot::Sender<RequestVoteResponse>),
+    AppendEntries(AppendEntriesRequest, Reply<AppendEntriesResponse>),
+    InstallSnapshot(InstallSnapshotRequest, Reply<InstallSnapshotResponse>),
+    Propose(Command, Reply<()>),
+    GetDigests(GetDigestsRequest, oneshot::Sender<GetDigestsResponse>),
+    GetSnapshot(Reply<GetSnapshotResponse>),
+    VoteResult {
+        peer_id: String,
+        term: u64,
+        result: Result<RequestVoteResponse, String>,
+    },
+    AppendEntriesResult {
+        peer_id: String,
+        term: u64,
+        prev_log_index: u64,
+        num_entries: u64,
+        result: Result<AppendEntriesResponse, String>,
+    },
+    InstallSnapshotResult {
+        peer_id: String,
+        term: u64,
+        last_included_index: u64,
+        result: Result<InstallSnapshotResponse, String>,
+    },
+    SnapshotPulled {
+        peer_id: String,
+        result: Result<GetSnapshotResponse, String>,
+    },
+}
+
+/// The replication progress of a follower, which is tracked by the leader.
+struct Progress {
+    next_index: u64,
+    match_index: u64,
+    in_flight: bool,
+}
+
+fn election_timeout() -> Duration {
+    rand::thread_rng().gen_range(ELECTION_TIMEOUT_MIN..ELECTION_TIMEOUT_MAX)
+}
+
+fn digest_to_proto(digest: &Digest) -> IndexDigest {
+    IndexDigest {
+        name: digest.name.clone(),
+        version: digest.version,
+        deleted: digest.deleted,
+    }
+}
+
+fn digest_from_proto(digest: IndexDigest) -> Digest {
+    Digest {
+        name: digest.name,
+        version: digest.version,
+        deleted: digest.deleted,
+    }
+}
+
+/// The indices that are outdated in the local digests, if the snapshot may be pulled from the
+/// peer. The snapshot is pulled only from the leader or from a peer that has applied a later
+/// entry than this node, so that a peer with a stale state does not roll back this node. The
+/// positions are the terms and the indices of the last applied entries.
+fn indices_to_pull(
+    local: &[Digest],
+    remote: &[Digest],
+    local_applied: (u64, u64),
+    remote_applied: (u64, u64),
+    from_leader: bool,
+) -> Vec<String> {
+    if !from_leader && remote_applied <= local_applied {
+        return Vec::new();
+    }
+    outdated_indices(local, remote)
+}
+
+async fn pull_snapshot(
+    mut client: RaftServiceClient<Channel>,
+) -> Result<GetSnapshotResponse, String> {
+    match timeout(RPC_TIMEOUT, client.get_snapshot(GetSnapshotRequest {})).await {
+        Ok(Ok(response)) => Ok(response.into_inner()),
+        Ok(Err(status)) => Err(status.to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// The task that owns the Raft state. The RPCs, the proposals and the responses from the peers
+/// are handled one at a time as events.
+pub struct Replica {
+    id: String,
+    initial_voters: Vec<String>,
+    voters: Vec<String>,
+    snapshot_threshold: u64,
+    log: RaftLog,
+    metastore: Arc<Metastore>,
+    tls_config: Option<TlsConfig>,
+    sender: mpsc::UnboundedSender<Event>,
+    status_sender: watch::Sender<Status>,
+    role: Role,
+    leader: Option<String>,
+    commit_index: u64,
+    applied_index: u64,
+    votes: HashSet<String>,
+    members: Vec<RaftMember>,
+    learners: Vec<String>,
+    progress: HashMap<String, Progress>,
+    clients: HashMap<String, RaftServiceClient<Channel>>,
+    proposals: BTreeMap<u64, (u64, Reply<()>)>, // index -> (term, reply)
+    deadline: Instant,
+    anti_entropy_deadline: Instant,
+}
+
+impl Replica {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: String,
+        voters: Vec<String>,
+        snapshot_threshold: u64,
+        log: RaftLog,
+        applied_index: u64,
+        metastore: Arc<Metastore>,
+        tls_config: Option<TlsConfig>,
+        sender: mpsc::UnboundedSender<Event>,
+        status_sender: watch::Sender<Status>,
+    ) -> Self {
+        // The voters that have been changed in the log take precedence over the ones given at
+        // startup.
+        let initial_voters = voters;
+        let voters = log
+            .voters()
+            .map(<[String]>::to_vec)
+            .unwrap_or_else(|| initial_voters.clone());
+
+        // A single voter does not have to wait for the other voters to start an election.
+        let deadline = if voters == [id.clone()] {
+            Instant::now()
+        } else {
+            Instant::now() + election_timeout()
+        };
+
+        Replica {
+            id,
+            initial_voters,
+            voters,
+            snapshot_threshold,
+            log,
+            metastore,
+            tls_config,
+            sender,
+            status_sender,
+            role: Role::Follower,
+            leader: None,
+            commit_index: applied_index,
+            applied_index,
+            votes: HashSet::new(),
+            members: Vec::new(),
+            learners: Vec::new(),
+            progress: HashMap::new(),
+            clients: HashMap::new(),
+            proposals: BTreeMap::new(),
+            deadline,
+            anti_entropy_deadline: Instant::now() + ANTI_ENTROPY_INTERVAL,
+        }
+    }
+
+    pub async fn run<S>(mut self, mut receiver: mpsc::UnboundedReceiver<Event>, mut members: S)
+    where
+        S: Stream<Item = Vec<RaftMember>> + Unpin,
+    {
+        info!(id = ?self.id, voters = ?self.voters, "Starting Raft.");
+        loop {
+            tokio::select! {
+                event = receiver.recv() => match event {
+                    Some(event) => self.handle_event(event).await,
+                    None => break,
+                },
+                Some(members) = members.next() => self.update_members(members).await,
+                _ = sleep_until(self.deadline) => self.tick().await,
+                _ = sleep_until(self.anti_entropy_deadline) => self.start_anti_entropy().await,
+            }
+            self.publish_status();
+        }
+        info!(id = ?self.id, "Raft has been stopped.");
+    }
+
+    fn is_voter(&self) -> bool {
+        self.voters.contains(&self.id)
+    }
+
+    fn quorum(&self) -> usize {
+        self.voters.len() / 2 + 1
+    }
+
+    fn current_term(&self) -> u64 {
+        self.log.hard_state().current_term
+    }
+
+    /// The term and the index of the last applied entry.
+    fn applied_position(&self) -> (u64, u64) {
+        (
+            self.log.term(self.applied_index).unwrap_or_default(),
+            self.applied_index,
+        )
+    }
+
+    fn publish_status(&self) {
+        let status = Status {
+            role: self.role,
+            term: self.current_term(),
+            leader: self.leader.clone(),
+            commit_index: self.commit_index,
+            applied_index: self.applied_index,
+            is_voter: self.is_voter(),
+        };
+        self.status_sender.send_if_modified(|current| {
+            if *current == status {
+                false
+            } else {
+                *current = status;
+                true
+            }
+        });
+    }
+
+    async fn save_hard_state(&mut self, current_term: u64, voted_for: Option<String>) -> bool {
+        let hard_state = HardState {
+            current_term,
+            voted_for,
+            commit_index: self.commit_index,
+        };
+        match self.log.save_hard_state(hard_state).await {
+            Ok(_) => true,
+            Err(error) => {
+                error!(?error, "Failed to save Raft hard state.");
+                false
+            }
+        }
+    }
+
+    fn peers(&self) -> Vec<String> {
+        let mut peers: Vec<String> = self
+            .voters
+            .iter()
+            .chain(self.learners.iter())
+            .filter(|peer_id| **peer_id != self.id)
+            .cloned()
+            .collect();
+        peers.sort();
+        peers.dedup();
+        peers
+    }
+
+    fn client(&mut self, peer_id: &str) -> Option<RaftServiceClient<Channel>> {
+        if let Some(client) = self.clients.get(peer_id) {
+            return Some(client.clone());
+        }
+
+        let addr = match peer_id.parse::<SocketAddr>() {
+            Ok(addr) => addr,
+            Err(error) => {
+                error!(?peer_id, ?error, "Invalid Raft peer address.");
+                return None;
+            }
+        };
+        match create_channel(&addr, self.tls_config.as_ref()) {
+            Ok(channel) => {
+                let client = RaftServiceClient::new(channel);
+                self.clients.insert(peer_id.to_string(), client.clone());
+                Some(client)
+            }
+            Err(error) => {
+                error!(?peer_id, ?error, "Failed to create Raft client.");
+                None
+            }
+        }
+    }
+
+    async fn update_members(&mut self, members: Vec<RaftMember>) {
+        self.members = members;
+        self.update_peers();
+        self.remove_leaving_voter().await;
+    }
+
+    fn update_peers(&mut self) {
+        self.learners = self
+            .members
+            .iter()
+            .map(|member| member.id.clone())
+            .filter(|member| *member != self.id && !self.voters.contains(member))
+            .collect();
+
+        if self.role == Role::Leader {
+            let peers = self.peers();
+            self.progress.retain(|peer_id, _| peers.contains(peer_id));
+            self.clients.retain(|peer_id, _| peers.contains(peer_id));
+            let next_index = self.log.last_index() + 1;
+            for peer_id in peers {
+                self.progress.entry(peer_id).or_insert(Progress {
+                    next_index,
+                    match_index: 0,
+                    in_flight: false,
+                });
+            }
+        }
+    }
+
+    /// Take the voters from the latest change in the log, which is in effect as soon as it has
+    /// been appended, or the voters given at startup.
+    fn update_voters(&mut self) {
+        let voters = self
+            .log
+            .voters()
+            .map(<[String]>::to_vec)
+            .unwrap_or_else(|| self.initial_voters.clone());
+        if voters != self.voters {
+            info!(id = ?self.id, ?voters, "Raft voters have been changed.");
+            self.voters = voters;
+            self.update_peers();
+        }
+    }
+
+    /// Whether the latest change to the voters has been committed.
+    fn is_voters_change_committed(&self) -> bool {
+        (self.commit_index + 1..=self.log.last_index()).all(|index| {
+            self.log
+                .entry(index)
+                .is_none_or(|entry| entry.voters.is_none())
+        })
+    }
+
+    /// Remove a leaving member from the voters, unless it is the last voter. The voters are
+    /// removed one at a time, so that the majorities of the old and the new voters overlap.
+    async fn remove_leaving_voter(&mut self) {
+        // The previous change must have been committed, and so must an entry of this term so
+        // that a change appended by a former leader is not overlooked.
+        if self.role != Role::Leader
+            || self.voters.len() <= 1
+            || self.log.term(self.commit_index) != Some(self.current_term())
+            || !self.is_voters_change_committed()
+        {
+            return;
+        }
+        let leaving = match self.voters.iter().find(|voter| {
+            self.members
+                .iter()
+                .any(|member| member.leaving && member.id == **voter)
+        }) {
+            Some(voter) => voter.clone(),
+            None => return,
+        };
+        let voters: Vec<String> = self
+            .voters
+            .iter()
+            .filter(|voter| **voter != leaving)
+            .cloned()
+            .collect();
+        info!(voter = ?leaving, ?voters, "Removing leaving Raft voter.");
+
+        let entry = LogEntry {
+            term: self.current_term(),
+            index: self.log.last_index() + 1,
+            command: None,
+            voters: Some(voters),
+        };
+        if let Err(error) = self.log.append(vec![entry]).await {
+            error!(?error, "Failed to append entry.");
+            return;
+        }
+        self.update_voters();
+        self.advance_commit_index().await;
+        self.replicate().await;
+    }
+
+    async fn tick(&mut self) {
+        match self.role {
+            Role::Leader => {
+                self.remove_leaving_voter().await;
+                self.replicate().await;
+                self.deadline = Instant::now() + HEARTBEAT_INTERVAL;
+            }
+            _ if self.is_voter() => self.start_election().await,
+            _ => {
+                // The learners never start an election.
+                self.deadline = Instant::now() + election_timeout();
+            }
+        }
+    }
+
+    async fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::RequestVote(request, reply) => {
+                let response = self.handle_request_vote(request).await;
+                let _ = reply.send(response);
+            }
+            Event::AppendEntries(request, reply) => {
+                let response = self.handle_append_entries(request).await;
+                let _ = reply.send(response);
+            }
+            Event::InstallSnapshot(request, reply) => {
+                let response = self.handle_install_snapshot(request).await;
+                let _ = reply.send(response);
+            }
+            Event::Propose(command, reply) => self.handle_propose(command, reply).await,
+            Event::GetDigests(request, reply) => {
+                let response = self.handle_get_digests(request).await;
+                let _ = reply.send(response);
+            }
+            Event::GetSnapshot(reply) => {
+                let response = self.handle_get_snapshot().await;
+                let _ = reply.send(response);
+            }
+            Event::VoteResult {
+                peer_id,
+                term,
+                result,
+            } => self.handle_vote_result(peer_id, term, result).await,
+            Event::AppendEntriesResult {
+                peer_id,
+                term,
+                prev_log_index,
+                num_entries,
+                result,
+            } => {
+                self.handle_append_entries_result(
+                    peer_id,
+                    term,
+                    prev_log_index + num_entries,
+                    result,
+                )
+                .await
+            }
+            Event::InstallSnapshotResult {
+                peer_id,
+                term,
+                last_included_index,
+                result,
+            } => {
+                self.handle_install_snapshot_result(peer_id, term, last_included_index, result)
+                    .await
+            }
+            Event::SnapshotPulled { peer_id, result } => {
+                self.handle_snapshot_pulled(peer_id, result).await
+            }
+        }
+    }
+
+    /// Step down to a follower, e.g. when a higher term has been seen.
+    async fn become_follower(&mut self, term: u64, leader: Option<String>) {
+        if term > self.current_term() {
+            self.save_hard_state(term, None).await;
+        }
+        if self.role == Role::Leader {
+            info!(id = ?self.id, term, "Stepping down from leader.");
+            self.fail_proposals();
+        }
+        if self.role != Role::Follower || self.leader != leader {
+            info!(id = ?self.id, term, ?leader, "Following the leader.");
+        }
+        self.role = Role::Follower;
+        self.leader = leader;
+        self.votes.clear();
+        self.progress.clear();
+        self.deadline = Instant::now() + election_timeout();
+    }
+
+    fn fail_proposals(&mut self) {
+        for (_, (_, reply)) in std::mem::take(&mut self.proposals) {
+            let _ = reply.send(Err(RaftErrorKind::LeadershipLost.with_error(
+                anyhow::anyhow!("Leadership has been lost before the command was committed."),
+            )));
+        }
+    }
+
+    async fn start_election(&mut self) {
+        let term = self.current_term() + 1;
+        if !self.save_hard_state(term, Some(self.id.clone())).await {
+            self.deadline = Instant::now() + election_timeout();
+            return;
+        }
+        info!(id = ?self.id, term, "Starting an election.");
+
+        self.role = Role::Candidate;
+        self.leader = None;
+        self.votes.clear();
+        self.votes.insert(self.id.clone());
+        self.deadline = Instant::now() + election_timeout();
+
+        if self.votes.len() >= self.quorum() {
+            self.become_leader().await;
+            return;
+        }
+
+        let request = RequestVoteRequest {
+            term,
+            candidate_id: self.id.clone(),
+            last_log_index: self.log.last_index(),
+            last_log_term: self.log.last_term(),
+        };
+        let voters: Vec<String> = self
+            .voters
+            .iter()
+            .filter(|voter| **voter != self.id)
+            .cloned()
+            .collect();
+        for peer_id in voters {
+            let mut client = match self.client(&peer_id) {
+                Some(client) => client,
+                None => continue,
+            };
+            let request = request.clone();
+            let sender = self.sender.clone();
+            tokio::spawn(async move {
+                let result = match timeout(RPC_TIMEOUT, client.request_vote(request)).await {
+                    Ok(Ok(response)) => Ok(response.into_inner()),
+                    Ok(Err(status)) => Err(status.to_string()),
+                    Err(error) => Err(error.to_string()),
+                };
+                let _ = sender.send(Event::VoteResult {
+                    peer_id,
+                    term,
+                    result,
+                });
+            });
+        }
+    }
+
+    async fn handle_vote_result(
+        &mut self,
+        peer_id: String,
+        term: u64,
+        result: Result<RequestVoteResponse, String>,
+    ) {
+        let response = match result {
+            Ok(response) => response,
+            Err(error) => {
+                debug!(?peer_id, ?error, "Failed to request vote.");
+                return;
+            }
+        };
+        if response.term > self.current_term() {
+            self.become_follower(response.term, None).await;
+            return;
+        }
+        if self.role != Role::Candidate || term != self.current_term() || !response.vote_granted {
+            return;
+        }
+
+        self.votes.insert(peer_id);
+        if self.votes.len() >= self.quorum() {
+            self.become_leader().await;
+        }
+    }
+
+    async fn become_leader(&mut self) {
+        info!(id = ?self.id, term = self.current_term(), "Became leader.");
+        self.role = Role::Leader;
+        self.leader = Some(self.id.clone());
+
+        let next_index = self.log.last_index() + 1;
+        self.progress = self
+            .peers()
+            .into_iter()
+            .map(|peer_id| {
+                (
+                    peer_id,
+                    Progress {
+                        next_index,
+                        match_index: 0,
+                        in_flight: false,
+                    },
+                )
+            })
+            .collect();
+
+        // The entries of the previous terms are committed along with an entry of this term.
+        let entry = LogEntry {
+            term: self.current_term(),
+            index: next_index,
+            command: None,
+            voters: None,
+        };
+        if let Err(error) = self.log.append(vec![entry]).await {
+            error!(?error, "Failed to append entry.");
+        }
+        self.advance_commit_index().await;
+        self.replicate().await;
+        self.deadline = Instant::now() + HEARTBEAT_INTERVAL;
+    }
+
+    async fn handle_request_vote(&mut self, request: RequestVoteRequest) -> RequestVoteResponse {
+        if request.term > self.current_term() {
+            self.become_follower(request.term, None).await;
+        }
+
+        let term = self.current_term();
+        let voted_for = self.log.hard_state().voted_for.clone();
+        let log_is_up_to_date = (request.last_log_term, request.last_log_index)
+            >= (self.log.last_term(), self.log.last_index());
+        // The removed voters that have not learned of their removal are not elected.
+        let mut vote_granted = self.is_voter()
+            && self.voters.contains(&request.candidate_id)
+            && request.term == term
+            && voted_for
+                .as_ref()
+                .is_none_or(|voted_for| *voted_for == request.candidate_id)
+            && log_is_up_to_date;
+        if vote_granted {
+            vote_granted = self
+                .save_hard_state(term, Some(request.candidate_id.clone()))
+                .await;
+            self.deadline = Instant::now() + election_timeout();
+        }
+        debug!(candidate_id = ?request.candidate_id, term, vote_granted, "Requested vote.");
+
+        RequestVoteResponse { term, vote_granted }
+    }
+
+    async fn handle_append_entries(
+        &mut self,
+        request: AppendEntriesRequest,
+    ) -> Result<AppendEntriesResponse, RaftError> {
+        let reject = |term: u64, last_log_index: u64| AppendEntriesResponse {
+            term,
+            success: false,
+            last_log_index,
+        };
+
+        if request.term < self.current_term() {
+            return Ok(reject(self.current_term(), self.log.last_index()));
+        }
+        if request.term > self.current_term()
+            || self.role != Role::Follower
+            || self.leader.as_ref() != Some(&request.leader_id)
+        {
+            self.become_follower(request.term, Some(request.leader_id.clone()))
+                .await;
+        }
+        self.deadline = Instant::now() + election_timeout();
+        let term = self.current_term();
+
+        let last_new_index = request.prev_log_index + request.entries.len() as u64;
+        let mut entries = request
+            .entries
+            .into_iter()
+            .map(LogEntry::from_proto)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The entries up to the snapshot have been committed, so they match the leader's.
+        if request.prev_log_index < self.log.snapshot_index() {
+            let snapshot_index = self.log.snapshot_index();
+            entries.retain(|entry| entry.index > snapshot_index);
+        } else {
+            match self.log.term(request.prev_log_index) {
+                None => return Ok(reject(term, self.log.last_index())),
+                Some(prev_log_term) if prev_log_term != request.prev_log_term => {
+                    return Ok(reject(term, request.prev_log_index - 1));
+                }
+                _ => (),
+            }
+        }
+
+        let mut new_entries = Vec::new();
+        for entry in entries {
+            if !new_entries.is_empty() {
+                new_entries.push(entry);
+                continue;
+            }
+            match self.log.term(entry.index) {
+                Some(existing_term) if existing_term == entry.term => continue,
+                Some(_) => {
+                    // Remove the conflicting entry and the ones that follow it.
+                    self.log.truncate(entry.index).await?;
+                    new_entries.push(entry);
+                }
+                None => new_entries.push(entry),
+            }
+        }
+        self.log.append(new_entries).await?;
+        self.update_voters();
+
+        // A delayed request may not contain all of the committed entries, and the commit index
+        // never goes backwards.
+        let commit_index = request.leader_commit.min(last_new_index);
+        if commit_index > self.commit_index {
+            self.commit_index = commit_index;
+            self.save_hard_state(term, self.log.hard_state().voted_for.clone())
+                .await;
+            self.apply_committed_entries().await;
+        }
+
+        Ok(AppendEntriesResponse {
+            term,
+            success: true,
+            last_log_index: last_new_index,
+        })
+    }
+
+    async fn handle_install_snapshot(
+        &mut self,
+        request: InstallSnapshotRequest,
+    ) -> Result<InstallSnapshotResponse, RaftError> {
+        if request.term < self.current_term() {
+            return Ok(InstallSnapshotResponse {
+                term: self.current_term(),
+            });
+        }
+        if request.term > self.current_term()
+            || self.role != Role::Follower
+            || self.leader.as_ref() != Some(&request.leader_id)
+        {
+            self.become_follower(request.term, Some(request.leader_id.clone()))
+                .await;
+        }
+        self.deadline = Instant::now() + election_timeout();
+        let term = self.current_term();
+
+        if request.last_included_index <= self.commit_index {
+            return Ok(InstallSnapshotResponse { term });
+        }
+
+        info!(
+            index = request.last_included_index,
+            term = request.last_included_term,
+            "Installing Raft snapshot."
+        );
+        self.restore_snapshot(
+            request.last_included_index,
+            request.last_included_term,
+            request.data,
+            voters_from_proto(request.voters),
+        )
+        .await?;
+
+        Ok(InstallSnapshotResponse { term })
+    }
+
+    /// Replace the state with a snapshot of the committed state.
+    async fn restore_snapshot(
+        &mut self,
+        index: u64,
+        term: u64,
+        data: Vec<u8>,
+        voters: Option<Vec<String>>,
+    ) -> Result<(), RaftError> {
+        self.metastore
+            .restore(&data)
+            .await
+            .map_err(|error| RaftErrorKind::ApplyFailure.with_error(error))?;
+        self.log.install_snapshot(index, term, data, voters).await?;
+        self.update_voters();
+        self.commit_index = index;
+        self.applied_index = index;
+        self.save_hard_state(self.current_term(), self.log.hard_state().voted_for.clone())
+            .await;
+
+        Ok(())
+    }
+
+    /// Compare the digests of the index metadata with a random peer, and pull the snapshot from
+    /// the peer if this node is behind. The leader replicates the log only to the members that
+    /// it knows, so this repairs the nodes that it does not reach, e.g. while the gossip is
+    /// partitioned.
+    async fn start_anti_entropy(&mut self) {
+        self.anti_entropy_deadline = Instant::now() + ANTI_ENTROPY_INTERVAL;
+
+        // The leader has all the committed entries.
+        if self.role == Role::Leader {
+            return;
+        }
+        let peers = self.peers();
+        if peers.is_empty() {
+            return;
+        }
+        let peer_id = peers[rand::thread_rng().gen_range(0..peers.len())].clone();
+        let mut client = match self.client(&peer_id) {
+            Some(client) => client,
+            None => return,
+        };
+
+        let local = self.metastore.digests().await;
+        let local_applied = self.applied_position();
+        let from_leader = self.leader.as_ref() == Some(&peer_id);
+        let request = GetDigestsRequest {
+            node_id: self.id.clone(),
+            digests: local.iter().map(digest_to_proto).collect(),
+            applied_index: local_applied.1,
+            applied_term: local_applied.0,
+        };
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let response = match timeout(RPC_TIMEOUT, client.get_digests(request)).await {
+                Ok(Ok(response)) => response.into_inner(),
+                Ok(Err(status)) => {
+                    debug!(?peer_id, ?status, "Failed to get index metadata digests.");
+                    return;
+                }
+                Err(error) => {
+                    debug!(?peer_id, ?error, "Failed to get index metadata digests.");
+                    return;
+                }
+            };
+            let remote_applied = (response.applied_term, response.applied_index);
+            let remote: Vec<Digest> = response
+                .digests
+                .into_iter()
+                .map(digest_from_proto)
+                .collect();
+
+            let outdated =
+                indices_to_pull(&local, &remote, local_applied, remote_applied, from_leader);
+            if outdated.is_empty() {
+                return;
+            }
+            info!(?peer_id, ?outdated, "Index metadata is outdated.");
+            let result = pull_snapshot(client).await;
+            let _ = sender.send(Event::SnapshotPulled { peer_id, result });
+        });
+    }
+
+    async fn handle_get_digests(&mut self, request: GetDigestsRequest) -> GetDigestsResponse {
+        let local = self.metastore.digests().await;
+        let local_applied = self.applied_position();
+
+        // The peer that is ahead of this node is the one to pull the snapshot from.
+        let remote: Vec<Digest> = request.digests.into_iter().map(digest_from_proto).collect();
+        let outdated = indices_to_pull(
+            &local,
+            &remote,
+            local_applied,
+            (request.applied_term, request.applied_index),
+            self.leader.as_ref() == Some(&request.node_id),
+        );
+        if !outdated.is_empty() && self.role != Role::Leader {
+            info!(peer_id = ?request.node_id, ?outdated, "Index metadata is outdated.");
+            if let Some(client) = self.client(&request.node_id) {
+                let sender = self.sender.clone();
+                let peer_id = request.node_id;
+                tokio::spawn(async move {
+                    let result = pull_snapshot(client).await;
+                    let _ = sender.send(Event::SnapshotPulled { peer_id, result });
+                });
+            }
+        }
+
+        GetDigestsResponse {
+            digests: local.iter().map(digest_to_proto).collect(),
+            applied_index: local_applied.1,
+            applied_term: local_applied.0,
+        }
+    }
+
+    async fn handle_get_snapshot(&mut self) -> Result<GetSnapshotResponse, RaftError> {
+        let data = self
+            .metastore
+            .snapshot()
+            .await
+            .map_err(|error| RaftErrorKind::SerializationFailure.with_error(error))?;
+
+        Ok(GetSnapshotResponse {
+            last_included_index: self.applied_index,
+            last_included_term: self.log.term(self.applied_index).unwrap_or_default(),
+            data,
+            voters: self
+                .log
+                .voters_at(self.applied_index)
+                .map(<[String]>::to_vec)
+                .unwrap_or_default(),
+        })
+    }
+
+    async fn handle_snapshot_pulled(
+        &mut self,
+        peer_id: String,
+        result: Result<GetSnapshotResponse, String>,
+    ) {
+        let response = match result {
+            Ok(response) => response,
+            Err(error) => {
+                warn!(?peer_id, ?error, "Failed to pull Raft snapshot.");
+                return;
+            }
+        };
+        if self.role == Role::Leader {
+            return;
+        }
+        // The snapshot of a peer that has not caught up with this node, or whose state has
+        // diverged from the log of this node, is stale.
+        let position = (response.last_included_term, response.last_included_index);
+        if response.last_included_index <= self.commit_index
+            || position <= self.applied_position()
+            || self
+                .log
+                .term(response.last_included_index)
+                .is_some_and(|term| term != response.last_included_term)
+        {
+            info!(
+                ?peer_id,
+                index = response.last_included_index,
+                term = response.last_included_term,
+                "Rejected stale Raft snapshot."
+            );
+            return;
+        }
+
+        info!(
+            ?peer_id,
+            index = response.last_included_index,
+            term = response.last_included_term,
+            "Installing pulled Raft snapshot."
+        );
+        if let Err(error) = self
+            .restore_snapshot(
+                response.last_included_index,
+                response.last_included_term,
+                response.data,
+                voters_from_proto(response.voters),
+            )
+            .await
+        {
+            error!(?peer_id, ?error, "Failed to install pulled Raft snapshot.");
+        }
+    }
+
+    async fn handle_propose(&mut self, command: Command, reply: Reply<()>) {
+        if self.role != Role::Leader {
+            let _ = reply.send(Err(RaftErrorKind::NotLeader.with_error(anyhow::anyhow!(
+                "This node is not the leader. The leader is {:?}.",
+                self.leader
+            ))));
+            return;
+        }
+
+        let term = self.current_term();
+        let entry = LogEntry {
+            term,
+            index: self.log.last_index() + 1,
+            command: Some(command),
+            voters: None,
+        };
+        let index = entry.index;
+        if let Err(error) = self.log.append(vec![entry]).await {
+            let _ = reply.send(Err(error));
+            return;
+        }
+        self.proposals.insert(index, (term, reply));
+
+        self.advance_commit_index().await;
+        self.replicate().await;
+    }
+
+    /// Send the entries that the followers do not have, or a heartbeat if they have all of them.
+    async fn replicate(&mut self) {
+        let peer_ids: Vec<String> = self.progress.keys().cloned().collect();
+        for peer_id in peer_ids {
+            self.replicate_to(&peer_id);
+        }
+    }
+
+    fn replicate_to(&mut self, peer_id: &str) {
+        let next_index = match self.progress.get(peer_id) {
+            Some(progress) if !progress.in_flight => progress.next_index,
+            _ => return,
+        };
+        let mut client = match self.client(peer_id) {
+            Some(client) => client,
+            None => return,
+        };
+        let term = self.current_term();
+        let sender = self.sender.clone();
+        let peer_id = peer_id.to_string();
+
+        if next_index <= self.log.snapshot_index() {
+            let data = match self.log.snapshot_data() {
+                Some(data) => data.to_vec(),
+                None => return,
+            };
+            let request = InstallSnapshotRequest {
+                term,
+                leader_id: self.id.clone(),
+                last_included_index: self.log.snapshot_index(),
+                last_included_term: self.log.snapshot_term(),
+                data,
+                voters: self
+                    .log
+                    .voters_at(self.log.snapshot_index())
+                    .map(<[String]>::to_vec)
+                    .unwrap_or_default(),
+            };
+            let last_included_index = request.last_included_index;
+            if let Some(progress) = self.progress.get_mut(&peer_id) {
+                progress.in_flight = true;
+            }
+            tokio::spawn(async move {
+                let result = match timeout(RPC_TIMEOUT, client.install_snapshot(request)).await {
+                    Ok(Ok(response)) => Ok(response.into_inner()),
+                    Ok(Err(status)) => Err(status.to_string()),
+                    Err(error) => Err(error.to_string()),
+                };
+                let _ = sender.send(Event::InstallSnapshotResult {
+                    peer_id,
+                    term,
+                    last_included_index,
+                    result,
+                });
+            });
+            return;
+        }
+
+        let prev_log_index = next_index - 1;
+        let entries = match self
+            .log
+            .entries_from(next_index, MAX_ENTRIES_PER_REQUEST)
+            .iter()
+            .map(LogEntry::to_proto)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(entries) => entries,
+            Err(error) => {
+                error!(?error, "Failed to encode entries.");
+                return;
+            }
+        };
+        let num_entries = entries.len() as u64;
+        let request = AppendEntriesRequest {
+            term,
+            leader_id: self.id.clone(),
+            prev_log_index,
+            prev_log_term: self.log.term(prev_log_index).unwrap_or_default(),
+            entries,
+            leader_commit: self.commit_index,
+        };
+        if let Some(progress) = self.progress.get_mut(&peer_id) {
+            progress.in_flight = true;
+        }
+        tokio::spawn(async move {
+            let result = match timeout(RPC_TIMEOUT, client.append_entries(request)).await {
+                Ok(Ok(response)) => Ok(response.into_inner()),
+                Ok(Err(status)) => Err(status.to_string()),
+                Err(error) => Err(error.to_string()),
+            };
+            let _ = sender.send(Event::AppendEntriesResult {
+                peer_id,
+                term,
+                prev_log_index,
+                num_entries,
+                result,
+            });
+        });
+    }
+
+    async fn handle_append_entries_result(
+        &mut self,
+        peer_id: String,
+        term: u64,
+        last_sent_index: u64,
+        result: Result<AppendEntriesResponse, String>,
+    ) {
+        if let Some(progress) = self.progress.get_mut(&peer_id) {
+            progress.in_flight = false;
+        }
+        let response = match result {
+            Ok(response) => response,
+            Err(error) => {
+                debug!(?peer_id, ?error, "Failed to append entries.");
+                return;
+            }
+        };
+        if response.term > self.current_term() {
+            self.become_follower(response.term, None).await;
+            return;
+        }
+        if self.role != Role::Leader || term != self.current_term() {
+            return;
+        }
+
+        let last_index = self.log.last_index();
+        let progress = match self.progress.get_mut(&peer_id) {
+            Some(progress) => progress,
+            None => return,
+        };
+        if response.success {
+            progress.match_index = progress.match_index.max(last_sent_index);
+            progress.next_index = progress.match_index + 1;
+        } else {
+            // Go back to where the logs may match.
+            progress.next_index = (progress.next_index - 1)
+                .min(response.last_log_index + 1)
+                .max(1);
+        }
+        let has_more = progress.next_index <= last_index;
+
+        if response.success {
+            self.advance_commit_index().await;
+        }
+        if has_more {
+            self.replicate_to(&peer_id);
+        }
+    }
+
+    async fn handle_install_snapshot_result(
+        &mut self,
+        peer_id: String,
+        term: u64,
+        last_included_index: u64,
+        result: Result<InstallSnapshotResponse, String>,
+    ) {
+        if let Some(progress) = self.progress.get_mut(&peer_id) {
+            progress.in_flight = false;
+        }
+        let response = match result {
+            Ok(response) => response,
+            Err(error) => {
+                warn!(?peer_id, ?error, "Failed to install snapshot.");
+                return;
+            }
+        };
+        if response.term > self.current_term() {
+            self.become_follower(response.term, None).await;
+            return;
+        }
+        if self.role != Role::Leader || term != self.current_term() {
+            return;
+        }
+
+        if let Some(progress) = self.progress.get_mut(&peer_id) {
+            progress.match_index = progress.match_index.max(last_included_index);
+            progress.next_index = progress.match_index + 1;
+        }
+        self.advance_commit_index().await;
+        self.replicate_to(&peer_id);
+    }
+
+    /// Commit the entries that have been replicated to the majority of the voters.
+    async fn advance_commit_index(&mut self) {
+        if self.voters.is_empty() {
+            return;
+        }
+        let mut match_indices: Vec<u64> = self
+            .voters
+            .iter()
+            .map(|voter| {
+                if *voter == self.id {
+                    self.log.last_index()
+                } else {
+                    self.progress
+                        .get(voter)
+                        .map(|progress| progress.match_index)
+                        .unwrap_or_default()
+                }
+            })
+            .collect();
+        match_indices.sort_unstable_by(|a, b| b.cmp(a));
+        let index = match_indices[self.quorum() - 1];
+
+        // Only the entries of the current term are committed by counting the replicas.
+        if index > self.commit_index && self.log.term(index) == Some(self.current_term()) {
+            self.commit_index = index;
+            self.save_hard_state(self.current_term(), self.log.hard_state().voted_for.clone())
+                .await;
+            self.apply_committed_entries().await;
+
+            // The leader that has removed itself from the voters steps down once the followers
+            // have been told that the change has been committed.
+            if !self.is_voter() && self.is_voters_change_committed() {
+                self.replicate().await;
+                self.become_follower(self.current_term(), None).await;
+            }
+        }
+    }
+
+    async fn apply_committed_entries(&mut self) {
+        while self.applied_index < self.commit_index {
+            let index = self.applied_index + 1;
+            let entry = match self.log.entry(index) {
+                Some(entry) => entry.clone(),
+                None => {
+                    error!(index, "Committed entry is not in the log.");
+                    break;
+                }
+            };
+
+            let result = match entry.command.as_ref() {
+                Some(command) => self
+                    .metastore
+                    .apply(index, command)
+                    .await
+                    .map_err(|error| RaftErrorKind::ApplyFailure.with_error(error)),
+                None => Ok(()),
+            };
+            if let Err(error) = result.as_ref() {
+                warn!(index, ?error, "Failed to apply command.");
+            }
+            self.applied_index = index;
+
+            if let Some((term, reply)) = self.proposals.remove(&index) {
+                let result = if term == entry.term {
+                    result
+                } else {
+                    Err(RaftErrorKind::LeadershipLost.with_error(anyhow::anyhow!(
+                        "The command has been replaced by a new leader."
+                    )))
+                };
+                let _ = reply.send(result);
+            }
+        }
+
+        if self.applied_index >= self.log.snapshot_index() + self.snapshot_threshold {
+            self.take_snapshot().await;
+        }
+    }
+
+    async fn take_snapshot(&mut self) {
+        let data = match self.metastore.snapshot().await {
+            Ok(data) => data,
+            Err(error) => {
+                error!(?error, "Failed to take Raft snapshot.");
+                return;
+            }
+        };
+        if let Err(error) = self.log.compact(self.applied_index, data).await {
+            error!(?error, "Failed to compact Raft log.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use tempdir::TempDir;
+    use tokio::sync::{mpsc, watch};
+
+    use crate::{
+        index::{
+            metadata::Metadata,
+            metastore::{Command, Digest, Metastore},
+        },
+        proto::raft::{
+            AppendEntriesRequest, AppendEntriesResponse, GetSnapshotResponse,
+            InstallSnapshotRequest, RequestVoteRequest, RequestVoteResponse,
+        },
+        raft::{
+            log::{HardState, LogEntry, RaftLog},
+            replica::{indices_to_pull, Event, Replica},
+            RaftMember, Role, Status,
+        },
+    };
+
+    const VOTERS: [&str; 3] = ["127.0.0.1:1", "127.0.0.1:2", "127.0.0.1:3"];
+
+    // The peers are not listening, so the requests that the replica sends to them fail and
+    // the tests deliver the responses by calling the handlers.
+    async fn create_replica(
+        dir: &Path,
+        id: &str,
+        snapshot_threshold: u64,
+    ) -> (Replica, mpsc::UnboundedReceiver<Event>) {
+        let metastore = Metastore::new(dir.join("indices")).await.unwrap();
+        let log = RaftLog::open(&dir.join("raft")).await.unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (status_sender, _) = watch::channel(Status {
+            role: Role::Follower,
+            term: 0,
+            leader: None,
+            commit_index: 0,
+            applied_index: 0,
+            is_voter: false,
+        });
+        let replica = Replica::new(
+            id.to_string(),
+            VOTERS.iter().map(|voter| voter.to_string()).collect(),
+            snapshot_threshold,
+            log,
+            0,
+            Arc::new(metastore),
+            None,
+            sender,
+            status_sender,
+        );
+        (replica, receiver)
+    }
+
+    fn entry(term: u64, index: u64) -> LogEntry {
+        LogEntry {
+            term,
+            index,
+            command: None,
+            voters: None,
+        }
+    }
+
+    fn append_entries(
+        term: u64,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<LogEntry>,
+        leader_commit: u64,
+    ) -> AppendEntriesRequest {
+        AppendEntriesRequest {
+            term,
+            leader_id: VOTERS[0].to_string(),
+            prev_log_index,
+            prev_log_term,
+            entries: entries
+                .iter()
+                .map(LogEntry::to_proto)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            leader_commit,
+        }
+    }
+
+    fn request_vote(term: u64, candidate_id: &str, last_log_index: u64) -> RequestVoteRequest {
+        RequestVoteRequest {
+            term,
+            candidate_id: candidate_id.to_string(),
+            last_log_index,
+            last_log_term: if last_log_index == 0 { 0 } else { 1 },
+        }
+    }
+
+    fn vote(term: u64, vote_granted: bool) -> Result<RequestVoteResponse, String> {
+        Ok(RequestVoteResponse { term, vote_granted })
+    }
+
+    fn appended(term: u64, last_log_index: u64) -> Result<AppendEntriesResponse, String> {
+        Ok(AppendEntriesResponse {
+            term,
+            success: true,
+            last_log_index,
+        })
+    }
+
+    fn members(leaving: &[&str]) -> Vec<RaftMember> {
+        VOTERS
+            .iter()
+            .map(|voter| RaftMember {
+                id: voter.to_string(),
+                leaving: leaving.contains(voter),
+            })
+            .collect()
+    }
+
+    fn create_metadata() -> Metadata {
+        serde_json::from_str::<Metadata>(
+            r#"
+            {
+                "schema": [
+                    {
+                        "name": "title",
+                        "type": "text",
+                        "options": {
+                            "indexing": {
+                                "record": "position",
+                                "fieldnorms": true,
+                                "tokenizer": "default"
+                            },
+                            "stored": true
+                        }
+                    }
+                ],
+                "analyzers": {}
+            }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_split_votes() {
+        let tmp_dir = TempDir::new("test_split_votes").unwrap();
+        let (mut a, _a_receiver) = create_replica(&tmp_dir.path().join("a"), VOTERS[0], 100).await;
+        let (mut b, _b_receiver) = create_replica(&tmp_dir.path().join("b"), VOTERS[1], 100).await;
+
+        // A and C start an election in the same term, and B votes for C first.
+        a.start_election().await;
+        assert_eq!(a.role, Role::Candidate);
+        assert_eq!(a.current_term(), 1);
+        let response = b.handle_request_vote(request_vote(1, VOTERS[2], 0)).await;
+        assert!(response.vote_granted);
+        let response = b.handle_request_vote(request_vote(1, VOTERS[0], 0)).await;
+        assert!(!response.vote_granted);
+        assert_eq!(b.log.hard_state().voted_for.as_deref(), Some(VOTERS[2]));
+
+        // Neither of them has a quorum, so A stays a candidate until the next election.
+        a.handle_vote_result(VOTERS[1].to_string(), 1, vote(1, false))
+            .await;
+        a.handle_vote_result(VOTERS[2].to_string(), 1, vote(1, false))
+            .await;
+        assert_eq!(a.role, Role::Candidate);
+        assert_eq!(a.leader, None);
+
+        // A vote of the previous term is not counted.
+        a.start_election().await;
+        assert_eq!(a.current_term(), 2);
+        a.handle_vote_result(VOTERS[1].to_string(), 1, vote(2, true))
+            .await;
+        assert_eq!(a.role, Role::Candidate);
+
+        let response = b.handle_request_vote(request_vote(2, VOTERS[0], 0)).await;
+        assert!(response.vote_granted);
+        a.handle_vote_result(VOTERS[1].to_string(), 2, Ok(response))
+            .await;
+        assert_eq!(a.role, Role::Leader);
+        assert_eq!(a.leader.as_deref(), Some(VOTERS[0]));
+
+        // The leader appends an entry of its term to commit the entries of the previous terms.
+        assert_eq!(a.log.last_index(), 1);
+        assert_eq!(a.log.last_term(), 2);
+        assert_eq!(a.commit_index, 0);
+
+        // A candidate with an outdated log does not get the vote.
+        let (mut c, _c_receiver) = create_replica(&tmp_dir.path().join("c"), VOTERS[2], 100).await;
+        c.log.append(vec![entry(1, 1), entry(1, 2)]).await.unwrap();
+        let response = c.handle_request_vote(request_vote(3, VOTERS[1], 1)).await;
+        assert_eq!(response.term, 3);
+        assert!(!response.vote_granted);
+    }
+
+    #[tokio::test]
+    async fn test_append_entries_conflict() {
+        let tmp_dir = TempDir::new("test_append_entries_conflict").unwrap();
+        let (mut follower, _receiver) = create_replica(tmp_dir.path(), VOTERS[1], 100).await;
+        follower
+            .log
+            .append(vec![entry(1, 1), entry(1, 2), entry(2, 3)])
+            .await
+            .unwrap();
+
+        // The previous entry does not match, so the leader has to go back.
+        let response = follower
+            .handle_append_entries(append_entries(3, 3, 3, vec![entry(3, 4)], 0))
+            .await
+            .unwrap();
+        assert!(!response.success);
+        assert_eq!(response.term, 3);
+        assert_eq!(response.last_log_index, 2);
+        assert_eq!(follower.leader.as_deref(), Some(VOTERS[0]));
+
+        // A missing previous entry is rejected with the last index of the log.
+        let response = follower
+            .handle_append_entries(append_entries(3, 5, 3, vec![entry(3, 6)], 0))
+            .await
+            .unwrap();
+        assert!(!response.success);
+        assert_eq!(response.last_log_index, 3);
+
+        // The conflicting entry and the ones that follow it are replaced.
+        let response = follower
+            .handle_append_entries(append_entries(3, 2, 1, vec![entry(3, 3), entry(3, 4)], 4))
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(response.last_log_index, 4);
+        assert_eq!(follower.log.last_index(), 4);
+        assert_eq!(follower.log.term(2), Some(1));
+        assert_eq!(follower.log.term(3), Some(3));
+        assert_eq!(follower.log.term(4), Some(3));
+        assert_eq!(follower.commit_index, 4);
+        assert_eq!(follower.applied_index, 4);
+
+        // A request of the previous leader is rejected.
+        let response = follower
+            .handle_append_entries(append_entries(2, 4, 3, vec![entry(2, 5)], 4))
+            .await
+            .unwrap();
+        assert!(!response.success);
+        assert_eq!(response.term, 3);
+        assert_eq!(follower.log.last_index(), 4);
+
+        // A duplicated request does not truncate the entries that follow it.
+        follower
+            .handle_append_entries(append_entries(3, 4, 3, vec![entry(3, 5)], 4))
+            .await
+            .unwrap();
+        let response = follower
+            .handle_append_entries(append_entries(3, 3, 3, vec![entry(3, 4)], 4))
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(follower.log.last_index(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_stale_append_entries() {
+        let tmp_dir = TempDir::new("test_stale_append_entries").unwrap();
+        let (mut follower, _receiver) = create_replica(tmp_dir.path(), VOTERS[1], 100).await;
+        follower
+            .handle_append_entries(append_entries(
+                1,
+                0,
+                0,
+                vec![entry(1, 1), entry(1, 2), entry(1, 3), entry(1, 4)],
+                3,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(follower.commit_index, 3);
+
+        // A request that contains fewer entries than have been committed, e.g. a delayed one,
+        // does not lower the commit index.
+        let response = follower
+            .handle_append_entries(append_entries(1, 0, 0, vec![entry(1, 1)], 4))
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(follower.commit_index, 3);
+        assert_eq!(follower.applied_index, 3);
+        assert_eq!(follower.log.hard_state().commit_index, 3);
+        assert_eq!(follower.log.last_index(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_commit_current_term() {
+        let tmp_dir = TempDir::new("test_commit_current_term").unwrap();
+        let (mut leader, _receiver) = create_replica(tmp_dir.path(), VOTERS[0], 100).await;
+        leader
+            .log
+            .append(vec![entry(1, 1), entry(1, 2)])
+            .await
+            .unwrap();
+        leader
+            .log
+            .save_hard_state(HardState {
+                current_term: 1,
+                voted_for: None,
+                commit_index: 0,
+            })
+            .await
+            .unwrap();
+
+        leader.start_election().await;
+        leader
+            .handle_vote_result(VOTERS[1].to_string(), 2, vote(2, true))
+            .await;
+        assert_eq!(leader.role, Role::Leader);
+        assert_eq!(leader.log.last_index(), 3);
+        assert_eq!(leader.log.term(3), Some(2));
+
+        // The entries of the previous term are on a majority, but they are not committed by
+        // counting the replicas.
+        leader
+            .handle_append_entries_result(
+                VOTERS[1].to_string(),
+                2,
+                2,
+                Ok(AppendEntriesResponse {
+                    term: 2,
+                    success: true,
+                    last_log_index: 2,
+                }),
+            )
+            .await;
+        assert_eq!(leader.progress[VOTERS[1]].match_index, 2);
+        assert_eq!(leader.commit_index, 0);
+
+        // The entry of the current term commits itself and the entries that precede it.
+        leader
+            .handle_append_entries_result(
+                VOTERS[1].to_string(),
+                2,
+                3,
+                Ok(AppendEntriesResponse {
+                    term: 2,
+                    success: true,
+                    last_log_index: 3,
+                }),
+            )
+            .await;
+        assert_eq!(leader.commit_index, 3);
+        assert_eq!(leader.applied_index, 3);
+        assert_eq!(leader.log.hard_state().commit_index, 3);
+
+        // A response with a higher term makes the leader step down.
+        leader
+            .handle_append_entries_result(
+                VOTERS[2].to_string(),
+                2,
+                3,
+                Ok(AppendEntriesResponse {
+                    term: 3,
+                    success: false,
+                    last_log_index: 0,
+                }),
+            )
+            .await;
+        assert_eq!(leader.role, Role::Follower);
+        assert_eq!(leader.current_term(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_install_snapshot_and_compaction() {
+        let tmp_dir = TempDir::new("test_install_snapshot_and_compaction").unwrap();
+
+        let source = Metastore::new(tmp_dir.path().join("source")).await.unwrap();
+        source
+            .apply(
+                1,
+                &Command::CreateIndex {
+                    name: "foo".to_string(),
+                    metadata: create_metadata(),
+                },
+            )
+            .await
+            .unwrap();
+        let data = source.snapshot().await.unwrap();
+
+        let dir = tmp_dir.path().join("follower");
+        let (mut follower, _receiver) = create_replica(&dir, VOTERS[1], 3).await;
+        follower
+            .log
+            .append(vec![entry(1, 1), entry(1, 2)])
+            .await
+            .unwrap();
+
+        follower
+            .handle_install_snapshot(InstallSnapshotRequest {
+                term: 2,
+                leader_id: VOTERS[0].to_string(),
+                last_included_index: 5,
+                last_included_term: 2,
+                data: data.clone(),
+                voters: Vec::new(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(follower.log.snapshot_index(), 5);
+        assert_eq!(follower.log.last_index(), 5);
+        assert_eq!(follower.log.term(5), Some(2));
+        assert!(follower.log.entry(2).is_none());
+        assert_eq!(follower.commit_index, 5);
+        assert_eq!(follower.applied_index, 5);
+        assert!(follower.metastore.metadatas().await.contains_key("foo"));
+
+        // A snapshot that is not newer than the committed state is ignored.
+        follower
+            .handle_install_snapshot(InstallSnapshotRequest {
+                term: 2,
+                leader_id: VOTERS[0].to_string(),
+                last_included_index: 4,
+                last_included_term: 2,
+                data: b"{}".to_vec(),
+                voters: Vec::new(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(follower.log.snapshot_index(), 5);
+        assert!(follower.metastore.metadatas().await.contains_key("foo"));
+
+        // The entries following the snapshot are appended, and the log is compacted once the
+        // number of the applied entries reaches the threshold.
+        let response = follower
+            .handle_append_entries(append_entries(2, 5, 2, vec![entry(2, 6), entry(2, 7)], 7))
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(follower.applied_index, 7);
+        assert_eq!(follower.log.snapshot_index(), 5);
+
+        follower
+            .handle_append_entries(append_entries(2, 7, 2, vec![entry(2, 8)], 8))
+            .await
+            .unwrap();
+        assert_eq!(follower.applied_index, 8);
+        assert_eq!(follower.log.snapshot_index(), 8);
+        assert_eq!(follower.log.snapshot_term(), 2);
+        assert_eq!(follower.log.last_index(), 8);
+        assert!(follower.log.entry(6).is_none());
+
+        // The compacted log is recovered on restart.
+        drop(follower);
+        let log = RaftLog::open(&dir.join("raft")).await.unwrap();
+        assert_eq!(log.snapshot_index(), 8);
+        assert_eq!(log.last_index(), 8);
+        let metastore = Metastore::new(dir.join("restored")).await.unwrap();
+        metastore
+            .restore(log.snapshot_data().unwrap())
+            .await
+            .unwrap();
+        assert!(metastore.metadatas().await.contains_key("foo"));
+    }
+
+    #[tokio::test]
+    async fn test_restart() {
+        let tmp_dir = TempDir::new("test_restart").unwrap();
+        let dir = tmp_dir.path();
+
+        let (mut replica, _receiver) = create_replica(dir, VOTERS[1], 100).await;
+        let response = replica
+            .handle_request_vote(request_vote(1, VOTERS[0], 0))
+            .await;
+        assert!(response.vote_granted);
+        replica
+            .handle_append_entries(append_entries(
+                1,
+                0,
+                0,
+                vec![entry(1, 1), entry(1, 2), entry(1, 3)],
+                2,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(replica.commit_index, 2);
+        drop(replica);
+
+        let log = RaftLog::open(&dir.join("raft")).await.unwrap();
+        let hard_state = log.hard_state().clone();
+        assert_eq!(hard_state.current_term, 1);
+        assert_eq!(hard_state.voted_for.as_deref(), Some(VOTERS[0]));
+        assert_eq!(hard_state.commit_index, 2);
+        assert_eq!(log.last_index(), 3);
+        assert_eq!(log.term(3), Some(1));
+        drop(log);
+
+        // The vote is not given to another candidate in the same term after the restart.
+        let (mut replica, _receiver) = create_replica(dir, VOTERS[1], 100).await;
+        assert_eq!(replica.current_term(), 1);
+        assert_eq!(replica.log.last_index(), 3);
+        let response = replica
+            .handle_request_vote(request_vote(1, VOTERS[2], 3))
+            .await;
+        assert!(!response.vote_granted);
+        let response = replica
+            .handle_request_vote(request_vote(1, VOTERS[0], 3))
+            .await;
+        assert!(response.vote_granted);
+
+        // The uncommitted entry is still in the log and is committed by the leader.
+        replica
+            .handle_append_entries(append_entries(1, 3, 1, vec![], 3))
+            .await
+            .unwrap();
+        assert_eq!(replica.commit_index, 3);
+        assert_eq!(replica.log.hard_state().commit_index, 3);
+    }
+
+    #[tokio::test]
+    async fn test_remove_leaving_voter() {
+        let tmp_dir = TempDir::new("test_remove_leaving_voter").unwrap();
+        let (mut leader, _leader_receiver) =
+            create_replica(&tmp_dir.path().join("leader"), VOTERS[0], 100).await;
+        leader.start_election().await;
+        leader
+            .handle_vote_result(VOTERS[1].to_string(), 1, vote(1, true))
+            .await;
+        assert_eq!(leader.role, Role::Leader);
+
+        // The voters are not changed until an entry of the current term has been committed.
+        leader.update_members(members(&[VOTERS[2]])).await;
+        assert_eq!(leader.log.last_index(), 1);
+        leader
+            .handle_append_entries_result(VOTERS[1].to_string(), 1, 1, appended(1, 1))
+            .await;
+        assert_eq!(leader.commit_index, 1);
+
+        leader.remove_leaving_voter().await;
+        assert_eq!(leader.log.last_index(), 2);
+        assert_eq!(leader.voters, vec![VOTERS[0], VOTERS[1]]);
+        assert_eq!(leader.learners, vec![VOTERS[2]]);
+
+        // The next voter is removed only after the previous change has been committed.
+        leader
+            .update_members(members(&[VOTERS[0], VOTERS[2]]))
+            .await;
+        assert_eq!(leader.log.last_index(), 2);
+        leader
+            .handle_append_entries_result(VOTERS[1].to_string(), 1, 2, appended(1, 2))
+            .await;
+        assert_eq!(leader.commit_index, 2);
+
+        // The leader that removes itself steps down once the change has been committed by the
+        // remaining voter.
+        leader.remove_leaving_voter().await;
+        assert_eq!(leader.voters, vec![VOTERS[1]]);
+        assert_eq!(leader.role, Role::Leader);
+        leader
+            .handle_append_entries_result(VOTERS[1].to_string(), 1, 3, appended(1, 3))
+            .await;
+        assert_eq!(leader.commit_index, 3);
+        assert_eq!(leader.role, Role::Follower);
+        assert!(!leader.is_voter());
+
+        // The changes take effect on the follower as soon as they have been appended.
+        let (mut follower, _follower_receiver) =
+            create_replica(&tmp_dir.path().join("follower"), VOTERS[1], 100).await;
+        follower
+            .handle_append_entries(append_entries(1, 0, 0, leader.log.entries_from(1, 10), 0))
+            .await
+            .unwrap();
+        assert_eq!(follower.voters, vec![VOTERS[1]]);
+
+        // The last voter is not removed.
+        follower.start_election().await;
+        assert_eq!(follower.role, Role::Leader);
+        follower.update_members(members(VOTERS.as_slice())).await;
+        follower.remove_leaving_voter().await;
+        assert_eq!(follower.voters, vec![VOTERS[1]]);
+        assert_eq!(follower.log.last_index(), 4);
+
+        // A removed voter that has not learned of its removal is not elected.
+        let (mut voter, _voter_receiver) =
+            create_replica(&tmp_dir.path().join("voter"), VOTERS[1], 100).await;
+        voter
+            .handle_append_entries(append_entries(1, 0, 0, leader.log.entries_from(1, 2), 0))
+            .await
+            .unwrap();
+        assert_eq!(voter.voters, vec![VOTERS[0], VOTERS[1]]);
+        let response = voter
+            .handle_request_vote(request_vote(2, VOTERS[2], 2))
+            .await;
+        assert!(!response.vote_granted);
+
+        // The changes are reverted along with the conflicting entries.
+        voter
+            .handle_append_entries(append_entries(2, 1, 1, vec![entry(2, 2)], 0))
+            .await
+            .unwrap();
+        assert_eq!(voter.voters, VOTERS.to_vec());
+    }
+
+    #[test]
+    fn test_indices_to_pull() {
+        let digest = |name: &str, version: u64, deleted: bool| Digest {
+            name: name.to_string(),
+            version,
+            deleted,
+        };
+        let local = vec![digest("foo", 1, false), digest("bar", 2, false)];
+        let remote = vec![
+            digest("foo", 3, false),
+            digest("bar", 2, false),
+            digest("baz", 4, true),
+        ];
+
+        // The indices whose digests differ are pulled from a peer that is ahead of this node.
+        assert_eq!(
+            indices_to_pull(&local, &remote, (1, 2), (2, 4), false),
+            vec!["foo".to_string(), "baz".to_string()]
+        );
+        assert!(indices_to_pull(&local, &local, (1, 2), (2, 4), false).is_empty());
+
+        // Nothing is pulled from a peer that has not applied a later entry, unless it is the
+        // leader.
+        assert!(indices_to_pull(&local, &remote, (2, 4), (2, 4), false).is_empty());
+        assert!(indices_to_pull(&local, &remote, (2, 4), (1, 5), false).is_empty());
+        assert_eq!(
+            indices_to_pull(&local, &remote, (2, 4), (2, 4), true),
+            vec!["foo".to_string(), "baz".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_pulled() {
+        let tmp_dir = TempDir::new("test_snapshot_pulled").unwrap();
+        let create_index = Command::CreateIndex {
+            name: "foo".to_string(),
+            metadata: create_metadata(),
+        };
+
+        let (mut replica, _receiver) =
+            create_replica(&tmp_dir.path().join("local"), VOTERS[1], 100).await;
+        replica
+            .handle_append_entries(append_entries(
+                1,
+                0,
+                0,
+                vec![LogEntry {
+                    term: 1,
+                    index: 1,
+                    command: Some(create_index.clone()),
+                    voters: None,
+                }],
+                1,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(replica.applied_index, 1);
+        assert!(replica.metastore.metadatas().await.contains_key("foo"));
+
+        // The state of a peer that has applied the deletion of the index.
+        let remote = Metastore::new(tmp_dir.path().join("remote")).await.unwrap();
+        remote.apply(1, &create_index).await.unwrap();
+        let stale_data = remote.snapshot().await.unwrap();
+        remote
+            .apply(
+                2,
+                &Command::DeleteIndex {
+                    name: "foo".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        let data = remote.snapshot().await.unwrap();
+
+        // A snapshot that is not newer than the applied state is rejected.
+        replica
+            .handle_snapshot_pulled(
+                VOTERS[2].to_string(),
+                Ok(GetSnapshotResponse {
+                    last_included_index: 1,
+                    last_included_term: 1,
+                    data: stale_data.clone(),
+                    voters: Vec::new(),
+                }),
+            )
+            .await;
+        assert_eq!(replica.applied_index, 1);
+        assert_eq!(replica.log.snapshot_index(), 0);
+
+        // So is a snapshot of a lower term than the applied entry, which has diverged.
+        replica
+            .handle_snapshot_pulled(
+                VOTERS[2].to_string(),
+                Ok(GetSnapshotResponse {
+                    last_included_index: 2,
+                    last_included_term: 0,
+                    data: data.clone(),
+                    voters: Vec::new(),
+                }),
+            )
+            .await;
+        assert_eq!(replica.applied_index, 1);
+        assert!(replica.metastore.metadatas().await.contains_key("foo"));
+
+        // The deletion is propagated by the tombstone in the snapshot.
+        replica
+            .handle_snapshot_pulled(
+                VOTERS[2].to_string(),
+                Ok(GetSnapshotResponse {
+                    last_included_index: 2,
+                    last_included_term: 1,
+                    data,
+                    voters: Vec::new(),
+                }),
+            )
+            .await;
+        assert_eq!(replica.applied_index, 2);
+        assert_eq!(replica.commit_index, 2);
+        assert_eq!(replica.log.snapshot_index(), 2);
+        assert!(replica.metastore.metadatas().await.is_empty());
+        assert_eq!(
+            replica.metastore.digests().await,
+            vec![Digest {
+                name: "foo".to_string(),
+                version: 2,
+                deleted: true,
+            }]
+        );
+
+        // The index is not restored by the stale snapshot afterwards.
+        replica
+            .handle_snapshot_pulled(
+                VOTERS[2].to_string(),
+                Ok(GetSnapshotResponse {
+                    last_included_index: 1,
+                    last_included_term: 1,
+                    data: stale_data,
+                    voters: Vec::new(),
+                }),
+            )
+            .await;
+        assert!(replica.metastore.metadatas().await.is_empty());
+    }
+}
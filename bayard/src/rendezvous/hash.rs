@@ -55,6 +55,7 @@
 #![warn(missing_docs)]
 use std::borrow::Borrow;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -158,11 +159,84 @@ where
         self.calc_candidates(item).take(num)
     }
 
+    /// Returns the top N candidate nodes for `item`, spread across as many groups
+    /// (e.g., zones) as possible.
+    ///
+    /// The highest priority node of each group is selected first,
+    /// and the remaining nodes fill the rest in the order of priority.
+    /// The nodes whose group is `None` are treated as if each of them were in its own group.
+    /// The selected nodes are returned in the order of priority.
+    pub fn calc_top_n_candidates_by_group<T, G, F>(&self, item: &T, num: usize, group: F) -> Vec<&N>
+    where
+        T: Hash,
+        G: Hash + Eq,
+        F: Fn(&N) -> Option<G>,
+    {
+        let candidates = self.calc_candidates(item).collect::<Vec<_>>();
+        let mut selected = vec![false; candidates.len()];
+        let mut count = 0;
+
+        // Select the highest priority node of each group.
+        let mut groups = HashSet::new();
+        for (i, node) in candidates.iter().enumerate() {
+            if count >= num {
+                break;
+            }
+            let is_new_group = match group(node) {
+                Some(g) => groups.insert(g),
+                None => true,
+            };
+            if is_new_group {
+                selected[i] = true;
+                count += 1;
+            }
+        }
+
+        // Fill the rest with the remaining nodes.
+        for is_selected in selected.iter_mut() {
+            if count >= num {
+                break;
+            }
+            if !*is_selected {
+                *is_selected = true;
+                count += 1;
+            }
+        }
+
+        candidates
+            .into_iter()
+            .zip(selected)
+            .filter_map(|(node, is_selected)| if is_selected { Some(node) } else { None })
+            .collect()
+    }
+
     /// Returns the rotated candidate node from the top N candidate nodes for `item`.
     pub fn rotate_candidate<T: Hash>(&self, item: &T, num: usize) -> Option<&N> {
         // Get candidates.
         let candidates = self.calc_top_n_candidates(item, num).collect::<Vec<_>>();
 
+        self.rotate(candidates)
+    }
+
+    /// Returns the rotated candidate node from the top N candidate nodes for `item`,
+    /// which are spread across the groups as `calc_top_n_candidates_by_group`.
+    pub fn rotate_candidate_by_group<T, G, F>(&self, item: &T, num: usize, group: F) -> Option<&N>
+    where
+        T: Hash,
+        G: Hash + Eq,
+        F: Fn(&N) -> Option<G>,
+    {
+        // Get candidates.
+        let candidates = self.calc_top_n_candidates_by_group(item, num, group);
+
+        self.rotate(candidates)
+    }
+
+    fn rotate<'a>(&self, candidates: Vec<&'a N>) -> Option<&'a N> {
+        if candidates.is_empty() {
+            return None;
+        }
+
         // Get current index number.
         let index = self.index.load(Ordering::Relaxed);
 
@@ -375,6 +449,39 @@ mod tests {
         assert_calc_top_n_candidates!(nodes, &1, 5, [&"bar", &"baz", &"foo", &"qux"]);
     }
 
+    #[test]
+    fn calc_top_n_candidates_by_group() {
+        let mut nodes = RendezvousNodes::default();
+        nodes.insert("foo");
+        nodes.insert("bar");
+        nodes.insert("baz");
+        nodes.insert("qux");
+        let zones: HashMap<&str, &str> = [("foo", "a"), ("bar", "a"), ("baz", "a"), ("qux", "b")]
+            .into_iter()
+            .collect();
+        let zone = |node: &&str| zones.get(node).copied();
+
+        // "qux" is the only node in zone "b", so it is preferred to "baz" and "foo".
+        assert_eq!(
+            nodes.calc_top_n_candidates_by_group(&1, 2, zone),
+            [&"bar", &"qux"]
+        );
+        assert_eq!(
+            nodes.calc_top_n_candidates_by_group(&1, 3, zone),
+            [&"bar", &"baz", &"qux"]
+        );
+        assert_eq!(
+            nodes.calc_top_n_candidates_by_group(&1, 5, zone),
+            [&"bar", &"baz", &"foo", &"qux"]
+        );
+
+        // Without groups, the candidates are the same as the top N candidates.
+        assert_eq!(
+            nodes.calc_top_n_candidates_by_group(&1, 2, |_| None::<&str>),
+            [&"bar", &"baz"]
+        );
+    }
+
     #[test]
     fn rotate_candidate() {
         let mut nodes = RendezvousNodes::default();
@@ -0,0 +1,660 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use lru::LruCache;
+use prost::Message;
+use tantivy::{
+    query::{ConstScorer, Explanation, Query, Scorer, Weight},
+    schema::Term,
+    DocId, DocSet, Score, Searcher, SearcherGeneration, SegmentId, SegmentReader, Warmer,
+    TERMINATED,
+};
+use tantivy_common::{BitSet, TinySet};
+
+use crate::proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery};
+use crate::proto::index::{SearchRequest, SearchResponse};
+
+pub const DEFAULT_FILTER_CACHE_SIZE: usize = 64 * 1024 * 1024;
+pub const DEFAULT_REQUEST_CACHE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Hit and miss counters of a cache.
+#[derive(Default, Debug)]
+pub struct CacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheStats {
+    fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Returns the ratio of lookups that were served from the cache.
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits();
+        let total = hits + self.misses();
+        if total == 0 {
+            0.0
+        } else {
+            hits as f64 / total as f64
+        }
+    }
+}
+
+/// LRU cache bounded by the estimated memory size of its entries.
+struct MemoryBoundedLru<K: Hash + Eq, V> {
+    entries: LruCache<K, (V, usize)>,
+    memory_usage: usize,
+    memory_budget: usize,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> MemoryBoundedLru<K, V> {
+    fn new(memory_budget: usize) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            memory_usage: 0,
+            memory_budget,
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.entries.get(key).map(|(value, _size)| value.clone())
+    }
+
+    fn put(&mut self, key: K, value: V, size: usize) {
+        // Entries larger than the whole budget are never cached.
+        if size > self.memory_budget {
+            return;
+        }
+
+        if let Some((_value, old_size)) = self.entries.put(key, (value, size)) {
+            self.memory_usage -= old_size;
+        }
+        self.memory_usage += size;
+
+        while self.memory_usage > self.memory_budget {
+            match self.entries.pop_lru() {
+                Some((_key, (_value, size))) => self.memory_usage -= size,
+                None => break,
+            }
+        }
+    }
+
+    fn retain<F>(&mut self, f: F)
+    where
+        F: Fn(&K) -> bool,
+    {
+        let keys = self
+            .entries
+            .iter()
+            .filter(|(key, _entry)| !f(key))
+            .map(|(key, _entry)| key.clone())
+            .collect::<Vec<K>>();
+        for key in keys {
+            if let Some((_value, size)) = self.entries.pop(&key) {
+                self.memory_usage -= size;
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.memory_usage = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Locks the entries of a cache.
+///
+/// A panic while the lock was held may have left the entries inconsistent,
+/// so a poisoned cache is cleared instead of failing every later search.
+fn lock<K: Hash + Eq + Clone, V: Clone>(
+    entries: &Mutex<MemoryBoundedLru<K, V>>,
+) -> MutexGuard<'_, MemoryBoundedLru<K, V>> {
+    entries.lock().unwrap_or_else(|error| {
+        let mut guard = error.into_inner();
+        guard.clear();
+        entries.clear_poison();
+        guard
+    })
+}
+
+/// Per-segment cache of the documents matched by filter queries.
+pub struct FilterCache {
+    entries: Mutex<MemoryBoundedLru<(SegmentId, String), Arc<BitSet>>>,
+    stats: CacheStats,
+}
+
+impl FilterCache {
+    pub fn new(memory_budget: usize) -> Self {
+        Self {
+            entries: Mutex::new(MemoryBoundedLru::new(memory_budget)),
+            stats: CacheStats::default(),
+        }
+    }
+
+    fn get(&self, segment_id: SegmentId, key: &str) -> Option<Arc<BitSet>> {
+        let bitset = lock(&self.entries).get(&(segment_id, key.to_string()));
+        match bitset {
+            Some(_) => self.stats.hit(),
+            None => self.stats.miss(),
+        }
+        bitset
+    }
+
+    fn put(&self, segment_id: SegmentId, key: String, bitset: Arc<BitSet>) {
+        let size = bitset_size(&bitset) + key.len();
+        lock(&self.entries).put((segment_id, key), bitset, size);
+    }
+
+    fn retain_segments(&self, segment_ids: &HashSet<SegmentId>) {
+        lock(&self.entries).retain(|(segment_id, _key)| segment_ids.contains(segment_id));
+    }
+
+    fn clear(&self) {
+        lock(&self.entries).clear();
+    }
+
+    pub fn len(&self) -> usize {
+        lock(&self.entries).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        lock(&self.entries).memory_usage
+    }
+
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+}
+
+/// Shard-level cache of search responses keyed by the request and the reader generation.
+pub struct RequestCache {
+    entries: Mutex<MemoryBoundedLru<(u64, Vec<u8>), SearchResponse>>,
+    stats: CacheStats,
+}
+
+impl RequestCache {
+    pub fn new(memory_budget: usize) -> Self {
+        Self {
+            entries: Mutex::new(MemoryBoundedLru::new(memory_budget)),
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn get(&self, generation_id: u64, request: &SearchRequest) -> Option<SearchResponse> {
+        let response = lock(&self.entries).get(&(generation_id, request.encode_to_vec()));
+        match response {
+            Some(_) => self.stats.hit(),
+            None => self.stats.miss(),
+        }
+        response
+    }
+
+    pub fn put(&self, generation_id: u64, request: &SearchRequest, response: SearchResponse) {
+        let key = request.encode_to_vec();
+        let size = key.len() + response.encoded_len();
+        lock(&self.entries).put((generation_id, key), response, size);
+    }
+
+    fn retain_generations(&self, generation_ids: &HashSet<u64>) {
+        lock(&self.entries).retain(|(generation_id, _key)| generation_ids.contains(generation_id));
+    }
+
+    fn clear(&self) {
+        lock(&self.entries).clear();
+    }
+
+    pub fn len(&self) -> usize {
+        lock(&self.entries).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        lock(&self.entries).memory_usage
+    }
+
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+}
+
+/// Caches of a shard.
+///
+/// It is registered as a warmer of the shard's index reader, so that
+/// entries belonging to previous reader generations are dropped on reload.
+pub struct ShardCache {
+    filter_cache: Arc<FilterCache>,
+    request_cache: RequestCache,
+}
+
+impl ShardCache {
+    pub fn new(filter_cache_size: usize, request_cache_size: usize) -> Self {
+        Self {
+            filter_cache: Arc::new(FilterCache::new(filter_cache_size)),
+            request_cache: RequestCache::new(request_cache_size),
+        }
+    }
+
+    pub fn filter_cache(&self) -> &Arc<FilterCache> {
+        &self.filter_cache
+    }
+
+    pub fn request_cache(&self) -> &RequestCache {
+        &self.request_cache
+    }
+
+    /// Drops all the cached entries, e.g. when the analyzers have been reloaded.
+    pub fn clear(&self) {
+        self.filter_cache.clear();
+        self.request_cache.clear();
+    }
+}
+
+impl Warmer for ShardCache {
+    fn warm(&self, searcher: &Searcher) -> tantivy::Result<()> {
+        // A reader reload invalidates the cached responses of the previous generations.
+        let generation_ids = HashSet::from([searcher.generation().generation_id()]);
+        self.request_cache.retain_generations(&generation_ids);
+
+        let segment_ids = searcher
+            .segment_readers()
+            .iter()
+            .map(|segment_reader| segment_reader.segment_id())
+            .collect::<HashSet<SegmentId>>();
+        self.filter_cache.retain_segments(&segment_ids);
+
+        Ok(())
+    }
+
+    fn garbage_collect(&self, live_generations: &[&SearcherGeneration]) {
+        let generation_ids = live_generations
+            .iter()
+            .map(|generation| generation.generation_id())
+            .collect::<HashSet<u64>>();
+        self.request_cache.retain_generations(&generation_ids);
+
+        let segment_ids = live_generations
+            .iter()
+            .flat_map(|generation| generation.segments().keys().cloned())
+            .collect::<HashSet<SegmentId>>();
+        self.filter_cache.retain_segments(&segment_ids);
+    }
+}
+
+fn bitset_size(bitset: &BitSet) -> usize {
+    (bitset.max_value() as usize).div_ceil(64) * 8
+}
+
+/// Returns the canonical form of the query, in which the keys of the options are sorted.
+pub fn canonical_query_key(query: &ProtoQuery) -> String {
+    let kind = match ProtoQueryKind::from_i32(query.kind) {
+        Some(kind) => kind.as_str_name(),
+        None => "UNKNOWN",
+    };
+    let options = match serde_json::from_slice::<serde_json::Value>(query.options.as_slice()) {
+        Ok(options) => options.to_string(),
+        Err(_) => String::from_utf8_lossy(query.options.as_slice()).into_owned(),
+    };
+    format!("{}:{}", kind, options)
+}
+
+/// A query that caches the documents matched by the wrapped filter query per segment.
+pub struct CachedFilterQuery {
+    key: String,
+    query: Box<dyn Query>,
+    cache: Arc<FilterCache>,
+}
+
+impl CachedFilterQuery {
+    pub fn new(key: String, query: Box<dyn Query>, cache: Arc<FilterCache>) -> Self {
+        Self { key, query, cache }
+    }
+}
+
+impl Clone for CachedFilterQuery {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            query: self.query.box_clone(),
+            cache: Arc::clone(&self.cache),
+        }
+    }
+}
+
+impl fmt::Debug for CachedFilterQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachedFilterQuery")
+            .field("key", &self.key)
+            .field("query", &self.query)
+            .finish()
+    }
+}
+
+impl Query for CachedFilterQuery {
+    fn weight(
+        &self,
+        searcher: &Searcher,
+        _scoring_enabled: bool,
+    ) -> tantivy::Result<Box<dyn Weight>> {
+        let weight = self.query.weight(searcher, false)?;
+        Ok(Box::new(CachedFilterWeight {
+            key: self.key.clone(),
+            weight,
+            cache: Arc::clone(&self.cache),
+        }))
+    }
+
+    fn query_terms(&self, terms: &mut BTreeMap<Term, bool>) {
+        self.query.query_terms(terms);
+    }
+}
+
+struct CachedFilterWeight {
+    key: String,
+    weight: Box<dyn Weight>,
+    cache: Arc<FilterCache>,
+}
+
+impl Weight for CachedFilterWeight {
+    fn scorer(&self, reader: &SegmentReader, boost: Score) -> tantivy::Result<Box<dyn Scorer>> {
+        let bitset = match self.cache.get(reader.segment_id(), &self.key) {
+            Some(bitset) => bitset,
+            None => {
+                let mut bitset = BitSet::with_max_value(reader.max_doc());
+                let mut scorer = self.weight.scorer(reader, 1.0)?;
+                let mut doc = scorer.doc();
+                while doc != TERMINATED {
+                    bitset.insert(doc);
+                    doc = scorer.advance();
+                }
+                let bitset = Arc::new(bitset);
+                self.cache
+                    .put(reader.segment_id(), self.key.clone(), Arc::clone(&bitset));
+                bitset
+            }
+        };
+        Ok(Box::new(ConstScorer::new(
+            SharedBitSetDocSet::from(bitset),
+            boost,
+        )))
+    }
+
+    fn explain(&self, reader: &SegmentReader, doc: DocId) -> tantivy::Result<Explanation> {
+        self.weight.explain(reader, doc)
+    }
+}
+
+/// A `DocSet` iterating over a bitset shared with the filter cache.
+///
+/// Mirrors tantivy's `BitSetDocSet`, which takes ownership of the bitset and would
+/// require a copy of the cached entry on every hit.
+struct SharedBitSetDocSet {
+    docs: Arc<BitSet>,
+    cursor_bucket: u32,
+    cursor_tinyset: TinySet,
+    doc: DocId,
+}
+
+impl SharedBitSetDocSet {
+    fn go_to_bucket(&mut self, bucket: u32) {
+        self.cursor_bucket = bucket;
+        self.cursor_tinyset = self.docs.tinyset(bucket);
+    }
+}
+
+impl From<Arc<BitSet>> for SharedBitSetDocSet {
+    fn from(docs: Arc<BitSet>) -> Self {
+        // An empty segment has no bucket to scan.
+        if docs.max_value() == 0 {
+            return Self {
+                docs,
+                cursor_bucket: 0,
+                cursor_tinyset: TinySet::empty(),
+                doc: TERMINATED,
+            };
+        }
+        let cursor_tinyset = docs.tinyset(0);
+        let mut docset = Self {
+            docs,
+            cursor_bucket: 0,
+            cursor_tinyset,
+            doc: 0,
+        };
+        docset.advance();
+        docset
+    }
+}
+
+impl DocSet for SharedBitSetDocSet {
+    fn advance(&mut self) -> DocId {
+        if self.doc == TERMINATED {
+            return TERMINATED;
+        }
+        if let Some(lower) = self.cursor_tinyset.pop_lowest() {
+            self.doc = (self.cursor_bucket * 64) | lower;
+            return self.doc;
+        }
+        match self.docs.first_non_empty_bucket(self.cursor_bucket + 1) {
+            Some(bucket) => {
+                self.go_to_bucket(bucket);
+                let lower = self.cursor_tinyset.pop_lowest().unwrap_or_default();
+                self.doc = (bucket * 64) | lower;
+            }
+            None => self.doc = TERMINATED,
+        }
+        self.doc
+    }
+
+    fn seek(&mut self, target: DocId) -> DocId {
+        if target >= self.docs.max_value() {
+            self.doc = TERMINATED;
+            return TERMINATED;
+        }
+        let target_bucket = target / 64;
+        if target_bucket > self.cursor_bucket {
+            self.go_to_bucket(target_bucket);
+            self.cursor_tinyset = self
+                .cursor_tinyset
+                .intersect(TinySet::range_greater_or_equal(target));
+            self.advance()
+        } else {
+            let mut doc = self.doc;
+            while doc < target {
+                doc = self.advance();
+            }
+            doc
+        }
+    }
+
+    fn doc(&self) -> DocId {
+        self.doc
+    }
+
+    fn size_hint(&self) -> u32 {
+        self.docs.len() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tantivy::{
+        collector::Count,
+        doc,
+        query::{Query, TermQuery},
+        schema::{IndexRecordOption, Schema, STRING},
+        DocSet, Index, Term, TERMINATED,
+    };
+    use tantivy_common::BitSet;
+
+    use crate::{
+        proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery, SearchRequest},
+        search::cache::{
+            canonical_query_key, CachedFilterQuery, FilterCache, RequestCache, SharedBitSetDocSet,
+        },
+    };
+
+    #[test]
+    fn test_canonical_query_key() {
+        let query1 = ProtoQuery {
+            kind: ProtoQueryKind::Term as i32,
+            options: br#"{"term": "a", "field": "text"}"#.to_vec(),
+        };
+        let query2 = ProtoQuery {
+            kind: ProtoQueryKind::Term as i32,
+            options: br#"{"field":"text","term":"a"}"#.to_vec(),
+        };
+        assert_eq!(canonical_query_key(&query1), canonical_query_key(&query2));
+    }
+
+    #[test]
+    fn test_cached_filter_query() {
+        let mut schema_builder = Schema::builder();
+        let text = schema_builder.add_text_field("text", STRING);
+        let schema = schema_builder.build();
+        let index = Index::create_in_ram(schema);
+        let mut writer = index.writer_with_num_threads(1, 10_000_000).unwrap();
+        writer.add_document(doc!(text=>"a")).unwrap();
+        writer.add_document(doc!(text=>"b")).unwrap();
+        writer.add_document(doc!(text=>"a")).unwrap();
+        writer.commit().unwrap();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        let cache = Arc::new(FilterCache::new(1024));
+        let term_query: Box<dyn Query> = Box::new(TermQuery::new(
+            Term::from_field_text(text, "a"),
+            IndexRecordOption::Basic,
+        ));
+        let query = CachedFilterQuery::new("a".to_string(), term_query, Arc::clone(&cache));
+
+        assert_eq!(searcher.search(&query, &Count).unwrap(), 2);
+        assert_eq!(cache.stats().misses(), 1);
+        assert_eq!(cache.stats().hits(), 0);
+
+        assert_eq!(searcher.search(&query, &Count).unwrap(), 2);
+        assert_eq!(cache.stats().hits(), 1);
+        assert_eq!(cache.stats().hit_rate(), 0.5);
+        assert_eq!(cache.len(), 1);
+
+        // Hits share the cached bitset instead of copying it.
+        let segment_id = searcher.segment_reader(0).segment_id();
+        let first = cache.get(segment_id, "a").unwrap();
+        let second = cache.get(segment_id, "a").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_shared_bitset_docset() {
+        let mut bitset = BitSet::with_max_value(200);
+        for doc in [1, 3, 64, 130, 199] {
+            bitset.insert(doc);
+        }
+        let bitset = Arc::new(bitset);
+
+        let mut docset = SharedBitSetDocSet::from(Arc::clone(&bitset));
+        let mut docs = Vec::new();
+        while docset.doc() != TERMINATED {
+            docs.push(docset.doc());
+            docset.advance();
+        }
+        assert_eq!(docs, vec![1, 3, 64, 130, 199]);
+
+        let mut docset = SharedBitSetDocSet::from(Arc::clone(&bitset));
+        assert_eq!(docset.seek(2), 3);
+        assert_eq!(docset.seek(65), 130);
+        assert_eq!(docset.seek(130), 130);
+        assert_eq!(docset.seek(200), TERMINATED);
+
+        let mut docset = SharedBitSetDocSet::from(Arc::new(BitSet::with_max_value(0)));
+        assert_eq!(docset.doc(), TERMINATED);
+        assert_eq!(docset.advance(), TERMINATED);
+    }
+
+    #[test]
+    fn test_request_cache_memory_budget() {
+        let request = SearchRequest {
+            name: "example".to_string(),
+            ..Default::default()
+        };
+        let response = Default::default();
+
+        let cache = RequestCache::new(1024);
+        cache.put(1, &request, response);
+        assert!(cache.get(1, &request).is_some());
+        assert!(cache.get(2, &request).is_none());
+
+        // Exceeding the budget evicts the least recently used entries.
+        let cache = RequestCache::new(10);
+        for i in 0..4 {
+            let request = SearchRequest {
+                name: format!("index{}", i),
+                ..Default::default()
+            };
+            cache.put(1, &request, Default::default());
+        }
+        assert!(cache.memory_usage() <= 10);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_request_cache_poisoned() {
+        let request = SearchRequest {
+            name: "example".to_string(),
+            ..Default::default()
+        };
+
+        let cache = RequestCache::new(1024);
+        cache.put(1, &request, Default::default());
+
+        // A panic while the lock is held poisons the mutex.
+        let result = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _guard = cache.entries.lock().unwrap();
+                    panic!("panic while holding the lock");
+                })
+                .join()
+        });
+        assert!(result.is_err());
+        assert!(cache.entries.is_poisoned());
+
+        // The poisoned cache is cleared and can be used again.
+        assert!(cache.get(1, &request).is_none());
+        assert!(!cache.entries.is_poisoned());
+        assert_eq!(cache.memory_usage(), 0);
+        cache.put(1, &request, Default::default());
+        assert!(cache.get(1, &request).is_some());
+    }
+}
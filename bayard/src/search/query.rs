@@ -1,27 +1,37 @@
 pub mod all;
 pub mod boolean;
 pub mod boost;
+pub mod function_score;
 pub mod fuzzy_term;
+pub mod geo_bounding_box;
+pub mod geo_distance;
+pub mod more_like_this;
 pub mod phrase;
 pub mod query_string;
 pub mod range;
 pub mod regex;
 pub mod term;
 
-use std::fmt;
+use std::{fmt, sync::Arc};
 
 use tantivy::{
     query::{Query, QueryClone},
     Searcher,
 };
 
-use crate::proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery};
+use crate::{
+    proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery},
+    search::cache::FilterCache,
+};
 
 use self::{
     all::create_all_query,
     boolean::{create_boolean_query, BooleanQueryArgs},
     boost::{create_boost_query, BoostQueryArgs},
     fuzzy_term::{create_fuzzy_term_query, FuzzyTermQueryArgs},
+    geo_bounding_box::{create_geo_bounding_box_query, GeoBoundingBoxQueryArgs},
+    geo_distance::{create_geo_distance_query, GeoDistanceQueryArgs},
+    more_like_this::{create_more_like_this_query, MoreLikeThisQueryArgs},
     phrase::{create_phrase_query, PhraseQueryArgs},
     query_string::{create_query_string_query, QueryStringQueryArgs},
     range::{create_range_query, RangeQueryArgs},
@@ -71,9 +81,13 @@ impl QueryFactoryError {
     }
 }
 
+/// Create a query.
+///
+/// If `filter_cache` is given, the documents matched by filter clauses are cached per segment.
 pub fn create_query(
     query: &ProtoQuery,
     searcher: &Searcher,
+    filter_cache: Option<&Arc<FilterCache>>,
 ) -> Result<Box<dyn Query>, QueryFactoryError> {
     let kind = ProtoQueryKind::from_i32(query.kind).ok_or_else(|| {
         QueryFactoryErrorKind::InvalidArgument
@@ -85,12 +99,12 @@ pub fn create_query(
         ProtoQueryKind::Boolean => {
             let args = serde_json::from_slice::<BooleanQueryArgs>(query.options.as_slice())
                 .map_err(|e| QueryFactoryErrorKind::InvalidArgument.with_error(e))?;
-            create_boolean_query(&args, searcher)?.box_clone()
+            create_boolean_query(&args, searcher, filter_cache)?.box_clone()
         }
         ProtoQueryKind::Boost => {
             let args = serde_json::from_slice::<BoostQueryArgs>(query.options.as_slice())
                 .map_err(|e| QueryFactoryErrorKind::InvalidArgument.with_error(e))?;
-            create_boost_query(&args, searcher)?.box_clone()
+            create_boost_query(&args, searcher, filter_cache)?.box_clone()
         }
         ProtoQueryKind::FuzzyTerm => {
             let args = serde_json::from_slice::<FuzzyTermQueryArgs>(query.options.as_slice())
@@ -122,6 +136,29 @@ pub fn create_query(
                 .map_err(|e| QueryFactoryErrorKind::InvalidArgument.with_error(e))?;
             create_term_query(&args, searcher)?.box_clone()
         }
+        ProtoQueryKind::MoreLikeThis => {
+            let args = serde_json::from_slice::<MoreLikeThisQueryArgs>(query.options.as_slice())
+                .map_err(|e| QueryFactoryErrorKind::InvalidArgument.with_error(e))?;
+            create_more_like_this_query(&args, searcher)?.box_clone()
+        }
+        ProtoQueryKind::GeoDistance => {
+            let args = serde_json::from_slice::<GeoDistanceQueryArgs>(query.options.as_slice())
+                .map_err(|e| QueryFactoryErrorKind::InvalidArgument.with_error(e))?;
+            create_geo_distance_query(&args, searcher)?.box_clone()
+        }
+        ProtoQueryKind::GeoBoundingBox => {
+            let args = serde_json::from_slice::<GeoBoundingBoxQueryArgs>(query.options.as_slice())
+                .map_err(|e| QueryFactoryErrorKind::InvalidArgument.with_error(e))?;
+            create_geo_bounding_box_query(&args, searcher)?.box_clone()
+        }
+        ProtoQueryKind::FunctionScore => {
+            // The function score is applied by the collector, so it can only be the top-level query.
+            return Err(
+                QueryFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                    "Function score query is only supported as the top-level query."
+                )),
+            );
+        }
         _ => {
             return Err(QueryFactoryErrorKind::InvalidArgument
                 .with_error(anyhow::anyhow!("Unsupported query kind {:?}", kind)))
@@ -1,12 +1,15 @@
-use std::fmt;
+use std::{fmt, sync::Arc};
 
 use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Unexpected, Visitor};
 use tantivy::{
-    query::{BooleanQuery, Occur, Query},
+    query::{BooleanQuery, BoostQuery, Occur, Query},
     Searcher,
 };
 
-use crate::proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery};
+use crate::{
+    proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery},
+    search::cache::{canonical_query_key, CachedFilterQuery, FilterCache},
+};
 
 use super::{create_query, QueryFactoryError};
 
@@ -18,6 +21,8 @@ pub enum Occurrence {
     MustNot,
     #[serde(rename = "should")]
     Should,
+    #[serde(rename = "filter")]
+    Filter,
 }
 
 #[derive(Serialize, Debug)]
@@ -272,23 +277,70 @@ impl<'de> Deserialize<'de> for BooleanQueryArgs {
 pub fn create_boolean_query(
     args: &BooleanQueryArgs,
     searcher: &Searcher,
+    filter_cache: Option<&Arc<FilterCache>>,
 ) -> Result<BooleanQuery, QueryFactoryError> {
     let mut subqueries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
     for subquery in args.subqueries.iter() {
-        let occurrence = match subquery.occurrence {
-            Occurrence::Must => Occur::Must,
-            Occurrence::MustNot => Occur::MustNot,
-            Occurrence::Should => Occur::Should,
+        let query = create_query(&subquery.query, searcher, filter_cache)?;
+
+        let (occurrence, query) = match subquery.occurrence {
+            Occurrence::Must => (Occur::Must, query),
+            Occurrence::MustNot => (Occur::MustNot, query),
+            Occurrence::Should => (Occur::Should, query),
+            Occurrence::Filter => {
+                let query = match filter_cache {
+                    Some(filter_cache) => Box::new(CachedFilterQuery::new(
+                        canonical_query_key(&subquery.query),
+                        query,
+                        Arc::clone(filter_cache),
+                    )),
+                    None => query,
+                };
+                (Occur::Must, create_filter_query(query))
+            }
         };
 
-        let query = create_query(&subquery.query, searcher)?;
-
         subqueries.push((occurrence, query));
     }
 
     Ok(BooleanQuery::new(subqueries))
 }
 
+/// Wrap a query so that it only restricts the matching documents and
+/// does not contribute to the score.
+pub fn create_filter_query(query: Box<dyn Query>) -> Box<dyn Query> {
+    Box::new(BoostQuery::new(query, 0.0))
+}
+
+/// Create a boolean query message that combines the given subqueries,
+/// e.g. to restrict a query with a filter.
+pub fn create_boolean_proto_query(subqueries: Vec<SubQuery>) -> ProtoQuery {
+    let subqueries = subqueries
+        .iter()
+        .map(|subquery| {
+            serde_json::json!({
+                "occurrence": subquery.occurrence,
+                "query": proto_query_to_json(&subquery.query),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    ProtoQuery {
+        kind: ProtoQueryKind::Boolean as i32,
+        options: serde_json::to_vec(&serde_json::json!({ "subqueries": subqueries }))
+            .unwrap_or_default(),
+    }
+}
+
+// The options are embedded as a JSON object rather than as bytes, as in the request bodies.
+fn proto_query_to_json(query: &ProtoQuery) -> serde_json::Value {
+    let kind = ProtoQueryKind::from_i32(query.kind).unwrap_or(ProtoQueryKind::Unknown);
+    let options = serde_json::from_slice::<serde_json::Value>(query.options.as_slice())
+        .unwrap_or_else(|_| serde_json::json!({}));
+
+    serde_json::json!({ "kind": kind, "options": options })
+}
+
 #[cfg(test)]
 mod tests {
     use tantivy::{
@@ -299,8 +351,11 @@ mod tests {
     };
 
     use crate::{
-        proto::index::query::Kind as ProtoQueryKind,
-        search::query::boolean::{create_boolean_query, BooleanQueryArgs, Occurrence, SubQuery},
+        proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery},
+        search::query::boolean::{
+            create_boolean_proto_query, create_boolean_query, BooleanQueryArgs, Occurrence,
+            SubQuery,
+        },
     };
 
     #[test]
@@ -451,7 +506,7 @@ mod tests {
         "#;
         let opts_json_bytes = opts_json_str.as_bytes();
         let opts = serde_json::from_slice::<BooleanQueryArgs>(opts_json_bytes).unwrap();
-        let query = create_boolean_query(&opts, &searcher).unwrap();
+        let query = create_boolean_query(&opts, &searcher, None).unwrap();
 
         let (top_docs, count) = searcher
             .search(&query, &(TopDocs::with_limit(20), Count))
@@ -467,4 +522,86 @@ mod tests {
 
         assert_eq!(count, 3);
     }
+
+    #[test]
+    fn test_boolean_query_filter() {
+        let index = create_test_index().unwrap();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        let opts_json_str = r#"
+            {
+                "subqueries": [
+                    {
+                        "occurrence": "should",
+                        "query": {
+                            "kind": "term",
+                            "options": {
+                                "term": "c",
+                                "field": "text"
+                            }
+                        }
+                    },
+                    {
+                        "occurrence": "filter",
+                        "query": {
+                            "kind": "term",
+                            "options": {
+                                "term": "a",
+                                "field": "text"
+                            }
+                        }
+                    }
+                ]
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+        let opts = serde_json::from_slice::<BooleanQueryArgs>(opts_json_bytes).unwrap();
+        assert_eq!(opts.subqueries[1].occurrence, Occurrence::Filter);
+        let query = create_boolean_query(&opts, &searcher, None).unwrap();
+
+        let (top_docs, count) = searcher
+            .search(&query, &(TopDocs::with_limit(20), Count))
+            .unwrap();
+
+        assert_eq!(count, 3);
+
+        // Documents matching only the filter clause do not get any score.
+        let scores = top_docs
+            .iter()
+            .map(|(score, _doc_address)| *score)
+            .collect::<Vec<_>>();
+        assert_eq!(scores.len(), 3);
+        assert!(scores[0] > 0.0);
+        assert_eq!(scores[1], 0.0);
+        assert_eq!(scores[2], 0.0);
+    }
+
+    #[test]
+    fn test_create_boolean_proto_query() {
+        let index = create_test_index().unwrap();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        let term_query = |term: &str| ProtoQuery {
+            kind: ProtoQueryKind::Term as i32,
+            options: serde_json::to_vec(&serde_json::json!({"term": term, "field": "text"}))
+                .unwrap(),
+        };
+        let proto_query = create_boolean_proto_query(vec![
+            SubQuery::new(Occurrence::Must, term_query("a")),
+            SubQuery::new(Occurrence::Filter, term_query("c")),
+        ]);
+        assert_eq!(proto_query.kind, ProtoQueryKind::Boolean as i32);
+
+        // The message can be read back as the boolean query options.
+        let opts = serde_json::from_slice::<BooleanQueryArgs>(&proto_query.options).unwrap();
+        assert_eq!(opts.subqueries.len(), 2);
+        assert_eq!(opts.subqueries[0].query, term_query("a"));
+        assert_eq!(opts.subqueries[1].occurrence, Occurrence::Filter);
+        let query = create_boolean_query(&opts, &searcher, None).unwrap();
+
+        let count = searcher.search(&query, &Count).unwrap();
+        assert_eq!(count, 1);
+    }
 }
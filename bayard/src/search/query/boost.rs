@@ -1,9 +1,12 @@
-use std::fmt;
+use std::{fmt, sync::Arc};
 
 use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Unexpected, Visitor};
 use tantivy::{query::BoostQuery, Searcher};
 
-use crate::proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery};
+use crate::{
+    proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery},
+    search::cache::FilterCache,
+};
 
 use super::{create_query, QueryFactoryError};
 
@@ -143,8 +146,9 @@ impl<'de> Deserialize<'de> for BoostQueryArgs {
 pub fn create_boost_query(
     args: &BoostQueryArgs,
     searcher: &Searcher,
+    filter_cache: Option<&Arc<FilterCache>>,
 ) -> Result<BoostQuery, QueryFactoryError> {
-    let query = create_query(&args.query, searcher)?;
+    let query = create_query(&args.query, searcher, filter_cache)?;
 
     Ok(BoostQuery::new(query, args.boost))
 }
@@ -205,7 +209,7 @@ mod tests {
         "#;
         let opts_json_bytes = opts_json_str.as_bytes();
         let opts = serde_json::from_slice::<BoostQueryArgs>(opts_json_bytes).unwrap();
-        let query = create_boost_query(&opts, &searcher).unwrap();
+        let query = create_boost_query(&opts, &searcher, None).unwrap();
 
         let explanation = query.explain(&searcher, DocAddress::new(0, 0u32)).unwrap();
         assert_eq!(
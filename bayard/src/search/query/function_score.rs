@@ -0,0 +1,707 @@
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value;
+use tantivy::schema::{Cardinality, Field, FieldType, Schema, Type};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery};
+
+use super::{QueryFactoryError, QueryFactoryErrorKind};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Modifier {
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "log")]
+    Log,
+    #[serde(rename = "log1p")]
+    Log1p,
+    #[serde(rename = "log2p")]
+    Log2p,
+    #[serde(rename = "ln")]
+    Ln,
+    #[serde(rename = "ln1p")]
+    Ln1p,
+    #[serde(rename = "ln2p")]
+    Ln2p,
+    #[serde(rename = "square")]
+    Square,
+    #[serde(rename = "sqrt")]
+    Sqrt,
+    #[serde(rename = "reciprocal")]
+    Reciprocal,
+}
+
+impl Modifier {
+    fn apply(&self, value: f64) -> f64 {
+        match self {
+            Modifier::None => value,
+            Modifier::Log => value.log10(),
+            Modifier::Log1p => (value + 1.0).log10(),
+            Modifier::Log2p => (value + 2.0).log10(),
+            Modifier::Ln => value.ln(),
+            Modifier::Ln1p => value.ln_1p(),
+            Modifier::Ln2p => (value + 2.0).ln(),
+            Modifier::Square => value * value,
+            Modifier::Sqrt => value.sqrt(),
+            Modifier::Reciprocal => 1.0 / value,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreMode {
+    #[default]
+    #[serde(rename = "multiply")]
+    Multiply,
+    #[serde(rename = "sum")]
+    Sum,
+    #[serde(rename = "avg")]
+    Avg,
+    #[serde(rename = "first")]
+    First,
+    #[serde(rename = "max")]
+    Max,
+    #[serde(rename = "min")]
+    Min,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoostMode {
+    #[default]
+    #[serde(rename = "multiply")]
+    Multiply,
+    #[serde(rename = "replace")]
+    Replace,
+    #[serde(rename = "sum")]
+    Sum,
+    #[serde(rename = "avg")]
+    Avg,
+    #[serde(rename = "max")]
+    Max,
+    #[serde(rename = "min")]
+    Min,
+}
+
+fn default_factor() -> f64 {
+    1.0
+}
+
+fn default_decay() -> f64 {
+    0.5
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FieldValueFactorArgs {
+    field: String,
+    #[serde(default = "default_factor")]
+    factor: f64,
+    #[serde(default)]
+    modifier: Modifier,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DecayFunctionArgs {
+    field: String,
+    origin: Value,
+    scale: Value,
+    #[serde(default)]
+    offset: Option<Value>,
+    #[serde(default = "default_decay")]
+    decay: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ScoreFunctionArgs {
+    #[serde(default)]
+    field_value_factor: Option<FieldValueFactorArgs>,
+    #[serde(default)]
+    gauss: Option<DecayFunctionArgs>,
+    #[serde(default)]
+    exp: Option<DecayFunctionArgs>,
+    #[serde(default)]
+    linear: Option<DecayFunctionArgs>,
+    #[serde(default = "default_weight")]
+    weight: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct FunctionScoreQueryArgs {
+    #[serde(deserialize_with = "deserialize_query")]
+    query: ProtoQuery,
+    functions: Vec<ScoreFunctionArgs>,
+    #[serde(default)]
+    score_mode: ScoreMode,
+    #[serde(default)]
+    boost_mode: BoostMode,
+}
+
+impl FunctionScoreQueryArgs {
+    pub fn query(&self) -> &ProtoQuery {
+        &self.query
+    }
+}
+
+fn deserialize_query<'de, D>(deserializer: D) -> Result<ProtoQuery, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Value::deserialize(deserializer)?;
+
+    let query_kind_str = value["kind"]
+        .as_str()
+        .ok_or_else(|| de::Error::missing_field("kind"))?;
+    let query_kind = ProtoQueryKind::from_str(query_kind_str).ok_or_else(|| {
+        de::Error::custom(format!(
+            "failed to convert query type {:?} from string",
+            query_kind_str
+        ))
+    })?;
+
+    let query_opts = match value["options"].as_object() {
+        Some(opts) => serde_json::to_vec(&opts).map_err(|e| de::Error::custom(format!("{}", e)))?,
+        None => Vec::new(),
+    };
+
+    Ok(ProtoQuery {
+        kind: query_kind as i32,
+        options: query_opts,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecayKind {
+    Gauss,
+    Exp,
+    Linear,
+}
+
+#[derive(Debug, Clone)]
+enum ScoreFunctionKind {
+    FieldValueFactor {
+        factor: f64,
+        modifier: Modifier,
+    },
+    Decay {
+        kind: DecayKind,
+        origin: f64,
+        scale: f64,
+        offset: f64,
+        decay: f64,
+    },
+}
+
+/// A score function bound to a fast field of the schema.
+#[derive(Debug, Clone)]
+pub struct ScoreFunction {
+    field: Field,
+    value_type: Type,
+    kind: ScoreFunctionKind,
+    weight: f32,
+}
+
+impl ScoreFunction {
+    pub fn field(&self) -> Field {
+        self.field
+    }
+
+    pub fn value_type(&self) -> Type {
+        self.value_type
+    }
+
+    /// Compute the weighted score of the function for the given field value.
+    /// Values that cannot be scored (e.g. the logarithm of a negative number) score 0.
+    pub fn score(&self, value: f64) -> f32 {
+        let score = match &self.kind {
+            ScoreFunctionKind::FieldValueFactor { factor, modifier } => {
+                modifier.apply(factor * value)
+            }
+            ScoreFunctionKind::Decay {
+                kind,
+                origin,
+                scale,
+                offset,
+                decay,
+            } => {
+                let distance = ((value - origin).abs() - offset).max(0.0);
+                match kind {
+                    DecayKind::Gauss => {
+                        let sigma_squared = -scale.powi(2) / (2.0 * decay.ln());
+                        (-distance.powi(2) / (2.0 * sigma_squared)).exp()
+                    }
+                    DecayKind::Exp => (decay.ln() / scale * distance).exp(),
+                    DecayKind::Linear => {
+                        let s = scale / (1.0 - decay);
+                        ((s - distance) / s).max(0.0)
+                    }
+                }
+            }
+        };
+
+        if score.is_finite() {
+            score as f32 * self.weight
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The score functions and how their scores are combined with the query score.
+#[derive(Debug, Clone)]
+pub struct FunctionScore {
+    functions: Vec<ScoreFunction>,
+    score_mode: ScoreMode,
+    boost_mode: BoostMode,
+}
+
+impl FunctionScore {
+    pub fn functions(&self) -> &[ScoreFunction] {
+        &self.functions
+    }
+
+    /// Combine the query score with the scores of the functions.
+    /// `value_of` returns the field value of the `i`-th function for the document.
+    pub fn score<F>(&self, query_score: f32, mut value_of: F) -> f32
+    where
+        F: FnMut(usize) -> f64,
+    {
+        if self.functions.is_empty() {
+            return query_score;
+        }
+
+        let scores = self
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, function)| function.score(value_of(i)));
+        let function_score = match self.score_mode {
+            ScoreMode::Multiply => scores.product(),
+            ScoreMode::Sum => scores.sum(),
+            ScoreMode::Avg => {
+                let total_weight: f32 = self.functions.iter().map(|f| f.weight).sum();
+                if total_weight == 0.0 {
+                    0.0
+                } else {
+                    scores.sum::<f32>() / total_weight
+                }
+            }
+            ScoreMode::First => scores.take(1).sum(),
+            ScoreMode::Max => scores.fold(f32::MIN, f32::max),
+            ScoreMode::Min => scores.fold(f32::MAX, f32::min),
+        };
+
+        match self.boost_mode {
+            BoostMode::Multiply => query_score * function_score,
+            BoostMode::Replace => function_score,
+            BoostMode::Sum => query_score + function_score,
+            BoostMode::Avg => (query_score + function_score) / 2.0,
+            BoostMode::Max => query_score.max(function_score),
+            BoostMode::Min => query_score.min(function_score),
+        }
+    }
+}
+
+/// Parse a duration such as `10d` into seconds.
+/// Available units are `ms`, `s`, `m`, `h`, `d` and `w`. A number without a unit is taken as seconds.
+fn parse_duration(value: &Value) -> Option<f64> {
+    if let Some(secs) = value.as_f64() {
+        return Some(secs);
+    }
+
+    let value = value.as_str()?.trim();
+    let pos = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(pos);
+    let number = number.parse::<f64>().ok()?;
+    let multiplier = match unit.trim() {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3_600.0,
+        "d" => 86_400.0,
+        "w" => 604_800.0,
+        _ => return None,
+    };
+    Some(number * multiplier)
+}
+
+/// Parse a date such as `2022-01-01T00:00:00Z` or `now` into the UNIX timestamp in seconds.
+fn parse_date(value: &Value) -> Option<f64> {
+    if let Some(secs) = value.as_f64() {
+        return Some(secs);
+    }
+
+    match value.as_str()? {
+        "now" => Some(OffsetDateTime::now_utc().unix_timestamp() as f64),
+        date => OffsetDateTime::parse(date, &Rfc3339)
+            .ok()
+            .map(|date| date.unix_timestamp() as f64),
+    }
+}
+
+fn create_decay_function(
+    kind: DecayKind,
+    args: &DecayFunctionArgs,
+    value_type: Type,
+) -> Result<ScoreFunctionKind, QueryFactoryError> {
+    let (origin, scale, offset) = match value_type {
+        Type::Date => (
+            parse_date(&args.origin),
+            parse_duration(&args.scale),
+            args.offset.as_ref().map_or(Some(0.0), parse_duration),
+        ),
+        _ => (
+            args.origin.as_f64(),
+            args.scale.as_f64(),
+            args.offset
+                .as_ref()
+                .map_or(Some(0.0), |offset| offset.as_f64()),
+        ),
+    };
+
+    let origin = origin.ok_or_else(|| {
+        QueryFactoryErrorKind::InvalidArgument
+            .with_error(anyhow::anyhow!("Invalid origin: {}", args.origin))
+    })?;
+    let scale = scale.filter(|scale| *scale > 0.0).ok_or_else(|| {
+        QueryFactoryErrorKind::InvalidArgument
+            .with_error(anyhow::anyhow!("Invalid scale: {}", args.scale))
+    })?;
+    let offset = offset.filter(|offset| *offset >= 0.0).ok_or_else(|| {
+        QueryFactoryErrorKind::InvalidArgument
+            .with_error(anyhow::anyhow!("Invalid offset: {:?}", args.offset))
+    })?;
+    if !(args.decay > 0.0 && args.decay < 1.0) {
+        return Err(
+            QueryFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                "Decay must be between 0 and 1: {}",
+                args.decay
+            )),
+        );
+    }
+
+    Ok(ScoreFunctionKind::Decay {
+        kind,
+        origin,
+        scale,
+        offset,
+        decay: args.decay,
+    })
+}
+
+/// Create the function score to be applied to the query score.
+pub fn create_function_score(
+    args: &FunctionScoreQueryArgs,
+    schema: &Schema,
+) -> Result<FunctionScore, QueryFactoryError> {
+    let mut functions = Vec::new();
+    for function_args in args.functions.iter() {
+        let (field_name, kind) = match (
+            &function_args.field_value_factor,
+            &function_args.gauss,
+            &function_args.exp,
+            &function_args.linear,
+        ) {
+            (Some(args), None, None, None) => (&args.field, None),
+            (None, Some(args), None, None) => (&args.field, Some((DecayKind::Gauss, args))),
+            (None, None, Some(args), None) => (&args.field, Some((DecayKind::Exp, args))),
+            (None, None, None, Some(args)) => (&args.field, Some((DecayKind::Linear, args))),
+            _ => {
+                return Err(
+                    QueryFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                        "Exactly one of field_value_factor, gauss, exp or linear must be specified"
+                    )),
+                )
+            }
+        };
+
+        let field = schema.get_field(field_name).ok_or_else(|| {
+            QueryFactoryErrorKind::InvalidArgument
+                .with_error(anyhow::anyhow!("Field {} does not exist", field_name))
+        })?;
+        let field_type = schema.get_field_entry(field).field_type();
+        if !field_type.is_fast() {
+            return Err(QueryFactoryErrorKind::InvalidArgument
+                .with_error(anyhow::anyhow!("Field {} must be fast field", field_name)));
+        }
+        // Only a single value per document can be read while scoring.
+        let cardinality = match field_type {
+            FieldType::I64(options)
+            | FieldType::U64(options)
+            | FieldType::F64(options)
+            | FieldType::Date(options) => options.get_fastfield_cardinality(),
+            _ => None,
+        };
+        if cardinality == Some(Cardinality::MultiValues) {
+            return Err(
+                QueryFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                    "Field {} must be single-valued fast field",
+                    field_name
+                )),
+            );
+        }
+        let value_type = field_type.value_type();
+        match value_type {
+            Type::I64 | Type::U64 | Type::F64 | Type::Date => (),
+            _ => {
+                return Err(QueryFactoryErrorKind::InvalidArgument
+                    .with_error(anyhow::anyhow!("Unsupported field type: {:?}", value_type)))
+            }
+        }
+
+        let kind = match kind {
+            Some((decay_kind, decay_args)) => {
+                create_decay_function(decay_kind, decay_args, value_type)?
+            }
+            None => {
+                let field_value_factor = function_args
+                    .field_value_factor
+                    .as_ref()
+                    .expect("field_value_factor exists");
+                ScoreFunctionKind::FieldValueFactor {
+                    factor: field_value_factor.factor,
+                    modifier: field_value_factor.modifier,
+                }
+            }
+        };
+
+        functions.push(ScoreFunction {
+            field,
+            value_type,
+            kind,
+            weight: function_args.weight,
+        });
+    }
+
+    Ok(FunctionScore {
+        functions,
+        score_mode: args.score_mode,
+        boost_mode: args.boost_mode,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::schema::{Cardinality, NumericOptions, Schema, FAST, INDEXED, TEXT};
+
+    use crate::{
+        proto::index::query::Kind as ProtoQueryKind,
+        search::query::function_score::{
+            create_function_score, BoostMode, FunctionScoreQueryArgs, Modifier, ScoreMode,
+        },
+    };
+
+    fn create_test_schema() -> Schema {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("description", TEXT);
+        schema_builder.add_u64_field("popularity", INDEXED | FAST);
+        schema_builder.add_date_field("publish_date", INDEXED | FAST);
+        schema_builder.add_u64_field(
+            "ratings",
+            NumericOptions::default()
+                .set_indexed()
+                .set_fast(Cardinality::MultiValues),
+        );
+        schema_builder.build()
+    }
+
+    #[test]
+    fn test_function_score_query_args() {
+        let opts_json_str = r#"
+            {
+                "query": {
+                    "kind": "term",
+                    "options": {
+                        "term": "rust",
+                        "field": "description"
+                    }
+                },
+                "functions": [
+                    {
+                        "field_value_factor": {
+                            "field": "popularity",
+                            "factor": 1.2,
+                            "modifier": "log1p"
+                        }
+                    },
+                    {
+                        "gauss": {
+                            "field": "publish_date",
+                            "origin": "2022-01-01T00:00:00Z",
+                            "scale": "30d"
+                        },
+                        "weight": 2.0
+                    }
+                ],
+                "score_mode": "sum",
+                "boost_mode": "replace"
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+
+        // Deserialize the JSON into a struct.
+        let args = serde_json::from_slice::<FunctionScoreQueryArgs>(opts_json_bytes).unwrap();
+
+        assert_eq!(args.query.kind, ProtoQueryKind::Term as i32);
+        assert_eq!(args.functions.len(), 2);
+        assert_eq!(
+            args.functions[0]
+                .field_value_factor
+                .as_ref()
+                .unwrap()
+                .modifier,
+            Modifier::Log1p
+        );
+        assert_eq!(args.functions[1].weight, 2.0);
+        assert_eq!(args.score_mode, ScoreMode::Sum);
+        assert_eq!(args.boost_mode, BoostMode::Replace);
+    }
+
+    #[test]
+    fn test_field_value_factor() {
+        let schema = create_test_schema();
+
+        let opts_json_str = r#"
+            {
+                "query": {
+                    "kind": "all"
+                },
+                "functions": [
+                    {
+                        "field_value_factor": {
+                            "field": "popularity",
+                            "modifier": "sqrt"
+                        }
+                    }
+                ]
+            }
+        "#;
+        let args = serde_json::from_str::<FunctionScoreQueryArgs>(opts_json_str).unwrap();
+        let function_score = create_function_score(&args, &schema).unwrap();
+
+        assert_eq!(function_score.score(2.0, |_| 16.0), 8.0);
+    }
+
+    #[test]
+    fn test_decay_functions() {
+        let schema = create_test_schema();
+
+        // The score is the decay at the distance of the scale.
+        for kind in ["gauss", "exp", "linear"] {
+            let opts_json_str = format!(
+                r#"
+                {{
+                    "query": {{
+                        "kind": "all"
+                    }},
+                    "functions": [
+                        {{
+                            "{}": {{
+                                "field": "publish_date",
+                                "origin": "2022-01-01T00:00:00Z",
+                                "scale": "10d",
+                                "offset": "1d",
+                                "decay": 0.5
+                            }}
+                        }}
+                    ],
+                    "boost_mode": "replace"
+                }}
+                "#,
+                kind
+            );
+            let args = serde_json::from_str::<FunctionScoreQueryArgs>(&opts_json_str).unwrap();
+            let function_score = create_function_score(&args, &schema).unwrap();
+
+            // 2022-01-01T00:00:00Z
+            let origin = 1_640_995_200.0;
+            assert_eq!(function_score.score(1.0, |_| origin + 86_400.0), 1.0);
+            let score = function_score.score(1.0, |_| origin - 11.0 * 86_400.0);
+            assert!((score - 0.5).abs() < 1e-6, "{} {}", kind, score);
+        }
+    }
+
+    #[test]
+    fn test_score_mode() {
+        let schema = create_test_schema();
+
+        let opts_json_str = r#"
+            {
+                "query": {
+                    "kind": "all"
+                },
+                "functions": [
+                    {
+                        "field_value_factor": {
+                            "field": "popularity"
+                        },
+                        "weight": 2.0
+                    },
+                    {
+                        "linear": {
+                            "field": "popularity",
+                            "origin": 0,
+                            "scale": 10
+                        }
+                    }
+                ],
+                "score_mode": "max",
+                "boost_mode": "sum"
+            }
+        "#;
+        let args = serde_json::from_str::<FunctionScoreQueryArgs>(opts_json_str).unwrap();
+        let function_score = create_function_score(&args, &schema).unwrap();
+
+        // max(2.0 * 3.0, 1.0 - 3.0 / 20.0) + 1.5
+        assert_eq!(function_score.score(1.5, |_| 3.0), 7.5);
+    }
+
+    #[test]
+    fn test_function_score_invalid_field() {
+        let schema = create_test_schema();
+
+        let opts_json_str = r#"
+            {
+                "query": {
+                    "kind": "all"
+                },
+                "functions": [
+                    {
+                        "field_value_factor": {
+                            "field": "description"
+                        }
+                    }
+                ]
+            }
+        "#;
+        let args = serde_json::from_str::<FunctionScoreQueryArgs>(opts_json_str).unwrap();
+        assert!(create_function_score(&args, &schema).is_err());
+    }
+
+    #[test]
+    fn test_function_score_multi_valued_field() {
+        let schema = create_test_schema();
+
+        let opts_json_str = r#"
+            {
+                "query": {
+                    "kind": "all"
+                },
+                "functions": [
+                    {
+                        "field_value_factor": {
+                            "field": "ratings"
+                        }
+                    }
+                ]
+            }
+        "#;
+        let args = serde_json::from_str::<FunctionScoreQueryArgs>(opts_json_str).unwrap();
+        assert!(create_function_score(&args, &schema).is_err());
+    }
+}
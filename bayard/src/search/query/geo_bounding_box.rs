@@ -0,0 +1,192 @@
+use std::ops::Bound;
+
+use serde_json::Value;
+use tantivy::{
+    query::{BooleanQuery, Occur, Query, RangeQuery},
+    Searcher,
+};
+
+use crate::index::geo_point::{GeoPoint, LAT_FIELD_SUFFIX, LON_FIELD_SUFFIX};
+
+use super::{QueryFactoryError, QueryFactoryErrorKind};
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GeoBoundingBoxQueryArgs {
+    field: String,
+    top_left: Value,
+    bottom_right: Value,
+}
+
+/// Create a geo bounding box query.
+pub fn create_geo_bounding_box_query(
+    args: &GeoBoundingBoxQueryArgs,
+    searcher: &Searcher,
+) -> Result<BooleanQuery, QueryFactoryError> {
+    let schema = searcher.schema();
+
+    let get_field = |suffix: &str| {
+        let field_name = format!("{}{}", args.field, suffix);
+        schema.get_field(&field_name).ok_or_else(|| {
+            QueryFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                "Field {} is not a geo point field",
+                args.field
+            ))
+        })
+    };
+    let lat_field = get_field(LAT_FIELD_SUFFIX)?;
+    let lon_field = get_field(LON_FIELD_SUFFIX)?;
+
+    let top_left = GeoPoint::from_json(&args.top_left).ok_or_else(|| {
+        QueryFactoryErrorKind::InvalidArgument
+            .with_error(anyhow::anyhow!("Invalid top_left: {}", args.top_left))
+    })?;
+    let bottom_right = GeoPoint::from_json(&args.bottom_right).ok_or_else(|| {
+        QueryFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+            "Invalid bottom_right: {}",
+            args.bottom_right
+        ))
+    })?;
+    if top_left.lat < bottom_right.lat {
+        return Err(
+            QueryFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                "top_left must not be south of bottom_right"
+            )),
+        );
+    }
+
+    let lat_query: Box<dyn Query> = Box::new(RangeQuery::new_f64_bounds(
+        lat_field,
+        Bound::Included(bottom_right.lat),
+        Bound::Included(top_left.lat),
+    ));
+
+    // If the box crosses the antimeridian, the longitude range is split into two.
+    let lon_query: Box<dyn Query> = if top_left.lon <= bottom_right.lon {
+        Box::new(RangeQuery::new_f64_bounds(
+            lon_field,
+            Bound::Included(top_left.lon),
+            Bound::Included(bottom_right.lon),
+        ))
+    } else {
+        Box::new(BooleanQuery::new(vec![
+            (
+                Occur::Should,
+                Box::new(RangeQuery::new_f64_bounds(
+                    lon_field,
+                    Bound::Included(top_left.lon),
+                    Bound::Included(180.0),
+                )) as Box<dyn Query>,
+            ),
+            (
+                Occur::Should,
+                Box::new(RangeQuery::new_f64_bounds(
+                    lon_field,
+                    Bound::Included(-180.0),
+                    Bound::Included(bottom_right.lon),
+                )),
+            ),
+        ]))
+    };
+
+    Ok(BooleanQuery::new(vec![
+        (Occur::Must, lat_query),
+        (Occur::Must, lon_query),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::{collector::Count, Document, Index};
+
+    use crate::{
+        index::geo_point::{expand_geo_point_fields, expand_geo_point_values},
+        search::query::geo_bounding_box::{create_geo_bounding_box_query, GeoBoundingBoxQueryArgs},
+    };
+
+    #[test]
+    fn test_geo_bounding_box_query_args() {
+        let opts_json_str = r#"
+            {
+                "field": "location",
+                "top_left": {
+                    "lat": 36.0,
+                    "lon": 139.0
+                },
+                "bottom_right": {
+                    "lat": 35.0,
+                    "lon": 140.0
+                }
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+
+        // Deserialize the JSON into a struct.
+        let args = serde_json::from_slice::<GeoBoundingBoxQueryArgs>(opts_json_bytes).unwrap();
+
+        assert_eq!(args.field, "location");
+        assert_eq!(args.top_left["lat"], 36.0);
+        assert_eq!(args.bottom_right["lon"], 140.0);
+    }
+
+    fn create_test_index() -> tantivy::Result<Index> {
+        let schema_json = serde_json::json!([
+            {
+                "name": "location",
+                "type": "geo_point",
+                "options": {
+                    "stored": true
+                }
+            }
+        ]);
+        let schema = serde_json::from_value(expand_geo_point_fields(schema_json).unwrap()).unwrap();
+        let index = Index::create_in_ram(schema);
+        let mut writer = index.writer_with_num_threads(1, 10_000_000)?;
+        for doc_json in [
+            serde_json::json!({"location": "35.681,139.767"}),
+            serde_json::json!({"location": "34.702,135.496"}),
+            serde_json::json!({"location": "-17.713,178.065"}),
+            serde_json::json!({"location": "-14.275,-170.702"}),
+        ] {
+            let mut fields = doc_json.as_object().unwrap().clone();
+            expand_geo_point_values(&mut fields, &["location".to_string()]).unwrap();
+            let doc: Document = index.schema().json_object_to_doc(fields).unwrap();
+            writer.add_document(doc)?;
+        }
+        writer.commit()?;
+        Ok(index)
+    }
+
+    #[test]
+    fn test_geo_bounding_box_query() {
+        let index = create_test_index().unwrap();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        let opts_json_str = r#"
+            {
+                "field": "location",
+                "top_left": "36.0,135.0",
+                "bottom_right": "34.0,140.0"
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+        let opts = serde_json::from_slice::<GeoBoundingBoxQueryArgs>(opts_json_bytes).unwrap();
+        let query = create_geo_bounding_box_query(&opts, &searcher).unwrap();
+        let count = searcher.search(&query, &Count).unwrap();
+        assert_eq!(count, 2);
+
+        // The box crosses the antimeridian.
+        let opts_json_str = r#"
+            {
+                "field": "location",
+                "top_left": "-10.0,170.0",
+                "bottom_right": "-20.0,-165.0"
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+        let opts = serde_json::from_slice::<GeoBoundingBoxQueryArgs>(opts_json_bytes).unwrap();
+        let query = create_geo_bounding_box_query(&opts, &searcher).unwrap();
+        let count = searcher.search(&query, &Count).unwrap();
+        assert_eq!(count, 2);
+    }
+}
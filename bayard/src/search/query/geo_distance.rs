@@ -0,0 +1,341 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde_json::Value;
+use tantivy::{
+    fastfield::{DynamicFastFieldReader, FastFieldReader},
+    query::{BooleanQuery, Explanation, Occur, Query, Scorer, TermQuery, Weight},
+    schema::{Field, IndexRecordOption},
+    DocId, DocSet, Score, Searcher, SegmentReader, Term, TERMINATED,
+};
+
+use crate::index::geo_point::{
+    geohash_cover, parse_distance, GeoPoint, GEOHASH_FIELD_SUFFIX, LAT_FIELD_SUFFIX,
+    LON_FIELD_SUFFIX,
+};
+
+use super::{QueryFactoryError, QueryFactoryErrorKind};
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GeoDistanceQueryArgs {
+    field: String,
+    origin: Value,
+    distance: Value,
+}
+
+/// Create a geo distance query.
+pub fn create_geo_distance_query(
+    args: &GeoDistanceQueryArgs,
+    searcher: &Searcher,
+) -> Result<GeoDistanceQuery, QueryFactoryError> {
+    let schema = searcher.schema();
+
+    let get_field = |suffix: &str| {
+        let field_name = format!("{}{}", args.field, suffix);
+        schema.get_field(&field_name).ok_or_else(|| {
+            QueryFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                "Field {} is not a geo point field",
+                args.field
+            ))
+        })
+    };
+    let geohash_field = get_field(GEOHASH_FIELD_SUFFIX)?;
+    let lat_field = get_field(LAT_FIELD_SUFFIX)?;
+    let lon_field = get_field(LON_FIELD_SUFFIX)?;
+
+    let origin = GeoPoint::from_json(&args.origin).ok_or_else(|| {
+        QueryFactoryErrorKind::InvalidArgument
+            .with_error(anyhow::anyhow!("Invalid origin: {}", args.origin))
+    })?;
+    let distance = parse_distance(&args.distance)
+        .filter(|distance| *distance >= 0.0)
+        .ok_or_else(|| {
+            QueryFactoryErrorKind::InvalidArgument
+                .with_error(anyhow::anyhow!("Invalid distance: {}", args.distance))
+        })?;
+
+    // The candidates are the documents in the geohash cells that cover the circle.
+    let cells = geohash_cover(&origin, distance);
+    let candidates = BooleanQuery::new(
+        cells
+            .iter()
+            .map(|cell| {
+                let query: Box<dyn Query> = Box::new(TermQuery::new(
+                    Term::from_field_text(geohash_field, cell),
+                    IndexRecordOption::Basic,
+                ));
+                (Occur::Should, query)
+            })
+            .collect(),
+    );
+
+    Ok(GeoDistanceQuery {
+        candidates: Box::new(candidates),
+        lat_field,
+        lon_field,
+        origin,
+        distance,
+    })
+}
+
+/// Matches the documents whose geo point is within the distance from the origin.
+/// The candidates are narrowed down by the geohash terms, and the distances are
+/// calculated from the coordinates in the fast fields.
+pub struct GeoDistanceQuery {
+    candidates: Box<dyn Query>,
+    lat_field: Field,
+    lon_field: Field,
+    origin: GeoPoint,
+    distance: f64,
+}
+
+impl Clone for GeoDistanceQuery {
+    fn clone(&self) -> Self {
+        Self {
+            candidates: self.candidates.box_clone(),
+            lat_field: self.lat_field,
+            lon_field: self.lon_field,
+            origin: self.origin,
+            distance: self.distance,
+        }
+    }
+}
+
+impl fmt::Debug for GeoDistanceQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeoDistanceQuery")
+            .field("origin", &self.origin)
+            .field("distance", &self.distance)
+            .finish()
+    }
+}
+
+impl Query for GeoDistanceQuery {
+    fn weight(
+        &self,
+        searcher: &Searcher,
+        _scoring_enabled: bool,
+    ) -> tantivy::Result<Box<dyn Weight>> {
+        let weight = self.candidates.weight(searcher, false)?;
+        Ok(Box::new(GeoDistanceWeight {
+            weight,
+            lat_field: self.lat_field,
+            lon_field: self.lon_field,
+            origin: self.origin,
+            distance: self.distance,
+        }))
+    }
+
+    fn query_terms(&self, terms: &mut BTreeMap<Term, bool>) {
+        self.candidates.query_terms(terms);
+    }
+}
+
+struct GeoDistanceWeight {
+    weight: Box<dyn Weight>,
+    lat_field: Field,
+    lon_field: Field,
+    origin: GeoPoint,
+    distance: f64,
+}
+
+impl GeoDistanceWeight {
+    fn open(&self, reader: &SegmentReader, boost: Score) -> tantivy::Result<GeoDistanceScorer> {
+        let mut scorer = GeoDistanceScorer {
+            candidates: self.weight.scorer(reader, 1.0)?,
+            lat_reader: reader.fast_fields().f64(self.lat_field)?,
+            lon_reader: reader.fast_fields().f64(self.lon_field)?,
+            origin: self.origin,
+            distance: self.distance,
+            boost,
+        };
+        if !scorer.is_within() {
+            scorer.advance();
+        }
+        Ok(scorer)
+    }
+}
+
+impl Weight for GeoDistanceWeight {
+    fn scorer(&self, reader: &SegmentReader, boost: Score) -> tantivy::Result<Box<dyn Scorer>> {
+        Ok(Box::new(self.open(reader, boost)?))
+    }
+
+    fn explain(&self, reader: &SegmentReader, doc: DocId) -> tantivy::Result<Explanation> {
+        let mut scorer = self.open(reader, 1.0)?;
+        if scorer.seek(doc) != doc {
+            return Err(tantivy::TantivyError::InvalidArgument(format!(
+                "Document #({}) does not match",
+                doc
+            )));
+        }
+        Ok(Explanation::new("GeoDistanceQuery", scorer.score()))
+    }
+}
+
+struct GeoDistanceScorer {
+    candidates: Box<dyn Scorer>,
+    lat_reader: DynamicFastFieldReader<f64>,
+    lon_reader: DynamicFastFieldReader<f64>,
+    origin: GeoPoint,
+    distance: f64,
+    boost: Score,
+}
+
+impl GeoDistanceScorer {
+    fn is_within(&self) -> bool {
+        let doc = self.candidates.doc();
+        if doc == TERMINATED {
+            return true;
+        }
+        let point = GeoPoint {
+            lat: self.lat_reader.get(doc),
+            lon: self.lon_reader.get(doc),
+        };
+        self.origin.distance(&point) <= self.distance
+    }
+}
+
+impl DocSet for GeoDistanceScorer {
+    fn advance(&mut self) -> DocId {
+        loop {
+            let doc = self.candidates.advance();
+            if self.is_within() {
+                return doc;
+            }
+        }
+    }
+
+    fn doc(&self) -> DocId {
+        self.candidates.doc()
+    }
+
+    fn size_hint(&self) -> u32 {
+        self.candidates.size_hint()
+    }
+}
+
+impl Scorer for GeoDistanceScorer {
+    fn score(&mut self) -> Score {
+        self.boost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::{
+        collector::{Count, TopDocs},
+        Document, Index,
+    };
+
+    use crate::{
+        index::geo_point::{expand_geo_point_fields, expand_geo_point_values},
+        search::query::geo_distance::{create_geo_distance_query, GeoDistanceQueryArgs},
+    };
+
+    #[test]
+    fn test_geo_distance_query_args() {
+        let opts_json_str = r#"
+            {
+                "field": "location",
+                "origin": {
+                    "lat": 35.681,
+                    "lon": 139.767
+                },
+                "distance": "10km"
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+
+        // Deserialize the JSON into a struct.
+        let args = serde_json::from_slice::<GeoDistanceQueryArgs>(opts_json_bytes).unwrap();
+
+        assert_eq!(args.field, "location");
+        assert_eq!(args.distance, "10km");
+    }
+
+    fn create_test_index() -> tantivy::Result<Index> {
+        let schema_json = serde_json::json!([
+            {
+                "name": "name",
+                "type": "text",
+                "options": {
+                    "stored": true
+                }
+            },
+            {
+                "name": "location",
+                "type": "geo_point",
+                "options": {
+                    "stored": true
+                }
+            }
+        ]);
+        let schema = serde_json::from_value(expand_geo_point_fields(schema_json).unwrap()).unwrap();
+        let index = Index::create_in_ram(schema);
+        let mut writer = index.writer_with_num_threads(1, 10_000_000)?;
+        for doc_json in [
+            serde_json::json!({"name": "Tokyo", "location": "35.681,139.767"}),
+            serde_json::json!({"name": "Shinjuku", "location": "35.690,139.700"}),
+            serde_json::json!({"name": "Yokohama", "location": "35.466,139.622"}),
+            serde_json::json!({"name": "Osaka", "location": "34.702,135.496"}),
+            serde_json::json!({"name": "Nowhere"}),
+        ] {
+            let mut fields = doc_json.as_object().unwrap().clone();
+            expand_geo_point_values(&mut fields, &["location".to_string()]).unwrap();
+            let doc: Document = index.schema().json_object_to_doc(fields).unwrap();
+            writer.add_document(doc)?;
+        }
+        writer.commit()?;
+        Ok(index)
+    }
+
+    #[test]
+    fn test_geo_distance_query() {
+        let index = create_test_index().unwrap();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        let opts_json_str = r#"
+            {
+                "field": "location",
+                "origin": "35.681,139.767",
+                "distance": "10km"
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+        let opts = serde_json::from_slice::<GeoDistanceQueryArgs>(opts_json_bytes).unwrap();
+        let query = create_geo_distance_query(&opts, &searcher).unwrap();
+        let count = searcher.search(&query, &Count).unwrap();
+        assert_eq!(count, 2);
+
+        let opts_json_str = r#"
+            {
+                "field": "location",
+                "origin": "35.681,139.767",
+                "distance": "50km"
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+        let opts = serde_json::from_slice::<GeoDistanceQueryArgs>(opts_json_bytes).unwrap();
+        let query = create_geo_distance_query(&opts, &searcher).unwrap();
+        let (top_docs, count) = searcher
+            .search(&query, &(TopDocs::with_limit(10), Count))
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(top_docs.len(), 3);
+
+        // The circle is too large to be covered by the geohash cells around the origin.
+        let opts_json_str = r#"
+            {
+                "field": "location",
+                "origin": "35.681,139.767",
+                "distance": "5000km"
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+        let opts = serde_json::from_slice::<GeoDistanceQueryArgs>(opts_json_bytes).unwrap();
+        let query = create_geo_distance_query(&opts, &searcher).unwrap();
+        let count = searcher.search(&query, &Count).unwrap();
+        assert_eq!(count, 4);
+    }
+}
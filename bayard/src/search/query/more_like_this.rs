@@ -0,0 +1,431 @@
+use serde_json::{Map, Value};
+use tantivy::{
+    collector::TopDocs,
+    query::{BooleanQuery, MoreLikeThisQuery, Occur, Query, TermQuery},
+    schema::{Field, IndexRecordOption, Value as TValue},
+    Searcher, Term,
+};
+
+use crate::{
+    index::DOC_ID_FIELD_NAME,
+    proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery},
+};
+
+use super::{QueryFactoryError, QueryFactoryErrorKind};
+
+const MORE_LIKE_THIS_KIND: &str = "more_like_this";
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct MoreLikeThisQueryArgs {
+    #[serde(default)]
+    doc_id: Option<String>,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    document: Option<Map<String, Value>>,
+    fields: Vec<String>,
+    #[serde(default)]
+    min_doc_frequency: Option<u64>,
+    #[serde(default)]
+    max_doc_frequency: Option<u64>,
+    #[serde(default)]
+    min_term_frequency: Option<usize>,
+    #[serde(default)]
+    max_query_terms: Option<usize>,
+    #[serde(default)]
+    min_word_length: Option<usize>,
+    #[serde(default)]
+    max_word_length: Option<usize>,
+    #[serde(default)]
+    boost_factor: Option<f32>,
+    #[serde(default)]
+    stop_words: Vec<String>,
+}
+
+/// Create a more like this query.
+pub fn create_more_like_this_query(
+    args: &MoreLikeThisQueryArgs,
+    searcher: &Searcher,
+) -> Result<Box<dyn Query>, QueryFactoryError> {
+    let schema = searcher.schema();
+
+    let fields = args
+        .fields
+        .iter()
+        .map(|field_name| {
+            schema.get_field(field_name).ok_or_else(|| {
+                QueryFactoryErrorKind::InvalidArgument
+                    .with_error(anyhow::anyhow!("Field {} does not exist", field_name))
+            })
+        })
+        .collect::<Result<Vec<Field>, QueryFactoryError>>()?;
+
+    let doc_fields = if let Some(document) = &args.document {
+        // The source document has been resolved by the coordinator.
+        let doc = schema
+            .json_object_to_doc(document.clone())
+            .map_err(|error| QueryFactoryErrorKind::InvalidArgument.with_error(error))?;
+        fields
+            .iter()
+            .map(|field| {
+                (
+                    *field,
+                    doc.get_all(*field).cloned().collect::<Vec<TValue>>(),
+                )
+            })
+            .collect::<Vec<(Field, Vec<TValue>)>>()
+    } else if let Some(text) = &args.text {
+        fields
+            .iter()
+            .map(|field| (*field, vec![TValue::Str(text.clone())]))
+            .collect::<Vec<(Field, Vec<TValue>)>>()
+    } else if let Some(doc_id) = &args.doc_id {
+        // The source document is looked up in this shard.
+        let doc_id_field = schema.get_field(DOC_ID_FIELD_NAME).ok_or_else(|| {
+            QueryFactoryErrorKind::CreateError.with_error(anyhow::anyhow!(
+                "{:?} field does not exist.",
+                DOC_ID_FIELD_NAME
+            ))
+        })?;
+        let doc_id_query = TermQuery::new(
+            Term::from_field_text(doc_id_field, doc_id),
+            IndexRecordOption::Basic,
+        );
+        let top_docs = searcher
+            .search(&doc_id_query, &TopDocs::with_limit(1))
+            .map_err(|error| QueryFactoryErrorKind::CreateError.with_error(error))?;
+        let (_score, doc_address) = top_docs.first().ok_or_else(|| {
+            QueryFactoryErrorKind::InvalidArgument
+                .with_error(anyhow::anyhow!("Document {} does not exist", doc_id))
+        })?;
+        let doc = searcher
+            .doc(*doc_address)
+            .map_err(|error| QueryFactoryErrorKind::CreateError.with_error(error))?;
+        fields
+            .iter()
+            .map(|field| {
+                (
+                    *field,
+                    doc.get_all(*field).cloned().collect::<Vec<TValue>>(),
+                )
+            })
+            .collect::<Vec<(Field, Vec<TValue>)>>()
+    } else {
+        return Err(QueryFactoryErrorKind::InvalidArgument
+            .with_error(anyhow::anyhow!("Either doc_id or text must be specified")));
+    };
+
+    let mut builder = MoreLikeThisQuery::builder();
+    if let Some(min_doc_frequency) = args.min_doc_frequency {
+        builder = builder.with_min_doc_frequency(min_doc_frequency);
+    }
+    if let Some(max_doc_frequency) = args.max_doc_frequency {
+        builder = builder.with_max_doc_frequency(max_doc_frequency);
+    }
+    if let Some(min_term_frequency) = args.min_term_frequency {
+        builder = builder.with_min_term_frequency(min_term_frequency);
+    }
+    if let Some(max_query_terms) = args.max_query_terms {
+        builder = builder.with_max_query_terms(max_query_terms);
+    }
+    if let Some(min_word_length) = args.min_word_length {
+        builder = builder.with_min_word_length(min_word_length);
+    }
+    if let Some(max_word_length) = args.max_word_length {
+        builder = builder.with_max_word_length(max_word_length);
+    }
+    if let Some(boost_factor) = args.boost_factor {
+        builder = builder.with_boost_factor(boost_factor);
+    }
+    if !args.stop_words.is_empty() {
+        builder = builder.with_stop_words(args.stop_words.clone());
+    }
+    let query: Box<dyn Query> = Box::new(builder.with_document_fields(doc_fields));
+
+    // Exclude the source document from the results.
+    match &args.doc_id {
+        Some(doc_id) => {
+            let doc_id_field = schema.get_field(DOC_ID_FIELD_NAME).ok_or_else(|| {
+                QueryFactoryErrorKind::CreateError.with_error(anyhow::anyhow!(
+                    "{:?} field does not exist.",
+                    DOC_ID_FIELD_NAME
+                ))
+            })?;
+            let doc_id_query: Box<dyn Query> = Box::new(TermQuery::new(
+                Term::from_field_text(doc_id_field, doc_id),
+                IndexRecordOption::Basic,
+            ));
+            Ok(Box::new(BooleanQuery::new(vec![
+                (Occur::Must, query),
+                (Occur::MustNot, doc_id_query),
+            ])))
+        }
+        None => Ok(query),
+    }
+}
+
+/// Returns the pairs of the document ID and the fields of the more like this queries
+/// in the given query whose source document has not been resolved yet.
+pub fn unresolved_more_like_this_docs(query: &ProtoQuery) -> Vec<(String, Vec<String>)> {
+    let mut docs = Vec::new();
+    if query.kind == ProtoQueryKind::MoreLikeThis as i32 {
+        if let Ok(options) = serde_json::from_slice::<Value>(query.options.as_slice()) {
+            collect_unresolved_doc(&options, &mut docs);
+        }
+    } else if let Ok(options) = serde_json::from_slice::<Value>(query.options.as_slice()) {
+        walk_nested_queries(&options, &mut |options| {
+            collect_unresolved_doc(options, &mut docs);
+        });
+    }
+    docs
+}
+
+/// Embeds the fetched source documents into the more like this queries in the given query.
+pub fn resolve_more_like_this_docs(query: &mut ProtoQuery, documents: &Map<String, Value>) {
+    let mut options = match serde_json::from_slice::<Value>(query.options.as_slice()) {
+        Ok(options) => options,
+        Err(_) => return,
+    };
+    if query.kind == ProtoQueryKind::MoreLikeThis as i32 {
+        embed_doc(&mut options, documents);
+    } else {
+        walk_nested_queries_mut(&mut options, &mut |options| {
+            embed_doc(options, documents);
+        });
+    }
+    if let Ok(options) = serde_json::to_vec(&options) {
+        query.options = options;
+    }
+}
+
+fn collect_unresolved_doc(options: &Value, docs: &mut Vec<(String, Vec<String>)>) {
+    if options.get("document").is_some() || options.get("text").is_some() {
+        return;
+    }
+    if let Some(doc_id) = options.get("doc_id").and_then(|doc_id| doc_id.as_str()) {
+        let fields = options
+            .get("fields")
+            .and_then(|fields| fields.as_array())
+            .map(|fields| {
+                fields
+                    .iter()
+                    .filter_map(|field| field.as_str().map(|field| field.to_string()))
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        docs.push((doc_id.to_string(), fields));
+    }
+}
+
+fn embed_doc(options: &mut Value, documents: &Map<String, Value>) {
+    if options.get("document").is_some() {
+        return;
+    }
+    let doc_id = match options.get("doc_id").and_then(|doc_id| doc_id.as_str()) {
+        Some(doc_id) => doc_id.to_string(),
+        None => return,
+    };
+    if let (Some(document), Some(options)) = (documents.get(&doc_id), options.as_object_mut()) {
+        options.insert("document".to_string(), document.clone());
+    }
+}
+
+// Nested queries are expressed as `{"kind": <KIND>, "options": <OPTIONS>}` objects.
+fn walk_nested_queries<F>(value: &Value, f: &mut F)
+where
+    F: FnMut(&Value),
+{
+    match value {
+        Value::Object(object) => {
+            if object.get("kind").and_then(|kind| kind.as_str()) == Some(MORE_LIKE_THIS_KIND) {
+                if let Some(options) = object.get("options") {
+                    f(options);
+                }
+                return;
+            }
+            for value in object.values() {
+                walk_nested_queries(value, f);
+            }
+        }
+        Value::Array(array) => {
+            for value in array.iter() {
+                walk_nested_queries(value, f);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn walk_nested_queries_mut<F>(value: &mut Value, f: &mut F)
+where
+    F: FnMut(&mut Value),
+{
+    match value {
+        Value::Object(object) => {
+            if object.get("kind").and_then(|kind| kind.as_str()) == Some(MORE_LIKE_THIS_KIND) {
+                if let Some(options) = object.get_mut("options") {
+                    f(options);
+                }
+                return;
+            }
+            for value in object.values_mut() {
+                walk_nested_queries_mut(value, f);
+            }
+        }
+        Value::Array(array) => {
+            for value in array.iter_mut() {
+                walk_nested_queries_mut(value, f);
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Map, Value};
+    use tantivy::{
+        collector::{Count, TopDocs},
+        doc,
+        schema::{Schema, STORED, STRING, TEXT},
+        Index,
+    };
+
+    use crate::{
+        proto::index::{query::Kind as ProtoQueryKind, Query as ProtoQuery},
+        search::query::more_like_this::{
+            create_more_like_this_query, resolve_more_like_this_docs,
+            unresolved_more_like_this_docs, MoreLikeThisQueryArgs,
+        },
+    };
+
+    #[test]
+    fn test_more_like_this_query_args() {
+        let opts_json_str = r#"
+            {
+                "doc_id": "1",
+                "fields": ["text"],
+                "min_doc_frequency": 1,
+                "min_term_frequency": 1
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+
+        // Deserialize the JSON into a struct.
+        let args = serde_json::from_slice::<MoreLikeThisQueryArgs>(opts_json_bytes).unwrap();
+
+        assert_eq!(args.doc_id, Some("1".to_string()));
+        assert_eq!(args.text, None);
+        assert_eq!(args.fields, vec!["text".to_string()]);
+        assert_eq!(args.min_doc_frequency, Some(1));
+        assert_eq!(args.min_term_frequency, Some(1));
+        assert_eq!(args.max_query_terms, None);
+    }
+
+    #[test]
+    fn test_resolve_more_like_this_docs() {
+        let opts_json_str = r#"
+            {
+                "subqueries": [
+                    {
+                        "occurrence": "must",
+                        "query": {
+                            "kind": "more_like_this",
+                            "options": {
+                                "doc_id": "1",
+                                "fields": ["text"]
+                            }
+                        }
+                    }
+                ]
+            }
+        "#;
+        let mut query = ProtoQuery {
+            kind: ProtoQueryKind::Boolean as i32,
+            options: opts_json_str.as_bytes().to_vec(),
+        };
+
+        let docs = unresolved_more_like_this_docs(&query);
+        assert_eq!(docs, vec![("1".to_string(), vec!["text".to_string()])]);
+
+        let mut documents = Map::new();
+        documents.insert(
+            "1".to_string(),
+            serde_json::json!({"text": ["the old man and the sea"]}),
+        );
+        resolve_more_like_this_docs(&mut query, &documents);
+
+        assert!(unresolved_more_like_this_docs(&query).is_empty());
+        let options = serde_json::from_slice::<Value>(query.options.as_slice()).unwrap();
+        assert_eq!(
+            options["subqueries"][0]["query"]["options"]["document"]["text"][0],
+            "the old man and the sea"
+        );
+    }
+
+    fn create_test_index() -> tantivy::Result<Index> {
+        let mut schema_builder = Schema::builder();
+        let id = schema_builder.add_text_field("_id", STRING | STORED);
+        let text = schema_builder.add_text_field("text", TEXT | STORED);
+        let schema = schema_builder.build();
+        let index = Index::create_in_ram(schema);
+        let mut writer = index.writer_with_num_threads(1, 10_000_000)?;
+        writer.add_document(doc!(id=>"1", text=>"the old man and the sea"))?;
+        writer.add_document(doc!(id=>"2", text=>"an old man sailing on the sea"))?;
+        writer.add_document(doc!(id=>"3", text=>"send this message to alice"))?;
+        writer.add_document(doc!(id=>"4", text=>"a lady was riding and old bike"))?;
+        writer.commit()?;
+        Ok(index)
+    }
+
+    #[test]
+    fn test_more_like_this_query() {
+        let index = create_test_index().unwrap();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        // Search by the document ID.
+        let opts_json_str = r#"
+            {
+                "doc_id": "1",
+                "fields": ["text"],
+                "min_doc_frequency": 1,
+                "min_term_frequency": 1,
+                "min_word_length": 3
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+        let opts = serde_json::from_slice::<MoreLikeThisQueryArgs>(opts_json_bytes).unwrap();
+        let query = create_more_like_this_query(&opts, &searcher).unwrap();
+
+        let (top_docs, count) = searcher
+            .search(&query, &(TopDocs::with_limit(20), Count))
+            .unwrap();
+
+        // The source document is excluded.
+        assert_eq!(count, 2);
+        let doc = searcher.doc(top_docs[0].1).unwrap();
+        assert_eq!(
+            doc.get_first(index.schema().get_field("_id").unwrap())
+                .unwrap()
+                .as_text(),
+            Some("2")
+        );
+
+        // Search by the raw text.
+        let opts_json_str = r#"
+            {
+                "text": "lady riding",
+                "fields": ["text"],
+                "min_doc_frequency": 1,
+                "min_term_frequency": 1
+            }
+        "#;
+        let opts_json_bytes = opts_json_str.as_bytes();
+        let opts = serde_json::from_slice::<MoreLikeThisQueryArgs>(opts_json_bytes).unwrap();
+        let query = create_more_like_this_query(&opts, &searcher).unwrap();
+
+        let count = searcher.search(&query, &Count).unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -1,5 +1,7 @@
 use tantivy::{query::PhraseQuery, Searcher, Term};
 
+use crate::index::{analyzer::analyze_text, search_tokenizer::search_analyzer};
+
 use super::{QueryFactoryError, QueryFactoryErrorKind};
 
 #[derive(Deserialize, Serialize)]
@@ -19,13 +21,40 @@ pub fn create_phrase_query(
             .with_error(anyhow::anyhow!("Field {} does not exist", &args.field))
     })?;
 
-    let phrase_terms: Vec<Term> = args
-        .phrase_terms
-        .iter()
-        .map(|phrase_term| Term::from_field_text(field, phrase_term))
-        .collect();
+    // If the field has a search tokenizer, the phrase terms are analyzed with it.
+    let phrase_terms: Vec<(usize, Term)> = match search_analyzer(searcher, &args.field) {
+        Some(analyzer) => {
+            let mut phrase_terms = Vec::new();
+            let mut offset = 0;
+            for phrase_term in args.phrase_terms.iter() {
+                let tokens = analyze_text(&analyzer, phrase_term);
+                for token in tokens.iter() {
+                    phrase_terms.push((
+                        offset + token.position,
+                        Term::from_field_text(field, &token.text),
+                    ));
+                }
+                offset += tokens
+                    .iter()
+                    .map(|token| token.position + token.position_length)
+                    .max()
+                    .unwrap_or(0);
+            }
+            phrase_terms
+        }
+        None => args
+            .phrase_terms
+            .iter()
+            .enumerate()
+            .map(|(offset, phrase_term)| (offset, Term::from_field_text(field, phrase_term)))
+            .collect(),
+    };
+    if phrase_terms.len() < 2 {
+        return Err(QueryFactoryErrorKind::InvalidArgument
+            .with_error(anyhow::anyhow!("A phrase query needs at least two terms")));
+    }
 
-    let mut query = PhraseQuery::new(phrase_terms);
+    let mut query = PhraseQuery::new_with_offset(phrase_terms);
 
     query.set_slop(args.slop);
 
@@ -34,6 +63,8 @@ pub fn create_phrase_query(
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use tantivy::{
         collector::{Count, TopDocs},
         doc,
@@ -41,7 +72,10 @@ mod tests {
         Index,
     };
 
-    use crate::search::query::phrase::{create_phrase_query, PhraseQueryArgs};
+    use crate::{
+        index::search_tokenizer::register_search_analyzers,
+        search::query::phrase::{create_phrase_query, PhraseQueryArgs},
+    };
 
     #[test]
     fn test_phrase_query_args() {
@@ -113,4 +147,23 @@ mod tests {
 
         assert_eq!(count, 2);
     }
+
+    #[test]
+    fn test_phrase_query_with_search_tokenizer() {
+        let index = create_test_index().unwrap();
+        let search_tokenizers = HashMap::from([("text".to_string(), "default".to_string())]);
+        register_search_analyzers(index.tokenizers(), &search_tokenizers).unwrap();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        // The phrase terms are lowercased and split by the search tokenizer.
+        let opts = PhraseQueryArgs {
+            phrase_terms: vec!["A B".to_string(), "C".to_string()],
+            field: "text".to_string(),
+            slop: 0,
+        };
+        let query = create_phrase_query(&opts, &searcher).unwrap();
+        let count = searcher.search(&query, &Count).unwrap();
+        assert_eq!(count, 2);
+    }
 }
@@ -1,15 +1,77 @@
+use std::str::FromStr;
+
 use tantivy::{
-    query::{Query, QueryParser},
-    schema::Field,
-    Searcher,
+    query::{BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, Query, QueryParser},
+    schema::{Field, FieldEntry, FieldType, Schema},
+    Searcher, Term,
 };
+use tantivy_query_grammar::{UserInputAst, UserInputBound, UserInputLeaf};
+use tracing::debug;
+
+use crate::index::search_tokenizer::{search_analyzer, search_analyzer_name};
 
 use super::{QueryFactoryError, QueryFactoryErrorKind};
 
+// The characters that must be escaped in a field name of a query string.
+const FIELD_NAME_SPECIAL_CHARS: &[char] = &[
+    '+', '^', '`', ':', '{', '}', '"', '[', ']', '(', ')', '~', '!', '\\', '*', ' ',
+];
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DefaultOperator {
+    #[default]
+    #[serde(alias = "OR")]
+    Or,
+    #[serde(alias = "AND")]
+    And,
+}
+
+fn default_fuzzy_distance() -> u8 {
+    1
+}
+
+fn default_transposition_cost_one() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct QueryStringFuzzyArgs {
+    #[serde(default = "default_fuzzy_distance")]
+    distance: u8,
+    #[serde(default = "default_transposition_cost_one")]
+    transposition_cost_one: bool,
+    #[serde(default)]
+    prefix: bool,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct QueryStringQueryArgs {
     query: String,
     default_search_fields: Vec<String>,
+    #[serde(default)]
+    default_operator: DefaultOperator,
+    #[serde(default)]
+    fuzzy: Option<QueryStringFuzzyArgs>,
+    #[serde(default)]
+    lenient: bool,
+}
+
+/// Parse a default search field with an optional boost, e.g. `title^3`.
+fn parse_field_boost(field_name: &str) -> Result<(&str, Option<f32>), QueryFactoryError> {
+    match field_name.rsplit_once('^') {
+        Some((name, boost)) => {
+            let boost = f32::from_str(boost)
+                .ok()
+                .filter(|boost| boost.is_finite() && *boost >= 0.0)
+                .ok_or_else(|| {
+                    QueryFactoryErrorKind::InvalidArgument
+                        .with_error(anyhow::anyhow!("Invalid field boost: {}", field_name))
+                })?;
+            Ok((name, Some(boost)))
+        }
+        None => Ok((field_name, None)),
+    }
 }
 
 /// Create a query string query.
@@ -17,38 +79,281 @@ pub fn create_query_string_query(
     args: &QueryStringQueryArgs,
     searcher: &Searcher,
 ) -> Result<Box<dyn Query>, QueryFactoryError> {
-    let default_fields: Vec<Field> = if args.default_search_fields.is_empty() {
+    let default_fields: Vec<(Field, Option<f32>)> = if args.default_search_fields.is_empty() {
         return Err(QueryFactoryErrorKind::InvalidArgument
             .with_error(anyhow::anyhow!("No default search fields provided.")));
     } else {
         let mut fields = Vec::new();
         for field_name in args.default_search_fields.iter() {
+            let (field_name, boost) = parse_field_boost(field_name)?;
             let field = searcher.schema().get_field(field_name).ok_or_else(|| {
                 QueryFactoryErrorKind::InvalidArgument
                     .with_error(anyhow::anyhow!("Field {} does not exist", field_name))
             })?;
-            fields.push(field);
+            fields.push((field, boost));
         }
         fields
     };
 
-    let query_parser = QueryParser::for_index(searcher.index(), default_fields);
+    let schema = search_schema(searcher);
+    let mut query_parser = QueryParser::new(
+        schema.clone(),
+        default_fields
+            .iter()
+            .map(|(field, _boost)| *field)
+            .collect(),
+        searcher.index().tokenizers().clone(),
+    );
+    if args.default_operator == DefaultOperator::And {
+        query_parser.set_conjunction_by_default();
+    }
+    for (field, boost) in default_fields.iter() {
+        if let Some(boost) = boost {
+            query_parser.set_field_boost(*field, *boost);
+        }
+    }
+
+    if args.fuzzy.is_none() && !args.lenient {
+        return query_parser
+            .parse_query(&args.query)
+            .map_err(|error| QueryFactoryErrorKind::InvalidArgument.with_error(error));
+    }
+
+    // The clauses are converted one by one, so that the terms on the default fields can be
+    // expanded to fuzzy queries and the clauses that fail to be parsed can be ignored.
+    let ast = tantivy_query_grammar::parse_query(&args.query).map_err(|_| {
+        QueryFactoryErrorKind::InvalidArgument
+            .with_error(anyhow::anyhow!("Syntax error: {}", args.query))
+    })?;
+    let converter = AstConverter {
+        searcher,
+        schema: &schema,
+        query_parser: &query_parser,
+        default_fields: &default_fields,
+        default_occur: match args.default_operator {
+            DefaultOperator::Or => Occur::Should,
+            DefaultOperator::And => Occur::Must,
+        },
+        fuzzy: args.fuzzy.as_ref(),
+        lenient: args.lenient,
+    };
+
+    Ok(converter
+        .convert(ast)?
+        .unwrap_or_else(|| Box::new(BooleanQuery::new(Vec::new()))))
+}
+
+struct AstConverter<'a> {
+    searcher: &'a Searcher,
+    schema: &'a Schema,
+    query_parser: &'a QueryParser,
+    default_fields: &'a [(Field, Option<f32>)],
+    default_occur: Occur,
+    fuzzy: Option<&'a QueryStringFuzzyArgs>,
+    lenient: bool,
+}
+
+impl<'a> AstConverter<'a> {
+    /// Convert the AST to a query. `None` is returned if all the clauses are ignored.
+    fn convert(&self, ast: UserInputAst) -> Result<Option<Box<dyn Query>>, QueryFactoryError> {
+        match ast {
+            UserInputAst::Clause(clauses) => {
+                let mut subqueries = Vec::new();
+                for (occur, clause) in clauses {
+                    if let Some(subquery) = self.convert(clause)? {
+                        subqueries.push((occur.unwrap_or(self.default_occur), subquery));
+                    }
+                }
+                if !subqueries.is_empty()
+                    && subqueries
+                        .iter()
+                        .all(|(occur, _subquery)| *occur == Occur::MustNot)
+                {
+                    return Err(QueryFactoryErrorKind::InvalidArgument
+                        .with_error(anyhow::anyhow!("Invalid query: Only excluding terms given")));
+                }
+                match subqueries.len() {
+                    0 => Ok(None),
+                    1 => Ok(subqueries.pop().map(|(_occur, subquery)| subquery)),
+                    _ => Ok(Some(Box::new(BooleanQuery::new(subqueries)))),
+                }
+            }
+            UserInputAst::Boost(ast, boost) => Ok(self
+                .convert(*ast)?
+                .map(|query| Box::new(BoostQuery::new(query, boost as f32)) as Box<dyn Query>)),
+            UserInputAst::Leaf(leaf) => {
+                if let UserInputLeaf::Literal(literal) = leaf.as_ref() {
+                    if literal.field_name.is_none() {
+                        if let Some(query) = self.fuzzy_query(&literal.phrase) {
+                            return Ok(Some(query));
+                        }
+                    }
+                }
+
+                let query_str = leaf_to_query_string(&leaf);
+                match self.query_parser.parse_query(&query_str) {
+                    Ok(query) => Ok(Some(query)),
+                    Err(error) if self.lenient => {
+                        debug!(
+                            ?query_str,
+                            ?error,
+                            "Ignore the clause that failed to be parsed."
+                        );
+                        Ok(None)
+                    }
+                    Err(error) => Err(QueryFactoryErrorKind::InvalidArgument.with_error(error)),
+                }
+            }
+        }
+    }
+
+    /// Create fuzzy term queries on the default fields if the text is a single term.
+    /// `None` is returned if the fuzzy expansion does not apply to the text.
+    fn fuzzy_query(&self, text: &str) -> Option<Box<dyn Query>> {
+        let fuzzy = self.fuzzy?;
+
+        let mut subqueries = Vec::new();
+        for (field, boost) in self.default_fields.iter() {
+            let tokenizer_name = match self.schema.get_field_entry(*field).field_type() {
+                FieldType::Str(options) => options.get_indexing_options()?.tokenizer(),
+                _ => return None,
+            };
+            let analyzer = self.searcher.index().tokenizers().get(tokenizer_name)?;
+            let mut tokens = Vec::new();
+            analyzer
+                .token_stream(text)
+                .process(&mut |token| tokens.push(token.text.clone()));
+            if tokens.len() != 1 {
+                return None;
+            }
+
+            let term = Term::from_field_text(*field, &tokens[0]);
+            let query: Box<dyn Query> = if fuzzy.prefix {
+                Box::new(FuzzyTermQuery::new_prefix(
+                    term,
+                    fuzzy.distance,
+                    fuzzy.transposition_cost_one,
+                ))
+            } else {
+                Box::new(FuzzyTermQuery::new(
+                    term,
+                    fuzzy.distance,
+                    fuzzy.transposition_cost_one,
+                ))
+            };
+            let query = match boost {
+                Some(boost) => Box::new(BoostQuery::new(query, *boost)),
+                None => query,
+            };
+            subqueries.push((Occur::Should, query));
+        }
+
+        if subqueries.len() == 1 {
+            subqueries.pop().map(|(_occur, query)| query)
+        } else {
+            Some(Box::new(BooleanQuery::new(subqueries)))
+        }
+    }
+}
+
+fn escape_field_name(field_name: &str) -> String {
+    let mut escaped = String::with_capacity(field_name.len());
+    for c in field_name.chars() {
+        if FIELD_NAME_SPECIAL_CHARS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Convert the leaf back to a query string that is parsed to the same leaf.
+fn leaf_to_query_string(leaf: &UserInputLeaf) -> String {
+    match leaf {
+        UserInputLeaf::Literal(literal) => match &literal.field_name {
+            Some(field_name) => format!("{}:\"{}\"", escape_field_name(field_name), literal.phrase),
+            None => format!("\"{}\"", literal.phrase),
+        },
+        UserInputLeaf::All => "*".to_string(),
+        UserInputLeaf::Range {
+            field,
+            lower,
+            upper,
+        } => {
+            let field = field
+                .as_ref()
+                .map(|field_name| format!("{}:", escape_field_name(field_name)))
+                .unwrap_or_default();
+            let lower = match lower {
+                UserInputBound::Inclusive(bound) => format!("[{}", bound),
+                UserInputBound::Exclusive(bound) => format!("{{{}", bound),
+                UserInputBound::Unbounded => "{*".to_string(),
+            };
+            let upper = match upper {
+                UserInputBound::Inclusive(bound) => format!("{}]", bound),
+                UserInputBound::Exclusive(bound) => format!("{}}}", bound),
+                UserInputBound::Unbounded => "*}".to_string(),
+            };
+            format!("{}{} TO {}", field, lower, upper)
+        }
+    }
+}
+
+/// Returns the schema in which the text fields that have search tokenizers are tokenized
+/// with them, so that the query parser analyzes the query text with the search tokenizers.
+fn search_schema(searcher: &Searcher) -> Schema {
+    let schema = searcher.schema();
+    if !schema
+        .fields()
+        .any(|(_field, field_entry)| search_analyzer(searcher, field_entry.name()).is_some())
+    {
+        return schema.clone();
+    }
+
+    let mut schema_builder = Schema::builder();
+    for (_field, field_entry) in schema.fields() {
+        let search_field_entry = match field_entry.field_type() {
+            FieldType::Str(options) if search_analyzer(searcher, field_entry.name()).is_some() => {
+                options.get_indexing_options().map(|indexing_options| {
+                    FieldEntry::new_text(
+                        field_entry.name().to_string(),
+                        options.clone().set_indexing_options(
+                            indexing_options
+                                .clone()
+                                .set_tokenizer(&search_analyzer_name(field_entry.name())),
+                        ),
+                    )
+                })
+            }
+            _ => None,
+        };
+        // The fields are added in the same order, so the field IDs are preserved.
+        schema_builder.add_field(search_field_entry.unwrap_or_else(|| field_entry.clone()));
+    }
 
-    query_parser
-        .parse_query(&args.query)
-        .map_err(|error| QueryFactoryErrorKind::InvalidArgument.with_error(error))
+    schema_builder.build()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use tantivy::{
         collector::{Count, TopDocs},
         doc,
-        schema::{Schema, TEXT},
+        schema::{
+            IndexRecordOption, Schema, TextFieldIndexing, TextOptions, INDEXED, STORED, TEXT,
+        },
+        tokenizer::{LowerCaser, NgramTokenizer, TextAnalyzer},
         Index,
     };
 
-    use crate::search::query::query_string::{create_query_string_query, QueryStringQueryArgs};
+    use crate::{
+        index::search_tokenizer::register_search_analyzers,
+        search::query::query_string::{
+            create_query_string_query, DefaultOperator, QueryStringQueryArgs,
+        },
+    };
 
     #[test]
     fn test_query_string_query_args() {
@@ -117,4 +422,201 @@ mod tests {
 
         assert_eq!(count, 1);
     }
+
+    #[test]
+    fn test_create_query_string_query_with_search_tokenizer() {
+        let mut schema_builder = Schema::builder();
+        let title = schema_builder.add_text_field(
+            "title",
+            TextOptions::default().set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer("autocomplete")
+                    .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+            ),
+        );
+        let schema = schema_builder.build();
+        let index = Index::create_in_ram(schema);
+        index.tokenizers().register(
+            "autocomplete",
+            TextAnalyzer::from(NgramTokenizer::prefix_only(1, 10)).filter(LowerCaser),
+        );
+        let search_tokenizers = HashMap::from([("title".to_string(), "default".to_string())]);
+        register_search_analyzers(index.tokenizers(), &search_tokenizers).unwrap();
+
+        let mut writer = index.writer_with_num_threads(1, 10_000_000).unwrap();
+        writer.add_document(doc!(title=>"Hello")).unwrap();
+        writer.add_document(doc!(title=>"Help")).unwrap();
+        writer.commit().unwrap();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        // The query text is not split into n-grams.
+        for (query_text, expected) in [("hel", 2), ("Hello", 1), ("helicopter", 0)] {
+            let opts = QueryStringQueryArgs {
+                query: query_text.to_string(),
+                default_search_fields: vec!["title".to_string()],
+                default_operator: DefaultOperator::Or,
+                fuzzy: None,
+                lenient: false,
+            };
+            let query = create_query_string_query(&opts, &searcher).unwrap();
+            let count = searcher.search(&query, &Count).unwrap();
+            assert_eq!(count, expected);
+        }
+    }
+
+    fn create_test_index_with_fields() -> Index {
+        let mut schema_builder = Schema::builder();
+        let title = schema_builder.add_text_field("title", TEXT | STORED);
+        let body = schema_builder.add_text_field("body", TEXT);
+        let price = schema_builder.add_u64_field("price", INDEXED);
+        let schema = schema_builder.build();
+        let index = Index::create_in_ram(schema);
+        let mut writer = index.writer_with_num_threads(1, 10_000_000).unwrap();
+        writer
+            .add_document(doc!(title=>"rust search", body=>"engine", price=>10u64))
+            .unwrap();
+        writer
+            .add_document(doc!(title=>"engine", body=>"rust search", price=>20u64))
+            .unwrap();
+        writer
+            .add_document(doc!(title=>"search", body=>"python", price=>30u64))
+            .unwrap();
+        writer.commit().unwrap();
+        index
+    }
+
+    fn search_titles(index: &Index, json_str: &str) -> Vec<String> {
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+        let title = searcher.schema().get_field("title").unwrap();
+
+        let args = serde_json::from_str::<QueryStringQueryArgs>(json_str).unwrap();
+        let query = create_query_string_query(&args, &searcher).unwrap();
+        searcher
+            .search(&query, &TopDocs::with_limit(10))
+            .unwrap()
+            .into_iter()
+            .map(|(_score, doc_address)| {
+                let doc = searcher.doc(doc_address).unwrap();
+                doc.get_first(title).unwrap().as_text().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_query_string_query_default_operator() {
+        let index = create_test_index_with_fields();
+
+        let titles = search_titles(
+            &index,
+            r#"{"query": "rust search", "default_search_fields": ["title", "body"]}"#,
+        );
+        assert_eq!(titles.len(), 3);
+
+        let titles = search_titles(
+            &index,
+            r#"
+            {
+                "query": "rust search",
+                "default_search_fields": ["title", "body"],
+                "default_operator": "AND"
+            }
+            "#,
+        );
+        assert_eq!(titles.len(), 2);
+    }
+
+    #[test]
+    fn test_query_string_query_field_boost() {
+        let index = create_test_index_with_fields();
+
+        let titles = search_titles(
+            &index,
+            r#"{"query": "engine", "default_search_fields": ["title", "body^10"]}"#,
+        );
+        assert_eq!(titles, vec!["rust search", "engine"]);
+
+        let titles = search_titles(
+            &index,
+            r#"{"query": "engine", "default_search_fields": ["title^10", "body"]}"#,
+        );
+        assert_eq!(titles, vec!["engine", "rust search"]);
+
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+        let args = serde_json::from_str::<QueryStringQueryArgs>(
+            r#"{"query": "engine", "default_search_fields": ["title^high"]}"#,
+        )
+        .unwrap();
+        assert!(create_query_string_query(&args, &searcher).is_err());
+    }
+
+    #[test]
+    fn test_query_string_query_lenient() {
+        let index = create_test_index_with_fields();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        let args = serde_json::from_str::<QueryStringQueryArgs>(
+            r#"{"query": "python price:ten", "default_search_fields": ["body"]}"#,
+        )
+        .unwrap();
+        assert!(create_query_string_query(&args, &searcher).is_err());
+
+        let titles = search_titles(
+            &index,
+            r#"
+            {
+                "query": "python price:ten",
+                "default_search_fields": ["body"],
+                "lenient": true
+            }
+            "#,
+        );
+        assert_eq!(titles, vec!["search"]);
+
+        let titles = search_titles(
+            &index,
+            r#"
+            {
+                "query": "+search +price:[15 TO 25] unknown:field",
+                "default_search_fields": ["title", "body"],
+                "lenient": true
+            }
+            "#,
+        );
+        assert_eq!(titles, vec!["engine"]);
+    }
+
+    #[test]
+    fn test_query_string_query_fuzzy() {
+        let index = create_test_index_with_fields();
+
+        let json_str = r#"
+            {
+                "query": "pyton",
+                "default_search_fields": ["title", "body"]
+            }
+            "#;
+        assert!(search_titles(&index, json_str).is_empty());
+
+        let json_str = r#"
+            {
+                "query": "+pyton +title:search",
+                "default_search_fields": ["title", "body"],
+                "fuzzy": {}
+            }
+            "#;
+        assert_eq!(search_titles(&index, json_str), vec!["search"]);
+
+        let json_str = r#"
+            {
+                "query": "serch",
+                "default_search_fields": ["title"],
+                "fuzzy": {"distance": 1, "transposition_cost_one": true, "prefix": false}
+            }
+            "#;
+        assert_eq!(search_titles(&index, json_str).len(), 2);
+    }
 }
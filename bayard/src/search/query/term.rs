@@ -1,5 +1,7 @@
 use tantivy::{query::TermQuery, Searcher, Term};
 
+use crate::index::{analyzer::analyze_text, search_tokenizer::search_analyzer};
+
 use super::{QueryFactoryError, QueryFactoryErrorKind};
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -18,7 +20,24 @@ pub fn create_term_query(
             .with_error(anyhow::anyhow!("Field {} does not exist", &args.field))
     })?;
 
-    let term = Term::from_field_text(field, &args.term);
+    // If the field has a search tokenizer, the term is analyzed with it.
+    let term_text = match search_analyzer(searcher, &args.field) {
+        Some(analyzer) => match analyze_text(&analyzer, &args.term).as_slice() {
+            [token] => token.text.clone(),
+            tokens => {
+                return Err(
+                    QueryFactoryErrorKind::InvalidArgument.with_error(anyhow::anyhow!(
+                        "Term {} is analyzed into {} tokens",
+                        &args.term,
+                        tokens.len()
+                    )),
+                )
+            }
+        },
+        None => args.term.clone(),
+    };
+
+    let term = Term::from_field_text(field, &term_text);
 
     let segment_postings_options = searcher
         .schema()
@@ -37,6 +56,8 @@ pub fn create_term_query(
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use tantivy::{
         collector::{Count, TopDocs},
         doc,
@@ -44,7 +65,10 @@ mod tests {
         Index,
     };
 
-    use crate::search::query::term::{create_term_query, TermQueryArgs};
+    use crate::{
+        index::search_tokenizer::register_search_analyzers,
+        search::query::term::{create_term_query, TermQueryArgs},
+    };
 
     #[test]
     fn test_term_query_args() {
@@ -110,4 +134,28 @@ mod tests {
 
         assert_eq!(count, 1);
     }
+
+    #[test]
+    fn test_term_query_with_search_tokenizer() {
+        let index = create_test_index().unwrap();
+        let search_tokenizers = HashMap::from([("text".to_string(), "default".to_string())]);
+        register_search_analyzers(index.tokenizers(), &search_tokenizers).unwrap();
+        let reader = index.reader().unwrap();
+        let searcher = reader.searcher();
+
+        // The term is lowercased by the search tokenizer.
+        let opts = TermQueryArgs {
+            field: "text".to_string(),
+            term: "AAA".to_string(),
+        };
+        let query = create_term_query(&opts, &searcher).unwrap();
+        let count = searcher.search(&query, &Count).unwrap();
+        assert_eq!(count, 1);
+
+        let opts = TermQueryArgs {
+            field: "text".to_string(),
+            term: "aaa bbb".to_string(),
+        };
+        assert!(create_term_query(&opts, &searcher).is_err());
+    }
 }
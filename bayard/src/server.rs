@@ -4,20 +4,23 @@ use axum::{
     routing::{delete, get, post, put},
     Extension, Router, Server as AxumServer,
 };
+use axum_server::Handle;
 use futures_util::FutureExt;
-use http::Uri;
 use tokio::sync::{watch::Receiver, RwLock};
-use tonic::transport::{Endpoint as TonicEndpoint, Server as TonicServer};
+use tonic::transport::Server as TonicServer;
 use tracing::info;
 
 use crate::{
-    client::Client,
+    audit::{service::AuditedIndexService, AuditLog},
+    auth::{middleware::AuthLayer, Authenticator},
+    client::{create_channel, Client},
     handler::{
-        cluster::nodes,
+        cluster::{decommission_node, nodes, placement_violations},
         healthcheck::{liveness, readiness},
         index::{
-            commit, create_index, delete_documents, delete_index, get_index, modify_index,
-            put_documents, rollback, search,
+            analyze, commit, create_index, delete_documents, delete_index, delete_user_dictionary,
+            get_cache_stats, get_index, modify_index, put_documents, put_user_dictionary,
+            reload_analyzers, rollback, search,
         },
     },
     node::Node,
@@ -25,27 +28,56 @@ use crate::{
         cluster::cluster_service_server::ClusterServiceServer,
         healthcheck::health_check_service_server::HealthCheckServiceServer,
         index::index_service_server::IndexServiceServer,
+        raft::raft_service_server::RaftServiceServer,
+        shard::shard_service_server::ShardServiceServer,
     },
-    service::{cluster::ClusterService, healthcheck::HealthCheckService, index::IndexService},
+    raft::Raft,
+    service::{
+        cluster::ClusterService, healthcheck::HealthCheckService, index::IndexService,
+        raft::RaftService, shard::ShardService,
+    },
+    tls::TlsConfig,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_grpc_server(
     grpc_address: SocketAddr,
     node: Arc<RwLock<Node>>,
     client: Arc<Client>,
+    raft: Arc<Raft>,
+    tls_config: Option<TlsConfig>,
+    authenticator: Option<Arc<Authenticator>>,
+    audit_log: Option<Arc<AuditLog>>,
     mut rx_signal: Receiver<()>,
 ) {
+    let mut server_builder = TonicServer::builder();
+    if let Some(tls_config) = tls_config.as_ref() {
+        info!(
+            client_auth = tls_config.client_auth(),
+            "Enabling TLS for gRPC server."
+        );
+        server_builder = server_builder
+            .tls_config(tls_config.server_tls_config())
+            .unwrap();
+    }
+
     info!(?grpc_address, "Starting gRPC server.");
-    TonicServer::builder()
+    server_builder
+        .layer(AuthLayer::grpc(authenticator, tls_config))
         .add_service(ClusterServiceServer::new(ClusterService::new(Arc::clone(
             &node,
         ))))
         .add_service(HealthCheckServiceServer::new(HealthCheckService::new()))
-        .add_service(IndexServiceServer::new(
+        .add_service(IndexServiceServer::new(AuditedIndexService::new(
             IndexService::new(Arc::clone(&node), Arc::clone(&client))
                 .await
                 .unwrap(),
-        ))
+            audit_log,
+        )))
+        .add_service(RaftServiceServer::new(RaftService::new(raft)))
+        .add_service(ShardServiceServer::new(ShardService::new(Arc::clone(
+            &node,
+        ))))
         .serve_with_shutdown(
             grpc_address,
             rx_signal.changed().map(|s| {
@@ -60,21 +92,19 @@ pub async fn handle_grpc_server(
 pub async fn handle_http_server(
     http_address: SocketAddr,
     grpc_address: SocketAddr,
+    tls_config: Option<TlsConfig>,
+    authenticator: Option<Arc<Authenticator>>,
     mut rx_signal: Receiver<()>,
 ) {
-    let uri = Uri::builder()
-        .scheme("http")
-        .authority(grpc_address.to_string())
-        .path_and_query("/")
-        .build()
-        .unwrap();
-    let channel = TonicEndpoint::from(uri).connect_lazy();
+    let channel = create_channel(&grpc_address, tls_config.as_ref()).unwrap();
 
     // Create the router.
     let app = Router::new()
         .route("/healthcheck/livez", get(liveness))
         .route("/healthcheck/readyz", get(readiness))
         .route("/cluster/nodes", get(nodes))
+        .route("/cluster/placement_violations", get(placement_violations))
+        .route("/cluster/decommission", post(decommission_node))
         .route("/indices/:index", put(create_index))
         .route("/indices/:index", delete(delete_index))
         .route("/indices/:index", get(get_index))
@@ -84,15 +114,51 @@ pub async fn handle_http_server(
         .route("/indices/:index/commit", get(commit))
         .route("/indices/:index/rollback", get(rollback))
         .route("/indices/:index/search", post(search))
-        .layer(Extension(channel));
+        .route("/indices/:index/cache_stats", get(get_cache_stats))
+        .route("/indices/:index/_analyze", post(analyze))
+        .route("/indices/:index/reload_analyzers", post(reload_analyzers))
+        .route(
+            "/indices/:index/user_dictionaries/:file_name",
+            put(put_user_dictionary),
+        )
+        .route(
+            "/indices/:index/user_dictionaries/:file_name",
+            delete(delete_user_dictionary),
+        )
+        .layer(Extension(channel))
+        .layer(AuthLayer::http(authenticator));
+
+    let tls_config = match tls_config {
+        Some(tls_config) => tls_config,
+        None => {
+            info!(?http_address, "Starting HTTP server.");
+            AxumServer::bind(&http_address)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(rx_signal.changed().map(|s| {
+                    info!(?http_address, "Stopping HTTP server.");
+                    drop(s);
+                }))
+                .await
+                .unwrap();
+            return;
+        }
+    };
+
+    info!("Enabling TLS for HTTP server.");
+    let rustls_config = tls_config.rustls_config().await.unwrap();
+
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        let _ = rx_signal.changed().await;
+        info!(?http_address, "Stopping HTTP server.");
+        shutdown_handle.graceful_shutdown(None);
+    });
 
     info!(?http_address, "Starting HTTP server.");
-    AxumServer::bind(&http_address)
-        .serve(app.into_make_service())
-        .with_graceful_shutdown(rx_signal.changed().map(|s| {
-            info!(?http_address, "Stopping HTTP server.");
-            drop(s);
-        }))
+    axum_server::bind_rustls(http_address, rustls_config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
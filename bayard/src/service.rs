@@ -3,6 +3,8 @@ use std::fmt;
 pub mod cluster;
 pub mod healthcheck;
 pub mod index;
+pub mod raft;
+pub mod shard;
 
 #[derive(Debug, Clone, Copy)]
 pub enum ServiceErrorKind {
@@ -1,17 +1,23 @@
 use std::sync::Arc;
 
 use tokio::sync::RwLock;
-use tonic::{Request, Response, Status};
+use tonic::{Code, Request, Response, Status};
 use tracing::debug;
 
 use crate::{
+    auth::{authorize, Privilege},
     node::Node,
     proto::cluster::{
-        cluster_service_server::ClusterService as ProtoClusterService, GetNodesRequest,
-        GetNodesResponse, Metadata, Node as ProtoNode,
+        cluster_service_server::ClusterService as ProtoClusterService, DecommissionNodeRequest,
+        DecommissionNodeResponse, GetNodesRequest, GetNodesResponse, GetPlacementViolationsRequest,
+        GetPlacementViolationsResponse, Metadata, Node as ProtoNode,
     },
 };
 
+// Decommissioning a node moves the shards of all indices, so it requires the manage privilege on
+// all of them.
+const ALL_INDICES: &str = "*";
+
 pub struct ClusterService {
     node: Arc<RwLock<Node>>,
 }
@@ -28,7 +34,7 @@ impl ProtoClusterService for ClusterService {
         &self,
         request: Request<GetNodesRequest>,
     ) -> Result<Response<GetNodesResponse>, Status> {
-        debug!(?request, "get_nodes");
+        debug!(request = ?request.get_ref(), "get_nodes");
 
         let mut nodes = Vec::new();
 
@@ -45,6 +51,9 @@ impl ProtoClusterService for ClusterService {
                     http_address: metadata
                         .http_address
                         .map_or(String::new(), |addr| addr.to_string()),
+                    capacity: metadata.capacity(),
+                    zone: metadata.zone.unwrap_or_default(),
+                    leaving: metadata.leaving,
                 }),
             });
         } else {
@@ -67,6 +76,9 @@ impl ProtoClusterService for ClusterService {
                         http_address: metadata
                             .http_address
                             .map_or(String::new(), |addr| addr.to_string()),
+                        capacity: metadata.capacity(),
+                        zone: metadata.zone.unwrap_or_default(),
+                        leaving: metadata.leaving,
                     }),
                 });
             } else {
@@ -81,4 +93,42 @@ impl ProtoClusterService for ClusterService {
 
         Ok(Response::new(reply))
     }
+
+    async fn get_placement_violations(
+        &self,
+        request: Request<GetPlacementViolationsRequest>,
+    ) -> Result<Response<GetPlacementViolationsResponse>, Status> {
+        debug!(request = ?request.get_ref(), "get_placement_violations");
+
+        match self
+            .node
+            .read()
+            .await
+            .placement_violations(request.into_inner())
+            .await
+        {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
+        }
+    }
+
+    async fn decommission_node(
+        &self,
+        request: Request<DecommissionNodeRequest>,
+    ) -> Result<Response<DecommissionNodeResponse>, Status> {
+        debug!(request = ?request.get_ref(), "decommission_node");
+
+        authorize(&request, Privilege::Manage, ALL_INDICES).map_err(|error| error.to_status())?;
+
+        match self
+            .node
+            .read()
+            .await
+            .decommission(request.into_inner())
+            .await
+        {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
+        }
+    }
 }
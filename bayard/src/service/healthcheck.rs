@@ -27,7 +27,7 @@ impl ProtoHealthCheckService for HealthCheckService {
         &self,
         request: Request<LivenessRequest>,
     ) -> Result<Response<LivenessResponse>, Status> {
-        debug!(?request, "liveness");
+        debug!(request = ?request.get_ref(), "liveness");
 
         let reply = LivenessResponse {
             state: LivenessState::Alive as i32,
@@ -40,7 +40,7 @@ impl ProtoHealthCheckService for HealthCheckService {
         &self,
         request: Request<ReadinessRequest>,
     ) -> Result<Response<ReadinessResponse>, Status> {
-        debug!(?request, "liveness");
+        debug!(request = ?request.get_ref(), "liveness");
 
         let reply = ReadinessResponse {
             state: ReadinessState::Ready as i32,
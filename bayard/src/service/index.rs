@@ -1,23 +1,39 @@
-use std::sync::Arc;
+use std::{future::Future, sync::Arc};
 
 use tokio::{sync::RwLock, time::Instant};
-use tonic::{Code, Request, Response, Status};
-use tracing::info;
+use tonic::{metadata::AsciiMetadataValue, transport::Channel, Code, Request, Response, Status};
+use tracing::{debug, info, warn};
 
 use crate::{
+    auth::{authorize, document_filter, Credentials, Privilege},
     client::Client,
-    node::Node,
+    node::{Node, NodeError},
     proto::index::{
-        index_service_server::IndexService as ProtoIndexService, CommitRequest, CommitResponse,
-        CreateIndexRequest, CreateIndexResponse, DeleteDocumentsRequest, DeleteDocumentsResponse,
-        DeleteIndexRequest, DeleteIndexResponse, GetIndexRequest, GetIndexResponse,
+        index_service_client::IndexServiceClient,
+        index_service_server::IndexService as ProtoIndexService, query::Kind as ProtoQueryKind,
+        AnalyzeRequest, AnalyzeResponse, CommitRequest, CommitResponse, CreateIndexRequest,
+        CreateIndexResponse, DeleteDocumentsRequest, DeleteDocumentsResponse, DeleteIndexRequest,
+        DeleteIndexResponse, DeleteUserDictionaryRequest, DeleteUserDictionaryResponse,
+        GetCacheStatsRequest, GetCacheStatsResponse, GetIndexRequest, GetIndexResponse,
         ModifyIndexRequest, ModifyIndexResponse, PutDocumentsRequest, PutDocumentsResponse,
-        RollbackRequest, RollbackResponse, SearchRequest, SearchResponse,
+        PutUserDictionaryRequest, PutUserDictionaryResponse, ReloadAnalyzersRequest,
+        ReloadAnalyzersResponse, RollbackRequest, RollbackResponse, SearchRequest, SearchResponse,
     },
+    search::query::boolean::{create_boolean_proto_query, Occurrence, SubQuery},
 };
 
 use super::ServiceError;
 
+// The metadata that marks the requests forwarded to the leader, so that they are not forwarded
+// again while the leadership is changing.
+const X_FORWARDED_TO_LEADER: &str = "x-bayard-forwarded-to-leader";
+
+enum Leader {
+    Local,
+    Remote(IndexServiceClient<Channel>),
+    Unavailable(String),
+}
+
 pub struct IndexService {
     node: Arc<RwLock<Node>>,
     client: Arc<Client>,
@@ -27,6 +43,71 @@ impl IndexService {
     pub async fn new(node: Arc<RwLock<Node>>, client: Arc<Client>) -> Result<Self, ServiceError> {
         Ok(Self { node, client })
     }
+
+    /// Find the node that accepts the changes of the index metadata.
+    async fn leader<T>(&self, request: &Request<T>) -> Leader {
+        let node = self.node.read().await;
+        if node.is_leader() {
+            return Leader::Local;
+        }
+        if request.metadata().contains_key(X_FORWARDED_TO_LEADER) {
+            return Leader::Unavailable("This node is no longer the leader.".to_string());
+        }
+
+        let leader = match node.leader() {
+            Some(leader) => leader,
+            None => return Leader::Unavailable("The leader is unknown.".to_string()),
+        };
+        match self.client.leader_client(&leader) {
+            Ok(client) => Leader::Remote(client),
+            Err(error) => {
+                warn!(?leader, ?error, "Failed to create leader client.");
+                Leader::Unavailable(format!("Failed to connect to the leader {}.", leader))
+            }
+        }
+    }
+
+    /// Make the change of the index metadata with `local` on the leader, or forward the request
+    /// to the leader with `remote`.
+    async fn on_leader<T, R, L, LF, F, RF>(
+        &self,
+        request: Request<T>,
+        local: L,
+        remote: F,
+    ) -> Result<Response<R>, Status>
+    where
+        L: FnOnce(Arc<RwLock<Node>>, T) -> LF,
+        LF: Future<Output = Result<R, NodeError>>,
+        F: FnOnce(IndexServiceClient<Channel>, Request<T>) -> RF,
+        RF: Future<Output = Result<Response<R>, Status>>,
+    {
+        match self.leader(&request).await {
+            Leader::Local => local(Arc::clone(&self.node), request.into_inner())
+                .await
+                .map(Response::new)
+                .map_err(|error| Status::new(Code::Internal, error.to_string())),
+            Leader::Remote(client) => {
+                let credentials = Credentials::from_request(&request);
+                debug!("Forwarding the request to the leader.");
+                remote(
+                    client,
+                    forward_to_leader(&credentials, request.into_inner()),
+                )
+                .await
+            }
+            Leader::Unavailable(message) => Err(Status::new(Code::Unavailable, message)),
+        }
+    }
+}
+
+/// Create the request that is forwarded to the leader with the credentials of the original one.
+fn forward_to_leader<T>(credentials: &Credentials, message: T) -> Request<T> {
+    let mut request = credentials.request(message);
+    request.metadata_mut().insert(
+        X_FORWARDED_TO_LEADER,
+        AsciiMetadataValue::from_static("true"),
+    );
+    request
 }
 
 #[tonic::async_trait]
@@ -37,12 +118,17 @@ impl ProtoIndexService for IndexService {
     ) -> Result<tonic::Response<CreateIndexResponse>, Status> {
         let now = Instant::now();
 
-        let req = request.into_inner();
+        authorize(&request, Privilege::Manage, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
 
-        let resp = match self.node.read().await.create_index(req).await {
-            Ok(resp) => Ok(Response::new(resp)),
-            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
-        };
+        // The changes of the index metadata are made by the leader.
+        let resp = self
+            .on_leader(
+                request,
+                |node, req| async move { node.read().await.create_index(req).await },
+                |mut client, req| async move { client.create_index(req).await },
+            )
+            .await;
 
         info!(elapsed = ?now.elapsed(), "Create index completed.");
 
@@ -55,12 +141,17 @@ impl ProtoIndexService for IndexService {
     ) -> Result<tonic::Response<DeleteIndexResponse>, Status> {
         let now = Instant::now();
 
-        let req = request.into_inner();
+        authorize(&request, Privilege::Manage, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
 
-        let resp = match self.node.read().await.delete_index(req).await {
-            Ok(resp) => Ok(Response::new(resp)),
-            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
-        };
+        // The changes of the index metadata are made by the leader.
+        let resp = self
+            .on_leader(
+                request,
+                |node, req| async move { node.read().await.delete_index(req).await },
+                |mut client, req| async move { client.delete_index(req).await },
+            )
+            .await;
 
         info!(elapsed = ?now.elapsed(), "Delete index completed.");
 
@@ -73,6 +164,9 @@ impl ProtoIndexService for IndexService {
     ) -> Result<tonic::Response<GetIndexResponse>, Status> {
         let now = Instant::now();
 
+        authorize(&request, Privilege::Read, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+
         let req = request.into_inner();
 
         let resp = match self.node.read().await.get_index(req).await {
@@ -91,12 +185,17 @@ impl ProtoIndexService for IndexService {
     ) -> Result<tonic::Response<ModifyIndexResponse>, Status> {
         let now = Instant::now();
 
-        let req = request.into_inner();
+        authorize(&request, Privilege::Manage, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
 
-        let resp = match self.node.read().await.modify_index(req).await {
-            Ok(resp) => Ok(Response::new(resp)),
-            Err(e) => Err(Status::new(Code::Internal, e.to_string())),
-        };
+        // The changes of the index metadata are made by the leader.
+        let resp = self
+            .on_leader(
+                request,
+                |node, req| async move { node.read().await.modify_index(req).await },
+                |mut client, req| async move { client.modify_index(req).await },
+            )
+            .await;
 
         info!(elapsed = ?now.elapsed(), "Modify index completed.");
 
@@ -109,10 +208,14 @@ impl ProtoIndexService for IndexService {
     ) -> Result<tonic::Response<PutDocumentsResponse>, Status> {
         let now = Instant::now();
 
+        authorize(&request, Privilege::Write, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+        let credentials = Credentials::from_request(&request);
+
         let req = request.into_inner();
 
         let resp = if req.shard_id.is_empty() {
-            self.client.put_documents(Request::new(req)).await
+            self.client.put_documents(credentials.request(req)).await
         } else {
             let resp = self
                 .node
@@ -140,10 +243,14 @@ impl ProtoIndexService for IndexService {
     ) -> Result<tonic::Response<DeleteDocumentsResponse>, Status> {
         let now = Instant::now();
 
+        authorize(&request, Privilege::Write, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+        let credentials = Credentials::from_request(&request);
+
         let req = request.into_inner();
 
         let resp = if req.shard_id.is_empty() {
-            self.client.delete_documents(Request::new(req)).await
+            self.client.delete_documents(credentials.request(req)).await
         } else {
             let resp = self
                 .node
@@ -171,10 +278,14 @@ impl ProtoIndexService for IndexService {
     ) -> Result<tonic::Response<CommitResponse>, Status> {
         let now = Instant::now();
 
+        authorize(&request, Privilege::Write, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+        let credentials = Credentials::from_request(&request);
+
         let req = request.into_inner();
 
         let resp = if req.shard_id.is_empty() {
-            self.client.commit(Request::new(req)).await
+            self.client.commit(credentials.request(req)).await
         } else {
             let resp = self.node.read().await.commit(req).await.map_err(|error| {
                 Status::new(
@@ -196,10 +307,14 @@ impl ProtoIndexService for IndexService {
     ) -> Result<tonic::Response<RollbackResponse>, Status> {
         let now = Instant::now();
 
+        authorize(&request, Privilege::Write, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+        let credentials = Credentials::from_request(&request);
+
         let req = request.into_inner();
 
         let resp = if req.shard_id.is_empty() {
-            self.client.rollback(Request::new(req)).await
+            self.client.rollback(credentials.request(req)).await
         } else {
             let resp = self
                 .node
@@ -227,10 +342,41 @@ impl ProtoIndexService for IndexService {
     ) -> Result<tonic::Response<SearchResponse>, Status> {
         let now = Instant::now();
 
-        let req = request.into_inner();
+        authorize(&request, Privilege::Read, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+        let credentials = Credentials::from_request(&request);
+        let filter = document_filter(&request, &request.get_ref().name);
+
+        let mut req = request.into_inner();
+
+        // The scores modified by the function score are not used when the hits are sorted by a
+        // field, so the combination is rejected instead of silently ignoring the functions.
+        if req.sort.is_some()
+            && req
+                .query
+                .as_ref()
+                .is_some_and(|query| query.kind == ProtoQueryKind::FunctionScore as i32)
+        {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "Function score query cannot be combined with sort.",
+            ));
+        }
+
+        // The document filter of the principal is added to the post filter of both the fan-out
+        // and the shard requests, so that the shards cannot be searched directly to bypass it.
+        if let Some(filter) = filter {
+            req.post_filter = Some(match req.post_filter.take() {
+                Some(post_filter) => create_boolean_proto_query(vec![
+                    SubQuery::new(Occurrence::Filter, post_filter),
+                    SubQuery::new(Occurrence::Filter, filter),
+                ]),
+                None => filter,
+            });
+        }
 
         let resp = if req.shard_id.is_empty() {
-            self.client.search(Request::new(req)).await
+            self.client.search(credentials.request(req)).await
         } else {
             let search_resp = self.node.read().await.search(req).await.map_err(|error| {
                 Status::new(
@@ -245,4 +391,115 @@ impl ProtoIndexService for IndexService {
 
         resp
     }
+
+    async fn get_cache_stats(
+        &self,
+        request: Request<GetCacheStatsRequest>,
+    ) -> Result<tonic::Response<GetCacheStatsResponse>, Status> {
+        let now = Instant::now();
+
+        authorize(&request, Privilege::Read, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+
+        let req = request.into_inner();
+
+        let resp = match self.node.read().await.get_cache_stats(req).await {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(e) => Err(Status::new(Code::Internal, e.to_string())),
+        };
+
+        info!(elapsed = ?now.elapsed(), "Get cache stats completed.");
+
+        resp
+    }
+
+    async fn analyze(
+        &self,
+        request: Request<AnalyzeRequest>,
+    ) -> Result<tonic::Response<AnalyzeResponse>, Status> {
+        let now = Instant::now();
+
+        authorize(&request, Privilege::Read, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+
+        let req = request.into_inner();
+
+        let resp = match self.node.read().await.analyze(req).await {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(e) => Err(Status::new(Code::Internal, e.to_string())),
+        };
+
+        info!(elapsed = ?now.elapsed(), "Analyze completed.");
+
+        resp
+    }
+
+    async fn reload_analyzers(
+        &self,
+        request: Request<ReloadAnalyzersRequest>,
+    ) -> Result<tonic::Response<ReloadAnalyzersResponse>, Status> {
+        let now = Instant::now();
+
+        authorize(&request, Privilege::Manage, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+
+        // The changes of the index metadata are made by the leader.
+        let resp = self
+            .on_leader(
+                request,
+                |node, req| async move { node.read().await.reload_analyzers(req).await },
+                |mut client, req| async move { client.reload_analyzers(req).await },
+            )
+            .await;
+
+        info!(elapsed = ?now.elapsed(), "Reload analyzers completed.");
+
+        resp
+    }
+
+    async fn put_user_dictionary(
+        &self,
+        request: Request<PutUserDictionaryRequest>,
+    ) -> Result<tonic::Response<PutUserDictionaryResponse>, Status> {
+        let now = Instant::now();
+
+        authorize(&request, Privilege::Manage, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+
+        // The changes of the index metadata are made by the leader.
+        let resp = self
+            .on_leader(
+                request,
+                |node, req| async move { node.read().await.put_user_dictionary(req).await },
+                |mut client, req| async move { client.put_user_dictionary(req).await },
+            )
+            .await;
+
+        info!(elapsed = ?now.elapsed(), "Put user dictionary completed.");
+
+        resp
+    }
+
+    async fn delete_user_dictionary(
+        &self,
+        request: Request<DeleteUserDictionaryRequest>,
+    ) -> Result<tonic::Response<DeleteUserDictionaryResponse>, Status> {
+        let now = Instant::now();
+
+        authorize(&request, Privilege::Manage, &request.get_ref().name)
+            .map_err(|error| error.to_status())?;
+
+        // The changes of the index metadata are made by the leader.
+        let resp = self
+            .on_leader(
+                request,
+                |node, req| async move { node.read().await.delete_user_dictionary(req).await },
+                |mut client, req| async move { client.delete_user_dictionary(req).await },
+            )
+            .await;
+
+        info!(elapsed = ?now.elapsed(), "Delete user dictionary completed.");
+
+        resp
+    }
 }
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use tonic::{Code, Request, Response, Status};
+
+use crate::{
+    proto::raft::{
+        raft_service_server::RaftService as ProtoRaftService, AppendEntriesRequest,
+        AppendEntriesResponse, GetDigestsRequest, GetDigestsResponse, GetSnapshotRequest,
+        GetSnapshotResponse, InstallSnapshotRequest, InstallSnapshotResponse, RequestVoteRequest,
+        RequestVoteResponse,
+    },
+    raft::Raft,
+};
+
+pub struct RaftService {
+    raft: Arc<Raft>,
+}
+
+impl RaftService {
+    pub fn new(raft: Arc<Raft>) -> Self {
+        Self { raft }
+    }
+}
+
+#[tonic::async_trait]
+impl ProtoRaftService for RaftService {
+    async fn request_vote(
+        &self,
+        request: Request<RequestVoteRequest>,
+    ) -> Result<Response<RequestVoteResponse>, Status> {
+        match self.raft.request_vote(request.into_inner()).await {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
+        }
+    }
+
+    async fn append_entries(
+        &self,
+        request: Request<AppendEntriesRequest>,
+    ) -> Result<Response<AppendEntriesResponse>, Status> {
+        match self.raft.append_entries(request.into_inner()).await {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
+        }
+    }
+
+    async fn install_snapshot(
+        &self,
+        request: Request<InstallSnapshotRequest>,
+    ) -> Result<Response<InstallSnapshotResponse>, Status> {
+        match self.raft.install_snapshot(request.into_inner()).await {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
+        }
+    }
+
+    async fn get_digests(
+        &self,
+        request: Request<GetDigestsRequest>,
+    ) -> Result<Response<GetDigestsResponse>, Status> {
+        match self.raft.get_digests(request.into_inner()).await {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
+        }
+    }
+
+    async fn get_snapshot(
+        &self,
+        request: Request<GetSnapshotRequest>,
+    ) -> Result<Response<GetSnapshotResponse>, Status> {
+        match self.raft.get_snapshot(request.into_inner()).await {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
+        }
+    }
+}
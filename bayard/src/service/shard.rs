@@ -0,0 +1,73 @@
+use std::{pin::Pin, sync::Arc};
+
+use futures_util::{Stream, TryStreamExt};
+use tokio::sync::RwLock;
+use tonic::{Code, Request, Response, Status};
+use tracing::debug;
+
+use crate::{
+    node::{Node, NodeErrorKind},
+    proto::shard::{
+        shard_service_server::ShardService as ProtoShardService, DownloadShardRequest,
+        GetShardStatusRequest, GetShardStatusResponse, ShardFileChunk,
+    },
+};
+
+pub struct ShardService {
+    node: Arc<RwLock<Node>>,
+}
+
+impl ShardService {
+    pub fn new(node: Arc<RwLock<Node>>) -> Self {
+        Self { node }
+    }
+}
+
+#[tonic::async_trait]
+impl ProtoShardService for ShardService {
+    type DownloadShardStream = Pin<Box<dyn Stream<Item = Result<ShardFileChunk, Status>> + Send>>;
+
+    async fn get_shard_status(
+        &self,
+        request: Request<GetShardStatusRequest>,
+    ) -> Result<Response<GetShardStatusResponse>, Status> {
+        debug!(request = ?request.get_ref(), "get_shard_status");
+
+        match self
+            .node
+            .read()
+            .await
+            .get_shard_status(request.into_inner())
+            .await
+        {
+            Ok(resp) => Ok(Response::new(resp)),
+            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
+        }
+    }
+
+    async fn download_shard(
+        &self,
+        request: Request<DownloadShardRequest>,
+    ) -> Result<Response<Self::DownloadShardStream>, Status> {
+        debug!(request = ?request.get_ref(), "download_shard");
+
+        match self
+            .node
+            .read()
+            .await
+            .download_shard(request.into_inner())
+            .await
+        {
+            Ok(snapshot) => {
+                Ok(Response::new(Box::pin(snapshot.into_chunks().map_err(
+                    |error| Status::new(Code::Internal, error.to_string()),
+                ))))
+            }
+            // The node that downloads the shard asks the other nodes if this node does not serve it.
+            Err(err) if matches!(err.kind(), NodeErrorKind::ShardNotFound) => {
+                Err(Status::new(Code::NotFound, err.to_string()))
+            }
+            Err(err) => Err(Status::new(Code::Internal, err.to_string())),
+        }
+    }
+}
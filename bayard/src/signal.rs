@@ -1,6 +1,9 @@
+use std::io;
+
 use signal_hook::{
     consts::signal::{SIGINT, SIGQUIT, SIGTERM},
     iterator::{exfiltrator::WithOrigin, SignalsInfo},
+    low_level::raise,
 };
 use tokio::sync::watch::Sender;
 use tracing::{debug, error, info};
@@ -22,3 +25,8 @@ pub fn handle_signals(tx_signal: Sender<()>) {
         }
     }
 }
+
+/// Stops the node in the same way as SIGTERM, e.g. when it has been decommissioned.
+pub fn raise_stop_signal() -> io::Result<()> {
+    raise(SIGTERM)
+}